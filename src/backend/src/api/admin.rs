@@ -3,12 +3,13 @@ use ic_cdk::{query, update};
 use serde_bytes::ByteBuf;
 use shared::{
     http::{HttpRequest, HttpResponse},
-    metrics::get_metrics,
+    metrics::get_metrics_with,
     std_canister_status,
     types::{backend_config::Config, Stats, Timestamp},
 };
 
 use crate::{
+    exchange,
     state::{read_config, read_state},
     status,
     types::StoredPrincipal,
@@ -34,7 +35,7 @@ pub fn http_request(request: HttpRequest) -> HttpResponse {
         .unwrap_or_else(|| unreachable!("Even splitting an empty string yields one entry"));
 
     match path {
-        "/metrics" => get_metrics(),
+        "/metrics" => get_metrics_with(exchange::aggregation::encode_metrics),
         "/status" => status::handle(),
         _ => HttpResponse {
             status_code: 404,
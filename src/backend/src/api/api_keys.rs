@@ -17,9 +17,9 @@ pub fn get_api_keys() -> ApiKeys {
 
 /// Overwrites the stored API keys.
 ///
-/// If `exchange_rate_enabled` or `exchange_rate_replicated` is omitted, the existing toggle is
-/// preserved so that routine key rotation does not accidentally pause exchange-rate refreshes or
-/// change their outcall replication mode.
+/// If `exchange_rate_enabled`, `exchange_rate_replicated` or `exchange_rate_max_deviation_bps` is
+/// omitted, the existing setting is preserved so that routine key rotation does not accidentally
+/// pause exchange-rate refreshes or change their outcall replication or aggregation mode.
 ///
 /// Restricted to canister controllers only.
 #[update(guard = "caller_is_controller")]
@@ -31,9 +31,13 @@ pub fn set_api_keys(api_keys: ApiKeys) {
         let exchange_rate_replicated = api_keys
             .exchange_rate_replicated
             .or(stored.exchange_rate_replicated);
+        let exchange_rate_max_deviation_bps = api_keys
+            .exchange_rate_max_deviation_bps
+            .or(stored.exchange_rate_max_deviation_bps);
         *stored = ApiKeys {
             exchange_rate_enabled,
             exchange_rate_replicated,
+            exchange_rate_max_deviation_bps,
             ..api_keys
         };
     });
@@ -1,32 +1,59 @@
-use std::collections::HashSet;
+use std::{collections::HashSet, str::FromStr};
 
+use bitcoin::Address;
+use candid::Principal;
 use ic_cdk::{
     api::{is_controller, msg_caller, time},
     query, update,
 };
 use shared::types::{
     bitcoin::{
-        BtcAddPendingTransactionError, BtcAddPendingTransactionRequest,
+        BtcAddPendingTransactionError, BtcAddPendingTransactionRequest, BtcAddressType,
+        BtcBuildTransactionError, BtcBuildTransactionRequest, BtcBuildTransactionResponse,
+        BtcBumpFeeError, BtcBumpFeeRequest, BtcBumpFeeResponse, BtcFeeBumpMethod,
         BtcGetFeePercentilesRequest, BtcGetFeePercentilesResponse, BtcGetPendingTransactionsError,
         BtcGetPendingTransactionsReponse, BtcGetPendingTransactionsRequest, PendingTransaction,
-        StoredPendingTransaction,
+        PendingTransactionStatus, StoredPendingTransaction,
     },
     result_types::{
-        BtcAddPendingTransactionResult, BtcGetFeePercentilesResult, BtcGetPendingTransactionsResult,
+        BtcAddPendingTransactionResult, BtcBuildTransactionResult, BtcBumpFeeResult,
+        BtcGetFeePercentilesResult, BtcGetPendingTransactionsResult,
     },
 };
 
 use crate::{
-    bitcoin::{api, pending_tx_model::BtcUserPendingTransactionsModel},
+    bitcoin::{
+        api::{self, MIN_CONFIRMATIONS_ACCEPTED_BTC_TX},
+        coin_selection, fee_bump,
+        pending_tx_model::{self, BtcUserPendingTransactionsModel},
+        tx_builder,
+    },
     delegation, signer,
     state::mutate_state,
+    types::StoredPrincipal,
+    user_profile::{self, model::UserProfileModel},
     utils::{
         guards::{caller_is_not_anonymous, caller_is_registered_user},
         rate_limiter::{self, BTC_ADD_PENDING_TX_RATE_LIMITER, BTC_GET_PENDING_TX_RATE_LIMITER},
     },
 };
 
-const MIN_CONFIRMATIONS_ACCEPTED_BTC_TX: u32 = 6;
+/// Returns `address_type`, or the caller's preferred address type if the request names none.
+fn address_type_or_preferred(
+    address_type: Option<BtcAddressType>,
+    principal: Principal,
+) -> BtcAddressType {
+    address_type.unwrap_or_else(|| {
+        mutate_state(|s| {
+            let user_profile_model =
+                UserProfileModel::new(&mut s.user_profile, &mut s.user_profile_updated);
+            user_profile::service::preferred_btc_address_type(
+                StoredPrincipal(principal),
+                &user_profile_model,
+            )
+        })
+    })
+}
 
 /// Retrieves the current fee percentiles for Bitcoin transactions from the cache
 /// for the specified network. Fee percentiles are measured in millisatoshi per byte
@@ -100,17 +127,17 @@ pub async fn btc_add_pending_transaction(
             return Err(BtcAddPendingTransactionError::DuplicateUtxos);
         }
 
-        let source_address = signer::btc_principal_to_p2wpkh_address(params.network, &principal)
+        let address_type = address_type_or_preferred(params.address_type, principal);
+        let source_address =
+            signer::btc_principal_to_address(params.network, &principal, address_type)
+                .await
+                .map_err(|msg| BtcAddPendingTransactionError::InternalError { msg })?;
+
+        let address_utxos = api::get_address_utxos(params.network, source_address.clone())
             .await
             .map_err(|msg| BtcAddPendingTransactionError::InternalError { msg })?;
-
-        let current_utxos = api::get_all_utxos(
-            params.network,
-            source_address.clone(),
-            Some(MIN_CONFIRMATIONS_ACCEPTED_BTC_TX),
-        )
-        .await
-        .map_err(|msg| BtcAddPendingTransactionError::InternalError { msg })?;
+        let current_utxos =
+            address_utxos.with_min_confirmations(params.network, MIN_CONFIRMATIONS_ACCEPTED_BTC_TX);
 
         let current_keys: HashSet<(&[u8], u32)> = current_utxos
             .iter()
@@ -132,7 +159,13 @@ pub async fn btc_add_pending_transaction(
                 None,
                 None,
             );
-            model.prune_pending_transactions(principal, &current_utxos, now_ns);
+            model.update_pending_transactions(
+                principal,
+                &source_address,
+                &address_utxos.utxos,
+                address_utxos.tip_height,
+                now_ns,
+            );
 
             if model.has_intersecting_pending_utxos(principal, &params.utxos) {
                 return Err(BtcAddPendingTransactionError::UtxosAlreadyReserved);
@@ -142,6 +175,8 @@ pub async fn btc_add_pending_transaction(
                 txid: params.txid,
                 utxos: params.utxos,
                 created_at_timestamp_ns: now_ns,
+                details: None,
+                status: Some(PendingTransactionStatus::Pending),
             };
             model
                 .add_pending_transaction(principal, source_address, current_pending_transaction)
@@ -151,6 +186,234 @@ pub async fn btc_add_pending_transaction(
     inner(params).await.into()
 }
 
+/// Builds an unsigned transaction sending `amount_satoshis` from the caller's address of the
+/// requested type, or else of their preferred type, to `recipient`, and reserves the UTXOs it
+/// spends as a pending transaction. The fee covers the inputs of that address type once signed.
+///
+/// The fee rate is the requested percentile of `btc_get_current_fee_percentiles`, and the UTXOs are
+/// chosen by the requested coin-selection strategy. UTXOs already reserved by other pending
+/// transactions of the caller are never selected, and the selection and
+/// reservation happen in a single state update, so concurrent calls cannot spend the same UTXOs.
+///
+/// Requires a valid II delegation chain to verify the caller authenticated
+/// through Internet Identity. Controllers bypass this check.
+///
+/// # Errors
+/// Errors are enumerated by: `BtcBuildTransactionError`.
+#[update(guard = "caller_is_registered_user")]
+pub async fn btc_build_transaction(
+    params: BtcBuildTransactionRequest,
+) -> BtcBuildTransactionResult {
+    async fn inner(
+        params: BtcBuildTransactionRequest,
+    ) -> Result<BtcBuildTransactionResponse, BtcBuildTransactionError> {
+        BTC_ADD_PENDING_TX_RATE_LIMITER
+            .with(rate_limiter::RateLimiter::check_caller)
+            .map_err(BtcBuildTransactionError::RateLimited)?;
+
+        let principal = msg_caller();
+        let now_ns = time();
+
+        let (ii_canister_ids, root_key, guard_enabled) = delegation::read_ii_verification_config();
+        delegation::require_ii_delegation(
+            params.ii_delegation_chain.as_ref(),
+            is_controller(&principal),
+            principal,
+            &ii_canister_ids,
+            &root_key,
+            now_ns,
+            guard_enabled,
+        )
+        .map_err(|msg| BtcBuildTransactionError::InvalidDelegationChain { msg })?;
+
+        let network = signer::transform_network(params.network);
+        let recipient = Address::from_str(&params.recipient)
+            .and_then(|address| address.require_network(network))
+            .map_err(|_| BtcBuildTransactionError::InvalidRecipient)?;
+
+        let fee_rate = api::get_fee_rate_at_percentile(params.network, params.fee_percentile);
+
+        let address_type = address_type_or_preferred(params.address_type, principal);
+        let source =
+            signer::btc_principal_to_source_address(params.network, &principal, address_type)
+                .await
+                .map_err(|msg| BtcBuildTransactionError::InternalError { msg })?;
+        let source_address = source.address().to_string();
+
+        let address_utxos = api::get_address_utxos(params.network, source_address.clone())
+            .await
+            .map_err(|msg| BtcBuildTransactionError::InternalError { msg })?;
+        let current_utxos =
+            address_utxos.with_min_confirmations(params.network, MIN_CONFIRMATIONS_ACCEPTED_BTC_TX);
+
+        mutate_state(|state| {
+            let mut model = BtcUserPendingTransactionsModel::new(
+                &mut state.btc_user_pending_transactions,
+                None,
+                None,
+            );
+            model.update_pending_transactions(
+                principal,
+                &source_address,
+                &address_utxos.utxos,
+                address_utxos.tip_height,
+                now_ns,
+            );
+
+            let reserved = model.get_reserved_outpoints(principal);
+            let available_utxos: Vec<_> = current_utxos
+                .iter()
+                .filter(|u| !reserved.contains(&(u.outpoint.txid.clone(), u.outpoint.vout)))
+                .cloned()
+                .collect();
+
+            let built = tx_builder::build_transaction(
+                &available_utxos,
+                &source,
+                &recipient,
+                params.amount_satoshis,
+                fee_rate,
+                coin_selection::strategy(params.coin_selection.unwrap_or_default()).as_ref(),
+            )?;
+
+            model
+                .add_pending_transaction(
+                    principal,
+                    source_address,
+                    built.to_stored(&recipient, params.amount_satoshis, now_ns),
+                )
+                .map_err(|msg| BtcBuildTransactionError::InternalError { msg })?;
+
+            Ok(BtcBuildTransactionResponse {
+                psbt: built.psbt,
+                txid: built.txid,
+                utxos: built.utxos,
+                fee_satoshis: built.fee_satoshis,
+                change_satoshis: built.change_satoshis,
+                vsize: built.vsize,
+            })
+        })
+    }
+    inner(params).await.into()
+}
+
+/// Bumps the fee of a pending transaction of the caller built by `btc_build_transaction`.
+///
+/// - `ReplaceByFee` builds a replacement spending the same UTXOs with a higher fee, taken from the
+///   change. The pending transaction is updated to the replacement, which keeps reserving the
+///   UTXOs and records the txids it replaced.
+/// - `ChildPaysForParent` builds a child spending the change output back to the caller, paying
+///   the fee of both. The child is recorded on the pending transaction.
+///
+/// Either way the reservation is renewed. The new transaction is returned unsigned; the
+/// caller signs and broadcasts it.
+///
+/// Requires a valid II delegation chain to verify the caller authenticated
+/// through Internet Identity. Controllers bypass this check.
+///
+/// # Errors
+/// Errors are enumerated by: `BtcBumpFeeError`.
+#[update(guard = "caller_is_registered_user")]
+pub async fn btc_bump_fee(params: BtcBumpFeeRequest) -> BtcBumpFeeResult {
+    async fn inner(params: BtcBumpFeeRequest) -> Result<BtcBumpFeeResponse, BtcBumpFeeError> {
+        BTC_ADD_PENDING_TX_RATE_LIMITER
+            .with(rate_limiter::RateLimiter::check_caller)
+            .map_err(BtcBumpFeeError::RateLimited)?;
+
+        let principal = msg_caller();
+        let now_ns = time();
+
+        let (ii_canister_ids, root_key, guard_enabled) = delegation::read_ii_verification_config();
+        delegation::require_ii_delegation(
+            params.ii_delegation_chain.as_ref(),
+            is_controller(&principal),
+            principal,
+            &ii_canister_ids,
+            &root_key,
+            now_ns,
+            guard_enabled,
+        )
+        .map_err(|msg| BtcBumpFeeError::InvalidDelegationChain { msg })?;
+
+        let network = signer::transform_network(params.network);
+        let fee_rate = api::get_fee_rate_at_percentile(params.network, params.fee_percentile);
+
+        let address_type = address_type_or_preferred(params.address_type, principal);
+        let source =
+            signer::btc_principal_to_source_address(params.network, &principal, address_type)
+                .await
+                .map_err(|msg| BtcBumpFeeError::InternalError { msg })?;
+        let source_address = source.address().to_string();
+
+        let address_utxos = api::get_address_utxos(params.network, source_address.clone())
+            .await
+            .map_err(|msg| BtcBumpFeeError::InternalError { msg })?;
+
+        mutate_state(|state| {
+            let mut model = BtcUserPendingTransactionsModel::new(
+                &mut state.btc_user_pending_transactions,
+                None,
+                None,
+            );
+            model.update_pending_transactions(
+                principal,
+                &source_address,
+                &address_utxos.utxos,
+                address_utxos.tip_height,
+                now_ns,
+            );
+
+            let pending = model
+                .get_pending_transactions(&principal, &source_address)
+                .into_iter()
+                .find(|tx| tx.txid == params.txid)
+                .ok_or(BtcBumpFeeError::PendingTransactionNotFound)?;
+            if pending
+                .status
+                .is_some_and(|status| status != PendingTransactionStatus::Pending)
+            {
+                return Err(BtcBumpFeeError::NotPending);
+            }
+            let details = pending
+                .details
+                .as_ref()
+                .ok_or(BtcBumpFeeError::NotBumpable)?;
+
+            let (built, updated) = match params.method {
+                BtcFeeBumpMethod::ReplaceByFee => {
+                    let recipient = Address::from_str(&details.recipient)
+                        .and_then(|address| address.require_network(network))
+                        .map_err(|err| BtcBumpFeeError::InternalError {
+                            msg: format!("Invalid stored recipient address: {err}"),
+                        })?;
+                    let replacement = fee_bump::build_replacement(
+                        &pending, details, &source, &recipient, fee_rate,
+                    )?;
+                    let updated = fee_bump::replaced(&pending, details, &replacement, now_ns);
+                    (replacement, updated)
+                }
+                BtcFeeBumpMethod::ChildPaysForParent => {
+                    let child = fee_bump::build_cpfp_child(&pending, details, &source, fee_rate)?;
+                    let updated = fee_bump::with_cpfp_child(&pending, details, &child, now_ns);
+                    (child, updated)
+                }
+            };
+
+            model
+                .replace_pending_transaction(principal, &source_address, &pending.txid, updated)
+                .map_err(|msg| BtcBumpFeeError::InternalError { msg })?;
+
+            Ok(BtcBumpFeeResponse {
+                psbt: built.psbt,
+                txid: built.txid,
+                fee_satoshis: built.fee_satoshis,
+                vsize: built.vsize,
+            })
+        })
+    }
+    inner(params).await.into()
+}
+
 /// Returns the pending Bitcoin transactions for the caller.
 ///
 /// Requires a valid II delegation chain to verify the caller authenticated
@@ -184,33 +447,45 @@ pub async fn btc_get_pending_transactions(
         )
         .map_err(|msg| BtcGetPendingTransactionsError::InvalidDelegationChain { msg })?;
 
-        let source_address = signer::btc_principal_to_p2wpkh_address(params.network, &principal)
+        let address_type = address_type_or_preferred(params.address_type, principal);
+        let source_address =
+            signer::btc_principal_to_address(params.network, &principal, address_type)
+                .await
+                .map_err(|msg| BtcGetPendingTransactionsError::InternalError { msg })?;
+
+        let address_utxos = api::get_address_utxos(params.network, source_address.clone())
             .await
             .map_err(|msg| BtcGetPendingTransactionsError::InternalError { msg })?;
 
-        let current_utxos = api::get_all_utxos(
-            params.network,
-            source_address.clone(),
-            Some(MIN_CONFIRMATIONS_ACCEPTED_BTC_TX),
-        )
-        .await
-        .map_err(|msg| BtcGetPendingTransactionsError::InternalError { msg })?;
-
         let stored_transactions = mutate_state(|state| {
             let mut model = BtcUserPendingTransactionsModel::new(
                 &mut state.btc_user_pending_transactions,
                 None,
                 None,
             );
-            model.prune_pending_transactions(principal, &current_utxos, now_ns);
+            model.update_pending_transactions(
+                principal,
+                &source_address,
+                &address_utxos.utxos,
+                address_utxos.tip_height,
+                now_ns,
+            );
             model.get_pending_transactions(&principal, &source_address)
         });
 
         let pending_transactions = stored_transactions
             .iter()
-            .map(|tx| PendingTransaction {
-                txid: tx.txid.clone(),
-                utxos: tx.utxos.clone(),
+            .map(|tx| {
+                let status = tx.status.unwrap_or(PendingTransactionStatus::Pending);
+                PendingTransaction {
+                    txid: tx.txid.clone(),
+                    utxos: tx.utxos.clone(),
+                    status,
+                    confirmations: pending_tx_model::confirmations(
+                        status,
+                        address_utxos.tip_height,
+                    ),
+                }
             })
             .collect();
 
@@ -1,14 +1,24 @@
-use ic_cdk::{query, update};
+use ic_cdk::{
+    api::{data_certificate, msg_caller},
+    query, update,
+};
 use shared::types::{
-    contact::{CreateContactRequest, UpdateContactRequest},
+    certification::{CertifiedUserData, CONTACTS_LABEL},
+    contact::{
+        BatchContactsRequest, ContactExportFormat, CreateContactRequest,
+        CreateVerifiedContactRequest, ImportContactsRequest, UpdateContactRequest,
+    },
     result_types::{
-        CreateContactResult, DeleteContactResult, GetContactResult, GetContactsResult,
+        BatchContactsResult, CreateContactResult, CreateVerifiedContactResult, DeleteContactResult,
+        ExportContactsResult, GetContactResult, GetContactsResult, ImportContactsResult,
         UpdateContactResult,
     },
 };
 
 use crate::{
+    certification::users::{certified_user_data, contacts_data},
     contacts,
+    types::StoredPrincipal,
     utils::guards::{caller_is_not_anonymous, caller_is_registered_user},
 };
 
@@ -27,6 +37,34 @@ pub async fn create_contact(request: CreateContactRequest) -> CreateContactResul
     result.into()
 }
 
+/// Applies create, update and delete operations to the caller's contacts, all or none of them.
+///
+/// Updates and deletions carry the `update_timestamp_ns` of the contact as last read by the
+/// client, and fail with `OutdatedContact` if it changed since.
+///
+/// # Errors
+/// Errors are enumerated by: `BatchContactsError`.
+#[update(guard = "caller_is_registered_user")]
+#[must_use]
+pub async fn batch_contacts(request: BatchContactsRequest) -> BatchContactsResult {
+    let result = contacts::batch_contacts(request).await;
+    result.into()
+}
+
+/// Creates a contact for another OISY user from their principal. The contact's addresses are
+/// derived by the backend and marked as verified, so they cannot be swapped for lookalikes.
+///
+/// # Errors
+/// Errors are enumerated by: `CreateVerifiedContactError`.
+#[update(guard = "caller_is_registered_user")]
+#[must_use]
+pub async fn create_verified_contact(
+    request: CreateVerifiedContactRequest,
+) -> CreateVerifiedContactResult {
+    let result = contacts::create_verified_contact(request).await;
+    result.into()
+}
+
 /// Updates an existing contact for the caller.
 ///
 /// # Errors
@@ -77,3 +115,44 @@ pub fn get_contacts() -> GetContactsResult {
     let result = Ok(contacts::get_contacts());
     result.into()
 }
+
+/// Adds the contacts in a CSV or vCard file to the caller's contacts.
+///
+/// Invalid contacts, and those beyond the contact limit, are skipped and reported per line rather
+/// than failing the whole import. See `ContactExportFormat` for the expected layout.
+///
+/// # Errors
+/// Errors are enumerated by: `ContactError`.
+#[update(guard = "caller_is_registered_user")]
+#[must_use]
+pub async fn import_contacts(request: ImportContactsRequest) -> ImportContactsResult {
+    let result = contacts::import_contacts(request).await;
+    result.into()
+}
+
+/// Returns the caller's contacts as a CSV or vCard file, without images.
+#[query(guard = "caller_is_not_anonymous")]
+#[must_use]
+pub fn export_contacts(format: ContactExportFormat) -> ExportContactsResult {
+    let result = Ok(contacts::export_contacts(format));
+    result.into()
+}
+
+/// Returns the caller's contacts, Candid-encoded as a `vec Contact`, with the IC certificate and
+/// witness proving that the canister stores them, so e.g. a contact's address can be trusted
+/// without trusting the replica that answered. Verify them with
+/// `shared::types::certification::verify_certified_contacts`.
+///
+/// Returns `None` when called as an update, where no certificate is available, and right after an
+/// upgrade until the caller's records are certified again.
+#[query(guard = "caller_is_not_anonymous")]
+#[must_use]
+pub fn get_certified_contacts() -> Option<CertifiedUserData> {
+    let certificate = data_certificate()?;
+    certified_user_data(
+        StoredPrincipal(msg_caller()),
+        CONTACTS_LABEL,
+        contacts_data(&contacts::get_contacts()),
+        certificate,
+    )
+}
@@ -1,10 +1,15 @@
-use ic_cdk::{api::msg_caller, update};
+use ic_cdk::{
+    api::{data_certificate, msg_caller},
+    query, update,
+};
 use shared::types::{
+    certification::{CertifiedUserData, CUSTOM_TOKENS_LABEL},
     custom_token::{CustomToken, CustomTokenId},
     token_id::TokenId,
 };
 
 use crate::{
+    certification::users::{certified_user_data, certify_custom_tokens, custom_tokens_data},
     state::{mutate_state, read_state},
     token::{self, MAX_TOKEN_LIST_LENGTH},
     types::StoredPrincipal,
@@ -65,6 +70,7 @@ pub fn set_custom_token(token: CustomToken) {
             std::slice::from_ref(&token),
             |t: &CustomToken| CustomTokenId::from(&t.token),
         );
+        certify_custom_tokens(stored_principal, &s.custom_token);
     });
 
     let CustomToken { token, .. } = token;
@@ -97,6 +103,7 @@ pub fn set_many_custom_tokens(tokens: Vec<CustomToken>) {
             &tokens,
             |t: &CustomToken| CustomTokenId::from(&t.token),
         );
+        certify_custom_tokens(stored_principal, &s.custom_token);
     });
 
     token::mark_tokens_active(&ids);
@@ -115,6 +122,7 @@ pub fn remove_custom_token(token: CustomToken) {
         };
 
         token::remove_from_user_token(stored_principal, &mut s.custom_token, &find);
+        certify_custom_tokens(stored_principal, &s.custom_token);
     });
 }
 
@@ -146,3 +154,27 @@ pub fn list_custom_tokens() -> Vec<CustomToken> {
 
     tokens
 }
+
+/// Lists the caller's custom tokens, Candid-encoded as a `vec CustomToken`, with the IC certificate
+/// and witness proving that the canister stores them. Verify them with
+/// `shared::types::certification::verify_certified_custom_tokens`.
+///
+/// Unlike [`list_custom_tokens`], this is a query and does not mark the tokens as active.
+///
+/// Returns `None` when called as an update, where no certificate is available, and right after an
+/// upgrade until the caller's records are certified again.
+#[query(guard = "caller_is_registered_user")]
+#[must_use]
+pub fn list_certified_custom_tokens() -> Option<CertifiedUserData> {
+    let certificate = data_certificate()?;
+    let stored_principal = StoredPrincipal(msg_caller());
+
+    let tokens = read_state(|s| s.custom_token.get(&stored_principal).unwrap_or_default().0);
+
+    certified_user_data(
+        stored_principal,
+        CUSTOM_TOKENS_LABEL,
+        custom_tokens_data(&tokens),
+        certificate,
+    )
+}
@@ -1,15 +1,27 @@
-use ic_cdk::{api::msg_caller, query, update};
-use shared::types::{exchange::ExchangeRate, token_id::TokenId};
+use ic_cdk::{
+    api::{data_certificate, msg_caller, time},
+    query, update,
+};
+use shared::types::{
+    exchange::{
+        CertifiedExchangeRate, ExchangeRate, GetExchangeRateHistoryRequest, PriceCandle,
+        PriceOverride, SetPriceOverrideRequest,
+    },
+    result_types::SetPriceOverrideResult,
+    token_id::TokenId,
+};
 
 use crate::{
     exchange::{
-        custom_tokens_to_mark, fetch_and_update_prices, is_exchange_rate_refresh_enabled,
-        note_rate_request, priceable_tokens_for_caller, release_refresh_lock, snapshot_and_stale,
-        try_acquire_refresh_lock,
+        certification, custom_tokens_to_mark, fetch_and_update_prices, fiat, history,
+        is_exchange_rate_refresh_enabled, note_rate_request, overrides,
+        priceable_tokens_for_caller, release_refresh_lock, snapshot_and_stale,
+        try_acquire_refresh_lock, with_staleness,
     },
-    state::{mutate_api_keys, read_state},
+    state::{mutate_api_keys, mutate_state, read_state},
     token,
     types::{StoredPrincipal, StoredTokenId},
+    user_profile::{self, model::UserProfileModel},
     utils::guards::{caller_is_controller, caller_is_not_anonymous},
 };
 
@@ -18,16 +30,16 @@ use crate::{
 /// "Priceable" means the union of:
 /// - the always-on native tokens (BTC, ICP, SOL, ETH on the supported EVM mainnets), and
 /// - the caller's custom tokens, filtered to variants the configured providers can actually price
-///   (testnets, NFTs and ERC-4626 vaults are excluded).
+///   or derive a price for (testnets and NFTs are excluded).
 ///
 /// The endpoint also re-marks the returned tokens as active so the
 /// background refresh timer keeps them warm. If any cached price is
 /// missing or older than [`crate::exchange::PRICE_STALENESS_THRESHOLD_SEC`]
 /// seconds, the endpoint kicks off a refresh for that subset **in the
 /// background** (via `ic_cdk::futures::spawn_migratory`) and returns the current cache
-/// snapshot immediately. Entries that are still missing or stale at the
-/// moment of the call are returned as `None`; subsequent calls will pick up
-/// the refreshed values once the spawned fetch lands.
+/// snapshot immediately. Stale entries are returned with `stale` set, so the
+/// frontend can grey them out, and missing entries as `None`; subsequent calls
+/// will pick up the refreshed values once the spawned fetch lands.
 ///
 /// This trade-off (return fast, refresh async) is intentional: under the
 /// previous "await-the-fetch" shape, a cold-cache caller could wait on the
@@ -59,7 +71,7 @@ pub fn get_exchange_rates() -> Vec<(TokenId, Option<ExchangeRate>)> {
         token::mark_tokens_active(&tokens_to_mark);
     }
 
-    let (snapshot, stale) = snapshot_and_stale(tokens);
+    let (mut snapshot, stale) = snapshot_and_stale(tokens);
     let refresh_lock = if stale.is_empty() || !is_exchange_rate_refresh_enabled() {
         None
     } else {
@@ -87,13 +99,72 @@ pub fn get_exchange_rates() -> Vec<(TokenId, Option<ExchangeRate>)> {
         });
     }
 
+    // Only the caller's preferred fiat currency is returned next to USD, keeping the response
+    // size independent of the number of supported currencies.
+    let currency = mutate_state(|s| {
+        let user_profile_model =
+            UserProfileModel::new(&mut s.user_profile, &mut s.user_profile_updated);
+        user_profile::service::preferred_fiat_currency(caller, &user_profile_model)
+    });
+    for (_, rate) in &mut snapshot {
+        *rate = rate.take().map(|rate| fiat::for_currency(rate, currency));
+    }
+
     snapshot
 }
 
+/// Returns the cached USD price of a token, however old, with `stale` set when it is older than
+/// [`crate::exchange::PRICE_STALENESS_THRESHOLD_SEC`] seconds.
 #[query(guard = "caller_is_not_anonymous")]
 #[must_use]
 pub fn get_exchange_rate(token_id: TokenId) -> Option<ExchangeRate> {
-    read_state(|s| s.exchange_rates.get(&StoredTokenId(token_id)).map(|c| c.0))
+    let rate = read_state(|s| s.exchange_rates.get(&StoredTokenId(token_id)).map(|c| c.0));
+    rate.map(|rate| with_staleness(rate, time()))
+}
+
+/// Returns the cached USD price of a token with the IC certificate and witness proving that the
+/// canister stored it, so high-value flows can trust the price without trusting the replica that
+/// answered. A token without a cached price comes with a proof of absence.
+///
+/// The certified rate is stored as is: `stale` is unset and the rate carries every fiat price, so
+/// callers check `usd.timestamp_ns` themselves. Verify the response with
+/// `shared::types::exchange::certification::verify_certified_exchange_rate`.
+///
+/// Returns `None` when called as an update, where no certificate is available.
+#[query(guard = "caller_is_not_anonymous")]
+#[must_use]
+pub fn get_certified_exchange_rate(token_id: TokenId) -> Option<CertifiedExchangeRate> {
+    let certificate = data_certificate()?;
+    Some(certification::certified_exchange_rate(
+        &StoredTokenId(token_id),
+        certificate,
+    ))
+}
+
+/// Returns the USD price history of a token at the requested resolution, as OHLC buckets
+/// overlapping `[from_ns, to_ns]`, oldest first.
+///
+/// The history is recorded by the exchange-rate refresh, so it only covers periods in which the
+/// token was refreshed. Hourly buckets are kept for 30 days and daily buckets for 2 years.
+#[query(guard = "caller_is_not_anonymous")]
+#[must_use]
+pub fn get_exchange_rate_history(request: GetExchangeRateHistoryRequest) -> Vec<PriceCandle> {
+    let GetExchangeRateHistoryRequest {
+        token_id,
+        from_ns,
+        to_ns,
+        resolution,
+    } = request;
+
+    read_state(|s| {
+        history::get_history(
+            &s.exchange_rate_history,
+            &StoredTokenId(token_id),
+            from_ns,
+            to_ns,
+            resolution,
+        )
+    })
 }
 
 /// Returns whether the backend is currently fetching and caching exchange rates.
@@ -134,3 +205,53 @@ pub fn set_exchange_rate_enabled(enabled: bool) {
 pub fn set_exchange_rate_replicated(replicated: bool) {
     mutate_api_keys(|keys| keys.exchange_rate_replicated = Some(replicated));
 }
+
+/// Sets `exchange_rate_max_deviation_bps`, without touching the stored API keys.
+///
+/// `Some(bps)` switches exchange-rate refresh to median aggregation across all providers,
+/// rejecting quotes more than `bps` basis points away from the median; `None` restores the
+/// first-valid-price mode. See [`crate::exchange::aggregation`].
+///
+/// Restricted to canister controllers only.
+#[update(guard = "caller_is_controller")]
+pub fn set_exchange_rate_max_deviation_bps(max_deviation_bps: Option<u32>) {
+    mutate_api_keys(|keys| keys.exchange_rate_max_deviation_bps = max_deviation_bps);
+}
+
+/// Sets or, with `price_override: None`, removes a controller-set price for a token.
+///
+/// An override takes precedence over every price provider from the next refresh of the token on:
+/// a static USD price, or a peg to another token's price (e.g. ckUSDC = USDC). Prices set this way
+/// carry `override` or `peg` as their `source`. See [`crate::exchange::overrides`].
+///
+/// Restricted to canister controllers only.
+#[update(guard = "caller_is_controller")]
+pub fn set_price_override(request: SetPriceOverrideRequest) -> SetPriceOverrideResult {
+    let SetPriceOverrideRequest {
+        token_id,
+        price_override,
+    } = request;
+
+    mutate_state(|s| {
+        overrides::set_price_override(
+            &mut s.price_overrides,
+            StoredTokenId(token_id),
+            price_override,
+        )
+    })
+    .into()
+}
+
+/// Lists the controller-set price overrides.
+///
+/// Restricted to canister controllers only.
+#[query(guard = "caller_is_controller")]
+#[must_use]
+pub fn list_price_overrides() -> Vec<(TokenId, PriceOverride)> {
+    read_state(|s| {
+        s.price_overrides
+            .iter()
+            .map(|entry| (entry.key().0.clone(), entry.value().0))
+            .collect()
+    })
+}
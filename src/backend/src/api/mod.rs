@@ -8,6 +8,8 @@ pub mod exchange;
 pub mod onramper;
 pub mod personal_note_shares;
 pub mod personal_notes;
+pub mod portfolio;
+pub mod price_alerts;
 pub mod signer;
 pub mod transactions;
 pub mod user_profile;
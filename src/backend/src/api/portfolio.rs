@@ -0,0 +1,42 @@
+use ic_cdk::{api::msg_caller, query, update};
+use shared::types::{
+    portfolio::{
+        GetPortfolioSnapshotsRequest, GetPortfolioSnapshotsResponse, SavePortfolioSnapshotRequest,
+    },
+    result_types::SavePortfolioSnapshotResult,
+};
+
+use crate::{
+    portfolio::service,
+    utils::guards::{caller_is_not_anonymous, caller_is_registered_user},
+};
+
+/// Values the submitted balances at the cached USD exchange rates and saves the result as the
+/// caller's portfolio snapshot for today, replacing an earlier snapshot of the same day.
+///
+/// Balances of tokens without a cached rate are left out of the total and counted in
+/// `unpriced_tokens`.
+///
+/// # Returns
+/// - `Ok(PortfolioSnapshot)` with the saved snapshot.
+///
+/// # Errors
+/// - `SnapshotsDisabled` if the caller has not enabled portfolio snapshots in their settings.
+/// - `TooManyBalances` if more than `MAX_PORTFOLIO_SNAPSHOT_BALANCES` balances are submitted.
+/// - `UserNotFound` if the caller has no user profile.
+#[update(guard = "caller_is_registered_user")]
+#[must_use]
+pub fn save_portfolio_snapshot(
+    request: SavePortfolioSnapshotRequest,
+) -> SavePortfolioSnapshotResult {
+    service::save_snapshot(msg_caller(), request).into()
+}
+
+/// Retrieves the caller's portfolio snapshots, newest first, with cursor-based pagination.
+#[query(guard = "caller_is_not_anonymous")]
+#[must_use]
+pub fn get_portfolio_snapshots(
+    request: GetPortfolioSnapshotsRequest,
+) -> GetPortfolioSnapshotsResponse {
+    service::get_snapshots(msg_caller(), request)
+}
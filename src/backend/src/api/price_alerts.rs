@@ -0,0 +1,97 @@
+use ic_cdk::{
+    api::{msg_caller, time},
+    query, update,
+};
+use shared::types::{
+    price_alert::{
+        CreatePriceAlertRequest, DeletePriceAlertRequest, PriceAlert, PriceAlertError,
+        TriggeredPriceAlert,
+    },
+    result_types::{CreatePriceAlertResult, DeletePriceAlertResult},
+};
+
+use crate::{
+    exchange::alerts,
+    state::{mutate_state, read_state},
+    utils::{
+        guards::{caller_is_not_anonymous, caller_is_registered_user},
+        rate_limiter::{self, CREATE_PRICE_ALERT_RATE_LIMITER},
+    },
+};
+
+/// Creates a price alert for the caller.
+///
+/// Alerts are evaluated by the exchange-rate refresh whenever a new rate of the token is stored.
+/// An alert triggers when its condition starts to hold and is then recorded in the caller's inbox
+/// (see `get_triggered_price_alerts`); it re-arms once the condition no longer holds. While nobody
+/// requests the token's rate, it is refreshed for its alerts at a lower cadence, so an alert may
+/// trigger several minutes late.
+///
+/// # Errors
+/// - `RateLimited` if the caller creates alerts too quickly.
+/// - `TokenNotPriceable` if no exchange rate is ever fetched for the token.
+/// - `InvalidThreshold` if the condition can never hold.
+/// - `TooManyAlerts` if the caller already has `MAX_PRICE_ALERTS_PER_USER` alerts.
+#[update(guard = "caller_is_registered_user")]
+#[must_use]
+pub fn create_price_alert(request: CreatePriceAlertRequest) -> CreatePriceAlertResult {
+    if let Err(e) = CREATE_PRICE_ALERT_RATE_LIMITER.with(rate_limiter::RateLimiter::check_caller) {
+        return Err(PriceAlertError::RateLimited(e)).into();
+    }
+
+    let CreatePriceAlertRequest {
+        token_id,
+        condition,
+    } = request;
+    mutate_state(|s| {
+        alerts::create_alert(
+            &mut s.price_alerts,
+            &mut s.price_alerts_by_token,
+            msg_caller(),
+            token_id,
+            condition,
+            time(),
+        )
+    })
+    .into()
+}
+
+/// Deletes one of the caller's price alerts. Its triggered alerts stay in the inbox.
+///
+/// # Errors
+/// - `AlertNotFound` if the caller has no alert with that id.
+#[update(guard = "caller_is_registered_user")]
+#[must_use]
+pub fn delete_price_alert(request: DeletePriceAlertRequest) -> DeletePriceAlertResult {
+    mutate_state(|s| {
+        alerts::delete_alert(
+            &mut s.price_alerts,
+            &mut s.price_alerts_by_token,
+            msg_caller(),
+            request.alert_id,
+        )
+    })
+    .into()
+}
+
+/// Returns the caller's price alerts, oldest first.
+#[query(guard = "caller_is_not_anonymous")]
+#[must_use]
+pub fn get_price_alerts() -> Vec<PriceAlert> {
+    read_state(|s| alerts::list_alerts(&s.price_alerts, msg_caller()))
+}
+
+/// Returns the caller's inbox of triggered price alerts, newest first.
+///
+/// The inbox keeps the latest `MAX_TRIGGERED_PRICE_ALERTS_PER_USER` entries.
+#[query(guard = "caller_is_not_anonymous")]
+#[must_use]
+pub fn get_triggered_price_alerts() -> Vec<TriggeredPriceAlert> {
+    read_state(|s| alerts::triggered_alerts(&s.triggered_price_alerts, msg_caller()))
+}
+
+/// Empties the caller's inbox of triggered price alerts.
+#[update(guard = "caller_is_registered_user")]
+pub fn clear_triggered_price_alerts() {
+    mutate_state(|s| alerts::clear_triggered_alerts(&mut s.triggered_price_alerts, msg_caller()));
+}
@@ -1,12 +1,17 @@
 use ic_cdk::{api::msg_caller, query, update};
 use shared::types::{
-    result_types::{GetUserTransactionsResult, SaveUserTransactionsResult},
-    user_transaction::{GetUserTransactionsRequest, SaveUserTransactionsRequest},
+    result_types::{
+        ExportUserTransactionsResult, GetUserActivityResult, GetUserTransactionsResult,
+        SaveUserTransactionsResult,
+    },
+    user_transaction::{
+        ExportUserTransactionsRequest, GetUserActivityRequest, GetUserTransactionsRequest,
+        SaveUserTransactionsRequest,
+    },
 };
 
 use crate::{
-    state::{mutate_state, read_state},
-    transactions::model,
+    transactions::service,
     utils::guards::{caller_is_not_anonymous, caller_is_registered_user},
 };
 
@@ -20,48 +25,50 @@ use crate::{
 #[query(guard = "caller_is_not_anonymous")]
 #[must_use]
 pub fn get_user_transactions(request: GetUserTransactionsRequest) -> GetUserTransactionsResult {
-    let GetUserTransactionsRequest {
-        token_id,
-        start,
-        max_results,
-    } = request;
+    let response = service::get_transactions(msg_caller(), request);
 
-    let principal = msg_caller();
+    GetUserTransactionsResult::Ok(response)
+}
 
-    let response = read_state(|state| {
-        model::get_transactions(
-            &state.user_transactions,
-            principal,
-            &token_id,
-            start,
-            max_results,
-        )
-    });
+/// Retrieves the caller's stored finalized transactions across all (or the requested) tokens,
+/// sorted by timestamp, newest first, with cursor-based pagination.
+///
+/// # Returns
+/// - `Ok(GetUserActivityResponse)` with the requested page of transactions.
+///
+/// Currently, this function always returns `Ok` for valid (non-anonymous) calls.
+/// The `Err(UserTransactionError)` variant is reserved for future validation logic.
+#[query(guard = "caller_is_not_anonymous")]
+#[must_use]
+pub fn get_user_activity(request: GetUserActivityRequest) -> GetUserActivityResult {
+    let response = service::get_activity(msg_caller(), request);
 
-    GetUserTransactionsResult::Ok(response)
+    GetUserActivityResult::Ok(response)
 }
 
-/// Saves finalized transactions for the caller. Transactions are deduplicated by hash.
+/// Exports the caller's stored finalized transactions as CSV or JSON, for example for tax
+/// reporting, in chunks of up to `MAX_EXPORT_USER_TRANSACTIONS_ROWS` rows.
+///
+/// Call again with `next_cursor` until it is `None` and concatenate the chunks' CSV content, or
+/// the arrays of the JSON chunks.
+///
+/// # Errors
+/// Errors are enumerated by: `UserTransactionError`.
+#[query(guard = "caller_is_not_anonymous")]
+pub fn export_user_transactions(
+    request: ExportUserTransactionsRequest,
+) -> ExportUserTransactionsResult {
+    service::export_transactions(msg_caller(), request).into()
+}
+
+/// Saves finalized transactions for the caller. Transactions are deduplicated by `id`.
+///
+/// Requests with a transaction whose `block_index` or `timestamp` is `u64::MAX`, reserved by the
+/// backend, are rejected as invalid arguments.
 ///
 /// # Errors
 /// Errors are enumerated by: `UserTransactionError`.
 #[update(guard = "caller_is_registered_user")]
 pub fn save_user_transactions(request: SaveUserTransactionsRequest) -> SaveUserTransactionsResult {
-    let SaveUserTransactionsRequest {
-        token_id,
-        transactions,
-    } = request;
-
-    let principal = msg_caller();
-
-    let result = mutate_state(|state| {
-        model::save_transactions(
-            &mut state.user_transactions,
-            principal,
-            &token_id,
-            &transactions,
-        )
-    });
-
-    result.into()
+    service::save_transactions(msg_caller(), request).into()
 }
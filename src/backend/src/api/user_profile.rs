@@ -1,16 +1,24 @@
-use ic_cdk::{api::msg_caller, query, update};
+use ic_cdk::{
+    api::{data_certificate, msg_caller},
+    query, update,
+};
 use shared::types::{
     agreement::{
         GetAgreementHistoryError, UpdateProviderAgreementsRequest, UpdateUserAgreementsRequest,
     },
+    bitcoin::UpdateBtcSettingsRequest,
+    certification::{CertifiedUserData, USER_PROFILE_LABEL},
     dapp::{AddDappSettingsError, AddHiddenDappIdRequest},
+    exchange::UpdateFiatSettingsRequest,
     experimental_feature::UpdateExperimentalFeaturesSettingsRequest,
     network::{SaveNetworksSettingsRequest, SetShowTestnetsRequest},
     notification::{AddDismissedNotificationError, AddDismissedNotificationRequest},
+    portfolio::UpdatePortfolioSettingsRequest,
     result_types::{
         AddUserDismissedNotificationResult, AddUserHiddenDappIdResult, CreateUserProfileResult,
         GetAgreementHistoryResult, GetUserProfileResult, SetUserShowTestnetsResult,
-        UpdateExperimentalFeaturesSettingsResult, UpdateProviderAgreementsResult,
+        UpdateBtcSettingsResult, UpdateExperimentalFeaturesSettingsResult,
+        UpdateFiatSettingsResult, UpdatePortfolioSettingsResult, UpdateProviderAgreementsResult,
         UpdateTransactionFilterSettingsResult, UpdateUserAgreementsResult,
         UpdateUserNetworkSettingsResult,
     },
@@ -19,6 +27,8 @@ use shared::types::{
 };
 
 use crate::{
+    certification::users::{certified_user_data, certify_user, user_profile_data},
+    portfolio,
     state::{self, mutate_state, read_state},
     types::StoredPrincipal,
     user_profile::{model::UserProfileModel, service},
@@ -26,7 +36,7 @@ use crate::{
 };
 
 /// Updates the user's preference to enable (or disable) networks in the interface, merging with any
-/// existing settings.
+/// existing settings. The testnet flag of EVM networks is set from the chain registry.
 ///
 /// # Returns
 /// - Returns `Ok(())` if the network settings were updated successfully, or if they were already
@@ -307,6 +317,95 @@ pub fn update_user_transaction_filter_settings(
     .into()
 }
 
+/// Updates the user's portfolio snapshot settings.
+///
+/// Disabling snapshots deletes the snapshots saved so far.
+///
+/// # Returns
+/// - Returns `Ok(())` if the portfolio settings were updated successfully, or if they were already
+///   set to the same value.
+///
+/// # Errors
+/// - Returns `Err` if the user profile is not found, or the user profile version is not up-to-date.
+#[update(guard = "caller_is_registered_user")]
+#[must_use]
+pub fn update_user_portfolio_settings(
+    request: UpdatePortfolioSettingsRequest,
+) -> UpdatePortfolioSettingsResult {
+    let user_principal = msg_caller();
+    let stored_principal = StoredPrincipal(user_principal);
+    let snapshots_enabled = request.settings.snapshots_enabled;
+
+    let result = mutate_state(|s| {
+        let mut user_profile_model =
+            UserProfileModel::new(&mut s.user_profile, &mut s.user_profile_updated);
+        service::update_portfolio_settings(
+            stored_principal,
+            request.current_user_version,
+            request.settings,
+            &mut user_profile_model,
+        )
+    });
+    if result.is_ok() && !snapshots_enabled {
+        portfolio::service::delete_snapshots(user_principal);
+    }
+    result.into()
+}
+
+/// Updates the user's preferred fiat currency, in which `get_exchange_rates` returns prices next to
+/// USD.
+///
+/// # Returns
+/// - Returns `Ok(())` if the fiat settings were updated successfully, or if they were already set
+///   to the same value.
+///
+/// # Errors
+/// - Returns `Err` if the user profile is not found, or the user profile version is not up-to-date.
+#[update(guard = "caller_is_registered_user")]
+#[must_use]
+pub fn update_user_fiat_settings(request: UpdateFiatSettingsRequest) -> UpdateFiatSettingsResult {
+    let stored_principal = StoredPrincipal(msg_caller());
+
+    mutate_state(|s| {
+        let mut user_profile_model =
+            UserProfileModel::new(&mut s.user_profile, &mut s.user_profile_updated);
+        service::update_fiat_settings(
+            stored_principal,
+            request.current_user_version,
+            request.settings,
+            &mut user_profile_model,
+        )
+    })
+    .into()
+}
+
+/// Updates the user's preferred BTC address type, used by the BTC endpoints when a request does
+/// not name one.
+///
+/// # Returns
+/// - Returns `Ok(())` if the BTC settings were updated successfully, or if they were already set
+///   to the same value.
+///
+/// # Errors
+/// - Returns `Err` if the user profile is not found, or the user profile version is not up-to-date.
+#[update(guard = "caller_is_registered_user")]
+#[must_use]
+pub fn update_user_btc_settings(request: UpdateBtcSettingsRequest) -> UpdateBtcSettingsResult {
+    let stored_principal = StoredPrincipal(msg_caller());
+
+    mutate_state(|s| {
+        let mut user_profile_model =
+            UserProfileModel::new(&mut s.user_profile, &mut s.user_profile_updated);
+        service::update_btc_settings(
+            stored_principal,
+            request.current_user_version,
+            request.settings,
+            &mut user_profile_model,
+        )
+    })
+    .into()
+}
+
 /// It creates a new user profile for the caller.
 /// If the user has already a profile, it will return that profile.
 ///
@@ -330,6 +429,8 @@ pub fn create_user_profile() -> CreateUserProfileResult {
 
         UserProfile::from(&stored_user)
     });
+    // Certifies the new user's (empty) contacts and custom tokens next to the profile.
+    read_state(|s| certify_user(s, stored_principal));
 
     Ok::<UserProfile, CreateUserProfileError>(user_profile).into()
 }
@@ -377,6 +478,32 @@ pub fn get_user_profile() -> GetUserProfileResult {
     .into()
 }
 
+/// Returns the caller's user profile, Candid-encoded, with the IC certificate and witness proving
+/// that the canister stores it, so it can be trusted without trusting the replica that answered.
+/// Verify it with `shared::types::certification::verify_certified_user_profile`.
+///
+/// Returns `None` without a profile, when called as an update, where no certificate is available,
+/// and right after an upgrade until the caller's records are certified again.
+#[query(guard = "caller_is_not_anonymous")]
+#[must_use]
+pub fn get_certified_user_profile() -> Option<CertifiedUserData> {
+    let certificate = data_certificate()?;
+    let stored_principal = StoredPrincipal(msg_caller());
+
+    let stored_user = mutate_state(|s| {
+        let user_profile_model =
+            UserProfileModel::new(&mut s.user_profile, &mut s.user_profile_updated);
+        service::find_profile(stored_principal, &user_profile_model).ok()
+    })?;
+
+    certified_user_data(
+        stored_principal,
+        USER_PROFILE_LABEL,
+        user_profile_data(&stored_user),
+        certificate,
+    )
+}
+
 /// Checks if the caller has an associated user profile.
 ///
 /// # Returns
@@ -15,7 +15,7 @@ use shared::{
             UpdateActiveUserTransactionRequest,
         },
         agreement::{UserAgreement, UserAgreements},
-        bitcoin::{PendingTransaction, StoredPendingTransaction},
+        bitcoin::{PendingTransaction, PendingTransactionStatus, StoredPendingTransaction},
         contact::{Contact, StoredContacts},
         custom_token::{CustomToken, CustomTokenId, ErcToken, ErcTokenId, Token},
         exchange::{ExchangeData, ExchangeRate},
@@ -24,6 +24,13 @@ use shared::{
         personal_note::{DeletePersonalNoteRequest, SetPersonalNoteRequest},
         token_id::TokenId,
         user_profile::{StoredUserProfile, UserProfile},
+        user_transaction::{
+            ExportUserTransactionsRequest, GetUserActivityRequest, GetUserTransactionsRequest,
+            IcrcTransactionData, IcrcTransactionType, NetworkTransactionData,
+            SaveUserTransactionsRequest, UserTransaction, UserTransactionCursor,
+            UserTransactionExportFormat, UserTransactionFilter, MAX_GET_USER_TRANSACTIONS_RESULTS,
+            MAX_SAVE_USER_TRANSACTIONS_BATCH, MAX_USER_TRANSACTIONS_PER_TOKEN,
+        },
         Stats,
     },
 };
@@ -31,11 +38,12 @@ use shared::{
 use crate::{
     active_user_transactions::model as active_user_transactions_model,
     api::admin::http_request,
-    bitcoin::pending_tx_model::BtcUserPendingTransactionsModel,
+    bitcoin::pending_tx_model::{confirmations, BtcUserPendingTransactionsModel},
     personal_notes::service as personal_notes_service,
     state::{mutate_state, read_config, read_state, State},
     token,
-    types::{Candid, StoredPrincipal, StoredTokenId},
+    transactions::{model as transactions_model, service as transactions_service},
+    types::{Candid, StoredPrincipal, StoredTokenId, UserTransactionKey},
     user_profile::{self, model::UserProfileModel},
 };
 
@@ -566,6 +574,8 @@ fn bench_btc_add_pending_transaction_with_count(existing_count: u8) -> BenchResu
                     txid: vec![i; 32],
                     utxos: vec![utxo.clone()],
                     created_at_timestamp_ns: 1_000_000_000,
+                    details: None,
+                    status: None,
                 };
                 model
                     .add_pending_transaction(principal, address.clone(), tx)
@@ -591,7 +601,13 @@ fn bench_btc_add_pending_transaction_with_count(existing_count: u8) -> BenchResu
     bench_fn(|| {
         mutate_state(|state| {
             with_btc_pending_model(state, |model| {
-                model.prune_pending_transactions(principal, &current_utxos, now_ns);
+                model.update_pending_transactions(
+                    principal,
+                    &address,
+                    &current_utxos,
+                    HEIGHT,
+                    now_ns,
+                );
 
                 assert!(
                     !model.has_intersecting_pending_utxos(principal, &new_utxos),
@@ -602,6 +618,8 @@ fn bench_btc_add_pending_transaction_with_count(existing_count: u8) -> BenchResu
                     txid: txid.clone(),
                     utxos: new_utxos.clone(),
                     created_at_timestamp_ns: now_ns,
+                    details: None,
+                    status: None,
                 };
 
                 model
@@ -635,6 +653,8 @@ fn bench_btc_get_pending_transactions_with_count(count: u8) -> BenchResult {
                     txid: vec![i; 32],
                     utxos: vec![utxo.clone()],
                     created_at_timestamp_ns: 1_000_000_000,
+                    details: None,
+                    status: None,
                 };
 
                 model
@@ -649,16 +669,21 @@ fn bench_btc_get_pending_transactions_with_count(count: u8) -> BenchResult {
     bench_fn(|| {
         let stored = mutate_state(|state| {
             with_btc_pending_model(state, |model| {
-                model.prune_pending_transactions(principal, &utxos, now_ns);
+                model.update_pending_transactions(principal, &address, &utxos, HEIGHT, now_ns);
                 model.get_pending_transactions(&principal, &address)
             })
         });
 
         let pending: Vec<PendingTransaction> = stored
             .iter()
-            .map(|tx| PendingTransaction {
-                txid: tx.txid.clone(),
-                utxos: tx.utxos.clone(),
+            .map(|tx| {
+                let status = tx.status.unwrap_or(PendingTransactionStatus::Pending);
+                PendingTransaction {
+                    txid: tx.txid.clone(),
+                    utxos: tx.utxos.clone(),
+                    status,
+                    confirmations: confirmations(status, HEIGHT),
+                }
             })
             .collect();
 
@@ -874,7 +899,11 @@ fn seed_exchange_rates(count: u64) {
                         price: Some(1.23 + f64::from(u32::try_from(i).unwrap_or_default())),
                         price_24h_change_pct: Some(0.5),
                         market_cap: Some(1e9),
+                        source: None,
+                        sources: None,
+                        stale: None,
                     },
+                    fiat: None,
                 }),
             );
         }
@@ -907,3 +936,221 @@ fn bench_get_exchange_rates_50() -> BenchResult {
         }));
     })
 }
+
+// ---------------------------------------------------------------------------
+// User transactions
+// ---------------------------------------------------------------------------
+
+fn make_user_transaction(block_index: u64) -> UserTransaction {
+    UserTransaction {
+        id: format!("{block_index:064x}"),
+        block_index,
+        timestamp: block_index,
+        from: format!("0x{:040x}", 1),
+        to: Some(format!("0x{:040x}", 2)),
+        value: Nat::from(1_000_000_u64),
+        network_data: NetworkTransactionData::Icrc(IcrcTransactionData {
+            fee: Some(Nat::from(10_000_u64)),
+            memo: None,
+            tx_type: IcrcTransactionType::Transfer,
+        }),
+    }
+}
+
+fn seed_user_transactions(count: u64) {
+    let principal = *bench_principal();
+    let transactions: Vec<UserTransaction> = (0..count).map(make_user_transaction).collect();
+    for batch in transactions.chunks(MAX_SAVE_USER_TRANSACTIONS_BATCH) {
+        transactions_service::save_transactions(
+            principal,
+            SaveUserTransactionsRequest {
+                token_id: TokenId::IcpNative,
+                transactions: batch.to_vec(),
+            },
+        )
+        .expect("bench: seeding user transactions failed");
+    }
+}
+
+fn get_user_transactions_request(
+    cursor: Option<UserTransactionCursor>,
+) -> GetUserTransactionsRequest {
+    GetUserTransactionsRequest {
+        token_id: TokenId::IcpNative,
+        start: None,
+        max_results: MAX_GET_USER_TRANSACTIONS_RESULTS,
+        cursor,
+        filter: None,
+    }
+}
+
+#[bench(raw)]
+fn bench_get_user_transactions_newest_page_10000() -> BenchResult {
+    seed_user_transactions(MAX_USER_TRANSACTIONS_PER_TOKEN as u64);
+    let principal = *bench_principal();
+
+    bench_fn(|| {
+        std::hint::black_box(transactions_service::get_transactions(
+            principal,
+            get_user_transactions_request(None),
+        ));
+    })
+}
+
+#[bench(raw)]
+fn bench_get_user_transactions_cursor_page_10000() -> BenchResult {
+    seed_user_transactions(MAX_USER_TRANSACTIONS_PER_TOKEN as u64);
+    let principal = *bench_principal();
+    let cursor = UserTransactionCursor {
+        block_index: 100,
+        id: format!("{:064x}", 100),
+    };
+
+    bench_fn(|| {
+        std::hint::black_box(transactions_service::get_transactions(
+            principal,
+            get_user_transactions_request(Some(cursor.clone())),
+        ));
+    })
+}
+
+#[bench(raw)]
+fn bench_get_user_transactions_filtered_no_match_10000() -> BenchResult {
+    seed_user_transactions(MAX_USER_TRANSACTIONS_PER_TOKEN as u64);
+    let principal = *bench_principal();
+
+    bench_fn(|| {
+        std::hint::black_box(transactions_service::get_transactions(
+            principal,
+            GetUserTransactionsRequest {
+                filter: Some(UserTransactionFilter {
+                    from: Some("no-such-address".to_string()),
+                    ..UserTransactionFilter::default()
+                }),
+                ..get_user_transactions_request(None)
+            },
+        ));
+    })
+}
+
+fn seed_user_activity(token_count: u64, transactions_per_token: u64) {
+    let principal = *bench_principal();
+    for chain_id in 0..token_count {
+        let transactions: Vec<UserTransaction> = (0..transactions_per_token)
+            .map(make_user_transaction)
+            .collect();
+        for batch in transactions.chunks(MAX_SAVE_USER_TRANSACTIONS_BATCH) {
+            transactions_service::save_transactions(
+                principal,
+                SaveUserTransactionsRequest {
+                    token_id: TokenId::EvmNative(chain_id),
+                    transactions: batch.to_vec(),
+                },
+            )
+            .expect("bench: seeding user activity failed");
+        }
+    }
+}
+
+fn bench_get_user_activity_with_filter(token_ids: Option<&[TokenId]>) -> BenchResult {
+    seed_user_activity(10, 1_000);
+    let principal = *bench_principal();
+
+    bench_fn(|| {
+        std::hint::black_box(transactions_service::get_activity(
+            principal,
+            GetUserActivityRequest {
+                token_ids: token_ids.map(<[TokenId]>::to_vec),
+                max_results: MAX_GET_USER_TRANSACTIONS_RESULTS,
+                cursor: None,
+                filter: None,
+            },
+        ));
+    })
+}
+
+#[bench(raw)]
+fn bench_get_user_activity_10_tokens() -> BenchResult {
+    bench_get_user_activity_with_filter(None)
+}
+
+#[bench(raw)]
+fn bench_get_user_activity_10_tokens_filtered_to_1() -> BenchResult {
+    bench_get_user_activity_with_filter(Some(&[TokenId::EvmNative(0)]))
+}
+
+fn bench_export_user_transactions_with_format(format: UserTransactionExportFormat) -> BenchResult {
+    seed_user_activity(10, 1_000);
+    let principal = *bench_principal();
+
+    bench_fn(|| {
+        std::hint::black_box(
+            transactions_service::export_transactions(
+                principal,
+                ExportUserTransactionsRequest {
+                    format,
+                    token_ids: None,
+                    min_timestamp: None,
+                    max_timestamp: None,
+                    cursor: None,
+                },
+            )
+            .expect("bench: export_user_transactions failed"),
+        );
+    })
+}
+
+#[bench(raw)]
+fn bench_export_user_transactions_csv_chunk() -> BenchResult {
+    bench_export_user_transactions_with_format(UserTransactionExportFormat::Csv)
+}
+
+#[bench(raw)]
+fn bench_export_user_transactions_json_chunk() -> BenchResult {
+    bench_export_user_transactions_with_format(UserTransactionExportFormat::Json)
+}
+
+#[bench(raw)]
+fn bench_save_user_transactions_batch_into_full_list() -> BenchResult {
+    let existing = MAX_USER_TRANSACTIONS_PER_TOKEN as u64;
+    seed_user_transactions(existing);
+    let principal = *bench_principal();
+    let request = SaveUserTransactionsRequest {
+        token_id: TokenId::IcpNative,
+        transactions: (existing..existing + MAX_SAVE_USER_TRANSACTIONS_BATCH as u64)
+            .map(make_user_transaction)
+            .collect(),
+    };
+
+    bench_fn(|| {
+        transactions_service::save_transactions(principal, request.clone())
+            .expect("bench: save_user_transactions failed");
+    })
+}
+
+#[bench(raw)]
+fn bench_migrate_legacy_user_transactions_10000() -> BenchResult {
+    mutate_state(|s| {
+        s.legacy_user_transactions.insert(
+            UserTransactionKey(bench_stored_principal(), StoredTokenId(TokenId::IcpNative)),
+            Candid(
+                (0..MAX_USER_TRANSACTIONS_PER_TOKEN as u64)
+                    .map(make_user_transaction)
+                    .collect(),
+            ),
+        );
+    });
+
+    bench_fn(|| {
+        mutate_state(|s| {
+            std::hint::black_box(transactions_model::migrate_legacy_transactions(
+                &mut s.legacy_user_transactions,
+                &mut s.user_transactions,
+                &mut s.user_transaction_counts,
+                &mut s.user_transaction_ids,
+                &mut s.user_activity_index,
+                MAX_USER_TRANSACTIONS_PER_TOKEN,
+            ));
+        });
+    })
+}
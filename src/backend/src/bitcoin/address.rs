@@ -0,0 +1,189 @@
+//! The addresses of a user, of each `BtcAddressType`, and what spending from them takes.
+use bitcoin::{
+    key::Secp256k1, psbt::Input, Address, CompressedPublicKey, ScriptBuf, XOnlyPublicKey,
+};
+use ic_cdk::bitcoin_canister::Network as BitcoinNetwork;
+use shared::types::bitcoin::BtcAddressType;
+
+use crate::signer::transform_network;
+
+/// Weight of a P2WPKH witness: the item count, a DER signature of maximal length with its
+/// sighash byte and a compressed public key, each with their length prefix.
+const P2WPKH_WITNESS_WEIGHT: u64 = 1 + (1 + 72) + (1 + 33);
+/// Weight of the P2SH-P2WPKH script sig, pushing the 22-byte P2WPKH redeem script.
+const P2SH_P2WPKH_SCRIPT_SIG_WEIGHT: u64 = 4 * (1 + 22);
+/// Weight of a Taproot key path witness: the item count and a Schnorr signature with the default
+/// sighash, with its length prefix.
+const P2TR_KEY_PATH_WITNESS_WEIGHT: u64 = 1 + (1 + 64);
+
+/// An address of the user that transactions are built to spend from.
+#[derive(Clone, Debug)]
+pub struct SourceAddress {
+    address_type: BtcAddressType,
+    public_key: CompressedPublicKey,
+    address: Address,
+}
+
+impl SourceAddress {
+    /// Derives the address of `address_type` from the signer's `public_key`: its ECDSA key for
+    /// P2WPKH and P2SH-P2WPKH, and its BIP-340 Schnorr key, used as the untweaked internal key, for
+    /// P2TR.
+    ///
+    /// # Errors
+    /// - The public key is not a valid compressed secp256k1 key.
+    pub fn new(
+        address_type: BtcAddressType,
+        public_key: &[u8],
+        network: BitcoinNetwork,
+    ) -> Result<Self, String> {
+        let public_key = CompressedPublicKey::from_slice(public_key)
+            .map_err(|err| format!("Invalid secp256k1 public key: {err}"))?;
+        let network = transform_network(network);
+        let address = match address_type {
+            BtcAddressType::P2wpkh => Address::p2wpkh(&public_key, network),
+            BtcAddressType::P2shP2wpkh => Address::p2shwpkh(&public_key, network),
+            BtcAddressType::P2tr => Address::p2tr(
+                &Secp256k1::verification_only(),
+                XOnlyPublicKey::from(public_key.0),
+                None,
+                network,
+            ),
+        };
+        Ok(Self {
+            address_type,
+            public_key,
+            address,
+        })
+    }
+
+    pub fn address(&self) -> &Address {
+        &self.address
+    }
+
+    pub fn script_pubkey(&self) -> ScriptBuf {
+        self.address.script_pubkey()
+    }
+
+    /// Weight added to an input by spending an output of this address: its script sig and its
+    /// witness, assuming signatures of maximal length.
+    pub fn satisfaction_weight(&self) -> u64 {
+        match self.address_type {
+            BtcAddressType::P2wpkh => P2WPKH_WITNESS_WEIGHT,
+            BtcAddressType::P2shP2wpkh => P2SH_P2WPKH_SCRIPT_SIG_WEIGHT + P2WPKH_WITNESS_WEIGHT,
+            BtcAddressType::P2tr => P2TR_KEY_PATH_WITNESS_WEIGHT,
+        }
+    }
+
+    /// The script sig of a signed input spending an output of this address. Unlike the witness, it
+    /// does not depend on the signature, and as it is part of the txid, it is known before signing.
+    pub fn script_sig(&self) -> ScriptBuf {
+        match self.address_type {
+            BtcAddressType::P2wpkh | BtcAddressType::P2tr => ScriptBuf::new(),
+            BtcAddressType::P2shP2wpkh => {
+                // A P2WPKH script is always 22 bytes long.
+                let mut redeem_script = [0; 22];
+                redeem_script.copy_from_slice(self.redeem_script().as_bytes());
+                ScriptBuf::builder().push_slice(redeem_script).into_script()
+            }
+        }
+    }
+
+    /// Sets the fields a signer needs, besides the spent output, to sign a PSBT input spending an
+    /// output of this address.
+    pub fn fill_psbt_input(&self, input: &mut Input) {
+        match self.address_type {
+            BtcAddressType::P2wpkh => {}
+            BtcAddressType::P2shP2wpkh => {
+                input.redeem_script = Some(self.redeem_script());
+            }
+            BtcAddressType::P2tr => {
+                input.tap_internal_key = Some(XOnlyPublicKey::from(self.public_key.0));
+            }
+        }
+    }
+
+    /// The P2WPKH script a P2SH-P2WPKH output commits to.
+    fn redeem_script(&self) -> ScriptBuf {
+        ScriptBuf::new_p2wpkh(&self.public_key.wpubkey_hash())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    const PUBKEY: &str = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+    /// The internal key of the first BIP-86 test vector.
+    const TAPROOT_PUBKEY: &str =
+        "02cc8a4bc64d897bddc5fbc2f670f7a8ba0b386779106cf1223c6fc5d7cd6fc115";
+
+    fn source(address_type: BtcAddressType) -> SourceAddress {
+        let pubkey = match address_type {
+            BtcAddressType::P2wpkh | BtcAddressType::P2shP2wpkh => PUBKEY,
+            BtcAddressType::P2tr => TAPROOT_PUBKEY,
+        };
+        SourceAddress::new(
+            address_type,
+            &hex::decode(pubkey).unwrap(),
+            BitcoinNetwork::Mainnet,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_derives_address_of_each_type() {
+        assert_eq!(
+            source(BtcAddressType::P2wpkh).address().to_string(),
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"
+        );
+        assert_eq!(
+            source(BtcAddressType::P2shP2wpkh).address().to_string(),
+            "3JvL6Ymt8MVWiCNHC7oWU6nLeHNJKLZGLN"
+        );
+        assert_eq!(
+            source(BtcAddressType::P2tr).address().to_string(),
+            "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr"
+        );
+    }
+
+    #[test]
+    fn test_rejects_invalid_public_key() {
+        assert!(
+            SourceAddress::new(BtcAddressType::P2tr, &[2; 32], BitcoinNetwork::Mainnet).is_err()
+        );
+    }
+
+    #[test]
+    fn test_script_sig_of_each_type() {
+        assert!(source(BtcAddressType::P2wpkh).script_sig().is_empty());
+        assert!(source(BtcAddressType::P2tr).script_sig().is_empty());
+        assert_eq!(
+            source(BtcAddressType::P2shP2wpkh)
+                .script_sig()
+                .to_hex_string(),
+            "160014751e76e8199196d454941c45d1b3a323f1433bd6"
+        );
+    }
+
+    #[test]
+    fn test_fills_psbt_input_of_each_type() {
+        let mut input = Input::default();
+        source(BtcAddressType::P2wpkh).fill_psbt_input(&mut input);
+        assert_eq!(input, Input::default());
+
+        source(BtcAddressType::P2shP2wpkh).fill_psbt_input(&mut input);
+        assert!(input
+            .redeem_script
+            .as_ref()
+            .is_some_and(|script| script.is_p2wpkh()));
+
+        let mut input = Input::default();
+        source(BtcAddressType::P2tr).fill_psbt_input(&mut input);
+        assert_eq!(
+            input.tap_internal_key.map(|key| key.to_string()),
+            Some(TAPROOT_PUBKEY[2..].to_string())
+        );
+    }
+}
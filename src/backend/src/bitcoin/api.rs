@@ -41,22 +41,49 @@ async fn get_utxos(
     .await
     .map_err(|err| err.to_string())
 }
-/// Returns all the UTXOs of a specific address.
+/// Number of confirmations after which a BTC transaction is considered final.
+pub const MIN_CONFIRMATIONS_ACCEPTED_BTC_TX: u32 = 6;
+
+/// The UTXOs of an address together with the height of the chain tip they were read at.
+pub struct AddressUtxos {
+    pub utxos: Vec<Utxo>,
+    pub tip_height: u32,
+}
+
+impl AddressUtxos {
+    /// Returns the UTXOs with at least `min_confirmations` confirmations.
+    ///
+    /// Regtest returns all UTXOs: tests with Regtest fail if `min_confirmations` is higher than 1.
+    pub fn with_min_confirmations(
+        &self,
+        network: BitcoinNetwork,
+        min_confirmations: u32,
+    ) -> Vec<Utxo> {
+        self.utxos
+            .iter()
+            .filter(|utxo| {
+                network == BitcoinNetwork::Regtest
+                    || (self.tip_height + 1).saturating_sub(utxo.height) >= min_confirmations
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+/// Returns all the UTXOs of a specific address with at least one confirmation.
 /// API interface returns a paginated view of the utxos but we need to get them all.
-pub async fn get_all_utxos(
+pub async fn get_address_utxos(
     network: BitcoinNetwork,
     address: String,
-    min_confirmations: Option<u32>,
-) -> Result<Vec<Utxo>, String> {
-    let final_min_confirmations = if network == BitcoinNetwork::Regtest {
-        // Tests with Regtest fail if min_confirmations is higher than 1.
-        Some(1)
-    } else {
-        min_confirmations
-    };
-    let filter = final_min_confirmations.map(UtxosFilter::MinConfirmations);
-    let mut utxos_response = get_utxos(network, address.clone(), filter).await?;
+) -> Result<AddressUtxos, String> {
+    let mut utxos_response = get_utxos(
+        network,
+        address.clone(),
+        Some(UtxosFilter::MinConfirmations(1)),
+    )
+    .await?;
 
+    let tip_height = utxos_response.tip_height;
     let mut all_utxos: Vec<Utxo> = utxos_response.utxos;
     let mut next_page: Option<Vec<u8>> = utxos_response.next_page;
     while next_page.is_some() {
@@ -66,7 +93,10 @@ pub async fn get_all_utxos(
         next_page = utxos_response.next_page;
     }
 
-    Ok(all_utxos)
+    Ok(AddressUtxos {
+        utxos: all_utxos,
+        tip_height,
+    })
 }
 
 /// Spawns a fee-cache update only if no previous update is still in flight.
@@ -197,6 +227,16 @@ pub fn get_current_fee_percentiles(network: BitcoinNetwork) -> Vec<MillisatoshiP
     }
 }
 
+/// Returns the fee rate at `percentile` of the current fee percentiles, or the highest fee rate if
+/// fewer percentiles are available.
+pub fn get_fee_rate_at_percentile(network: BitcoinNetwork, percentile: u8) -> MillisatoshiPerByte {
+    let fee_percentiles = get_current_fee_percentiles(network);
+    fee_percentiles
+        .get(usize::from(percentile).min(fee_percentiles.len().saturating_sub(1)))
+        .copied()
+        .unwrap_or_default()
+}
+
 /// Returns the default fee in millisatoshis per byte for a given Bitcoin network.
 /// This is used when actual fee data is not available from the Bitcoin API.
 fn get_default_fee_for_network(network: BitcoinNetwork) -> u64 {
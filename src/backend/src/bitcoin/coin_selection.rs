@@ -0,0 +1,572 @@
+//! Coin-selection strategies choosing the UTXOs a transaction spends.
+//!
+//! Every strategy is deterministic: the same UTXO set and target always yield the same selection,
+//! independently of the order in which the UTXOs are given.
+
+use std::{cmp::Reverse, collections::BTreeMap};
+
+use bitcoin::ScriptBuf;
+use ic_cdk::bitcoin_canister::{MillisatoshiPerByte, Utxo};
+use shared::types::bitcoin::{BtcCoinSelection, MAX_UTXOS_LEN};
+
+use crate::bitcoin::tx_builder::{estimate_vsize, fee_for_vsize};
+
+/// Fee rate at or below which `Consolidate` spends additional UTXOs.
+const CONSOLIDATION_MAX_FEE_RATE: MillisatoshiPerByte = 5_000;
+
+/// Number of search steps after which `BranchAndBound` gives up looking for a changeless match.
+const BRANCH_AND_BOUND_MAX_TRIES: usize = 100_000;
+
+/// A strategy choosing which UTXOs pay for a transaction.
+pub trait CoinSelection {
+    /// Selects UTXOs covering `target`, or returns `None` if `utxos` cannot cover it with at
+    /// most `MAX_UTXOS_LEN` inputs.
+    fn select(&self, utxos: &[Utxo], target: &SelectionTarget) -> Option<Selection>;
+}
+
+/// Returns the strategy implementing `kind`.
+pub fn strategy(kind: BtcCoinSelection) -> Box<dyn CoinSelection> {
+    match kind {
+        BtcCoinSelection::LargestFirst => Box::new(LargestFirst),
+        BtcCoinSelection::BranchAndBound => Box::new(BranchAndBound::default()),
+        BtcCoinSelection::PrivacyPreserving => Box::new(PrivacyPreserving),
+        BtcCoinSelection::Consolidate => Box::new(Consolidate::default()),
+    }
+}
+
+/// The payment a selection has to fund.
+pub struct SelectionTarget<'a> {
+    pub amount_satoshis: u64,
+    pub fee_rate: MillisatoshiPerByte,
+    pub recipient_script: &'a ScriptBuf,
+    pub change_script: &'a ScriptBuf,
+    /// The weight added to each input once signed.
+    pub input_satisfaction_weight: u64,
+}
+
+/// The UTXOs chosen for a transaction, with the resulting fee and change.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Selection {
+    pub utxos: Vec<Utxo>,
+    pub fee_satoshis: u64,
+    /// The change sent back to the source address, `0` if the transaction has no change output.
+    pub change_satoshis: u64,
+    pub vsize: u64,
+}
+
+impl SelectionTarget<'_> {
+    /// Computes the fee and change of a transaction spending `utxos`.
+    ///
+    /// The transaction gets a change output only if the change left after paying for it is above
+    /// the dust limit; otherwise the excess is added to the fee.
+    ///
+    /// Returns `None` if `utxos` is empty, has more than `MAX_UTXOS_LEN` entries or does not cover
+    /// the amount and the fee.
+    pub fn evaluate(&self, utxos: Vec<Utxo>) -> Option<Selection> {
+        if utxos.is_empty() || utxos.len() > MAX_UTXOS_LEN {
+            return None;
+        }
+        let total = utxos
+            .iter()
+            .fold(0u64, |total, utxo| total.saturating_add(utxo.value));
+
+        let vsize_without_change = self.estimate_vsize(utxos.len(), &[self.recipient_script]);
+        let fee_without_change = fee_for_vsize(vsize_without_change, self.fee_rate);
+        let excess = total.checked_sub(self.amount_satoshis.saturating_add(fee_without_change))?;
+
+        let vsize_with_change =
+            self.estimate_vsize(utxos.len(), &[self.recipient_script, self.change_script]);
+        let fee_with_change = fee_for_vsize(vsize_with_change, self.fee_rate);
+        let change = total.saturating_sub(self.amount_satoshis.saturating_add(fee_with_change));
+
+        Some(if change >= self.min_change() {
+            Selection {
+                utxos,
+                fee_satoshis: fee_with_change,
+                change_satoshis: change,
+                vsize: vsize_with_change,
+            }
+        } else {
+            Selection {
+                utxos,
+                fee_satoshis: fee_without_change + excess,
+                change_satoshis: 0,
+                vsize: vsize_without_change,
+            }
+        })
+    }
+
+    fn estimate_vsize(&self, n_inputs: usize, output_scripts: &[&ScriptBuf]) -> u64 {
+        estimate_vsize(n_inputs, self.input_satisfaction_weight, output_scripts)
+    }
+
+    fn min_change(&self) -> u64 {
+        self.change_script.minimal_non_dust().to_sat()
+    }
+
+    /// The virtual size added by each input.
+    fn input_vsize(&self) -> u64 {
+        self.estimate_vsize(1, &[self.recipient_script])
+            - self.estimate_vsize(0, &[self.recipient_script])
+    }
+
+    /// The value of `utxo` minus the fee of spending it, in millisatoshis.
+    fn effective_value_msat(&self, utxo: &Utxo) -> i128 {
+        i128::from(utxo.value) * 1_000 - i128::from(self.input_vsize() * self.fee_rate)
+    }
+
+    /// The effective value, in millisatoshis, that a changeless transaction has to spend.
+    fn changeless_target_msat(&self) -> i128 {
+        i128::from(self.amount_satoshis) * 1_000
+            + i128::from(self.estimate_vsize(0, &[self.recipient_script]) * self.fee_rate)
+    }
+
+    /// The excess, in millisatoshis, above which a transaction gets a change output: the fee of
+    /// the change output plus the smallest change worth keeping.
+    fn cost_of_change_msat(&self) -> i128 {
+        let change_output_vsize = self
+            .estimate_vsize(0, &[self.recipient_script, self.change_script])
+            - self.estimate_vsize(0, &[self.recipient_script]);
+        i128::from(change_output_vsize * self.fee_rate) + i128::from(self.min_change()) * 1_000
+    }
+}
+
+/// Sorts UTXOs by decreasing value, breaking ties by outpoint.
+fn sorted_largest_first(utxos: &[Utxo]) -> Vec<&Utxo> {
+    let mut sorted: Vec<&Utxo> = utxos.iter().collect();
+    sorted.sort_by(|a, b| {
+        (Reverse(a.value), &a.outpoint.txid, a.outpoint.vout).cmp(&(
+            Reverse(b.value),
+            &b.outpoint.txid,
+            b.outpoint.vout,
+        ))
+    });
+    sorted
+}
+
+/// Spends the largest UTXOs first, so that the transaction has as few inputs as possible.
+pub struct LargestFirst;
+
+impl CoinSelection for LargestFirst {
+    fn select(&self, utxos: &[Utxo], target: &SelectionTarget) -> Option<Selection> {
+        let sorted = sorted_largest_first(utxos);
+        (1..=sorted.len().min(MAX_UTXOS_LEN))
+            .find_map(|n| target.evaluate(sorted[..n].iter().copied().cloned().collect()))
+    }
+}
+
+/// Searches for UTXOs whose value matches the amount and the fee closely enough for the
+/// transaction to need no change output, which saves the fee of creating and later spending it.
+///
+/// Among the matches found within `max_tries` search steps, the one wasting the least value in
+/// fees is chosen. If there is none, falls back to `LargestFirst`.
+pub struct BranchAndBound {
+    pub max_tries: usize,
+}
+
+impl Default for BranchAndBound {
+    fn default() -> Self {
+        Self {
+            max_tries: BRANCH_AND_BOUND_MAX_TRIES,
+        }
+    }
+}
+
+impl CoinSelection for BranchAndBound {
+    fn select(&self, utxos: &[Utxo], target: &SelectionTarget) -> Option<Selection> {
+        self.select_changeless(utxos, target)
+            .or_else(|| LargestFirst.select(utxos, target))
+    }
+}
+
+impl BranchAndBound {
+    /// Runs a depth-first search over the UTXOs sorted by decreasing effective value, including
+    /// each UTXO before excluding it, and backtracking as soon as a branch either covers the
+    /// target, overshoots the changeless window or can no longer reach the target.
+    fn select_changeless(&self, utxos: &[Utxo], target: &SelectionTarget) -> Option<Selection> {
+        let mut candidates: Vec<(&Utxo, i128)> = sorted_largest_first(utxos)
+            .into_iter()
+            .map(|utxo| (utxo, target.effective_value_msat(utxo)))
+            .filter(|(_, value)| *value > 0)
+            .collect();
+        candidates.sort_by_key(|(_, value)| Reverse(*value));
+
+        let values: Vec<i128> = candidates.iter().map(|(_, value)| *value).collect();
+        let lower = target.changeless_target_msat();
+        let upper = lower + target.cost_of_change_msat();
+
+        let mut best: Option<(i128, Vec<usize>)> = None;
+        let mut selected: Vec<usize> = Vec::new();
+        let mut index = 0;
+        let mut sum: i128 = 0;
+        let mut remaining: i128 = values.iter().sum();
+
+        for _ in 0..self.max_tries {
+            let backtrack = if sum + remaining < lower || sum >= upper {
+                true
+            } else if sum >= lower {
+                let waste = sum - lower;
+                if best
+                    .as_ref()
+                    .is_none_or(|(best_waste, _)| waste < *best_waste)
+                {
+                    best = Some((waste, selected.clone()));
+                }
+                true
+            } else {
+                selected.len() == MAX_UTXOS_LEN
+            };
+
+            if !backtrack {
+                selected.push(index);
+                sum += values[index];
+                remaining -= values[index];
+                index += 1;
+                continue;
+            }
+
+            if best.as_ref().is_some_and(|(waste, _)| *waste == 0) {
+                break;
+            }
+            // Exclude the last included UTXO and explore the branch without it.
+            let Some(last) = selected.pop() else {
+                break;
+            };
+            sum -= values[last];
+            remaining += values[last + 1..index].iter().sum::<i128>();
+            index = last + 1;
+        }
+
+        let (_, indices) = best?;
+        target.evaluate(
+            indices
+                .into_iter()
+                .map(|i| candidates[i].0.clone())
+                .collect(),
+        )
+    }
+}
+
+/// Avoids linking, in a single transaction, coins received in unrelated payments.
+///
+/// All UTXOs of a user are held by the same address, so coin selection cannot avoid reusing it;
+/// what it can avoid is revealing, through the common-input-ownership heuristic, that coins paid
+/// to it by different senders belong together. Outputs of the same funding transaction are
+/// already linked on chain, so they are always spent together: the smallest funding
+/// transaction covering the target is spent in full, otherwise whole funding transactions are
+/// combined, largest first. Falls back to `LargestFirst` if that exceeds `MAX_UTXOS_LEN` inputs.
+pub struct PrivacyPreserving;
+
+impl CoinSelection for PrivacyPreserving {
+    fn select(&self, utxos: &[Utxo], target: &SelectionTarget) -> Option<Selection> {
+        let mut by_funding_tx: BTreeMap<&[u8], Vec<Utxo>> = BTreeMap::new();
+        for utxo in sorted_largest_first(utxos) {
+            by_funding_tx
+                .entry(utxo.outpoint.txid.as_slice())
+                .or_default()
+                .push(utxo.clone());
+        }
+        let mut groups: Vec<Vec<Utxo>> = by_funding_tx.into_values().collect();
+        groups.sort_by_key(|group| group.iter().map(|utxo| utxo.value).sum::<u64>());
+
+        if let Some(selection) = groups
+            .iter()
+            .find_map(|group| target.evaluate(group.clone()))
+        {
+            return Some(selection);
+        }
+
+        let mut combined = Vec::new();
+        for group in groups.into_iter().rev() {
+            combined.extend(group);
+            if combined.len() > MAX_UTXOS_LEN {
+                break;
+            }
+            if let Some(selection) = target.evaluate(combined.clone()) {
+                return Some(selection);
+            }
+        }
+
+        LargestFirst.select(utxos, target)
+    }
+}
+
+/// Spends the UTXOs `LargestFirst` would, and when fees are low also the smallest other UTXOs
+/// worth spending, up to `max_inputs` inputs.
+///
+/// Merging small UTXOs while inputs are cheap reduces the number of inputs, and so the fee, of
+/// later transactions sent when fees are high.
+pub struct Consolidate {
+    /// Fee rate above which no additional UTXOs are spent.
+    pub max_fee_rate: MillisatoshiPerByte,
+    pub max_inputs: usize,
+}
+
+impl Default for Consolidate {
+    fn default() -> Self {
+        Self {
+            max_fee_rate: CONSOLIDATION_MAX_FEE_RATE,
+            max_inputs: MAX_UTXOS_LEN,
+        }
+    }
+}
+
+impl CoinSelection for Consolidate {
+    fn select(&self, utxos: &[Utxo], target: &SelectionTarget) -> Option<Selection> {
+        let selection = LargestFirst.select(utxos, target)?;
+        if target.fee_rate > self.max_fee_rate {
+            return Some(selection);
+        }
+
+        let spare_inputs = self
+            .max_inputs
+            .min(MAX_UTXOS_LEN)
+            .saturating_sub(selection.utxos.len());
+        let mut inputs = selection.utxos.clone();
+        inputs.extend(
+            sorted_largest_first(utxos)
+                .into_iter()
+                .skip(selection.utxos.len())
+                .rev()
+                .filter(|utxo| target.effective_value_msat(utxo) > 0)
+                .take(spare_inputs)
+                .cloned(),
+        );
+
+        target.evaluate(inputs).or(Some(selection))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{CompressedPublicKey, Network};
+    use ic_cdk::bitcoin_canister::{Network as BitcoinNetwork, Outpoint};
+    use pretty_assertions::assert_eq;
+    use shared::types::bitcoin::BtcAddressType;
+
+    use super::*;
+    use crate::bitcoin::address::SourceAddress;
+
+    const SOURCE_PUBKEY: &str =
+        "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+    const RECIPIENT_PUBKEY: &str =
+        "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5";
+
+    fn script(pubkey: &str) -> ScriptBuf {
+        let key = CompressedPublicKey::from_slice(&hex::decode(pubkey).unwrap()).unwrap();
+        bitcoin::Address::p2wpkh(&key, Network::Regtest).script_pubkey()
+    }
+
+    fn utxo(tx: u8, vout: u32, value: u64) -> Utxo {
+        Utxo {
+            outpoint: Outpoint {
+                txid: vec![tx; 32],
+                vout,
+            },
+            value,
+            height: 100,
+        }
+    }
+
+    fn select(
+        strategy: &dyn CoinSelection,
+        utxos: &[Utxo],
+        amount_satoshis: u64,
+        fee_rate: MillisatoshiPerByte,
+    ) -> Option<Selection> {
+        let recipient_script = script(RECIPIENT_PUBKEY);
+        let source = SourceAddress::new(
+            BtcAddressType::P2wpkh,
+            &hex::decode(SOURCE_PUBKEY).unwrap(),
+            BitcoinNetwork::Regtest,
+        )
+        .unwrap();
+        strategy.select(
+            utxos,
+            &SelectionTarget {
+                amount_satoshis,
+                fee_rate,
+                recipient_script: &recipient_script,
+                change_script: &source.script_pubkey(),
+                input_satisfaction_weight: source.satisfaction_weight(),
+            },
+        )
+    }
+
+    fn values(selection: &Selection) -> Vec<u64> {
+        selection.utxos.iter().map(|utxo| utxo.value).collect()
+    }
+
+    #[test]
+    fn test_evaluate_adds_dust_change_to_fee() {
+        let selection = select(&LargestFirst, &[utxo(1, 0, 50_300)], 50_000, 2_000).unwrap();
+
+        assert_eq!(selection.vsize, 110);
+        assert_eq!(selection.fee_satoshis, 300);
+        assert_eq!(selection.change_satoshis, 0);
+    }
+
+    #[test]
+    fn test_largest_first_spends_fewest_inputs() {
+        let utxos = [utxo(1, 0, 10_000), utxo(2, 0, 60_000), utxo(3, 0, 30_000)];
+        let selection = select(&LargestFirst, &utxos, 80_000, 2_000).unwrap();
+
+        assert_eq!(values(&selection), vec![60_000, 30_000]);
+        assert_eq!(selection.fee_satoshis, 418);
+        assert_eq!(selection.change_satoshis, 90_000 - 80_000 - 418);
+    }
+
+    #[test]
+    fn test_largest_first_is_independent_of_input_order() {
+        let utxos = [utxo(1, 0, 30_000), utxo(2, 0, 30_000), utxo(3, 0, 30_000)];
+        let mut reversed = utxos.clone();
+        reversed.reverse();
+
+        assert_eq!(
+            select(&LargestFirst, &utxos, 40_000, 2_000),
+            select(&LargestFirst, &reversed, 40_000, 2_000)
+        );
+    }
+
+    #[test]
+    fn test_largest_first_fails_with_insufficient_funds() {
+        let utxos = [utxo(1, 0, 10_000), utxo(2, 0, 20_000)];
+
+        assert_eq!(select(&LargestFirst, &utxos, 30_000, 2_000), None);
+    }
+
+    #[test]
+    fn test_largest_first_respects_max_inputs() {
+        let utxos: Vec<Utxo> = (0..=u32::try_from(MAX_UTXOS_LEN).unwrap())
+            .map(|vout| utxo(1, vout, 1_000))
+            .collect();
+
+        assert!(select(&LargestFirst, &utxos, 100_000, 1_000).is_some());
+        assert_eq!(select(&LargestFirst, &utxos, 125_000, 1_000), None);
+    }
+
+    #[test]
+    fn test_branch_and_bound_finds_changeless_match() {
+        // At 1 sat/vB, a 2-input changeless transaction has a vsize of 178.
+        let utxos = [
+            utxo(1, 0, 70_000),
+            utxo(2, 0, 40_000),
+            utxo(3, 0, 25_000),
+            utxo(4, 0, 15_178),
+        ];
+        let selection = select(&BranchAndBound::default(), &utxos, 40_000, 1_000).unwrap();
+
+        assert_eq!(values(&selection), vec![25_000, 15_178]);
+        assert_eq!(selection.change_satoshis, 0);
+        assert_eq!(selection.fee_satoshis, 178);
+    }
+
+    #[test]
+    fn test_branch_and_bound_prefers_least_waste() {
+        let utxos = [utxo(1, 0, 40_150), utxo(2, 0, 40_120), utxo(3, 0, 90_000)];
+        let selection = select(&BranchAndBound::default(), &utxos, 40_000, 1_000).unwrap();
+
+        assert_eq!(values(&selection), vec![40_120]);
+        assert_eq!(selection.change_satoshis, 0);
+        assert_eq!(selection.fee_satoshis, 120);
+    }
+
+    #[test]
+    fn test_branch_and_bound_falls_back_to_largest_first() {
+        let utxos = [utxo(1, 0, 70_000), utxo(2, 0, 50_000)];
+        let selection = select(&BranchAndBound::default(), &utxos, 40_000, 1_000).unwrap();
+
+        assert_eq!(
+            Some(selection),
+            select(&LargestFirst, &utxos, 40_000, 1_000)
+        );
+    }
+
+    #[test]
+    fn test_branch_and_bound_gives_up_after_max_tries() {
+        let utxos = [
+            utxo(1, 0, 70_000),
+            utxo(2, 0, 40_000),
+            utxo(3, 0, 25_000),
+            utxo(4, 0, 15_178),
+        ];
+        let selection = select(&BranchAndBound { max_tries: 2 }, &utxos, 40_000, 1_000).unwrap();
+
+        assert_eq!(values(&selection), vec![70_000]);
+        assert!(selection.change_satoshis > 0);
+    }
+
+    #[test]
+    fn test_privacy_preserving_spends_smallest_covering_funding_tx() {
+        let utxos = [
+            utxo(1, 0, 100_000),
+            utxo(2, 0, 30_000),
+            utxo(2, 1, 30_000),
+            utxo(3, 0, 45_000),
+        ];
+        let selection = select(&PrivacyPreserving, &utxos, 50_000, 1_000).unwrap();
+
+        assert_eq!(
+            selection.utxos,
+            vec![utxo(2, 0, 30_000), utxo(2, 1, 30_000)]
+        );
+    }
+
+    #[test]
+    fn test_privacy_preserving_combines_whole_funding_txs() {
+        let utxos = [
+            utxo(1, 0, 20_000),
+            utxo(1, 1, 5_000),
+            utxo(2, 0, 30_000),
+            utxo(3, 0, 10_000),
+        ];
+        let selection = select(&PrivacyPreserving, &utxos, 50_000, 1_000).unwrap();
+
+        assert_eq!(values(&selection), vec![30_000, 20_000, 5_000]);
+    }
+
+    #[test]
+    fn test_privacy_preserving_fails_with_insufficient_funds() {
+        let utxos = [utxo(1, 0, 20_000), utxo(2, 0, 30_000)];
+
+        assert_eq!(select(&PrivacyPreserving, &utxos, 50_000, 1_000), None);
+    }
+
+    #[test]
+    fn test_consolidate_spends_small_utxos_when_fees_are_low() {
+        let utxos = [
+            utxo(1, 0, 100_000),
+            utxo(2, 0, 5_000),
+            utxo(3, 0, 2_000),
+            utxo(4, 0, 50),
+        ];
+        let selection = select(&Consolidate::default(), &utxos, 50_000, 1_000).unwrap();
+
+        // The 50 satoshi UTXO costs more than its value to spend and is left out.
+        assert_eq!(values(&selection), vec![100_000, 2_000, 5_000]);
+        assert_eq!(selection.vsize, 277);
+        assert_eq!(selection.change_satoshis, 107_000 - 50_000 - 277);
+    }
+
+    #[test]
+    fn test_consolidate_respects_max_inputs() {
+        let utxos = [utxo(1, 0, 100_000), utxo(2, 0, 5_000), utxo(3, 0, 2_000)];
+        let strategy = Consolidate {
+            max_inputs: 2,
+            ..Consolidate::default()
+        };
+        let selection = select(&strategy, &utxos, 50_000, 1_000).unwrap();
+
+        assert_eq!(values(&selection), vec![100_000, 2_000]);
+    }
+
+    #[test]
+    fn test_consolidate_behaves_like_largest_first_when_fees_are_high() {
+        let utxos = [utxo(1, 0, 100_000), utxo(2, 0, 5_000), utxo(3, 0, 2_000)];
+
+        assert_eq!(
+            select(&Consolidate::default(), &utxos, 50_000, 20_000),
+            select(&LargestFirst, &utxos, 50_000, 20_000)
+        );
+    }
+}
@@ -0,0 +1,495 @@
+//! Bumps the fee of pending transactions built by `tx_builder`, through RBF or CPFP.
+
+use bitcoin::Address;
+use ic_cdk::bitcoin_canister::{MillisatoshiPerByte, Outpoint, Utxo};
+use shared::types::bitcoin::{
+    BtcBumpFeeError, PendingTransactionStatus, StoredCpfpChild, StoredPendingTransaction,
+    StoredTransactionDetails, MAX_REPLACED_TXIDS,
+};
+
+use crate::bitcoin::{
+    address::SourceAddress,
+    coin_selection::{Selection, SelectionTarget},
+    tx_builder::{
+        build_psbt, estimate_vsize, fee_for_vsize, BuiltTransaction,
+        MIN_FEE_RATE_MILLISAT_PER_VBYTE,
+    },
+};
+
+/// The fee rate by which a replacement must at least exceed the fees it replaces (BIP-125).
+pub const INCREMENTAL_RELAY_FEE_RATE: MillisatoshiPerByte = 1_000;
+
+/// The output of transactions built by `tx_builder` paying the change back to the source.
+const CHANGE_VOUT: u32 = 1;
+
+/// Builds a replacement of `pending` spending the same UTXOs and paying the same recipient, at a
+/// fee rate of at least `fee_rate`.
+///
+/// As BIP-125 requires, the fee of the replacement also exceeds the fees of `pending` and of its
+/// CPFP child, if any, by the incremental relay fee. The higher fee is taken from the change.
+///
+/// # Errors
+/// - `TooManyReplacements`: `pending` has already been replaced `MAX_REPLACED_TXIDS` times.
+/// - `InsufficientFunds`: The UTXOs of `pending` do not cover the amount and the higher fee.
+/// - `InternalError`: A UTXO has a malformed txid.
+pub fn build_replacement(
+    pending: &StoredPendingTransaction,
+    details: &StoredTransactionDetails,
+    source: &SourceAddress,
+    recipient: &Address,
+    fee_rate: MillisatoshiPerByte,
+) -> Result<BuiltTransaction, BtcBumpFeeError> {
+    if details.replaced_txids.len() >= MAX_REPLACED_TXIDS {
+        return Err(BtcBumpFeeError::TooManyReplacements);
+    }
+
+    let replaced_fee = details.fee_satoshis
+        + details
+            .cpfp_child
+            .as_ref()
+            .map_or(0, |child| child.fee_satoshis);
+    let min_fee_rate =
+        (replaced_fee * 1_000).div_ceil(details.vsize.max(1)) + INCREMENTAL_RELAY_FEE_RATE;
+
+    let source_script = source.script_pubkey();
+    let recipient_script = recipient.script_pubkey();
+    let insufficient_funds = || BtcBumpFeeError::InsufficientFunds {
+        available_satoshis: pending.utxos.iter().map(|utxo| utxo.value).sum(),
+    };
+    let selection = SelectionTarget {
+        amount_satoshis: details.amount_satoshis,
+        fee_rate: fee_rate.max(min_fee_rate),
+        recipient_script: &recipient_script,
+        change_script: &source_script,
+        input_satisfaction_weight: source.satisfaction_weight(),
+    }
+    .evaluate(pending.utxos.clone())
+    .ok_or_else(insufficient_funds)?;
+
+    // Dropping a change output that became dust shrinks the transaction, so the fee is checked
+    // against its actual size.
+    if selection.fee_satoshis
+        < replaced_fee + fee_for_vsize(selection.vsize, INCREMENTAL_RELAY_FEE_RATE)
+    {
+        return Err(insufficient_funds());
+    }
+
+    build_psbt(selection, source, recipient_script, details.amount_satoshis)
+        .map_err(|msg| BtcBumpFeeError::InternalError { msg })
+}
+
+/// Builds a child spending the change output of `pending` back to `source`, paying a fee such
+/// that the parent and the child together pay at least `fee_rate`.
+///
+/// If `pending` already has a CPFP child, the new child replaces it and so also pays more than it
+/// by the incremental relay fee.
+///
+/// # Errors
+/// - `NoChangeOutput`: `pending` has no change output.
+/// - `InsufficientFunds`: The change does not cover the fee and an output above the dust limit.
+/// - `InternalError`: The txid of `pending` is malformed.
+pub fn build_cpfp_child(
+    pending: &StoredPendingTransaction,
+    details: &StoredTransactionDetails,
+    source: &SourceAddress,
+    fee_rate: MillisatoshiPerByte,
+) -> Result<BuiltTransaction, BtcBumpFeeError> {
+    if details.change_satoshis == 0 {
+        return Err(BtcBumpFeeError::NoChangeOutput);
+    }
+
+    let source_script = source.script_pubkey();
+    let fee_rate = fee_rate.max(MIN_FEE_RATE_MILLISAT_PER_VBYTE);
+    let vsize = estimate_vsize(1, source.satisfaction_weight(), &[&source_script]);
+
+    let package_fee = fee_for_vsize(details.vsize + vsize, fee_rate);
+    let mut fee = package_fee
+        .saturating_sub(details.fee_satoshis)
+        .max(fee_for_vsize(vsize, MIN_FEE_RATE_MILLISAT_PER_VBYTE));
+    if let Some(child) = &details.cpfp_child {
+        fee = fee.max(child.fee_satoshis + fee_for_vsize(vsize, INCREMENTAL_RELAY_FEE_RATE));
+    }
+
+    let amount_satoshis = details
+        .change_satoshis
+        .checked_sub(fee)
+        .filter(|amount| *amount >= source_script.minimal_non_dust().to_sat())
+        .ok_or(BtcBumpFeeError::InsufficientFunds {
+            available_satoshis: details.change_satoshis,
+        })?;
+
+    let change = Utxo {
+        outpoint: Outpoint {
+            txid: pending.txid.clone(),
+            vout: CHANGE_VOUT,
+        },
+        value: details.change_satoshis,
+        height: 0,
+    };
+    build_psbt(
+        Selection {
+            utxos: vec![change],
+            fee_satoshis: fee,
+            change_satoshis: 0,
+            vsize,
+        },
+        source,
+        source_script,
+        amount_satoshis,
+    )
+    .map_err(|msg| BtcBumpFeeError::InternalError { msg })
+}
+
+/// Returns the pending transaction tracking `replacement` in place of `pending`, reserving the
+/// same UTXOs and recording the txid of `pending` in the replacement chain.
+///
+/// The reservation is renewed, as the replacement is broadcast anew.
+pub fn replaced(
+    pending: &StoredPendingTransaction,
+    details: &StoredTransactionDetails,
+    replacement: &BuiltTransaction,
+    now_ns: u64,
+) -> StoredPendingTransaction {
+    let mut replaced_txids = details.replaced_txids.clone();
+    replaced_txids.push(pending.txid.clone());
+
+    StoredPendingTransaction {
+        txid: replacement.txid.clone(),
+        utxos: replacement.utxos.clone(),
+        created_at_timestamp_ns: now_ns,
+        details: Some(StoredTransactionDetails {
+            recipient: details.recipient.clone(),
+            amount_satoshis: details.amount_satoshis,
+            fee_satoshis: replacement.fee_satoshis,
+            change_satoshis: replacement.change_satoshis,
+            vsize: replacement.vsize,
+            replaced_txids,
+            cpfp_child: None,
+        }),
+        status: Some(PendingTransactionStatus::Pending),
+    }
+}
+
+/// Returns `pending` with `child` recorded as its CPFP child.
+///
+/// The reservation is renewed, as the child makes the parent likely to confirm.
+pub fn with_cpfp_child(
+    pending: &StoredPendingTransaction,
+    details: &StoredTransactionDetails,
+    child: &BuiltTransaction,
+    now_ns: u64,
+) -> StoredPendingTransaction {
+    StoredPendingTransaction {
+        txid: pending.txid.clone(),
+        utxos: pending.utxos.clone(),
+        created_at_timestamp_ns: now_ns,
+        details: Some(StoredTransactionDetails {
+            cpfp_child: Some(StoredCpfpChild {
+                txid: child.txid.clone(),
+                fee_satoshis: child.fee_satoshis,
+                vsize: child.vsize,
+            }),
+            ..details.clone()
+        }),
+        status: pending.status,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{hashes::Hash, psbt::Psbt, CompressedPublicKey, Network, Sequence};
+    use ic_cdk::bitcoin_canister::Network as BitcoinNetwork;
+    use pretty_assertions::{assert_eq, assert_ne};
+    use shared::types::bitcoin::BtcAddressType;
+
+    use super::*;
+    use crate::bitcoin::{coin_selection::LargestFirst, tx_builder::build_transaction};
+
+    const SOURCE_PUBKEY: &str =
+        "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+    const RECIPIENT_PUBKEY: &str =
+        "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5";
+
+    fn address(pubkey: &str) -> Address {
+        let key = CompressedPublicKey::from_slice(&hex::decode(pubkey).unwrap()).unwrap();
+        Address::p2wpkh(&key, Network::Regtest)
+    }
+
+    fn source() -> SourceAddress {
+        source_of(BtcAddressType::P2wpkh)
+    }
+
+    fn source_of(address_type: BtcAddressType) -> SourceAddress {
+        SourceAddress::new(
+            address_type,
+            &hex::decode(SOURCE_PUBKEY).unwrap(),
+            BitcoinNetwork::Regtest,
+        )
+        .unwrap()
+    }
+
+    fn utxo(seed: u8, value: u64) -> Utxo {
+        Utxo {
+            outpoint: Outpoint {
+                txid: vec![seed; 32],
+                vout: 0,
+            },
+            value,
+            height: 100,
+        }
+    }
+
+    /// A pending 1-input, 2-output transaction of 141 vbytes paying 2 sat/vB.
+    fn pending(value: u64, amount: u64) -> StoredPendingTransaction {
+        build_transaction(
+            &[utxo(1, value)],
+            &source(),
+            &address(RECIPIENT_PUBKEY),
+            amount,
+            2_000,
+            &LargestFirst,
+        )
+        .unwrap()
+        .to_stored(&address(RECIPIENT_PUBKEY), amount, 1_000)
+    }
+
+    fn replace(
+        pending: &StoredPendingTransaction,
+        fee_rate: MillisatoshiPerByte,
+    ) -> Result<BuiltTransaction, BtcBumpFeeError> {
+        build_replacement(
+            pending,
+            pending.details.as_ref().unwrap(),
+            &source(),
+            &address(RECIPIENT_PUBKEY),
+            fee_rate,
+        )
+    }
+
+    fn cpfp(
+        pending: &StoredPendingTransaction,
+        fee_rate: MillisatoshiPerByte,
+    ) -> Result<BuiltTransaction, BtcBumpFeeError> {
+        build_cpfp_child(
+            pending,
+            pending.details.as_ref().unwrap(),
+            &source(),
+            fee_rate,
+        )
+    }
+
+    #[test]
+    fn test_replacement_spends_same_utxos_with_higher_fee() {
+        let pending = pending(100_000, 50_000);
+        let replacement = replace(&pending, 10_000).unwrap();
+
+        assert_eq!(replacement.utxos, pending.utxos);
+        assert_eq!(replacement.vsize, 141);
+        assert_eq!(replacement.fee_satoshis, 1_410);
+        assert_eq!(replacement.change_satoshis, 100_000 - 50_000 - 1_410);
+        assert_ne!(replacement.txid, pending.txid);
+
+        let psbt = Psbt::deserialize(&replacement.psbt).unwrap();
+        assert_eq!(psbt.unsigned_tx.output[0].value.to_sat(), 50_000);
+        assert_eq!(
+            psbt.unsigned_tx.output[0].script_pubkey,
+            address(RECIPIENT_PUBKEY).script_pubkey()
+        );
+        assert_eq!(
+            psbt.unsigned_tx.input[0].sequence,
+            Sequence::ENABLE_RBF_NO_LOCKTIME
+        );
+    }
+
+    #[test]
+    fn test_replacement_pays_at_least_incremental_relay_fee() {
+        let pending = pending(100_000, 50_000);
+        let replacement = replace(&pending, 1_000).unwrap();
+
+        // The original paid 282 satoshis; BIP-125 requires 141 more.
+        assert_eq!(replacement.fee_satoshis, 423);
+    }
+
+    #[test]
+    fn test_replacement_pays_for_cpfp_child() {
+        let mut pending = pending(100_000, 50_000);
+        let child = cpfp(&pending, 10_000).unwrap();
+        pending = with_cpfp_child(&pending, pending.details.as_ref().unwrap(), &child, 2_000);
+
+        let replacement = replace(&pending, 1_000).unwrap();
+
+        assert!(replacement.fee_satoshis >= 282 + child.fee_satoshis + 141);
+    }
+
+    #[test]
+    fn test_replacement_drops_dust_change() {
+        let pending = pending(50_700, 50_000);
+        assert_eq!(pending.details.as_ref().unwrap().change_satoshis, 418);
+
+        let replacement = replace(&pending, 3_000).unwrap();
+
+        assert_eq!(replacement.change_satoshis, 0);
+        assert_eq!(replacement.vsize, 110);
+        assert_eq!(replacement.fee_satoshis, 700);
+    }
+
+    #[test]
+    fn test_replacement_fails_with_insufficient_funds() {
+        let pending = pending(50_700, 50_000);
+
+        assert_eq!(
+            replace(&pending, 10_000).unwrap_err(),
+            BtcBumpFeeError::InsufficientFunds {
+                available_satoshis: 50_700
+            }
+        );
+    }
+
+    #[test]
+    fn test_replacement_fails_after_max_replacements() {
+        let mut pending = pending(1_000_000, 50_000);
+        for _ in 0..MAX_REPLACED_TXIDS {
+            let replacement = replace(&pending, 1_000).unwrap();
+            pending = replaced(
+                &pending,
+                pending.details.as_ref().unwrap(),
+                &replacement,
+                2_000,
+            );
+        }
+
+        assert_eq!(
+            replace(&pending, 1_000).unwrap_err(),
+            BtcBumpFeeError::TooManyReplacements
+        );
+    }
+
+    #[test]
+    fn test_replaced_tracks_replacement_chain() {
+        let original = pending(100_000, 50_000);
+        let first = replace(&original, 5_000).unwrap();
+        let pending = replaced(&original, original.details.as_ref().unwrap(), &first, 2_000);
+        let second = replace(&pending, 10_000).unwrap();
+        let pending = replaced(&pending, pending.details.as_ref().unwrap(), &second, 3_000);
+
+        let details = pending.details.as_ref().unwrap();
+        assert_eq!(pending.txid, second.txid);
+        assert_eq!(pending.utxos, original.utxos);
+        assert_eq!(pending.created_at_timestamp_ns, 3_000);
+        assert_eq!(details.replaced_txids, vec![original.txid, first.txid]);
+        assert_eq!(details.fee_satoshis, second.fee_satoshis);
+        assert_eq!(details.change_satoshis, second.change_satoshis);
+    }
+
+    #[test]
+    fn test_cpfp_child_spends_change_at_package_fee_rate() {
+        let pending = pending(100_000, 50_000);
+        let change = pending.details.as_ref().unwrap().change_satoshis;
+        let child = cpfp(&pending, 10_000).unwrap();
+
+        // Parent and child are 141 + 110 vbytes, of which the parent already paid 282 satoshis.
+        assert_eq!(child.vsize, 110);
+        assert_eq!(child.fee_satoshis, 2_510 - 282);
+
+        let psbt = Psbt::deserialize(&child.psbt).unwrap();
+        let input = &psbt.unsigned_tx.input[0];
+        assert_eq!(
+            input.previous_output.txid.to_byte_array().to_vec(),
+            pending.txid
+        );
+        assert_eq!(input.previous_output.vout, CHANGE_VOUT);
+        assert_eq!(
+            psbt.inputs[0].witness_utxo.as_ref().unwrap().value.to_sat(),
+            change
+        );
+        assert_eq!(
+            psbt.unsigned_tx.output[0].value.to_sat(),
+            change - child.fee_satoshis
+        );
+        assert_eq!(
+            psbt.unsigned_tx.output[0].script_pubkey,
+            source().script_pubkey()
+        );
+    }
+
+    #[test]
+    fn test_cpfp_child_of_p2sh_p2wpkh_parent_spends_its_signed_txid() {
+        let source = source_of(BtcAddressType::P2shP2wpkh);
+        let built = build_transaction(
+            &[utxo(1, 100_000)],
+            &source,
+            &address(RECIPIENT_PUBKEY),
+            50_000,
+            2_000,
+            &LargestFirst,
+        )
+        .unwrap();
+        let parent = Psbt::deserialize(&built.psbt).unwrap();
+        let pending = built.to_stored(&address(RECIPIENT_PUBKEY), 50_000, 1_000);
+
+        let child =
+            build_cpfp_child(&pending, pending.details.as_ref().unwrap(), &source, 10_000).unwrap();
+
+        // The unsigned parent lacks the script sigs, and so has another txid.
+        let psbt = Psbt::deserialize(&child.psbt).unwrap();
+        let spent_txid = psbt.unsigned_tx.input[0]
+            .previous_output
+            .txid
+            .to_byte_array()
+            .to_vec();
+        assert_eq!(spent_txid, pending.txid);
+        assert_ne!(
+            spent_txid,
+            parent.unsigned_tx.compute_txid().to_byte_array().to_vec()
+        );
+    }
+
+    #[test]
+    fn test_cpfp_child_pays_at_least_min_relay_fee() {
+        let pending = pending(100_000, 50_000);
+
+        assert_eq!(cpfp(&pending, 1_000).unwrap().fee_satoshis, 110);
+    }
+
+    #[test]
+    fn test_cpfp_child_replacing_previous_child_pays_more() {
+        let pending = pending(100_000, 50_000);
+        let first = cpfp(&pending, 10_000).unwrap();
+        let pending = with_cpfp_child(&pending, pending.details.as_ref().unwrap(), &first, 2_000);
+
+        let second = cpfp(&pending, 10_000).unwrap();
+
+        assert_eq!(second.fee_satoshis, first.fee_satoshis + 110);
+        assert_eq!(
+            pending.details.as_ref().unwrap().cpfp_child,
+            Some(StoredCpfpChild {
+                txid: first.txid,
+                fee_satoshis: first.fee_satoshis,
+                vsize: first.vsize,
+            })
+        );
+        assert_eq!(pending.created_at_timestamp_ns, 2_000);
+    }
+
+    #[test]
+    fn test_cpfp_fails_without_change_output() {
+        let pending = pending(50_300, 50_000);
+
+        assert_eq!(
+            cpfp(&pending, 10_000).unwrap_err(),
+            BtcBumpFeeError::NoChangeOutput
+        );
+    }
+
+    #[test]
+    fn test_cpfp_fails_when_change_does_not_cover_fee() {
+        let pending = pending(50_700, 50_000);
+
+        assert_eq!(
+            cpfp(&pending, 10_000).unwrap_err(),
+            BtcBumpFeeError::InsufficientFunds {
+                available_satoshis: 418
+            }
+        );
+    }
+}
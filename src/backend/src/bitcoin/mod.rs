@@ -1,2 +1,7 @@
+pub(crate) mod address;
 pub(crate) mod api;
+pub(crate) mod coin_selection;
+pub(crate) mod fee_bump;
 pub(crate) mod pending_tx_model;
+pub(crate) mod pending_tx_tracker;
+pub(crate) mod tx_builder;
@@ -2,9 +2,12 @@ use std::collections::HashSet;
 
 use candid::Principal;
 use ic_cdk::bitcoin_canister::Utxo;
-use shared::types::bitcoin::StoredPendingTransaction;
+use shared::types::bitcoin::{PendingTransactionStatus, StoredPendingTransaction};
 
-use crate::types::{BtcUserPendingTransactionsMap, Candid, StoredPrincipal};
+use crate::{
+    bitcoin::api::MIN_CONFIRMATIONS_ACCEPTED_BTC_TX,
+    types::{BtcUserPendingTransactionsMap, Candid, StoredPrincipal},
+};
 
 const MAX_PENDING_TRANSACTIONS: usize = 1000;
 
@@ -12,6 +15,12 @@ const MAX_ADDRESS_COUNT_PER_USER: usize = 20;
 
 const HOUR_IN_NS: u64 = 60 * 60 * 1_000_000_000;
 
+/// Age after which a transaction that was not mined is considered dropped.
+const DROP_TIMEOUT_NS: u64 = 72 * HOUR_IN_NS;
+
+/// Time during which a dropped transaction is kept, so that its status can be shown.
+const DROPPED_RETENTION_NS: u64 = 24 * HOUR_IN_NS;
+
 // With this structure, if multiple users share the same address
 // they wouldn't share the pending transactions.
 // This is not possible with the current implementation of the addresses in CFS.
@@ -84,25 +93,57 @@ impl<'a> BtcUserPendingTransactionsModel<'a> {
         Ok(())
     }
 
-    /// Prunes pending transactions for a specific principal.
-    /// A pending transaction can be pruned for two reasons:
-    /// - Transaction is older than 1 hour. We consider that if a pending transaction is older than
-    ///   one hour it means it failed, and we can free to utxos to be used again.
-    /// - None of the transaction's utxos are present in the current utxos list. We use the pending
-    ///   transactions to avoid double spending. Once we know that an utxos is not available, we can
-    ///   remove the pending transaction. Normally, all utxos of a pending transaction should be
-    ///   present or not. Partial presence could happen if the utxos of a pending transaction were
-    ///   not really used in the transaction. We don't remove in partial presence because, in the
-    ///   end, partial presence will be temporary for one hour.
+    /// Replaces the pending transaction with id `txid` of a specific principal and address, keeping
+    /// its position in the list.
+    pub fn replace_pending_transaction(
+        &mut self,
+        principal: Principal,
+        address: &str,
+        txid: &[u8],
+        replacement: StoredPendingTransaction,
+    ) -> Result<(), String> {
+        let stored_principal = StoredPrincipal(principal);
+        let mut address_map = self
+            .pending_transactions_map
+            .get(&stored_principal)
+            .map(|c| c.0)
+            .unwrap_or_default();
+
+        let Some(transaction) = address_map
+            .get_mut(address)
+            .and_then(|list| list.iter_mut().find(|tx| tx.txid == txid))
+        else {
+            return Err("Pending transaction not found".to_string());
+        };
+        *transaction = replacement;
+
+        self.pending_transactions_map
+            .insert(stored_principal, Candid(address_map));
+        Ok(())
+    }
+
+    /// Updates the status of the pending transactions of a principal at `address`, given the UTXOs
+    /// of the address with at least one confirmation at `tip_height`:
+    /// - A transaction with an output among `utxos`, or one of the transactions it replaced or its
+    ///   CPFP child, is `Confirmed` at the height of that output.
+    /// - Otherwise, a transaction whose UTXOs are all spent is `Confirmed` at the height they were
+    ///   first seen spent at. Transactions added by the client may list UTXOs they do not spend,
+    ///   so for them a single spent UTXO is enough.
+    /// - A transaction built by the backend with only some of its UTXOs spent conflicts with a
+    ///   mined transaction and is `Dropped`.
+    /// - A transaction with none of its UTXOs spent is `Pending`, including a `Confirmed` one that
+    ///   was reorganized out, and `Dropped` once older than `DROP_TIMEOUT_NS`: by then it was
+    ///   evicted from mempools or never broadcast.
     ///
-    /// An empty `current_utxos` set is treated as "no confirmation information available", not as
-    /// "all utxos confirmed". Such transactions are only freed by the 1-hour age rule, so a caller
-    /// that fetches utxos for an address with no available utxos cannot wipe its non-expired
-    /// pending reservations.
-    pub fn prune_pending_transactions(
+    /// `Confirmed` transactions are removed once they have `MIN_CONFIRMATIONS_ACCEPTED_BTC_TX`
+    /// confirmations, when their change becomes spendable, and `Dropped` ones
+    /// `DROPPED_RETENTION_NS` after being dropped, so that the user can see what happened.
+    pub fn update_pending_transactions(
         &mut self,
         principal: Principal,
-        current_utxos: &[Utxo],
+        address: &str,
+        utxos: &[Utxo],
+        tip_height: u32,
         now_ns: u64,
     ) {
         let stored_principal = StoredPrincipal(principal);
@@ -113,44 +154,39 @@ impl<'a> BtcUserPendingTransactionsModel<'a> {
         else {
             return;
         };
+        let Some(transactions) = address_map.get(address) else {
+            return;
+        };
 
-        let mut changed = false;
-        let mut addresses_to_remove = Vec::new();
-        for (address, transactions) in &mut address_map {
-            let initial_len = transactions.len();
-            transactions.retain(|pending_transaction| {
-                let is_old = pending_transaction.created_at_timestamp_ns + HOUR_IN_NS < now_ns;
-
-                let none_of_tx_utxos_are_still_present = !current_utxos.is_empty()
-                    && pending_transaction
-                        .utxos
-                        .iter()
-                        .all(|utxo| !current_utxos.contains(utxo));
-
-                !is_old && !none_of_tx_utxos_are_still_present
-            });
-
-            if transactions.len() != initial_len {
-                changed = true;
-            }
+        let unspent: HashSet<(&[u8], u32)> = utxos
+            .iter()
+            .map(|u| (u.outpoint.txid.as_slice(), u.outpoint.vout))
+            .collect();
+        let updated: Vec<StoredPendingTransaction> = transactions
+            .iter()
+            .filter_map(|tx| {
+                let status = next_status(tx, utxos, &unspent, tip_height, now_ns)?;
+                Some(StoredPendingTransaction {
+                    status: Some(status),
+                    ..tx.clone()
+                })
+            })
+            .collect();
 
-            if transactions.is_empty() {
-                addresses_to_remove.push(address.clone());
-            }
+        if updated == *transactions {
+            return;
         }
-
-        for address in &addresses_to_remove {
-            address_map.remove(address.as_str());
-            changed = true;
+        if updated.is_empty() {
+            address_map.remove(address);
+        } else {
+            address_map.insert(address.to_string(), updated);
         }
 
-        if changed {
-            if address_map.is_empty() {
-                self.pending_transactions_map.remove(&stored_principal);
-            } else {
-                self.pending_transactions_map
-                    .insert(stored_principal, Candid(address_map));
-            }
+        if address_map.is_empty() {
+            self.pending_transactions_map.remove(&stored_principal);
+        } else {
+            self.pending_transactions_map
+                .insert(stored_principal, Candid(address_map));
         }
     }
 
@@ -182,14 +218,110 @@ impl<'a> BtcUserPendingTransactionsModel<'a> {
             .0
             .values()
             .flat_map(|txs| txs.iter())
+            .filter(|tx| reserves_utxos(tx))
             .flat_map(|tx| tx.utxos.iter())
             .any(|u| new_keys.contains(&(u.outpoint.txid.as_slice(), u.outpoint.vout)))
     }
+
+    /// Returns the `(txid, vout)` outpoints reserved by the pending transactions of a principal,
+    /// across all of its addresses.
+    pub fn get_reserved_outpoints(&self, principal: Principal) -> HashSet<(Vec<u8>, u32)> {
+        let stored_principal = StoredPrincipal(principal);
+        let Some(address_map) = self.pending_transactions_map.get(&stored_principal) else {
+            return HashSet::new();
+        };
+
+        address_map
+            .0
+            .into_values()
+            .flatten()
+            .filter(reserves_utxos)
+            .flat_map(|tx| tx.utxos)
+            .map(|u| (u.outpoint.txid, u.outpoint.vout))
+            .collect()
+    }
+}
+
+/// Returns the number of confirmations of a transaction with `status` at `tip_height`.
+pub fn confirmations(status: PendingTransactionStatus, tip_height: u32) -> u32 {
+    match status {
+        PendingTransactionStatus::Confirmed { height } => (tip_height + 1).saturating_sub(height),
+        PendingTransactionStatus::Pending | PendingTransactionStatus::Dropped { .. } => 0,
+    }
+}
+
+/// Whether the UTXOs of `tx` are kept from being spent by other transactions.
+fn reserves_utxos(tx: &StoredPendingTransaction) -> bool {
+    !matches!(tx.status, Some(PendingTransactionStatus::Dropped { .. }))
+}
+
+/// Returns the status of `tx` given the unspent `utxos` of its address, or `None` if it no longer
+/// needs to be tracked.
+fn next_status(
+    tx: &StoredPendingTransaction,
+    utxos: &[Utxo],
+    unspent: &HashSet<(&[u8], u32)>,
+    tip_height: u32,
+    now_ns: u64,
+) -> Option<PendingTransactionStatus> {
+    let status = tx.status.unwrap_or(PendingTransactionStatus::Pending);
+    if let PendingTransactionStatus::Dropped { timestamp_ns } = status {
+        return (now_ns <= timestamp_ns + DROPPED_RETENTION_NS).then_some(status);
+    }
+
+    let status = observed_status(tx, status, utxos, unspent, tip_height, now_ns);
+    (confirmations(status, tip_height) < MIN_CONFIRMATIONS_ACCEPTED_BTC_TX).then_some(status)
+}
+
+fn observed_status(
+    tx: &StoredPendingTransaction,
+    status: PendingTransactionStatus,
+    utxos: &[Utxo],
+    unspent: &HashSet<(&[u8], u32)>,
+    tip_height: u32,
+    now_ns: u64,
+) -> PendingTransactionStatus {
+    let mut txids: Vec<&[u8]> = vec![&tx.txid];
+    if let Some(details) = &tx.details {
+        txids.extend(details.replaced_txids.iter().map(Vec::as_slice));
+        txids.extend(details.cpfp_child.iter().map(|child| child.txid.as_slice()));
+    }
+    if let Some(height) = utxos
+        .iter()
+        .filter(|u| txids.contains(&u.outpoint.txid.as_slice()))
+        .map(|u| u.height)
+        .min()
+    {
+        return PendingTransactionStatus::Confirmed { height };
+    }
+
+    let spent = tx
+        .utxos
+        .iter()
+        .filter(|u| !unspent.contains(&(u.outpoint.txid.as_slice(), u.outpoint.vout)))
+        .count();
+    if spent == tx.utxos.len() || (spent > 0 && tx.details.is_none()) {
+        let height = match status {
+            PendingTransactionStatus::Confirmed { height } => height,
+            _ => tip_height,
+        };
+        return PendingTransactionStatus::Confirmed { height };
+    }
+    if spent > 0 || tx.created_at_timestamp_ns + DROP_TIMEOUT_NS < now_ns {
+        return PendingTransactionStatus::Dropped {
+            timestamp_ns: now_ns,
+        };
+    }
+    PendingTransactionStatus::Pending
 }
 
 #[cfg(test)]
 mod tests {
-    use std::{cell::RefCell, collections::HashMap, sync::LazyLock};
+    use std::{
+        cell::RefCell,
+        collections::{HashMap, HashSet},
+        sync::LazyLock,
+    };
 
     use candid::Principal;
     use ic_cdk::bitcoin_canister::{Outpoint, Utxo};
@@ -198,10 +330,17 @@ mod tests {
         DefaultMemoryImpl,
     };
     use pretty_assertions::assert_eq;
-    use shared::types::bitcoin::StoredPendingTransaction;
+    use shared::types::bitcoin::{
+        PendingTransactionStatus, StoredPendingTransaction, StoredTransactionDetails,
+    };
 
-    use super::{BtcUserPendingTransactionsModel, HOUR_IN_NS};
-    use crate::types::{BtcUserPendingTransactionsMap, Candid, StoredPrincipal};
+    use super::{
+        confirmations, BtcUserPendingTransactionsModel, DROPPED_RETENTION_NS, DROP_TIMEOUT_NS,
+    };
+    use crate::{
+        bitcoin::api::MIN_CONFIRMATIONS_ACCEPTED_BTC_TX,
+        types::{BtcUserPendingTransactionsMap, Candid, StoredPrincipal},
+    };
 
     const TXID_A: &[u8] = &[0xAA; 32];
     const TXID_B: &[u8] = &[0xBB; 32];
@@ -286,6 +425,8 @@ mod tests {
             txid: vec![],
             utxos: vec![(*UTXO_1).clone()],
             created_at_timestamp_ns: 1_000_000,
+            details: None,
+            status: None,
         };
 
         // Add the pending transaction
@@ -312,6 +453,8 @@ mod tests {
             txid: vec![],
             utxos: vec![(*UTXO_1).clone()],
             created_at_timestamp_ns: 1_000_000,
+            details: None,
+            status: None,
         };
 
         let result = model.add_pending_transaction(principal1, ADDRESS_1.to_string(), tx.clone());
@@ -332,21 +475,29 @@ mod tests {
             txid: vec![1, 2, 3],
             utxos: vec![(*UTXO_1).clone()],
             created_at_timestamp_ns: 1_000_000,
+            details: None,
+            status: None,
         };
         let tx2 = StoredPendingTransaction {
             txid: vec![4, 5, 6],
             utxos: vec![(*UTXO_2).clone()],
             created_at_timestamp_ns: 2_000_000,
+            details: None,
+            status: None,
         };
         let tx3 = StoredPendingTransaction {
             txid: vec![7, 8, 9],
             utxos: vec![(*UTXO_3).clone()],
             created_at_timestamp_ns: 3_000_000,
+            details: None,
+            status: None,
         };
         let tx4 = StoredPendingTransaction {
             txid: vec![10, 11, 12],
             utxos: vec![(*UTXO_4).clone()],
             created_at_timestamp_ns: 4_000_000,
+            details: None,
+            status: None,
         };
 
         // Add 3 transactions (max_pending_transactions = 3)
@@ -377,21 +528,29 @@ mod tests {
             txid: vec![1, 2, 3],
             utxos: vec![(*UTXO_1).clone()],
             created_at_timestamp_ns: 1_000_000,
+            details: None,
+            status: None,
         };
         let tx2 = StoredPendingTransaction {
             txid: vec![4, 5, 6],
             utxos: vec![(*UTXO_2).clone()],
             created_at_timestamp_ns: 2_000_000,
+            details: None,
+            status: None,
         };
         let tx3 = StoredPendingTransaction {
             txid: vec![7, 8, 9],
             utxos: vec![(*UTXO_3).clone()],
             created_at_timestamp_ns: 3_000_000,
+            details: None,
+            status: None,
         };
         let tx4 = StoredPendingTransaction {
             txid: vec![10, 11, 12],
             utxos: vec![(*UTXO_4).clone()],
             created_at_timestamp_ns: 4_000_000,
+            details: None,
+            status: None,
         };
 
         // Add 3 transactions (max_addresses_per_user = 3)
@@ -411,167 +570,343 @@ mod tests {
         assert_eq!(result.unwrap_err(), "Maximum address per user reached");
     }
 
+    const TIP_HEIGHT: u32 = 300;
+
+    fn pending(
+        txid: &[u8],
+        utxos: &[&Utxo],
+        details: Option<StoredTransactionDetails>,
+    ) -> StoredPendingTransaction {
+        StoredPendingTransaction {
+            txid: txid.to_vec(),
+            utxos: utxos.iter().map(|u| (*u).clone()).collect(),
+            created_at_timestamp_ns: 1_000_000,
+            details,
+            status: None,
+        }
+    }
+
+    fn details(replaced_txids: Vec<Vec<u8>>) -> StoredTransactionDetails {
+        StoredTransactionDetails {
+            recipient: "recipient".to_string(),
+            amount_satoshis: 1_000,
+            fee_satoshis: 200,
+            change_satoshis: 0,
+            vsize: 110,
+            replaced_txids,
+            cpfp_child: None,
+        }
+    }
+
+    fn output(txid: &[u8], height: u32) -> Utxo {
+        Utxo {
+            outpoint: Outpoint {
+                txid: txid.to_vec(),
+                vout: 1,
+            },
+            value: 500,
+            height,
+        }
+    }
+
+    fn statuses(
+        model: &BtcUserPendingTransactionsModel,
+        principal: &Principal,
+    ) -> Vec<Option<PendingTransactionStatus>> {
+        model
+            .get_pending_transactions(principal, ADDRESS_1)
+            .iter()
+            .map(|tx| tx.status)
+            .collect()
+    }
+
     #[test]
-    fn test_prune_old_pending_transactions() {
+    fn test_update_keeps_transactions_with_unspent_utxos_pending() {
         let (mut map, _mm) = setup();
         let mut model = BtcUserPendingTransactionsModel::new(&mut map, None, None);
         let principal = Principal::from_text(PRINCIPAL_TEXT_1).unwrap();
 
-        let yesterday_ns = 1_000_000;
-        let now_ns = yesterday_ns + HOUR_IN_NS;
+        model
+            .add_pending_transaction(
+                principal,
+                ADDRESS_1.to_string(),
+                pending(&[1], &[&UTXO_1, &UTXO_2], None),
+            )
+            .unwrap();
+
+        let utxos = [(*UTXO_1).clone(), (*UTXO_2).clone(), (*UTXO_3).clone()];
+        model.update_pending_transactions(principal, ADDRESS_1, &utxos, TIP_HEIGHT, 2_000_000);
+
+        assert_eq!(
+            statuses(&model, &principal),
+            vec![Some(PendingTransactionStatus::Pending)]
+        );
+    }
+
+    #[test]
+    fn test_update_confirms_transaction_with_output_at_address() {
+        let (mut map, _mm) = setup();
+        let mut model = BtcUserPendingTransactionsModel::new(&mut map, None, None);
+        let principal = Principal::from_text(PRINCIPAL_TEXT_1).unwrap();
 
-        let old_transaction = StoredPendingTransaction {
-            txid: vec![1, 2, 3],
-            utxos: vec![(*UTXO_1).clone()],
-            created_at_timestamp_ns: yesterday_ns,
-        };
         model
-            .add_pending_transaction(principal, ADDRESS_1.to_string(), old_transaction.clone())
+            .add_pending_transaction(
+                principal,
+                ADDRESS_1.to_string(),
+                pending(&[1], &[&UTXO_1], None),
+            )
             .unwrap();
 
-        let valid_transaction = StoredPendingTransaction {
-            txid: vec![4, 5, 6],
-            utxos: vec![(*UTXO_2).clone()],
-            created_at_timestamp_ns: now_ns,
+        let utxos = [output(&[1], TIP_HEIGHT - 1)];
+        model.update_pending_transactions(principal, ADDRESS_1, &utxos, TIP_HEIGHT, 2_000_000);
+
+        let status = PendingTransactionStatus::Confirmed {
+            height: TIP_HEIGHT - 1,
         };
+        assert_eq!(statuses(&model, &principal), vec![Some(status)]);
+        assert_eq!(confirmations(status, TIP_HEIGHT), 2);
+    }
+
+    #[test]
+    fn test_update_confirms_replaced_version_of_transaction() {
+        let (mut map, _mm) = setup();
+        let mut model = BtcUserPendingTransactionsModel::new(&mut map, None, None);
+        let principal = Principal::from_text(PRINCIPAL_TEXT_1).unwrap();
+
         model
-            .add_pending_transaction(principal, ADDRESS_1.to_string(), valid_transaction.clone())
+            .add_pending_transaction(
+                principal,
+                ADDRESS_1.to_string(),
+                pending(&[2], &[&UTXO_1], Some(details(vec![vec![1]]))),
+            )
             .unwrap();
 
-        let pending_txs = model.get_pending_transactions(&principal, ADDRESS_1);
-        assert_eq!(pending_txs.len(), 2);
+        let utxos = [output(&[1], TIP_HEIGHT)];
+        model.update_pending_transactions(principal, ADDRESS_1, &utxos, TIP_HEIGHT, 2_000_000);
 
-        let all_utxos = &[(*UTXO_1).clone(), (*UTXO_2).clone()];
+        assert_eq!(
+            statuses(&model, &principal),
+            vec![Some(PendingTransactionStatus::Confirmed {
+                height: TIP_HEIGHT
+            })]
+        );
+    }
 
-        model.prune_pending_transactions(principal, all_utxos, now_ns + 1);
+    #[test]
+    fn test_update_confirms_transaction_with_spent_utxos_at_first_seen_height() {
+        let (mut map, _mm) = setup();
+        let mut model = BtcUserPendingTransactionsModel::new(&mut map, None, None);
+        let principal = Principal::from_text(PRINCIPAL_TEXT_1).unwrap();
 
-        let pending_txs = model.get_pending_transactions(&principal, ADDRESS_1);
-        assert_eq!(pending_txs.len(), 1);
-        assert_eq!(pending_txs[0], valid_transaction);
+        model
+            .add_pending_transaction(
+                principal,
+                ADDRESS_1.to_string(),
+                pending(&[1], &[&UTXO_1, &UTXO_2], Some(details(vec![]))),
+            )
+            .unwrap();
+
+        let utxos = [(*UTXO_3).clone()];
+        model.update_pending_transactions(principal, ADDRESS_1, &utxos, TIP_HEIGHT, 2_000_000);
+        model.update_pending_transactions(principal, ADDRESS_1, &utxos, TIP_HEIGHT + 1, 3_000_000);
+
+        assert_eq!(
+            statuses(&model, &principal),
+            vec![Some(PendingTransactionStatus::Confirmed {
+                height: TIP_HEIGHT
+            })]
+        );
     }
 
     #[test]
-    fn test_prune_with_available_utxos() {
+    fn test_update_confirms_client_transaction_with_partially_spent_utxos() {
         let (mut map, _mm) = setup();
         let mut model = BtcUserPendingTransactionsModel::new(&mut map, None, None);
         let principal = Principal::from_text(PRINCIPAL_TEXT_1).unwrap();
 
-        let now_ns = 1_000_000_000_000;
+        model
+            .add_pending_transaction(
+                principal,
+                ADDRESS_1.to_string(),
+                pending(&[1], &[&UTXO_1, &UTXO_2], None),
+            )
+            .unwrap();
 
-        let transaction_1 = StoredPendingTransaction {
-            txid: vec![1, 2, 3],
-            utxos: vec![(*UTXO_1).clone()],
-            created_at_timestamp_ns: now_ns,
-        };
-        let transaction_2 = StoredPendingTransaction {
-            txid: vec![4, 5, 6],
-            utxos: vec![(*UTXO_2).clone()],
-            created_at_timestamp_ns: now_ns,
-        };
+        let utxos = [(*UTXO_2).clone()];
+        model.update_pending_transactions(principal, ADDRESS_1, &utxos, TIP_HEIGHT, 2_000_000);
+
+        assert_eq!(
+            statuses(&model, &principal),
+            vec![Some(PendingTransactionStatus::Confirmed {
+                height: TIP_HEIGHT
+            })]
+        );
+    }
+
+    #[test]
+    fn test_update_drops_built_transaction_with_partially_spent_utxos() {
+        let (mut map, _mm) = setup();
+        let mut model = BtcUserPendingTransactionsModel::new(&mut map, None, None);
+        let principal = Principal::from_text(PRINCIPAL_TEXT_1).unwrap();
 
         model
-            .add_pending_transaction(principal, ADDRESS_1.to_string(), transaction_1.clone())
+            .add_pending_transaction(
+                principal,
+                ADDRESS_1.to_string(),
+                pending(&[1], &[&UTXO_1, &UTXO_2], Some(details(vec![]))),
+            )
             .unwrap();
+
+        let utxos = [(*UTXO_2).clone()];
+        model.update_pending_transactions(principal, ADDRESS_1, &utxos, TIP_HEIGHT, 2_000_000);
+
+        assert_eq!(
+            statuses(&model, &principal),
+            vec![Some(PendingTransactionStatus::Dropped {
+                timestamp_ns: 2_000_000
+            })]
+        );
+        assert!(!model.has_intersecting_pending_utxos(principal, &[(*UTXO_2).clone()]));
+        assert!(model.get_reserved_outpoints(principal).is_empty());
+    }
+
+    #[test]
+    fn test_update_drops_transaction_not_mined_in_time() {
+        let (mut map, _mm) = setup();
+        let mut model = BtcUserPendingTransactionsModel::new(&mut map, None, None);
+        let principal = Principal::from_text(PRINCIPAL_TEXT_1).unwrap();
+
+        let transaction = pending(&[1], &[&UTXO_1], None);
+        let expired_ns = transaction.created_at_timestamp_ns + DROP_TIMEOUT_NS + 1;
         model
-            .add_pending_transaction(principal, ADDRESS_1.to_string(), transaction_2.clone())
+            .add_pending_transaction(principal, ADDRESS_1.to_string(), transaction)
             .unwrap();
 
-        let pending_txs = model.get_pending_transactions(&principal, ADDRESS_1);
-        assert_eq!(pending_txs.len(), 2);
+        let utxos = [(*UTXO_1).clone()];
+        model.update_pending_transactions(principal, ADDRESS_1, &utxos, TIP_HEIGHT, expired_ns - 1);
+        assert_eq!(
+            statuses(&model, &principal),
+            vec![Some(PendingTransactionStatus::Pending)]
+        );
 
-        let available_utxos = &[(*UTXO_1).clone()];
-        model.prune_pending_transactions(principal, available_utxos, now_ns);
+        model.update_pending_transactions(principal, ADDRESS_1, &utxos, TIP_HEIGHT, expired_ns);
+        assert_eq!(
+            statuses(&model, &principal),
+            vec![Some(PendingTransactionStatus::Dropped {
+                timestamp_ns: expired_ns
+            })]
+        );
 
-        let pending_txs = model.get_pending_transactions(&principal, ADDRESS_1);
-        assert_eq!(pending_txs.len(), 1);
-        assert_eq!(pending_txs[0], transaction_1);
+        model.update_pending_transactions(
+            principal,
+            ADDRESS_1,
+            &utxos,
+            TIP_HEIGHT,
+            expired_ns + DROPPED_RETENTION_NS + 1,
+        );
+        assert!(model
+            .get_pending_transactions(&principal, ADDRESS_1)
+            .is_empty());
+        assert!(model.get_reserved_outpoints(principal).is_empty());
     }
 
     #[test]
-    fn test_does_not_prune_with_partial_available_utxos() {
+    fn test_update_removes_transaction_once_deep_enough() {
         let (mut map, _mm) = setup();
         let mut model = BtcUserPendingTransactionsModel::new(&mut map, None, None);
         let principal = Principal::from_text(PRINCIPAL_TEXT_1).unwrap();
 
-        let now_ns = 1_000_000_000_000;
-
-        let transaction_1 = StoredPendingTransaction {
-            txid: vec![1, 2, 3],
-            utxos: vec![(*UTXO_1).clone()],
-            created_at_timestamp_ns: now_ns,
-        };
-        let transaction_2 = StoredPendingTransaction {
-            txid: vec![4, 5, 6],
-            utxos: vec![(*UTXO_2).clone(), (*UTXO_3).clone()],
-            created_at_timestamp_ns: now_ns,
-        };
-
-        model
-            .add_pending_transaction(principal, ADDRESS_1.to_string(), transaction_1.clone())
-            .unwrap();
         model
-            .add_pending_transaction(principal, ADDRESS_1.to_string(), transaction_2.clone())
+            .add_pending_transaction(
+                principal,
+                ADDRESS_1.to_string(),
+                pending(&[1], &[&UTXO_1], None),
+            )
             .unwrap();
 
-        let pending_txs = model.get_pending_transactions(&principal, ADDRESS_1);
-        assert_eq!(pending_txs.len(), 2);
-
-        let available_utxos = &[(*UTXO_1).clone(), (*UTXO_3).clone()];
-        model.prune_pending_transactions(principal, available_utxos, now_ns);
+        let utxos = [output(&[1], TIP_HEIGHT)];
+        let deep_tip_height = TIP_HEIGHT + MIN_CONFIRMATIONS_ACCEPTED_BTC_TX - 1;
+        model.update_pending_transactions(
+            principal,
+            ADDRESS_1,
+            &utxos,
+            deep_tip_height - 1,
+            2_000_000,
+        );
+        assert_eq!(
+            model.get_pending_transactions(&principal, ADDRESS_1).len(),
+            1
+        );
 
-        let pending_txs = model.get_pending_transactions(&principal, ADDRESS_1);
-        assert_eq!(pending_txs.len(), 2);
+        model.update_pending_transactions(principal, ADDRESS_1, &utxos, deep_tip_height, 3_000_000);
+        assert!(model
+            .get_pending_transactions(&principal, ADDRESS_1)
+            .is_empty());
     }
 
-    // Regression: an empty `current_utxos` means "no confirmation information available", not
-    // "all utxos confirmed". A caller fetching utxos for an address with none must not be able
-    // to wipe their non-expired pending reservations.
     #[test]
-    fn test_empty_current_utxos_retains_non_expired() {
+    fn test_update_returns_reorganized_transaction_to_pending() {
         let (mut map, _mm) = setup();
         let mut model = BtcUserPendingTransactionsModel::new(&mut map, None, None);
         let principal = Principal::from_text(PRINCIPAL_TEXT_1).unwrap();
 
-        let now_ns = 1_000_000_000_000;
-
-        let pending = StoredPendingTransaction {
-            txid: vec![1, 2, 3],
-            utxos: vec![(*UTXO_1).clone(), (*UTXO_2).clone()],
-            created_at_timestamp_ns: now_ns,
-        };
         model
-            .add_pending_transaction(principal, ADDRESS_1.to_string(), pending.clone())
+            .add_pending_transaction(
+                principal,
+                ADDRESS_1.to_string(),
+                pending(&[1], &[&UTXO_1], None),
+            )
             .unwrap();
 
-        model.prune_pending_transactions(principal, &[], now_ns);
+        model.update_pending_transactions(
+            principal,
+            ADDRESS_1,
+            &[output(&[1], TIP_HEIGHT)],
+            TIP_HEIGHT,
+            2_000_000,
+        );
+        model.update_pending_transactions(
+            principal,
+            ADDRESS_1,
+            &[(*UTXO_1).clone()],
+            TIP_HEIGHT,
+            3_000_000,
+        );
 
-        let pending_txs = model.get_pending_transactions(&principal, ADDRESS_1);
-        assert_eq!(pending_txs.len(), 1);
-        assert_eq!(pending_txs[0], pending);
+        assert_eq!(
+            statuses(&model, &principal),
+            vec![Some(PendingTransactionStatus::Pending)]
+        );
     }
 
     #[test]
-    fn test_empty_current_utxos_still_prunes_expired() {
+    fn test_update_only_affects_given_address() {
         let (mut map, _mm) = setup();
         let mut model = BtcUserPendingTransactionsModel::new(&mut map, None, None);
         let principal = Principal::from_text(PRINCIPAL_TEXT_1).unwrap();
 
-        let yesterday_ns = 1_000_000;
-        let now_ns = yesterday_ns + HOUR_IN_NS + 1;
-
-        let expired = StoredPendingTransaction {
-            txid: vec![1, 2, 3],
-            utxos: vec![(*UTXO_1).clone()],
-            created_at_timestamp_ns: yesterday_ns,
-        };
         model
-            .add_pending_transaction(principal, ADDRESS_1.to_string(), expired)
+            .add_pending_transaction(
+                principal,
+                ADDRESS_1.to_string(),
+                pending(&[1], &[&UTXO_1], None),
+            )
+            .unwrap();
+        model
+            .add_pending_transaction(
+                principal,
+                ADDRESS_2.to_string(),
+                pending(&[2], &[&UTXO_3], None),
+            )
             .unwrap();
 
-        model.prune_pending_transactions(principal, &[], now_ns);
+        model.update_pending_transactions(principal, ADDRESS_2, &[], TIP_HEIGHT, 2_000_000);
 
-        let pending_txs = model.get_pending_transactions(&principal, ADDRESS_1);
-        assert!(pending_txs.is_empty());
+        assert_eq!(statuses(&model, &principal), vec![None]);
+        assert_eq!(
+            model.get_pending_transactions(&principal, ADDRESS_2)[0].status,
+            Some(PendingTransactionStatus::Confirmed { height: TIP_HEIGHT })
+        );
     }
 
     #[test]
@@ -584,11 +919,15 @@ mod tests {
             txid: vec![1],
             utxos: vec![(*UTXO_1).clone()],
             created_at_timestamp_ns: 1_000_000,
+            details: None,
+            status: None,
         };
         let existing_2 = StoredPendingTransaction {
             txid: vec![2],
             utxos: vec![(*UTXO_2).clone()],
             created_at_timestamp_ns: 1_000_000,
+            details: None,
+            status: None,
         };
 
         model
@@ -610,6 +949,8 @@ mod tests {
             txid: vec![1],
             utxos: vec![(*UTXO_1).clone()],
             created_at_timestamp_ns: 1_000_000,
+            details: None,
+            status: None,
         };
 
         map.insert(
@@ -632,6 +973,8 @@ mod tests {
             txid: vec![1],
             utxos: vec![(*UTXO_1).clone()],
             created_at_timestamp_ns: 1_000_000,
+            details: None,
+            status: None,
         };
 
         model
@@ -662,6 +1005,8 @@ mod tests {
             txid: vec![1],
             utxos: vec![(*UTXO_1).clone(), (*UTXO_2).clone()],
             created_at_timestamp_ns: 1_000_000,
+            details: None,
+            status: None,
         };
         model
             .add_pending_transaction(principal, ADDRESS_1.to_string(), first)
@@ -683,6 +1028,8 @@ mod tests {
             txid: vec![1],
             utxos: vec![(*UTXO_1).clone(), (*UTXO_2).clone()],
             created_at_timestamp_ns: 1_000_000,
+            details: None,
+            status: None,
         };
         model
             .add_pending_transaction(principal, ADDRESS_1.to_string(), first)
@@ -706,6 +1053,8 @@ mod tests {
             txid: vec![1],
             utxos: vec![(*UTXO_3).clone()],
             created_at_timestamp_ns: 1_000_000,
+            details: None,
+            status: None,
         };
         model
             .add_pending_transaction(principal, ADDRESS_1.to_string(), first)
@@ -732,6 +1081,8 @@ mod tests {
                 height: 1,
             }],
             created_at_timestamp_ns: 1_000_000,
+            details: None,
+            status: None,
         };
 
         model
@@ -751,6 +1102,95 @@ mod tests {
         assert!(!model.has_intersecting_pending_utxos(principal, &[candidate]));
     }
 
+    #[test]
+    fn test_replace_pending_transaction() {
+        let (mut map, _mm) = setup();
+        let mut model = BtcUserPendingTransactionsModel::new(&mut map, None, None);
+        let principal = Principal::from_text(PRINCIPAL_TEXT_1).unwrap();
+
+        let existing_1 = StoredPendingTransaction {
+            txid: vec![1],
+            utxos: vec![(*UTXO_1).clone()],
+            created_at_timestamp_ns: 1_000_000,
+            details: None,
+            status: None,
+        };
+        let existing_2 = StoredPendingTransaction {
+            txid: vec![2],
+            utxos: vec![(*UTXO_2).clone()],
+            created_at_timestamp_ns: 1_000_000,
+            details: None,
+            status: None,
+        };
+        let replacement = StoredPendingTransaction {
+            txid: vec![3],
+            utxos: vec![(*UTXO_1).clone()],
+            created_at_timestamp_ns: 2_000_000,
+            details: None,
+            status: None,
+        };
+
+        model
+            .add_pending_transaction(principal, ADDRESS_1.to_string(), existing_1)
+            .unwrap();
+        model
+            .add_pending_transaction(principal, ADDRESS_1.to_string(), existing_2.clone())
+            .unwrap();
+
+        model
+            .replace_pending_transaction(principal, ADDRESS_1, &[1], replacement.clone())
+            .unwrap();
+
+        assert_eq!(
+            model.get_pending_transactions(&principal, ADDRESS_1),
+            vec![replacement.clone(), existing_2]
+        );
+        assert!(model
+            .replace_pending_transaction(principal, ADDRESS_1, &[1], replacement.clone())
+            .is_err());
+        assert!(model
+            .replace_pending_transaction(principal, ADDRESS_2, &[3], replacement)
+            .is_err());
+    }
+
+    #[test]
+    fn test_get_reserved_outpoints_across_addresses() {
+        let (mut map, _mm) = setup();
+        let mut model = BtcUserPendingTransactionsModel::new(&mut map, None, None);
+        let principal_1 = Principal::from_text(PRINCIPAL_TEXT_1).unwrap();
+        let principal_2 = Principal::from_text(PRINCIPAL_TEXT_2).unwrap();
+
+        assert!(model.get_reserved_outpoints(principal_1).is_empty());
+
+        let existing_1 = StoredPendingTransaction {
+            txid: vec![1],
+            utxos: vec![(*UTXO_1).clone()],
+            created_at_timestamp_ns: 1_000_000,
+            details: None,
+            status: None,
+        };
+        let existing_2 = StoredPendingTransaction {
+            txid: vec![2],
+            utxos: vec![(*UTXO_2).clone()],
+            created_at_timestamp_ns: 1_000_000,
+            details: None,
+            status: None,
+        };
+
+        model
+            .add_pending_transaction(principal_1, ADDRESS_1.to_string(), existing_1)
+            .unwrap();
+        model
+            .add_pending_transaction(principal_1, ADDRESS_2.to_string(), existing_2)
+            .unwrap();
+
+        assert_eq!(
+            model.get_reserved_outpoints(principal_1),
+            HashSet::from([(TXID_A.to_vec(), 0), (TXID_A.to_vec(), 1)])
+        );
+        assert!(model.get_reserved_outpoints(principal_2).is_empty());
+    }
+
     #[test]
     fn test_persistence_across_reinit() {
         let (memory_manager, _map) = {
@@ -765,6 +1205,8 @@ mod tests {
             txid: vec![1, 2, 3],
             utxos: vec![(*UTXO_1).clone()],
             created_at_timestamp_ns: 1_234_567,
+            details: None,
+            status: None,
         };
 
         {
@@ -0,0 +1,184 @@
+//! Periodic tracking of pending BTC transactions until they are confirmed or dropped.
+//!
+//! The endpoints update the pending transactions of the caller whenever they read them, but a user
+//! who does not come back would keep their transactions, and the UTXOs they reserve, forever.
+//! A timer therefore walks the stored principals in batches, resuming after the last principal
+//! checked, and updates their transactions with the current UTXOs of each address.
+
+use std::{cell::RefCell, ops::Bound};
+
+use bitcoin::{address::NetworkUnchecked, Address};
+use ic_cdk::bitcoin_canister::Network as BitcoinNetwork;
+use ic_cdk_timers::set_timer_interval;
+use shared::types::bitcoin::{
+    PENDING_TRANSACTIONS_CHECK_INTERVAL, PENDING_TRANSACTIONS_CHECK_TIMEOUT_NS,
+};
+
+use crate::{
+    bitcoin::{api, pending_tx_model::BtcUserPendingTransactionsModel},
+    signer::transform_network,
+    state::{mutate_state, read_state},
+    types::StoredPrincipal,
+};
+
+/// Upper bound on the number of addresses checked per timer tick. The addresses of a principal are
+/// always checked together, so a tick may overshoot by up to the number of addresses per user.
+const MAX_ADDRESSES_PER_CHECK: usize = 100;
+
+thread_local! {
+    /// `None` = idle; `Some(timestamp_ns)` = check started at that IC time.
+    static CHECK_STARTED_AT: RefCell<Option<u64>> = const { RefCell::new(None) };
+    /// The last principal checked, or `None` to start from the first one.
+    static CHECK_CURSOR: RefCell<Option<StoredPrincipal>> = const { RefCell::new(None) };
+}
+
+/// Sets up the periodic check of the stored pending transactions.
+pub fn init_pending_transactions_tracker() {
+    set_timer_interval(PENDING_TRANSACTIONS_CHECK_INTERVAL, || async {
+        spawn_check_if_idle();
+    });
+}
+
+/// Spawns a check only if no previous check is still in flight.
+/// If a previous check appears stuck (older than `PENDING_TRANSACTIONS_CHECK_TIMEOUT_NS`),
+/// the stale lock is cleared and a new check is allowed to proceed.
+fn spawn_check_if_idle() {
+    let now = ic_cdk::api::time();
+
+    let check_in_progress = CHECK_STARTED_AT.with(|cell| {
+        cell.borrow().is_some_and(|started| {
+            let elapsed = now.saturating_sub(started);
+            if elapsed > PENDING_TRANSACTIONS_CHECK_TIMEOUT_NS {
+                ic_cdk::eprintln!(
+                    "Pending transactions check appears stuck (started {}s ago), forcing unlock",
+                    elapsed / 1_000_000_000
+                );
+                false
+            } else {
+                true
+            }
+        })
+    });
+
+    if check_in_progress {
+        return;
+    }
+
+    CHECK_STARTED_AT.with(|cell| {
+        *cell.borrow_mut() = Some(now);
+    });
+
+    ic_cdk::futures::spawn_017_compat(async {
+        check_pending_transactions_batch().await;
+        CHECK_STARTED_AT.with(|cell| {
+            *cell.borrow_mut() = None;
+        });
+    });
+}
+
+/// Updates the pending transactions of the next batch of principals.
+///
+/// Addresses are checked one at a time to avoid concurrent inter-canister calls to the bitcoin
+/// canister.
+async fn check_pending_transactions_batch() {
+    let after = CHECK_CURSOR.with(|cell| *cell.borrow());
+    let (batch, last) = next_batch(after);
+    CHECK_CURSOR.with(|cell| {
+        *cell.borrow_mut() = last;
+    });
+
+    for (principal, address) in batch {
+        let Some(network) = address_network(&address) else {
+            ic_cdk::eprintln!("Skipping pending transactions of unknown address {address}");
+            continue;
+        };
+
+        match api::get_address_utxos(network, address.clone()).await {
+            Ok(address_utxos) => mutate_state(|state| {
+                BtcUserPendingTransactionsModel::new(
+                    &mut state.btc_user_pending_transactions,
+                    None,
+                    None,
+                )
+                .update_pending_transactions(
+                    principal.0,
+                    &address,
+                    &address_utxos.utxos,
+                    address_utxos.tip_height,
+                    ic_cdk::api::time(),
+                );
+            }),
+            Err(err) => {
+                ic_cdk::eprintln!("Failed to get UTXOs of {address}: {err}");
+            }
+        }
+    }
+}
+
+/// Returns the `(principal, address)` pairs of the principals following `after`, and the last
+/// principal returned, or `None` once the end of the map is reached.
+fn next_batch(
+    after: Option<StoredPrincipal>,
+) -> (Vec<(StoredPrincipal, String)>, Option<StoredPrincipal>) {
+    read_state(|state| {
+        let start = after.map_or(Bound::Unbounded, Bound::Excluded);
+        let mut batch = Vec::new();
+        let mut last = None;
+        for entry in state
+            .btc_user_pending_transactions
+            .range((start, Bound::Unbounded))
+        {
+            if batch.len() >= MAX_ADDRESSES_PER_CHECK {
+                return (batch, last);
+            }
+            let principal = *entry.key();
+            batch.extend(
+                entry
+                    .value()
+                    .0
+                    .into_keys()
+                    .map(|address| (principal, address)),
+            );
+            last = Some(principal);
+        }
+        (batch, None)
+    })
+}
+
+/// Returns the network of a stored address. Testnet and regtest P2SH addresses share their
+/// encoding, and are taken to be testnet addresses.
+fn address_network(address: &str) -> Option<BitcoinNetwork> {
+    let address = address.parse::<Address<NetworkUnchecked>>().ok()?;
+    [
+        BitcoinNetwork::Mainnet,
+        BitcoinNetwork::Testnet,
+        BitcoinNetwork::Regtest,
+    ]
+    .into_iter()
+    .find(|network| address.is_valid_for_network(transform_network(*network)))
+}
+
+#[cfg(test)]
+mod tests {
+    use ic_cdk::bitcoin_canister::Network as BitcoinNetwork;
+    use pretty_assertions::assert_eq;
+
+    use super::address_network;
+
+    #[test]
+    fn test_address_network() {
+        assert_eq!(
+            address_network("bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq"),
+            Some(BitcoinNetwork::Mainnet)
+        );
+        assert_eq!(
+            address_network("tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx"),
+            Some(BitcoinNetwork::Testnet)
+        );
+        assert_eq!(
+            address_network("bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080"),
+            Some(BitcoinNetwork::Regtest)
+        );
+        assert_eq!(address_network("not an address"), None);
+    }
+}
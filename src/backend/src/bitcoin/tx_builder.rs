@@ -0,0 +1,474 @@
+//! Builds unsigned Bitcoin transactions spending the outputs of a single address of the user.
+
+use bitcoin::{
+    absolute::LockTime, hashes::Hash, psbt::Psbt, transaction::Version, Address, Amount, OutPoint,
+    ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, Witness,
+};
+use ic_cdk::bitcoin_canister::{MillisatoshiPerByte, Utxo};
+use shared::types::bitcoin::{
+    BtcBuildTransactionError, PendingTransactionStatus, StoredPendingTransaction,
+    StoredTransactionDetails,
+};
+
+use crate::bitcoin::{
+    address::SourceAddress,
+    coin_selection::{CoinSelection, Selection, SelectionTarget},
+};
+
+/// The lowest fee rate relayed by default by Bitcoin Core nodes.
+pub const MIN_FEE_RATE_MILLISAT_PER_VBYTE: MillisatoshiPerByte = 1_000;
+
+/// Weight of the version and lock time fields.
+const TX_OVERHEAD_WEIGHT: u64 = 4 * (4 + 4);
+/// Weight of the `SegWit` marker and flag bytes.
+const SEGWIT_MARKER_WEIGHT: u64 = 2;
+/// Weight of the outpoint, empty script and sequence of an input.
+const INPUT_WEIGHT: u64 = 4 * (32 + 4 + 1 + 4);
+/// An unsigned transaction together with the UTXOs it spends.
+#[derive(Debug)]
+pub struct BuiltTransaction {
+    /// The transaction serialized as a BIP-174 PSBT.
+    pub psbt: Vec<u8>,
+    /// The id of the transaction once signed, in internal byte order.
+    pub txid: Vec<u8>,
+    pub utxos: Vec<Utxo>,
+    pub fee_satoshis: u64,
+    pub change_satoshis: u64,
+    pub vsize: u64,
+}
+
+impl BuiltTransaction {
+    /// Returns the pending transaction reserving the UTXOs of this transaction, with the details
+    /// needed to bump its fee.
+    pub fn to_stored(
+        &self,
+        recipient: &Address,
+        amount_satoshis: u64,
+        now_ns: u64,
+    ) -> StoredPendingTransaction {
+        StoredPendingTransaction {
+            txid: self.txid.clone(),
+            utxos: self.utxos.clone(),
+            created_at_timestamp_ns: now_ns,
+            details: Some(StoredTransactionDetails {
+                recipient: recipient.to_string(),
+                amount_satoshis,
+                fee_satoshis: self.fee_satoshis,
+                change_satoshis: self.change_satoshis,
+                vsize: self.vsize,
+                replaced_txids: Vec::new(),
+                cpfp_child: None,
+            }),
+            status: Some(PendingTransactionStatus::Pending),
+        }
+    }
+}
+
+/// Builds a transaction paying `amount_satoshis` to `recipient` from the `utxos` of `source`,
+/// sending any change above the dust limit back to `source`.
+///
+/// The UTXOs the transaction spends are chosen by `coin_selection`.
+///
+/// # Errors
+/// - `AmountTooLow`: The amount is below the dust limit of the recipient's output.
+/// - `InsufficientFunds`: The UTXOs, up to `MAX_UTXOS_LEN` of them, do not cover the amount and
+///   the fee.
+/// - `InternalError`: A UTXO has a malformed txid.
+pub fn build_transaction(
+    utxos: &[Utxo],
+    source: &SourceAddress,
+    recipient: &Address,
+    amount_satoshis: u64,
+    fee_rate: MillisatoshiPerByte,
+    coin_selection: &dyn CoinSelection,
+) -> Result<BuiltTransaction, BtcBuildTransactionError> {
+    let recipient_script = recipient.script_pubkey();
+    let min_satoshis = recipient_script.minimal_non_dust().to_sat();
+    if amount_satoshis < min_satoshis {
+        return Err(BtcBuildTransactionError::AmountTooLow { min_satoshis });
+    }
+
+    let source_script = source.script_pubkey();
+    let fee_rate = fee_rate.max(MIN_FEE_RATE_MILLISAT_PER_VBYTE);
+    let selection = coin_selection
+        .select(
+            utxos,
+            &SelectionTarget {
+                amount_satoshis,
+                fee_rate,
+                recipient_script: &recipient_script,
+                change_script: &source_script,
+                input_satisfaction_weight: source.satisfaction_weight(),
+            },
+        )
+        .ok_or_else(|| BtcBuildTransactionError::InsufficientFunds {
+            available_satoshis: utxos.iter().map(|utxo| utxo.value).sum(),
+        })?;
+
+    build_psbt(selection, source, recipient_script, amount_satoshis)
+        .map_err(|msg| BtcBuildTransactionError::InternalError { msg })
+}
+
+/// Builds the unsigned transaction spending the UTXOs of `selection`, held by `source`, and paying
+/// `amount_satoshis` to `recipient_script` and the change of `selection`, if any, back to `source`.
+///
+/// # Errors
+/// - A UTXO has a malformed txid.
+pub fn build_psbt(
+    selection: Selection,
+    source: &SourceAddress,
+    recipient_script: ScriptBuf,
+    amount_satoshis: u64,
+) -> Result<BuiltTransaction, String> {
+    let source_script = source.script_pubkey();
+    let mut outputs = vec![TxOut {
+        value: Amount::from_sat(amount_satoshis),
+        script_pubkey: recipient_script,
+    }];
+    if selection.change_satoshis > 0 {
+        outputs.push(TxOut {
+            value: Amount::from_sat(selection.change_satoshis),
+            script_pubkey: source_script.clone(),
+        });
+    }
+
+    let inputs = selection
+        .utxos
+        .iter()
+        .map(|utxo| {
+            Ok(TxIn {
+                previous_output: OutPoint {
+                    txid: txid_from_bytes(&utxo.outpoint.txid)?,
+                    vout: utxo.outpoint.vout,
+                },
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Witness::new(),
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let transaction = Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: inputs,
+        output: outputs,
+    };
+    // The txid covers the script sigs, which the unsigned transaction of a PSBT leaves empty.
+    let mut signed = transaction.clone();
+    for input in &mut signed.input {
+        input.script_sig = source.script_sig();
+    }
+    let txid = signed.compute_txid().to_byte_array().to_vec();
+
+    let mut psbt = Psbt::from_unsigned_tx(transaction)
+        .map_err(|err| format!("Failed to create PSBT: {err}"))?;
+    for (input, utxo) in psbt.inputs.iter_mut().zip(&selection.utxos) {
+        input.witness_utxo = Some(TxOut {
+            value: Amount::from_sat(utxo.value),
+            script_pubkey: source_script.clone(),
+        });
+        source.fill_psbt_input(input);
+    }
+
+    Ok(BuiltTransaction {
+        psbt: psbt.serialize(),
+        txid,
+        utxos: selection.utxos,
+        fee_satoshis: selection.fee_satoshis,
+        change_satoshis: selection.change_satoshis,
+        vsize: selection.vsize,
+    })
+}
+
+/// Estimates the virtual size of a transaction spending `n_inputs` outputs, each adding
+/// `input_satisfaction_weight` once signed, as returned by `SourceAddress::satisfaction_weight`.
+pub fn estimate_vsize(
+    n_inputs: usize,
+    input_satisfaction_weight: u64,
+    output_scripts: &[&ScriptBuf],
+) -> u64 {
+    let outputs_weight: u64 = output_scripts
+        .iter()
+        .map(|script| 4 * (8 + varint_len(script.len()) + script.len() as u64))
+        .sum();
+    let weight = TX_OVERHEAD_WEIGHT
+        + SEGWIT_MARKER_WEIGHT
+        + 4 * (varint_len(n_inputs) + varint_len(output_scripts.len()))
+        + n_inputs as u64 * (INPUT_WEIGHT + input_satisfaction_weight)
+        + outputs_weight;
+    weight.div_ceil(4)
+}
+
+/// Returns the fee, in satoshis, of `vsize` virtual bytes at `fee_rate`, rounded up.
+pub fn fee_for_vsize(vsize: u64, fee_rate: MillisatoshiPerByte) -> u64 {
+    vsize.saturating_mul(fee_rate).div_ceil(1_000)
+}
+
+fn varint_len(n: usize) -> u64 {
+    match n {
+        0..=0xfc => 1,
+        0xfd..=0xffff => 3,
+        0x1_0000..=0xffff_ffff => 5,
+        _ => 9,
+    }
+}
+
+fn txid_from_bytes(bytes: &[u8]) -> Result<Txid, String> {
+    let bytes: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| format!("Invalid UTXO txid length: {}", bytes.len()))?;
+    Ok(Txid::from_byte_array(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{
+        ecdsa::Signature,
+        script::PushBytes,
+        secp256k1::{Message, Secp256k1, SecretKey},
+        sighash::{EcdsaSighashType, SighashCache},
+        CompressedPublicKey, Network,
+    };
+    use ic_cdk::bitcoin_canister::{Network as BitcoinNetwork, Outpoint};
+    use pretty_assertions::{assert_eq, assert_ne};
+    use shared::types::bitcoin::BtcAddressType;
+
+    use super::*;
+    use crate::bitcoin::coin_selection::LargestFirst;
+
+    const SOURCE_PUBKEY: &str =
+        "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+    const RECIPIENT_PUBKEY: &str =
+        "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5";
+
+    fn address(pubkey: &str) -> Address {
+        let key = CompressedPublicKey::from_slice(&hex::decode(pubkey).unwrap()).unwrap();
+        Address::p2wpkh(&key, Network::Regtest)
+    }
+
+    fn source(address_type: BtcAddressType) -> SourceAddress {
+        SourceAddress::new(
+            address_type,
+            &hex::decode(SOURCE_PUBKEY).unwrap(),
+            BitcoinNetwork::Regtest,
+        )
+        .unwrap()
+    }
+
+    fn utxo(seed: u8, value: u64) -> Utxo {
+        Utxo {
+            outpoint: Outpoint {
+                txid: vec![seed; 32],
+                vout: u32::from(seed),
+            },
+            value,
+            height: 100,
+        }
+    }
+
+    fn build(utxos: &[Utxo], amount: u64) -> Result<BuiltTransaction, BtcBuildTransactionError> {
+        build_transaction(
+            utxos,
+            &source(BtcAddressType::P2wpkh),
+            &address(RECIPIENT_PUBKEY),
+            amount,
+            2_000,
+            &LargestFirst,
+        )
+    }
+
+    #[test]
+    fn test_estimate_vsize_of_p2wpkh_transaction() {
+        let source = source(BtcAddressType::P2wpkh);
+        let script = source.script_pubkey();
+        let weight = source.satisfaction_weight();
+        assert_eq!(estimate_vsize(1, weight, &[&script, &script]), 141);
+        assert_eq!(estimate_vsize(1, weight, &[&script]), 110);
+        assert_eq!(estimate_vsize(2, weight, &[&script, &script]), 209);
+    }
+
+    #[test]
+    fn test_build_sizes_inputs_per_address_type() {
+        let vsize = |address_type| {
+            build_transaction(
+                &[utxo(1, 100_000)],
+                &source(address_type),
+                &address(RECIPIENT_PUBKEY),
+                50_000,
+                1_000,
+                &LargestFirst,
+            )
+            .unwrap()
+            .vsize
+        };
+
+        assert_eq!(vsize(BtcAddressType::P2wpkh), 141);
+        assert_eq!(vsize(BtcAddressType::P2shP2wpkh), 165);
+        assert_eq!(vsize(BtcAddressType::P2tr), 142);
+    }
+
+    #[test]
+    fn test_psbt_has_taproot_internal_key() {
+        let source = source(BtcAddressType::P2tr);
+        let built = build_transaction(
+            &[utxo(1, 100_000)],
+            &source,
+            &address(RECIPIENT_PUBKEY),
+            50_000,
+            1_000,
+            &LargestFirst,
+        )
+        .unwrap();
+        let psbt = Psbt::deserialize(&built.psbt).unwrap();
+
+        assert_eq!(
+            psbt.inputs[0].tap_internal_key.map(|key| key.to_string()),
+            Some(SOURCE_PUBKEY[2..].to_string())
+        );
+        assert_eq!(
+            psbt.inputs[0].witness_utxo.as_ref().unwrap().script_pubkey,
+            source.script_pubkey()
+        );
+        assert_eq!(
+            psbt.unsigned_tx.output[1].script_pubkey,
+            source.script_pubkey()
+        );
+    }
+
+    #[test]
+    fn test_txid_is_that_of_signed_p2sh_p2wpkh_transaction() {
+        let source = source(BtcAddressType::P2shP2wpkh);
+        let built = build_transaction(
+            &[utxo(1, 60_000), utxo(2, 30_000)],
+            &source,
+            &address(RECIPIENT_PUBKEY),
+            80_000,
+            1_000,
+            &LargestFirst,
+        )
+        .unwrap();
+        let psbt = Psbt::deserialize(&built.psbt).unwrap();
+
+        // `SOURCE_PUBKEY` is the generator point, of secret key 1.
+        let mut secret_key = [0; 32];
+        secret_key[31] = 1;
+        let secret_key = SecretKey::from_slice(&secret_key).unwrap();
+        let secp = Secp256k1::signing_only();
+        let mut sighashes = SighashCache::new(&psbt.unsigned_tx);
+        let mut signed = psbt.unsigned_tx.clone();
+        for (index, (txin, input)) in signed.input.iter_mut().zip(&psbt.inputs).enumerate() {
+            let redeem_script = input.redeem_script.as_ref().unwrap();
+            let sighash = sighashes
+                .p2wpkh_signature_hash(
+                    index,
+                    redeem_script,
+                    input.witness_utxo.as_ref().unwrap().value,
+                    EcdsaSighashType::All,
+                )
+                .unwrap();
+            let signature = Signature::sighash_all(
+                secp.sign_ecdsa(&Message::from_digest(sighash.to_byte_array()), &secret_key),
+            );
+            txin.witness = Witness::p2wpkh(&signature, &secret_key.public_key(&secp));
+            txin.script_sig = ScriptBuf::builder()
+                .push_slice(<&PushBytes>::try_from(redeem_script.as_bytes()).unwrap())
+                .into_script();
+        }
+
+        assert_eq!(signed.compute_txid().to_byte_array().to_vec(), built.txid);
+        assert_ne!(
+            psbt.unsigned_tx.compute_txid().to_byte_array().to_vec(),
+            built.txid
+        );
+    }
+
+    #[test]
+    fn test_fee_for_vsize_rounds_up() {
+        assert_eq!(fee_for_vsize(141, 1_000), 141);
+        assert_eq!(fee_for_vsize(141, 1_500), 212);
+    }
+
+    #[test]
+    fn test_build_with_change() {
+        let built = build(&[utxo(1, 100_000)], 50_000).unwrap();
+
+        assert_eq!(built.vsize, 141);
+        assert_eq!(built.fee_satoshis, 282);
+        assert_eq!(built.change_satoshis, 100_000 - 50_000 - 282);
+        assert_eq!(built.utxos, vec![utxo(1, 100_000)]);
+    }
+
+    #[test]
+    fn test_build_applies_min_fee_rate() {
+        let built = build_transaction(
+            &[utxo(1, 100_000)],
+            &source(BtcAddressType::P2wpkh),
+            &address(RECIPIENT_PUBKEY),
+            50_000,
+            0,
+            &LargestFirst,
+        )
+        .unwrap();
+
+        assert_eq!(built.fee_satoshis, 141);
+    }
+
+    #[test]
+    fn test_build_fails_with_insufficient_funds() {
+        let utxos = [utxo(1, 10_000), utxo(2, 20_000)];
+
+        assert_eq!(
+            build(&utxos, 30_000).unwrap_err(),
+            BtcBuildTransactionError::InsufficientFunds {
+                available_satoshis: 30_000
+            }
+        );
+    }
+
+    #[test]
+    fn test_build_fails_below_dust() {
+        assert_eq!(
+            build(&[utxo(1, 100_000)], 100).unwrap_err(),
+            BtcBuildTransactionError::AmountTooLow { min_satoshis: 294 }
+        );
+    }
+
+    #[test]
+    fn test_build_fails_with_malformed_txid() {
+        let mut malformed = utxo(1, 100_000);
+        malformed.outpoint.txid.pop();
+
+        assert!(matches!(
+            build(&[malformed], 50_000),
+            Err(BtcBuildTransactionError::InternalError { .. })
+        ));
+    }
+
+    #[test]
+    fn test_psbt_round_trips_with_witness_utxos() {
+        let utxos = [utxo(1, 60_000), utxo(2, 30_000)];
+        let built = build(&utxos, 80_000).unwrap();
+        let psbt = Psbt::deserialize(&built.psbt).unwrap();
+
+        assert_eq!(
+            psbt.unsigned_tx.compute_txid().to_byte_array().to_vec(),
+            built.txid
+        );
+        assert_eq!(psbt.unsigned_tx.output.len(), 2);
+        assert_eq!(psbt.unsigned_tx.output[0].value.to_sat(), 80_000);
+        assert_eq!(
+            psbt.unsigned_tx.output[1].script_pubkey,
+            source(BtcAddressType::P2wpkh).script_pubkey()
+        );
+        for (input, utxo) in psbt.inputs.iter().zip(&utxos) {
+            let witness_utxo = input.witness_utxo.as_ref().unwrap();
+            assert_eq!(witness_utxo.value.to_sat(), utxo.value);
+        }
+        assert!(psbt
+            .unsigned_tx
+            .input
+            .iter()
+            .all(|input| input.sequence == Sequence::ENABLE_RBF_NO_LOCKTIME));
+    }
+}
@@ -0,0 +1,173 @@
+//! The canister's certified data.
+//!
+//! Everything the canister certifies lives in one heap hash tree whose root hash is the certified
+//! data; see [`shared::types::certification`] for its layout and the client-side checks. Certified
+//! queries return a witness of the tree pruned everywhere but at the requested entry.
+//!
+//! The tree is not persisted: exchange rates are re-certified on init and upgrade, user data by a
+//! chain of timers after an upgrade (see [`users`]).
+
+pub(crate) mod users;
+
+use std::cell::RefCell;
+
+use ic_certification::{
+    fork, fork_hash, labeled, labeled_hash, pruned, AsHashTree, Hash, HashTree, RbTree,
+};
+use serde::Serialize;
+use shared::types::{certification::USERS_LABEL, exchange::certification::EXCHANGE_RATES_LABEL};
+
+struct CertifiedTree {
+    /// Candid-encoded token id → Candid-encoded `ExchangeRate`, as in stable memory.
+    exchange_rates: RbTree<Vec<u8>, Vec<u8>>,
+    /// Principal → record label → hash of the Candid-encoded record.
+    users: RbTree<Vec<u8>, RbTree<Vec<u8>, Hash>>,
+}
+
+impl CertifiedTree {
+    fn exchange_rates_hash(&self) -> Hash {
+        labeled_hash(EXCHANGE_RATES_LABEL, &self.exchange_rates.root_hash())
+    }
+
+    fn users_hash(&self) -> Hash {
+        labeled_hash(USERS_LABEL, &self.users.root_hash())
+    }
+
+    fn root_hash(&self) -> Hash {
+        fork_hash(&self.exchange_rates_hash(), &self.users_hash())
+    }
+}
+
+thread_local! {
+    static CERTIFIED_TREE: RefCell<CertifiedTree> = const {
+        RefCell::new(CertifiedTree {
+            exchange_rates: RbTree::new(),
+            users: RbTree::new(),
+        })
+    };
+}
+
+/// Applies `f` to the tree and certifies the result.
+fn mutate_tree(f: impl FnOnce(&mut CertifiedTree)) {
+    let root_hash = CERTIFIED_TREE.with(|tree| {
+        let mut tree = tree.borrow_mut();
+        f(&mut tree);
+        tree.root_hash()
+    });
+    set_certified_data(root_hash);
+}
+
+fn set_certified_data(root_hash: Hash) {
+    // Unit tests run outside a canister, where the system API is unavailable.
+    #[cfg(target_arch = "wasm32")]
+    ic_cdk::api::certified_data_set(root_hash);
+    #[cfg(not(target_arch = "wasm32"))]
+    let _ = root_hash;
+}
+
+/// Replaces the certified exchange rates with `rates`.
+pub(crate) fn reset_exchange_rates(rates: impl IntoIterator<Item = (Vec<u8>, Vec<u8>)>) {
+    mutate_tree(|tree| {
+        tree.exchange_rates = RbTree::new();
+        for (token_id, rate) in rates {
+            tree.exchange_rates.insert(token_id, rate);
+        }
+    });
+}
+
+/// Adds or replaces the certified exchange rate of `token_id`, both Candid-encoded.
+pub(crate) fn set_exchange_rate(token_id: Vec<u8>, rate: Vec<u8>) {
+    mutate_tree(|tree| tree.exchange_rates.insert(token_id, rate));
+}
+
+/// Adds or replaces the hash certified under `users/<user>/<label>`.
+pub(crate) fn set_user_data(user: &[u8], label: &[u8], hash: Hash) {
+    mutate_tree(|tree| {
+        if tree.users.get(user).is_some() {
+            tree.users
+                .modify(user, |records| records.insert(label.to_vec(), hash));
+        } else {
+            let mut records = RbTree::new();
+            records.insert(label.to_vec(), hash);
+            tree.users.insert(user.to_vec(), records);
+        }
+    });
+}
+
+/// The witness of the exchange rate of `token_id`, or of its absence.
+pub(crate) fn exchange_rate_witness(token_id: &[u8]) -> HashTree {
+    CERTIFIED_TREE.with(|tree| {
+        let tree = tree.borrow();
+        fork(
+            labeled(EXCHANGE_RATES_LABEL, tree.exchange_rates.witness(token_id)),
+            pruned(tree.users_hash()),
+        )
+    })
+}
+
+/// The witness of `users/<user>/<label>`, or `None` if that record is not certified.
+pub(crate) fn user_data_witness(user: &[u8], label: &[u8]) -> Option<HashTree> {
+    CERTIFIED_TREE.with(|tree| {
+        let tree = tree.borrow();
+        tree.users.get(user)?.get(label)?;
+        Some(fork(
+            pruned(tree.exchange_rates_hash()),
+            labeled(
+                USERS_LABEL,
+                tree.users
+                    .nested_witness(user, |records| records.witness(label)),
+            ),
+        ))
+    })
+}
+
+/// `value` CBOR-encoded with the self-describing tag the IC interface spec requires.
+pub(crate) fn to_self_describing_cbor<T: Serialize>(value: &T) -> Vec<u8> {
+    let mut serializer = serde_cbor::Serializer::new(Vec::new());
+    serializer
+        .self_describe()
+        .expect("writing to a Vec cannot fail");
+    value
+        .serialize(&mut serializer)
+        .expect("failed to CBOR-encode witness");
+    serializer.into_inner()
+}
+
+#[cfg(test)]
+mod tests {
+    use ic_certification::LookupResult;
+    use pretty_assertions::assert_eq;
+    use shared::types::certification::{CONTACTS_LABEL, USER_PROFILE_LABEL};
+
+    use super::*;
+
+    fn root_hash() -> Hash {
+        CERTIFIED_TREE.with(|tree| tree.borrow().root_hash())
+    }
+
+    #[test]
+    fn witnesses_match_the_certified_data() {
+        set_exchange_rate(b"icp".to_vec(), b"rate".to_vec());
+        set_user_data(b"alice", CONTACTS_LABEL, [1; 32]);
+        set_user_data(b"alice", USER_PROFILE_LABEL, [2; 32]);
+        set_user_data(b"bob", USER_PROFILE_LABEL, [3; 32]);
+        set_user_data(b"alice", USER_PROFILE_LABEL, [4; 32]);
+
+        let rate = exchange_rate_witness(b"icp");
+        assert_eq!(rate.digest(), root_hash());
+        assert_eq!(
+            rate.lookup_path([EXCHANGE_RATES_LABEL, b"icp"]),
+            LookupResult::Found(b"rate")
+        );
+
+        let profile = user_data_witness(b"alice", USER_PROFILE_LABEL).unwrap();
+        assert_eq!(profile.digest(), root_hash());
+        assert_eq!(
+            profile.lookup_path([USERS_LABEL, b"alice", USER_PROFILE_LABEL]),
+            LookupResult::Found(&[4; 32])
+        );
+
+        assert_eq!(user_data_witness(b"bob", CONTACTS_LABEL), None);
+        assert_eq!(user_data_witness(b"carol", USER_PROFILE_LABEL), None);
+    }
+}
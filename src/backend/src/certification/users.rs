@@ -0,0 +1,138 @@
+//! Certified user profiles, contacts and custom tokens.
+//!
+//! Each record is certified as the hash of its Candid encoding under `users/<principal>/<label>`,
+//! updated wherever the record is written. The certified queries return the encoding itself, so
+//! the hash only has to match bytes the client receives.
+//!
+//! After an upgrade, `post_upgrade` schedules a chain of timers re-certifying a bounded batch of
+//! users each, in principal order. Until a user's batch has run, the certified queries return
+//! `None` for them.
+
+use std::{collections::BTreeMap, ops::Bound, time::Duration};
+
+use candid::{encode_one, CandidType};
+use ic_cdk_timers::set_timer;
+use serde_bytes::ByteBuf;
+use shared::types::{
+    certification::{
+        user_data_hash, CertifiedUserData, CONTACTS_LABEL, CUSTOM_TOKENS_LABEL, USER_PROFILE_LABEL,
+    },
+    contact::{Contact, StoredContacts},
+    custom_token::CustomToken,
+    user_profile::{StoredUserProfile, UserProfile},
+};
+
+use crate::{
+    certification,
+    state::{read_state, State},
+    types::{maps::CustomTokenMap, StoredPrincipal},
+};
+
+/// Upper bound on the number of users re-certified per timer tick.
+const USER_CERTIFICATION_BATCH_USERS: usize = 2_000;
+
+fn encode<T: CandidType>(value: &T) -> Vec<u8> {
+    encode_one(value).expect("failed to candid-encode user data")
+}
+
+/// The Candid encoding of `user`'s profile as returned by `get_user_profile`.
+pub(crate) fn user_profile_data(user: &StoredUserProfile) -> Vec<u8> {
+    encode(&UserProfile::from(user))
+}
+
+/// The Candid encoding of `contacts` as returned by `get_contacts`.
+pub(crate) fn contacts_data(contacts: &[Contact]) -> Vec<u8> {
+    encode(&contacts)
+}
+
+/// The Candid encoding of `tokens` as returned by `list_custom_tokens`.
+pub(crate) fn custom_tokens_data(tokens: &[CustomToken]) -> Vec<u8> {
+    encode(&tokens)
+}
+
+pub(crate) fn certify_user_profile(user: StoredPrincipal, profile: &StoredUserProfile) {
+    certify(user, USER_PROFILE_LABEL, &user_profile_data(profile));
+}
+
+pub(crate) fn certify_contacts(user: StoredPrincipal, contacts: &StoredContacts) {
+    let contacts: Vec<Contact> = contacts.contacts.values().cloned().collect();
+    certify(user, CONTACTS_LABEL, &contacts_data(&contacts));
+}
+
+/// Certifies `user`'s custom tokens as stored in `custom_tokens`.
+pub(crate) fn certify_custom_tokens(user: StoredPrincipal, custom_tokens: &CustomTokenMap) {
+    let tokens = custom_tokens.get(&user).unwrap_or_default().0;
+    certify(user, CUSTOM_TOKENS_LABEL, &custom_tokens_data(&tokens));
+}
+
+fn certify(user: StoredPrincipal, label: &[u8], data: &[u8]) {
+    certification::set_user_data(user.0.as_slice(), label, user_data_hash(data));
+}
+
+/// Certifies all of `user`'s records.
+pub(crate) fn certify_user(state: &State, user: StoredPrincipal) {
+    let profile = state
+        .user_profile_updated
+        .get(&user)
+        .and_then(|updated| state.user_profile.get(&(updated, user)));
+    if let Some(profile) = profile {
+        certify_user_profile(user, &profile);
+    }
+    let contacts = state.contact.get(&user).map_or_else(
+        || StoredContacts {
+            contacts: BTreeMap::new(),
+            update_timestamp_ns: 0,
+        },
+        |contacts| contacts.0,
+    );
+    certify_contacts(user, &contacts);
+    certify_custom_tokens(user, &state.custom_token);
+}
+
+/// `data` with the witness of `users/<user>/<label>` and `certificate`, the canister's data
+/// certificate, or `None` if the record is not certified yet.
+pub(crate) fn certified_user_data(
+    user: StoredPrincipal,
+    label: &[u8],
+    data: Vec<u8>,
+    certificate: Vec<u8>,
+) -> Option<CertifiedUserData> {
+    let witness = certification::user_data_witness(user.0.as_slice(), label)?;
+    Some(CertifiedUserData {
+        data: ByteBuf::from(data),
+        certificate: ByteBuf::from(certificate),
+        witness: ByteBuf::from(certification::to_self_describing_cbor(&witness)),
+    })
+}
+
+/// Schedules the re-certification of every user with a profile.
+pub(crate) fn schedule_user_data_certification() {
+    schedule_user_data_certification_batch(None);
+}
+
+fn schedule_user_data_certification_batch(after: Option<StoredPrincipal>) {
+    set_timer(Duration::ZERO, async move {
+        certify_users_batch(after);
+    });
+}
+
+fn certify_users_batch(after: Option<StoredPrincipal>) {
+    let last = read_state(|s| {
+        let start = after.map_or(Bound::Unbounded, Bound::Excluded);
+        let mut last = None;
+        for entry in s
+            .user_profile_updated
+            .range((start, Bound::Unbounded))
+            .take(USER_CERTIFICATION_BATCH_USERS)
+        {
+            let user = *entry.key();
+            certify_user(s, user);
+            last = Some(user);
+        }
+        last
+    });
+
+    if last.is_some() {
+        schedule_user_data_certification_batch(last);
+    }
+}
@@ -0,0 +1,630 @@
+//! Rendering of contacts as CSV or vCard, and parsing them back for an import.
+//!
+//! Pure formatting only: the caller reads the contacts from state, and assigns ids to and stores
+//! the parsed ones. Parsed contacts are validated like created ones, except for the per-user
+//! contact limit.
+
+use std::{collections::HashMap, str::FromStr};
+
+use shared::{
+    types::{
+        account::TokenAccountId,
+        contact::{
+            Contact, ContactAddressData, ContactExportFormat, ContactImportError,
+            ContactImportRowError, CreateContactRequest,
+        },
+    },
+    validate::Validate,
+};
+
+use crate::utils::csv;
+
+/// Column names, in order, of a CSV export.
+const CSV_HEADER: [&str; 4] = ["id", "name", "address", "label"];
+
+/// vCard property holding a contact address.
+const VCARD_ADDRESS_PROPERTY: &str = "X-OISY-ADDRESS";
+
+/// Maximum length of a vCard line, in bytes, before it is folded.
+const VCARD_MAX_LINE_BYTES: usize = 75;
+
+/// A valid contact read from an import, without an id.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct ParsedContact {
+    /// The line the contact starts on.
+    pub line: u64,
+    pub name: String,
+    pub addresses: Vec<ContactAddressData>,
+}
+
+/// A contact as read from an import, before validation.
+struct RawContact {
+    line: u64,
+    name: String,
+    /// The line, the address and the label of each address.
+    addresses: Vec<(u64, String, Option<String>)>,
+    error: Option<ContactImportRowError>,
+}
+
+impl RawContact {
+    fn new(line: u64, name: String) -> Self {
+        Self {
+            line,
+            name,
+            addresses: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Records the first error found in the contact.
+    fn fail(&mut self, line: u64, error: ContactImportError) {
+        self.error
+            .get_or_insert(ContactImportRowError { line, error });
+    }
+
+    fn validated(self) -> Result<ParsedContact, ContactImportRowError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        let contact_error = |error| ContactImportRowError {
+            line: self.line,
+            error,
+        };
+
+        let name_request = CreateContactRequest {
+            name: self.name,
+            addresses: None,
+            image: None,
+        };
+        if name_request.name.is_empty() || name_request.validate().is_err() {
+            return Err(contact_error(ContactImportError::InvalidName));
+        }
+
+        let mut addresses = Vec::with_capacity(self.addresses.len());
+        for (line, address, label) in self.addresses {
+            let token_account_id =
+                TokenAccountId::from_str(&address).map_err(|error| ContactImportRowError {
+                    line,
+                    error: ContactImportError::InvalidAddress { address, error },
+                })?;
+            let address = ContactAddressData {
+                token_account_id,
+                label,
+                verified_owner: None,
+            };
+            if address.validate().is_err() {
+                return Err(ContactImportRowError {
+                    line,
+                    error: ContactImportError::InvalidLabel,
+                });
+            }
+            addresses.push(address);
+        }
+
+        let contact = Contact {
+            id: 0,
+            name: name_request.name,
+            addresses,
+            update_timestamp_ns: 0,
+            image: None,
+        };
+        if contact.validate().is_err() {
+            return Err(contact_error(ContactImportError::TooManyAddresses));
+        }
+
+        Ok(ParsedContact {
+            line: self.line,
+            name: contact.name,
+            addresses: contact.addresses,
+        })
+    }
+}
+
+/// Renders `contacts` in `format`. Images and address verifications are not exported.
+pub(crate) fn render(format: ContactExportFormat, contacts: &[Contact]) -> String {
+    let mut content = String::new();
+    match format {
+        ContactExportFormat::Csv => {
+            csv::push_line(&mut content, CSV_HEADER.iter().copied());
+            for contact in contacts {
+                let id = contact.id.to_string();
+                if contact.addresses.is_empty() {
+                    csv::push_line(
+                        &mut content,
+                        [id.as_str(), &contact.name, "", ""].into_iter(),
+                    );
+                }
+                for address in &contact.addresses {
+                    let account = address.token_account_id.to_string();
+                    let label = address.label.as_deref().unwrap_or_default();
+                    csv::push_line(
+                        &mut content,
+                        [id.as_str(), &contact.name, &account, label].into_iter(),
+                    );
+                }
+            }
+        }
+        ContactExportFormat::VCard => {
+            for contact in contacts {
+                push_vcard_line(&mut content, "BEGIN:VCARD");
+                push_vcard_line(&mut content, "VERSION:4.0");
+                push_vcard_line(&mut content, &format!("FN:{}", escape_text(&contact.name)));
+                for address in &contact.addresses {
+                    let parameters = address
+                        .label
+                        .as_deref()
+                        .map(|label| format!(";LABEL=\"{}\"", escape_parameter(label)))
+                        .unwrap_or_default();
+                    push_vcard_line(
+                        &mut content,
+                        &format!(
+                            "{VCARD_ADDRESS_PROPERTY}{parameters}:{}",
+                            address.token_account_id
+                        ),
+                    );
+                }
+                push_vcard_line(&mut content, "END:VCARD");
+            }
+        }
+    }
+    content
+}
+
+/// Parses the contacts in `content`. Contacts with an error are skipped and reported instead.
+pub(crate) fn parse(
+    format: ContactExportFormat,
+    content: &str,
+) -> (Vec<ParsedContact>, Vec<ContactImportRowError>) {
+    let (raw_contacts, mut errors) = match format {
+        ContactExportFormat::Csv => parse_csv(content),
+        ContactExportFormat::VCard => parse_vcard(content),
+    };
+
+    let mut contacts = Vec::new();
+    for raw_contact in raw_contacts {
+        match raw_contact.validated() {
+            Ok(contact) => contacts.push(contact),
+            Err(error) => errors.push(error),
+        }
+    }
+    errors.sort_by_key(|error| error.line);
+    (contacts, errors)
+}
+
+fn malformed(line: u64) -> ContactImportRowError {
+    ContactImportRowError {
+        line,
+        error: ContactImportError::MalformedRecord,
+    }
+}
+
+fn parse_csv(content: &str) -> (Vec<RawContact>, Vec<ContactImportRowError>) {
+    let mut contacts: Vec<RawContact> = Vec::new();
+    let mut errors = Vec::new();
+    // Index in `contacts` of the contact with a given `id`.
+    let mut ids = HashMap::new();
+
+    for (i, (line, row)) in csv::parse(content).into_iter().enumerate() {
+        let Ok(fields) = row else {
+            errors.push(malformed(line));
+            continue;
+        };
+        let is_header = fields.len() == CSV_HEADER.len()
+            && fields
+                .iter()
+                .zip(CSV_HEADER)
+                .all(|(field, column)| field.trim().eq_ignore_ascii_case(column));
+        if i == 0 && is_header {
+            continue;
+        }
+        let [id, name, address, label] = match <[String; 4]>::try_from(fields) {
+            Ok(fields) => fields,
+            Err(fields) if (2..4).contains(&fields.len()) => {
+                let mut fields = fields.into_iter();
+                std::array::from_fn(|_| fields.next().unwrap_or_default())
+            }
+            Err(_) => {
+                errors.push(malformed(line));
+                continue;
+            }
+        };
+
+        let index = if id.is_empty() {
+            None
+        } else {
+            ids.get(&id).copied()
+        };
+        let contact = if let Some(index) = index {
+            let contact: &mut RawContact = &mut contacts[index];
+            if contact.name != name {
+                contact.fail(line, ContactImportError::InvalidName);
+            }
+            contact
+        } else {
+            if !id.is_empty() {
+                ids.insert(id, contacts.len());
+            }
+            contacts.push(RawContact::new(line, name));
+            contacts
+                .last_mut()
+                .unwrap_or_else(|| unreachable!("a contact was just pushed"))
+        };
+        if !address.is_empty() {
+            contact
+                .addresses
+                .push((line, address, Some(label).filter(|label| !label.is_empty())));
+        }
+    }
+    (contacts, errors)
+}
+
+fn parse_vcard(content: &str) -> (Vec<RawContact>, Vec<ContactImportRowError>) {
+    let mut contacts = Vec::new();
+    let mut errors = Vec::new();
+    let mut card: Option<RawContact> = None;
+
+    for (line, property) in unfold(content) {
+        let Some((name, parameters, value)) = split_property(&property) else {
+            match card.as_mut() {
+                Some(card) => card.fail(line, ContactImportError::MalformedRecord),
+                None => errors.push(malformed(line)),
+            }
+            continue;
+        };
+
+        match (name.to_ascii_uppercase().as_str(), card.as_mut()) {
+            ("BEGIN", _) if value.eq_ignore_ascii_case("VCARD") => {
+                if let Some(unterminated) = card.replace(RawContact::new(line, String::new())) {
+                    errors.push(malformed(unterminated.line));
+                }
+            }
+            ("END", Some(_)) if value.eq_ignore_ascii_case("VCARD") => {
+                contacts.extend(card.take());
+            }
+            ("FN", Some(card)) if card.name.is_empty() => card.name = unescape_text(value),
+            (VCARD_ADDRESS_PROPERTY, Some(card)) => {
+                let label = parameters.iter().find_map(|parameter| {
+                    let (key, value) = parameter.split_once('=')?;
+                    key.eq_ignore_ascii_case("LABEL")
+                        .then(|| unescape_parameter(value))
+                });
+                card.addresses.push((line, value.to_string(), label));
+            }
+            (_, Some(_)) => {}
+            (_, None) => errors.push(malformed(line)),
+        }
+    }
+    if let Some(unterminated) = card {
+        errors.push(malformed(unterminated.line));
+    }
+    (contacts, errors)
+}
+
+/// The logical lines of a vCard, with the line each starts on: lines starting with a space or tab
+/// continue the previous line. Empty lines are skipped.
+fn unfold(content: &str) -> Vec<(u64, String)> {
+    let mut lines: Vec<(u64, String)> = Vec::new();
+    for (line, text) in (1..).zip(content.split('\n')) {
+        let text = text.strip_suffix('\r').unwrap_or(text);
+        match (text.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some((_, previous))) => previous.push_str(continuation),
+            _ if text.is_empty() => {}
+            _ => lines.push((line, text.to_string())),
+        }
+    }
+    lines
+}
+
+/// Splits a vCard line into its property name, without group, its parameters and its value.
+fn split_property(property: &str) -> Option<(&str, Vec<&str>, &str)> {
+    let mut quoted = false;
+    let mut separators = Vec::new();
+    let mut value_start = None;
+    for (i, c) in property.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => separators.push(i),
+            ':' if !quoted => {
+                value_start = Some(i);
+                break;
+            }
+            _ => {}
+        }
+    }
+    let value_start = value_start?;
+
+    let name_end = separators.first().copied().unwrap_or(value_start);
+    let name = &property[..name_end];
+    let name = name.rsplit_once('.').map_or(name, |(_group, name)| name);
+    let parameters = separators
+        .iter()
+        .zip(separators.iter().skip(1).chain([&value_start]))
+        .map(|(start, end)| &property[start + 1..*end])
+        .collect();
+    Some((name, parameters, &property[value_start + 1..]))
+}
+
+/// Appends `line`, folded into lines of at most [`VCARD_MAX_LINE_BYTES`] bytes.
+fn push_vcard_line(content: &mut String, line: &str) {
+    let mut line_bytes = 0;
+    for c in line.chars() {
+        if line_bytes + c.len_utf8() > VCARD_MAX_LINE_BYTES {
+            content.push_str("\r\n ");
+            line_bytes = 1;
+        }
+        content.push(c);
+        line_bytes += c.len_utf8();
+    }
+    content.push_str("\r\n");
+}
+
+/// Escapes a vCard text value, see RFC 6350, section 3.4.
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | ',' | ';' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape_text(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => unescaped.push('\n'),
+            Some(escaped) => unescaped.push(escaped),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Escapes a quoted vCard parameter value, see RFC 6868.
+fn escape_parameter(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '^' => escaped.push_str("^^"),
+            '"' => escaped.push_str("^'"),
+            '\n' => escaped.push_str("^n"),
+            '\r' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape_parameter(value: &str) -> String {
+    let value = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value);
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        let escaped = match (c, chars.peek()) {
+            ('^', Some('^')) => Some('^'),
+            ('^', Some('\'')) => Some('"'),
+            ('^', Some('n')) => Some('\n'),
+            _ => None,
+        };
+        if let Some(escaped) = escaped {
+            chars.next();
+            unescaped.push(escaped);
+        } else {
+            unescaped.push(c);
+        }
+    }
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use candid::Principal;
+    use pretty_assertions::assert_eq;
+    use shared::types::account::{
+        conversion::ParseError, EthAddress, IcrcSubaccountId, Icrcv2AccountId,
+    };
+
+    use super::*;
+
+    const ETH_ADDRESS: &str = "0x1D1479C185d32EB90533a08b36B3CFa5F84A0E6B";
+
+    fn contacts() -> Vec<Contact> {
+        let owner = Principal::from_slice(&[1; 29]);
+        vec![
+            Contact {
+                id: 7,
+                name: "Doe, \"Jane\"; the ^ \\ one".to_string(),
+                addresses: vec![
+                    ContactAddressData {
+                        token_account_id: TokenAccountId::Eth(EthAddress::Public(
+                            ETH_ADDRESS.to_string(),
+                        )),
+                        label: Some("Main \"hot\" ^ wallet".to_string()),
+                        verified_owner: Some(owner),
+                    },
+                    ContactAddressData {
+                        token_account_id: TokenAccountId::Icrcv2(Icrcv2AccountId::WithPrincipal {
+                            owner,
+                            subaccount: Some(IcrcSubaccountId([3; 32])),
+                        }),
+                        label: None,
+                        verified_owner: None,
+                    },
+                ],
+                update_timestamp_ns: 1,
+                image: None,
+            },
+            Contact {
+                id: 8,
+                name: "=SUM(A1) with a name long enough to be folded over several vCard lines"
+                    .to_string(),
+                addresses: vec![],
+                update_timestamp_ns: 2,
+                image: None,
+            },
+        ]
+    }
+
+    /// What importing `contacts` yields: everything but ids, timestamps, images and
+    /// verifications.
+    fn imported(contacts: Vec<Contact>) -> Vec<(String, Vec<ContactAddressData>)> {
+        contacts
+            .into_iter()
+            .map(|contact| {
+                (
+                    contact.name,
+                    contact
+                        .addresses
+                        .into_iter()
+                        .map(|address| ContactAddressData {
+                            verified_owner: None,
+                            ..address
+                        })
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+
+    fn names_and_addresses(
+        (contacts, errors): (Vec<ParsedContact>, Vec<ContactImportRowError>),
+    ) -> (
+        Vec<(String, Vec<ContactAddressData>)>,
+        Vec<ContactImportRowError>,
+    ) {
+        (
+            contacts
+                .into_iter()
+                .map(|contact| (contact.name, contact.addresses))
+                .collect(),
+            errors,
+        )
+    }
+
+    #[test]
+    fn exports_can_be_imported() {
+        for format in [ContactExportFormat::Csv, ContactExportFormat::VCard] {
+            let content = render(format, &contacts());
+
+            assert_eq!(
+                names_and_addresses(parse(format, &content)),
+                (imported(contacts()), vec![]),
+                "{format:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn vcard_export_is_folded_and_escaped() {
+        let content = render(ContactExportFormat::VCard, &contacts());
+
+        assert!(content
+            .lines()
+            .all(|line| line.trim_end_matches('\r').len() <= VCARD_MAX_LINE_BYTES));
+        let unfolded = content.replace("\r\n ", "");
+        assert!(unfolded.contains("FN:Doe\\, \"Jane\"\\; the ^ \\\\ one\r\n"));
+        assert!(unfolded.contains(&format!(
+            "X-OISY-ADDRESS;LABEL=\"Main ^'hot^' ^^ wallet\":{ETH_ADDRESS}\r\n"
+        )));
+    }
+
+    #[test]
+    fn csv_import_reports_invalid_rows_and_keeps_the_valid_contacts() {
+        let content = "id,name,address,label\n\
+                       1,Alice,un4fu-tqaaa-aaaab-qadjq-cai,\n\
+                       1,Alice,not-an-address,\n\
+                       2,Bob\n\
+                       3,Carol,\"unterminated\n";
+
+        let (contacts, errors) = names_and_addresses(parse(ContactExportFormat::Csv, content));
+
+        assert_eq!(contacts, vec![("Bob".to_string(), vec![])]);
+        assert_eq!(
+            errors,
+            vec![
+                ContactImportRowError {
+                    line: 3,
+                    error: ContactImportError::InvalidAddress {
+                        address: "not-an-address".to_string(),
+                        error: ParseError::UnsupportedFormat,
+                    },
+                },
+                malformed(5),
+            ]
+        );
+    }
+
+    #[test]
+    fn csv_rows_without_id_are_separate_contacts() {
+        let content = ",Alice,un4fu-tqaaa-aaaab-qadjq-cai,Main\n,Alice\n1,Bob\n1,Robert\n";
+
+        let (contacts, errors) = names_and_addresses(parse(ContactExportFormat::Csv, content));
+
+        assert_eq!(
+            contacts,
+            vec![
+                (
+                    "Alice".to_string(),
+                    vec![ContactAddressData {
+                        token_account_id: TokenAccountId::from_str("un4fu-tqaaa-aaaab-qadjq-cai")
+                            .unwrap(),
+                        label: Some("Main".to_string()),
+                        verified_owner: None,
+                    }]
+                ),
+                ("Alice".to_string(), vec![]),
+            ]
+        );
+        assert_eq!(
+            errors,
+            vec![ContactImportRowError {
+                line: 4,
+                error: ContactImportError::InvalidName,
+            }]
+        );
+    }
+
+    #[test]
+    fn vcard_import_reports_invalid_cards_and_keeps_the_valid_contacts() {
+        let content = "BEGIN:VCARD\r\n\
+                       VERSION:4.0\r\n\
+                       item1.FN:Al\r\n ice\r\n\
+                       TEL:+41 00 000 00 00\r\n\
+                       END:VCARD\r\n\
+                       BEGIN:VCARD\r\n\
+                       END:VCARD\r\n\
+                       FN:Outside\r\n\
+                       BEGIN:VCARD\r\n\
+                       FN:Bob\r\n";
+
+        let (contacts, errors) = names_and_addresses(parse(ContactExportFormat::VCard, content));
+
+        assert_eq!(contacts, vec![("Alice".to_string(), vec![])]);
+        assert_eq!(
+            errors,
+            vec![
+                ContactImportRowError {
+                    line: 7,
+                    error: ContactImportError::InvalidName,
+                },
+                malformed(9),
+                malformed(10),
+            ]
+        );
+    }
+}
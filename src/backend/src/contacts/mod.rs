@@ -1,5 +1,7 @@
+mod format;
 mod service;
 
 pub(crate) use service::{
-    create_contact, delete_contact, get_contact, get_contacts, update_contact,
+    batch_contacts, create_contact, create_verified_contact, delete_contact, export_contacts,
+    get_contact, get_contacts, import_contacts, update_contact,
 };
@@ -1,18 +1,109 @@
 use std::collections::BTreeMap;
 
-use ic_cdk::api::{msg_caller, time};
-use shared::types::contact::{
-    Contact, ContactError, CreateContactRequest, StoredContacts, UpdateContactRequest,
-    MAX_CONTACTS_PER_USER,
+use candid::Principal;
+use futures::join;
+use ic_cdk::{
+    api::{msg_caller, time},
+    bitcoin_canister::Network as BitcoinNetwork,
+};
+use shared::types::{
+    account::{BtcAddress, EthAddress, Icrcv2AccountId, SolPrincipal, TokenAccountId},
+    bitcoin::BtcAddressType,
+    contact::{
+        validate_principal_memory_limit, verified_addresses_preserved, BatchContactsError,
+        BatchContactsRequest, Contact, ContactAddressData, ContactError, ContactExportFormat,
+        ContactImage, ContactImportError, ContactImportRowError, ContactOperation,
+        ContactOperationError, ContactOperationResult, CreateContactRequest,
+        CreateVerifiedContactError, CreateVerifiedContactRequest, ImportContactsRequest,
+        ImportContactsResponse, StoredContacts, UpdateContactRequest, MAX_CONTACTS_PER_USER,
+    },
 };
 
+use super::format;
 use crate::{
+    certification::users::certify_contacts,
+    signer,
     state::{mutate_state, read_state},
-    types::{Candid, StoredPrincipal},
+    types::{maps::ContactMap, Candid, StoredPrincipal},
+    user_profile::{
+        model::UserProfileModel,
+        service::{has_user_profile, preferred_btc_address_type},
+    },
     utils::random,
 };
 
 pub(crate) async fn create_contact(request: CreateContactRequest) -> Result<Contact, ContactError> {
+    let addresses = request.addresses.unwrap_or_default();
+    check_unverified(&addresses)?;
+    add_contact(request.name, addresses, request.image).await
+}
+
+/// Creates a contact for the OISY user `request.principal`, with their BTC, ETH, SOL and ICRC
+/// addresses derived by the backend (see [`verified_addresses`]) rather than entered by the caller.
+///
+/// # Returns
+/// * `Ok(Contact)` - The created contact
+/// * `Err(CreateVerifiedContactError::NotAnOisyUser)` - If the principal has no user profile
+/// * `Err(CreateVerifiedContactError::AddressDerivationFailed)` - If any of the addresses could
+///   not be derived
+/// * `Err(CreateVerifiedContactError::ContactError)` - If the contact could not be stored
+pub(crate) async fn create_verified_contact(
+    request: CreateVerifiedContactRequest,
+) -> Result<Contact, CreateVerifiedContactError> {
+    if !has_user_profile(StoredPrincipal(request.principal)) {
+        return Err(CreateVerifiedContactError::NotAnOisyUser);
+    }
+    let addresses = verified_addresses(request.principal).await?;
+    Ok(add_contact(request.name, addresses, None).await?)
+}
+
+/// Derives the addresses of the OISY user `principal` on every network the signer supports, each
+/// marked as verified to belong to `principal`. The BTC address is of the type `principal` prefers.
+async fn verified_addresses(
+    principal: Principal,
+) -> Result<Vec<ContactAddressData>, CreateVerifiedContactError> {
+    let btc_address_type = mutate_state(|s| {
+        let user_profile_model =
+            UserProfileModel::new(&mut s.user_profile, &mut s.user_profile_updated);
+        preferred_btc_address_type(StoredPrincipal(principal), &user_profile_model)
+    });
+    let (btc, eth, sol) = join!(
+        signer::btc_principal_to_address(BitcoinNetwork::Mainnet, &principal, btc_address_type),
+        signer::eth_principal_to_address(&principal),
+        signer::sol_principal_to_address(&principal),
+    );
+    let (Ok(btc), Ok(eth), Ok(sol)) = (btc, eth, sol) else {
+        return Err(CreateVerifiedContactError::AddressDerivationFailed);
+    };
+
+    Ok([
+        TokenAccountId::Icrcv2(Icrcv2AccountId::WithPrincipal {
+            owner: principal,
+            subaccount: None,
+        }),
+        TokenAccountId::Btc(match btc_address_type {
+            BtcAddressType::P2wpkh => BtcAddress::P2WPKH(btc),
+            BtcAddressType::P2shP2wpkh => BtcAddress::P2SH(btc),
+            BtcAddressType::P2tr => BtcAddress::P2TR(btc),
+        }),
+        TokenAccountId::Eth(EthAddress::Public(eth)),
+        TokenAccountId::Sol(SolPrincipal(sol)),
+    ]
+    .into_iter()
+    .map(|token_account_id| ContactAddressData {
+        token_account_id,
+        label: None,
+        verified_owner: Some(principal),
+    })
+    .collect())
+}
+
+/// Adds a new contact named `name` with `addresses` and `image` to the caller's contacts.
+async fn add_contact(
+    name: String,
+    addresses: Vec<ContactAddressData>,
+    image: Option<ContactImage>,
+) -> Result<Contact, ContactError> {
     let stored_principal = StoredPrincipal(msg_caller());
     let current_time = time();
 
@@ -23,53 +114,267 @@ pub(crate) async fn create_contact(request: CreateContactRequest) -> Result<Cont
 
     // Now do the state mutation without any async operations
     mutate_state(|s| {
-        // Get the user's contacts directly from the state instead of using the helper function
-        // `get_stored_contacts_safely` to avoid a "BorrowError" caused by nested state borrowing
-        let mut stored_contacts = if let Some(stored_contacts) = s.contact.get(&stored_principal) {
-            // Try to access the contacts safely with catch_unwind
-            if let Ok(contacts) = std::panic::catch_unwind(|| stored_contacts.clone()) {
-                contacts
-            } else {
-                // Log deserialization failure and create empty contacts
-                ic_cdk::api::debug_print(format!(
-                    "Failed to deserialize contacts for principal: {}. Creating empty contacts.",
-                    stored_principal.0
-                ));
-                create_empty_contacts()
-            }
-        } else {
-            create_empty_contacts()
-        };
-
-        if stored_contacts.contacts.len() >= MAX_CONTACTS_PER_USER {
-            return Err(ContactError::TooManyContacts);
-        }
+        let mut stored_contacts = stored_contacts_for_update(&s.contact, &stored_principal);
 
-        // Check if a contact with this ID already exists
-        if stored_contacts.contacts.contains_key(&new_id) {
-            return Err(ContactError::RandomnessError);
-        }
-
-        // Create the new contact - note that CreateContactRequest only has 'name'
         let new_contact = Contact {
             id: new_id,
-            name: request.name,
-            addresses: Vec::new(), // Start with an empty addresses list
+            name,
+            addresses,
             update_timestamp_ns: current_time,
-            image: None, // Start with no image
+            image,
         };
-
-        // Add the contact to the stored contacts
-        stored_contacts.contacts.insert(new_id, new_contact.clone());
+        insert_new_contact(&mut stored_contacts, new_contact.clone())?;
         stored_contacts.update_timestamp_ns = current_time;
 
         // Update the storage
+        certify_contacts(stored_principal, &stored_contacts);
         s.contact.insert(stored_principal, Candid(stored_contacts));
 
         Ok(new_contact)
     })
 }
 
+/// Applies `request.operations` to the caller's contacts in order. If any operation fails, none
+/// is applied.
+///
+/// Updates and deletions only apply to contacts whose `update_timestamp_ns` is still the one given
+/// with the operation, so a client cannot overwrite changes it has not seen.
+///
+/// # Returns
+/// * `Ok(Vec<ContactOperationResult>)` - The result of each operation
+/// * `Err(BatchContactsError)` - The first failed operation and its error, e.g.
+///   `ContactOperationError::OutdatedContact` if its contact changed in the meantime
+pub(crate) async fn batch_contacts(
+    request: BatchContactsRequest,
+) -> Result<Vec<ContactOperationResult>, BatchContactsError> {
+    let stored_principal = StoredPrincipal(msg_caller());
+    let current_time = time();
+
+    // Generate the random IDs BEFORE mutate_state, since it's an async operation
+    let creations = request
+        .operations
+        .iter()
+        .filter(|operation| matches!(operation, ContactOperation::Create(_)))
+        .count();
+    let mut new_ids = random::generate_random_u64s(creations)
+        .await
+        .map_err(|_| BatchContactsError {
+            operation: None,
+            error: ContactError::RandomnessError.into(),
+        })?
+        .into_iter();
+
+    mutate_state(|s| {
+        // Operations apply to a copy of the contacts, which is only stored if all succeed
+        let mut stored_contacts = stored_contacts_for_update(&s.contact, &stored_principal);
+
+        let mut results = Vec::with_capacity(request.operations.len());
+        for (index, operation) in (0..).zip(request.operations) {
+            let result = match operation {
+                ContactOperation::Create(request) => {
+                    let new_id = new_ids
+                        .next()
+                        .unwrap_or_else(|| unreachable!("an id was generated per creation"));
+                    let addresses = request.addresses.unwrap_or_default();
+                    let new_contact = Contact {
+                        id: new_id,
+                        name: request.name,
+                        addresses,
+                        update_timestamp_ns: current_time,
+                        image: request.image,
+                    };
+                    check_unverified(&new_contact.addresses)
+                        .and_then(|()| {
+                            insert_new_contact(&mut stored_contacts, new_contact.clone())
+                        })
+                        .map(|()| ContactOperationResult::Created(new_contact))
+                        .map_err(ContactOperationError::from)
+                }
+                ContactOperation::Update(request) => {
+                    check_unchanged(&stored_contacts, request.id, request.update_timestamp_ns)
+                        .and_then(|()| {
+                            replace_contact(&mut stored_contacts, request, current_time)
+                                .map_err(ContactOperationError::from)
+                        })
+                        .map(ContactOperationResult::Updated)
+                }
+                ContactOperation::Delete {
+                    id,
+                    update_timestamp_ns,
+                } => check_unchanged(&stored_contacts, id, update_timestamp_ns)
+                    .and_then(|()| {
+                        remove_contact(&mut stored_contacts, id)
+                            .map_err(ContactOperationError::from)
+                    })
+                    .map(ContactOperationResult::Deleted),
+            };
+            results.push(result.map_err(|error| BatchContactsError {
+                operation: Some(index),
+                error,
+            })?);
+        }
+
+        if !results.is_empty() {
+            stored_contacts.update_timestamp_ns = current_time;
+            certify_contacts(stored_principal, &stored_contacts);
+            s.contact.insert(stored_principal, Candid(stored_contacts));
+        }
+
+        Ok(results)
+    })
+}
+
+/// Rejects caller-supplied `addresses` that claim to be verified: verified addresses are derived
+/// by the backend, never supplied by the caller.
+fn check_unverified(addresses: &[ContactAddressData]) -> Result<(), ContactError> {
+    if addresses
+        .iter()
+        .any(|address| address.verified_owner.is_some())
+    {
+        return Err(ContactError::InvalidContactData);
+    }
+    Ok(())
+}
+
+/// Checks that the contact `id` exists and was last updated at `update_timestamp_ns`.
+fn check_unchanged(
+    stored_contacts: &StoredContacts,
+    id: u64,
+    update_timestamp_ns: u64,
+) -> Result<(), ContactOperationError> {
+    let contact = stored_contacts
+        .contacts
+        .get(&id)
+        .ok_or(ContactError::ContactNotFound)?;
+    if contact.update_timestamp_ns != update_timestamp_ns {
+        return Err(ContactOperationError::OutdatedContact);
+    }
+    Ok(())
+}
+
+/// Adds `contact` to `stored_contacts`, within the per-user contact and image limits.
+fn insert_new_contact(
+    stored_contacts: &mut StoredContacts,
+    contact: Contact,
+) -> Result<(), ContactError> {
+    if stored_contacts.contacts.len() >= MAX_CONTACTS_PER_USER {
+        return Err(ContactError::TooManyContacts);
+    }
+
+    // Check if a contact with this ID already exists
+    if stored_contacts.contacts.contains_key(&contact.id) {
+        return Err(ContactError::RandomnessError);
+    }
+
+    validate_principal_memory_limit(stored_contacts, contact.image.is_some())?;
+
+    stored_contacts.contacts.insert(contact.id, contact);
+    Ok(())
+}
+
+/// Replaces the contact `request.id` in `stored_contacts` with the contents of `request`.
+fn replace_contact(
+    stored_contacts: &mut StoredContacts,
+    request: UpdateContactRequest,
+    current_time: u64,
+) -> Result<Contact, ContactError> {
+    // Check if the contact exists
+    let Some(stored_contact) = stored_contacts.contacts.get(&request.id) else {
+        return Err(ContactError::ContactNotFound);
+    };
+
+    // Verified addresses are derived by the backend, never supplied by the caller
+    if !verified_addresses_preserved(stored_contact, &request.addresses) {
+        return Err(ContactError::InvalidContactData);
+    }
+
+    // Create an updated contact with current timestamp
+    let updated_contact = Contact {
+        id: request.id,
+        name: request.name,
+        addresses: request.addresses,
+        update_timestamp_ns: current_time,
+        image: request.image,
+    };
+
+    // Update the contact in the stored contacts
+    stored_contacts
+        .contacts
+        .insert(request.id, updated_contact.clone());
+    Ok(updated_contact)
+}
+
+/// Removes the contact `id` from `stored_contacts`.
+fn remove_contact(stored_contacts: &mut StoredContacts, id: u64) -> Result<u64, ContactError> {
+    // Remove the contact using the BTreeMap's remove method
+    stored_contacts
+        .contacts
+        .remove(&id)
+        .map(|_| id)
+        .ok_or(ContactError::ContactNotFound)
+}
+
+/// Adds the contacts in `request.content` to the caller's contacts. Contacts that are invalid or
+/// exceed `MAX_CONTACTS_PER_USER` are skipped and reported in the response; the others are
+/// created, with new ids.
+///
+/// # Returns
+/// * `Ok(ImportContactsResponse)` - The created contacts and the errors of the skipped ones
+/// * `Err(ContactError::RandomnessError)` - If no ids could be generated; nothing is imported
+pub(crate) async fn import_contacts(
+    request: ImportContactsRequest,
+) -> Result<ImportContactsResponse, ContactError> {
+    let stored_principal = StoredPrincipal(msg_caller());
+    let current_time = time();
+    let (parsed, mut errors) = format::parse(request.format, &request.content);
+
+    // Generate the random IDs BEFORE mutate_state, since it's an async operation
+    let new_ids = random::generate_random_u64s(parsed.len())
+        .await
+        .map_err(|_| ContactError::RandomnessError)?;
+
+    mutate_state(|s| {
+        let mut stored_contacts = stored_contacts_for_update(&s.contact, &stored_principal);
+
+        let mut imported = Vec::new();
+        for (contact, new_id) in parsed.into_iter().zip(new_ids) {
+            if stored_contacts.contacts.len() >= MAX_CONTACTS_PER_USER {
+                errors.push(ContactImportRowError {
+                    line: contact.line,
+                    error: ContactImportError::TooManyContacts,
+                });
+                continue;
+            }
+            if stored_contacts.contacts.contains_key(&new_id) {
+                return Err(ContactError::RandomnessError);
+            }
+            let new_contact = Contact {
+                id: new_id,
+                name: contact.name,
+                addresses: contact.addresses,
+                update_timestamp_ns: current_time,
+                image: None,
+            };
+            stored_contacts.contacts.insert(new_id, new_contact.clone());
+            imported.push(new_contact);
+        }
+        errors.sort_by_key(|error| error.line);
+
+        if !imported.is_empty() {
+            stored_contacts.update_timestamp_ns = current_time;
+            certify_contacts(stored_principal, &stored_contacts);
+            s.contact.insert(stored_principal, Candid(stored_contacts));
+        }
+
+        Ok(ImportContactsResponse { imported, errors })
+    })
+}
+
+/// The caller's contacts rendered in `format`.
+pub(crate) fn export_contacts(format: ContactExportFormat) -> String {
+    format::render(format, &get_contacts())
+}
+
 pub(crate) fn get_contacts() -> Vec<Contact> {
     let stored_principal = StoredPrincipal(msg_caller());
 
@@ -134,27 +439,11 @@ pub(crate) fn update_contact(request: UpdateContactRequest) -> Result<Contact, C
             return Err(ContactError::ContactNotFound);
         };
 
-        // Check if the contact exists
-        if !stored_contacts.contacts.contains_key(&request.id) {
-            return Err(ContactError::ContactNotFound);
-        }
-
-        // Create an updated contact with current timestamp
-        let updated_contact = Contact {
-            id: request.id,
-            name: request.name,
-            addresses: request.addresses,
-            update_timestamp_ns: current_time,
-            image: request.image,
-        };
-
-        // Update the contact in the stored contacts
-        stored_contacts
-            .contacts
-            .insert(request.id, updated_contact.clone());
+        let updated_contact = replace_contact(&mut stored_contacts, request, current_time)?;
         stored_contacts.update_timestamp_ns = current_time;
 
         // Update the storage
+        certify_contacts(stored_principal, &stored_contacts);
         s.contact.insert(stored_principal, Candid(stored_contacts));
 
         Ok(updated_contact)
@@ -169,6 +458,32 @@ fn create_empty_contacts() -> StoredContacts {
     }
 }
 
+/// The contacts of `stored_principal` in `contacts`, or empty contacts if there are none or they
+/// cannot be deserialized.
+///
+/// Takes the contacts map rather than reading the state, so it can be called within
+/// `mutate_state` without a "`BorrowError`" caused by nested state borrowing.
+fn stored_contacts_for_update(
+    contacts: &ContactMap,
+    stored_principal: &StoredPrincipal,
+) -> StoredContacts {
+    if let Some(stored_contacts) = contacts.get(stored_principal) {
+        // Try to access the contacts safely with catch_unwind
+        if let Ok(contacts) = std::panic::catch_unwind(|| stored_contacts.clone()) {
+            contacts
+        } else {
+            // Log deserialization failure and create empty contacts
+            ic_cdk::api::debug_print(format!(
+                "Failed to deserialize contacts for principal: {}. Creating empty contacts.",
+                stored_principal.0
+            ));
+            create_empty_contacts()
+        }
+    } else {
+        create_empty_contacts()
+    }
+}
+
 /// Safely retrieves stored contacts for a user principal, handling deserialization failures.
 ///
 /// # Arguments
@@ -219,16 +534,11 @@ pub(crate) fn delete_contact(contact_id: u64) -> Result<u64, ContactError> {
             return Err(ContactError::ContactNotFound);
         };
 
-        // Check if the contact exists
-        if !stored_contacts.contacts.contains_key(&contact_id) {
-            return Err(ContactError::ContactNotFound);
-        }
-
-        // Remove the contact using the BTreeMap's remove method
-        stored_contacts.contacts.remove(&contact_id);
+        remove_contact(&mut stored_contacts, contact_id)?;
         stored_contacts.update_timestamp_ns = current_time;
 
         // Update the storage
+        certify_contacts(stored_principal, &stored_contacts);
         s.contact.insert(stored_principal, Candid(stored_contacts));
 
         Ok(contact_id)
@@ -0,0 +1,299 @@
+//! Median aggregation of exchange-rate quotes from several providers.
+//!
+//! Used instead of the first-valid-price cascade when `exchange_rate_max_deviation_bps` is set, so
+//! that a single misbehaving upstream cannot push a wrong price to every user. How often each
+//! provider disagreed with the others is kept in heap counters and exported on `/metrics`.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::BTreeMap,
+};
+
+use ic_metrics_encoder::MetricsEncoder;
+use shared::types::exchange::ExchangeData;
+
+/// One provider's valid quote for a token.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ProviderQuote {
+    pub provider: &'static str,
+    pub data: ExchangeData,
+}
+
+/// Result of aggregating the quotes for one token.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct AggregatedPrice {
+    /// The aggregated price, or `None` when no quote agreed with the median.
+    pub data: Option<ExchangeData>,
+    /// Providers whose quote was rejected as an outlier.
+    pub outliers: Vec<&'static str>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct ProviderAgreement {
+    /// Quotes that took part in a median (tokens with at least two quotes).
+    compared: u64,
+    /// Compared quotes rejected as outliers.
+    outliers: u64,
+}
+
+thread_local! {
+    /// Per-provider agreement counters since the last upgrade.
+    static PROVIDER_AGREEMENT: RefCell<BTreeMap<&'static str, ProviderAgreement>> =
+        const { RefCell::new(BTreeMap::new()) };
+    /// Tokens whose quotes all deviated from their median, so no price was stored.
+    static NO_CONSENSUS: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Median of `prices`; the mean of the two middle values for an even count.
+fn median(prices: &mut [f64]) -> Option<f64> {
+    prices.sort_by(f64::total_cmp);
+    let mid = prices.len() / 2;
+    match prices.len() {
+        0 => None,
+        n if n % 2 == 0 => Some(f64::midpoint(prices[mid - 1], prices[mid])),
+        _ => Some(prices[mid]),
+    }
+}
+
+/// Aggregates one token's `quotes`, given in provider priority order.
+///
+/// Quotes further than `max_deviation_bps` basis points from the median of all quotes are
+/// rejected. The result carries the median of the remaining quotes as its price, their count as
+/// `sources`, and the provider and other fields (timestamp, 24h change, market cap) of the
+/// highest-priority remaining quote. A single quote is always accepted: there is nothing to
+/// compare it against.
+pub(crate) fn aggregate_quotes(
+    quotes: &[ProviderQuote],
+    max_deviation_bps: u32,
+) -> AggregatedPrice {
+    let mut prices: Vec<f64> = quotes.iter().filter_map(|q| q.data.price).collect();
+    let Some(overall_median) = median(&mut prices) else {
+        return AggregatedPrice {
+            data: None,
+            outliers: Vec::new(),
+        };
+    };
+
+    let max_deviation = overall_median * f64::from(max_deviation_bps) / 10_000.0;
+    let (agreeing, outliers): (Vec<&ProviderQuote>, Vec<&ProviderQuote>) =
+        quotes.iter().partition(|q| {
+            q.data
+                .price
+                .is_some_and(|p| (p - overall_median).abs() <= max_deviation)
+        });
+
+    let mut agreeing_prices: Vec<f64> = agreeing.iter().filter_map(|q| q.data.price).collect();
+    let data = median(&mut agreeing_prices).and_then(|price| {
+        agreeing.first().map(|q| ExchangeData {
+            price: Some(price),
+            source: Some(q.provider.to_string()),
+            sources: u32::try_from(agreeing.len()).ok(),
+            ..q.data.clone()
+        })
+    });
+
+    AggregatedPrice {
+        data,
+        outliers: outliers.iter().map(|q| q.provider).collect(),
+    }
+}
+
+/// Adds the outcome of one token's aggregation to the agreement counters.
+pub(crate) fn record_agreement(quotes: &[ProviderQuote], aggregated: &AggregatedPrice) {
+    if quotes.len() < 2 {
+        return;
+    }
+    PROVIDER_AGREEMENT.with(|stats| {
+        let mut stats = stats.borrow_mut();
+        for quote in quotes {
+            let entry = stats.entry(quote.provider).or_default();
+            entry.compared += 1;
+            if aggregated.outliers.contains(&quote.provider) {
+                entry.outliers += 1;
+            }
+        }
+    });
+    if aggregated.data.is_none() {
+        NO_CONSENSUS.with(|count| count.set(count.get() + 1));
+    }
+}
+
+/// Encodes the agreement counters in the Prometheus format.
+#[expect(clippy::cast_precision_loss)]
+pub(crate) fn encode_metrics(w: &mut MetricsEncoder<Vec<u8>>) -> std::io::Result<()> {
+    PROVIDER_AGREEMENT.with(|stats| {
+        let stats = stats.borrow();
+        let mut compared = w.counter_vec(
+            "ic_eth_wallet_exchange_provider_compared_quotes",
+            "Exchange-rate quotes compared against other providers, per provider",
+        )?;
+        for (provider, agreement) in stats.iter() {
+            compared = compared.value(&[("provider", provider)], agreement.compared as f64)?;
+        }
+        let mut outliers = w.counter_vec(
+            "ic_eth_wallet_exchange_provider_outlier_quotes",
+            "Exchange-rate quotes rejected for deviating from the median, per provider",
+        )?;
+        for (provider, agreement) in stats.iter() {
+            outliers = outliers.value(&[("provider", provider)], agreement.outliers as f64)?;
+        }
+        Ok::<(), std::io::Error>(())
+    })?;
+    w.encode_counter(
+        "ic_eth_wallet_exchange_rate_no_consensus",
+        NO_CONSENSUS.with(Cell::get) as f64,
+        "Token refreshes skipped because every provider quote deviated from the median",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn quote(provider: &'static str, price: f64, timestamp_ns: u64) -> ProviderQuote {
+        ProviderQuote {
+            provider,
+            data: ExchangeData {
+                timestamp_ns,
+                price: Some(price),
+                price_24h_change_pct: None,
+                market_cap: None,
+                source: None,
+                sources: None,
+                stale: None,
+            },
+        }
+    }
+
+    fn median_price(
+        price: f64,
+        timestamp_ns: u64,
+        provider: &'static str,
+        sources: u32,
+    ) -> ExchangeData {
+        ExchangeData {
+            source: Some(provider.to_string()),
+            sources: Some(sources),
+            ..quote(provider, price, timestamp_ns).data
+        }
+    }
+
+    #[test]
+    fn median_handles_odd_and_even_counts() {
+        assert_eq!(median(&mut []), None);
+        assert_eq!(median(&mut [3.0, 1.0, 2.0]), Some(2.0));
+        assert_eq!(median(&mut [4.0, 1.0, 3.0, 2.0]), Some(2.5));
+    }
+
+    #[test]
+    fn single_quote_is_accepted() {
+        let aggregated = aggregate_quotes(&[quote("a", 7.0, 1)], 0);
+
+        assert_eq!(
+            aggregated,
+            AggregatedPrice {
+                data: Some(median_price(7.0, 1, "a", 1)),
+                outliers: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn outlier_is_rejected_and_median_of_the_rest_is_used() {
+        let quotes = [
+            quote("a", 100.0, 1),
+            quote("b", 102.0, 2),
+            quote("c", 1_000.0, 3),
+        ];
+
+        let aggregated = aggregate_quotes(&quotes, 500);
+
+        assert_eq!(
+            aggregated,
+            AggregatedPrice {
+                data: Some(median_price(101.0, 1, "a", 2)),
+                outliers: vec!["c"],
+            }
+        );
+    }
+
+    #[test]
+    fn highest_priority_agreeing_quote_supplies_the_metadata() {
+        let quotes = [
+            quote("a", 1.0, 1),
+            quote("b", 100.0, 2),
+            quote("c", 101.0, 3),
+        ];
+
+        let aggregated = aggregate_quotes(&quotes, 500);
+
+        assert_eq!(
+            aggregated,
+            AggregatedPrice {
+                data: Some(median_price(100.5, 2, "b", 2)),
+                outliers: vec!["a"],
+            }
+        );
+    }
+
+    #[test]
+    fn two_disagreeing_quotes_have_no_consensus() {
+        let quotes = [quote("a", 100.0, 1), quote("b", 200.0, 2)];
+
+        let aggregated = aggregate_quotes(&quotes, 1_000);
+
+        assert_eq!(
+            aggregated,
+            AggregatedPrice {
+                data: None,
+                outliers: vec!["a", "b"],
+            }
+        );
+    }
+
+    #[test]
+    fn record_agreement_counts_compared_and_outlier_quotes() {
+        let quotes = [
+            quote("a", 100.0, 1),
+            quote("b", 101.0, 2),
+            quote("c", 500.0, 3),
+        ];
+        let disagreeing = [quote("a", 100.0, 1), quote("c", 500.0, 3)];
+
+        record_agreement(&quotes, &aggregate_quotes(&quotes, 500));
+        record_agreement(&disagreeing, &aggregate_quotes(&disagreeing, 500));
+        // A lone quote is not compared against anything.
+        record_agreement(&quotes[..1], &aggregate_quotes(&quotes[..1], 500));
+
+        let stats = PROVIDER_AGREEMENT.with(|stats| stats.borrow().clone());
+        assert_eq!(
+            stats,
+            BTreeMap::from([
+                (
+                    "a",
+                    ProviderAgreement {
+                        compared: 2,
+                        outliers: 1,
+                    }
+                ),
+                (
+                    "b",
+                    ProviderAgreement {
+                        compared: 1,
+                        outliers: 0,
+                    }
+                ),
+                (
+                    "c",
+                    ProviderAgreement {
+                        compared: 2,
+                        outliers: 2,
+                    }
+                ),
+            ])
+        );
+        assert_eq!(NO_CONSENSUS.with(Cell::get), 1);
+    }
+}
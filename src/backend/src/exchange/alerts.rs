@@ -0,0 +1,691 @@
+//! User price alerts, evaluated whenever the exchange-rate refresh stores a new rate.
+//!
+//! Alerts are edge-triggered: an armed alert triggers once its condition holds, records a
+//! [`TriggeredPriceAlert`] in the user's inbox and disarms; it re-arms once the condition no
+//! longer holds.
+
+use std::ops::Bound;
+
+use candid::Principal;
+use ic_stable_structures::Storable;
+use shared::types::{
+    exchange::{ExchangeData, ExchangeRateResolution},
+    price_alert::{
+        PriceAlert, PriceAlertCondition, PriceAlertError, TriggeredPriceAlert,
+        MAX_PRICE_ALERTS_PER_USER, MAX_TRIGGERED_PRICE_ALERTS_PER_USER,
+    },
+    token_id::TokenId,
+    Timestamp,
+};
+
+use crate::{
+    exchange::{history, providers::coingecko::is_priceable_token_id},
+    types::{
+        maps::{
+            ExchangeRateHistoryMap, PriceAlertMap, PriceAlertTokenIndexMap, TriggeredPriceAlertMap,
+        },
+        storable::{Candid, StoredTokenId},
+        StoredPrincipal,
+    },
+};
+
+const NANOS_PER_DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+/// Upper bound on the number of alerts evaluated in one message. The alerts of a token beyond it
+/// are evaluated in follow-up batches, see [`evaluate_alerts`].
+pub(crate) const MAX_ALERTS_EVALUATED_PER_BATCH: usize = 500;
+
+/// Largest principal in the key order, used as the upper bound of per-token index scans.
+fn max_principal() -> StoredPrincipal {
+    StoredPrincipal(Principal::from_slice(
+        &[u8::MAX; Principal::MAX_LENGTH_IN_BYTES],
+    ))
+}
+
+fn min_principal() -> StoredPrincipal {
+    StoredPrincipal(Principal::from_slice(&[]))
+}
+
+/// Checks that the condition's threshold can ever be crossed.
+pub(crate) fn validate_condition(condition: &PriceAlertCondition) -> Result<(), PriceAlertError> {
+    let valid = match *condition {
+        PriceAlertCondition::PriceAbove { usd } | PriceAlertCondition::PriceBelow { usd } => {
+            usd.is_finite() && usd > 0.0
+        }
+        PriceAlertCondition::Change24hAbove { pct }
+        | PriceAlertCondition::Change24hBelow { pct } => pct.is_finite(),
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(PriceAlertError::InvalidThreshold)
+    }
+}
+
+/// Whether `condition` holds for `price_usd` and `change_24h_pct`. Change conditions never hold
+/// while the 24h change is unknown.
+fn condition_holds(
+    condition: &PriceAlertCondition,
+    price_usd: f64,
+    change_24h_pct: Option<f64>,
+) -> bool {
+    match *condition {
+        PriceAlertCondition::PriceAbove { usd } => price_usd > usd,
+        PriceAlertCondition::PriceBelow { usd } => price_usd < usd,
+        PriceAlertCondition::Change24hAbove { pct } => change_24h_pct.is_some_and(|c| c > pct),
+        PriceAlertCondition::Change24hBelow { pct } => change_24h_pct.is_some_and(|c| c < pct),
+    }
+}
+
+/// The 24h price change of `data`, in percent.
+///
+/// Falls back to the change against the recorded hourly history when the provider does not report
+/// one.
+pub(crate) fn change_24h_pct(
+    exchange_rate_history: &ExchangeRateHistoryMap,
+    token_id: &StoredTokenId,
+    data: &ExchangeData,
+) -> Option<f64> {
+    if data.price_24h_change_pct.is_some() {
+        return data.price_24h_change_pct;
+    }
+    let price = data.price?;
+    let day_ago_ns = data.timestamp_ns.checked_sub(NANOS_PER_DAY)?;
+    let day_ago = history::get_history(
+        exchange_rate_history,
+        token_id,
+        day_ago_ns,
+        day_ago_ns,
+        ExchangeRateResolution::Hourly,
+    );
+    let previous = day_ago.first()?.close;
+    (previous > 0.0).then(|| (price / previous - 1.0) * 100.0)
+}
+
+/// Creates an alert for `principal`.
+///
+/// # Errors
+/// - `TokenNotPriceable` if no exchange rate is ever fetched for the token.
+/// - `InvalidThreshold` if the condition can never hold.
+/// - `TooManyAlerts` if the principal already has [`MAX_PRICE_ALERTS_PER_USER`] alerts.
+pub(crate) fn create_alert(
+    alerts: &mut PriceAlertMap,
+    by_token: &mut PriceAlertTokenIndexMap,
+    principal: Principal,
+    token_id: TokenId,
+    condition: PriceAlertCondition,
+    now: Timestamp,
+) -> Result<PriceAlert, PriceAlertError> {
+    if !is_priceable_token_id(&token_id) {
+        return Err(PriceAlertError::TokenNotPriceable);
+    }
+    validate_condition(&condition)?;
+
+    let principal = StoredPrincipal(principal);
+    let user_alerts = alerts.keys_range((principal, 0)..=(principal, u64::MAX));
+    let (count, last_id) = user_alerts.fold((0, None), |(count, _), (_, id)| (count + 1, Some(id)));
+    if count >= MAX_PRICE_ALERTS_PER_USER {
+        return Err(PriceAlertError::TooManyAlerts {
+            max: MAX_PRICE_ALERTS_PER_USER as u64,
+        });
+    }
+
+    let alert = PriceAlert {
+        id: last_id.map_or(0, |id: u64| id + 1),
+        token_id,
+        condition,
+        created_at_ns: now,
+        armed: true,
+        last_triggered_at_ns: None,
+    };
+    by_token.insert((token_key(&alert.token_id), principal, alert.id), ());
+    alerts.insert((principal, alert.id), Candid(alert.clone()));
+    Ok(alert)
+}
+
+/// Deletes one of the principal's alerts. Triggered alerts already in the inbox are kept.
+///
+/// # Errors
+/// - `AlertNotFound` if the principal has no alert with that id.
+pub(crate) fn delete_alert(
+    alerts: &mut PriceAlertMap,
+    by_token: &mut PriceAlertTokenIndexMap,
+    principal: Principal,
+    alert_id: u64,
+) -> Result<(), PriceAlertError> {
+    let principal = StoredPrincipal(principal);
+    let alert = alerts
+        .remove(&(principal, alert_id))
+        .ok_or(PriceAlertError::AlertNotFound)?;
+    by_token.remove(&(token_key(&alert.0.token_id), principal, alert_id));
+    Ok(())
+}
+
+/// The principal's alerts, oldest first.
+pub(crate) fn list_alerts(alerts: &PriceAlertMap, principal: Principal) -> Vec<PriceAlert> {
+    let principal = StoredPrincipal(principal);
+    alerts
+        .range((principal, 0)..=(principal, u64::MAX))
+        .map(|entry| entry.value().0)
+        .collect()
+}
+
+/// The tokens that have at least one alert.
+pub(crate) fn alert_token_ids(by_token: &PriceAlertTokenIndexMap) -> Vec<StoredTokenId> {
+    let mut token_ids = Vec::new();
+    let mut lower = Bound::Unbounded;
+    // Jumps from token to token instead of visiting every alert.
+    while let Some((token, _, _)) = by_token.keys_range((lower, Bound::Unbounded)).next() {
+        token_ids.push(StoredTokenId::from_bytes(token.as_slice().into()));
+        lower = Bound::Excluded((token, max_principal(), u64::MAX));
+    }
+    token_ids
+}
+
+/// A newly stored rate, as seen by the alerts of its token.
+#[derive(Clone, Copy)]
+pub(crate) struct ObservedPrice {
+    pub price_usd: f64,
+    pub change_24h_pct: Option<f64>,
+    pub timestamp_ns: Timestamp,
+}
+
+/// Outcome of evaluating a batch of the alerts of a token.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct AlertsEvaluation {
+    pub triggered: u64,
+    /// The last evaluated `(principal, alert_id)`, if the token has alerts beyond this batch.
+    pub resume_after: Option<(StoredPrincipal, u64)>,
+}
+
+/// Checks up to [`MAX_ALERTS_EVALUATED_PER_BATCH`] alerts of `token_id`, following `after`,
+/// against its new rate, records the alerts that trigger in their owners' inboxes and re-arms the
+/// alerts whose condition no longer holds.
+///
+/// `may_trigger` is asked before an alert of a principal triggers; when it refuses, the alert
+/// stays armed. Returns the number of triggered alerts and where to resume with the next batch.
+pub(crate) fn evaluate_alerts(
+    alerts: &mut PriceAlertMap,
+    by_token: &PriceAlertTokenIndexMap,
+    inbox: &mut TriggeredPriceAlertMap,
+    token_id: &StoredTokenId,
+    observed: ObservedPrice,
+    after: Option<(StoredPrincipal, u64)>,
+    mut may_trigger: impl FnMut(Principal) -> bool,
+) -> AlertsEvaluation {
+    let ObservedPrice {
+        price_usd,
+        change_24h_pct,
+        timestamp_ns: now,
+    } = observed;
+    let token = token_id.to_bytes().into_owned();
+    let lower = after.map_or(
+        Bound::Included((token.clone(), min_principal(), 0)),
+        |(principal, alert_id)| Bound::Excluded((token.clone(), principal, alert_id)),
+    );
+    let mut keys: Vec<(StoredPrincipal, u64)> = by_token
+        .keys_range((lower, Bound::Included((token, max_principal(), u64::MAX))))
+        .take(MAX_ALERTS_EVALUATED_PER_BATCH + 1)
+        .map(|(_, principal, alert_id)| (principal, alert_id))
+        .collect();
+    let has_more = keys.len() > MAX_ALERTS_EVALUATED_PER_BATCH;
+    keys.truncate(MAX_ALERTS_EVALUATED_PER_BATCH);
+
+    let mut triggered = 0;
+    for key in &keys {
+        let Some(Candid(mut alert)) = alerts.get(key) else {
+            continue;
+        };
+        let holds = condition_holds(&alert.condition, price_usd, change_24h_pct);
+        if holds && alert.armed && may_trigger(key.0 .0) {
+            push_triggered(
+                inbox,
+                key.0,
+                TriggeredPriceAlert {
+                    alert_id: alert.id,
+                    token_id: alert.token_id.clone(),
+                    condition: alert.condition,
+                    price_usd,
+                    price_24h_change_pct: change_24h_pct,
+                    triggered_at_ns: now,
+                },
+            );
+            alert.armed = false;
+            alert.last_triggered_at_ns = Some(now);
+            triggered += 1;
+        } else if !holds && !alert.armed {
+            alert.armed = true;
+        } else {
+            continue;
+        }
+        alerts.insert(*key, Candid(alert));
+    }
+
+    AlertsEvaluation {
+        triggered,
+        resume_after: keys.last().copied().filter(|_| has_more),
+    }
+}
+
+/// The principal's triggered alerts, newest first.
+pub(crate) fn triggered_alerts(
+    inbox: &TriggeredPriceAlertMap,
+    principal: Principal,
+) -> Vec<TriggeredPriceAlert> {
+    let principal = StoredPrincipal(principal);
+    inbox
+        .range((principal, 0, 0)..=(principal, u64::MAX, u64::MAX))
+        .rev()
+        .map(|entry| entry.value().0)
+        .collect()
+}
+
+/// Empties the principal's inbox and returns how many triggered alerts were removed.
+pub(crate) fn clear_triggered_alerts(
+    inbox: &mut TriggeredPriceAlertMap,
+    principal: Principal,
+) -> u64 {
+    let principal = StoredPrincipal(principal);
+    let keys: Vec<_> = inbox
+        .keys_range((principal, 0, 0)..=(principal, u64::MAX, u64::MAX))
+        .collect();
+    for key in &keys {
+        inbox.remove(key);
+    }
+    keys.len() as u64
+}
+
+fn token_key(token_id: &TokenId) -> Vec<u8> {
+    StoredTokenId(token_id.clone()).to_bytes().into_owned()
+}
+
+/// Adds `triggered` to the principal's inbox, dropping the oldest entries above
+/// [`MAX_TRIGGERED_PRICE_ALERTS_PER_USER`].
+fn push_triggered(
+    inbox: &mut TriggeredPriceAlertMap,
+    principal: StoredPrincipal,
+    triggered: TriggeredPriceAlert,
+) {
+    inbox.insert(
+        (principal, triggered.triggered_at_ns, triggered.alert_id),
+        Candid(triggered),
+    );
+    let keys: Vec<_> = inbox
+        .keys_range((principal, 0, 0)..=(principal, u64::MAX, u64::MAX))
+        .collect();
+    let excess = keys
+        .len()
+        .saturating_sub(MAX_TRIGGERED_PRICE_ALERTS_PER_USER);
+    for key in &keys[..excess] {
+        inbox.remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use candid::Principal;
+    use ic_stable_structures::{
+        memory_manager::{MemoryId, MemoryManager},
+        DefaultMemoryImpl,
+    };
+    use pretty_assertions::assert_eq;
+    use shared::types::{
+        exchange::ExchangeData,
+        price_alert::{
+            PriceAlertCondition, PriceAlertError, MAX_PRICE_ALERTS_PER_USER,
+            MAX_TRIGGERED_PRICE_ALERTS_PER_USER,
+        },
+        token_id::TokenId,
+    };
+
+    use super::{
+        alert_token_ids, change_24h_pct, clear_triggered_alerts, create_alert, delete_alert,
+        evaluate_alerts, list_alerts, triggered_alerts, ObservedPrice,
+        MAX_ALERTS_EVALUATED_PER_BATCH, NANOS_PER_DAY,
+    };
+    use crate::{
+        exchange::history::record_price,
+        types::{
+            maps::{
+                ExchangeRateHistoryMap, PriceAlertMap, PriceAlertTokenIndexMap,
+                TriggeredPriceAlertMap,
+            },
+            storable::StoredTokenId,
+        },
+    };
+
+    struct Maps {
+        alerts: PriceAlertMap,
+        by_token: PriceAlertTokenIndexMap,
+        inbox: TriggeredPriceAlertMap,
+        history: ExchangeRateHistoryMap,
+    }
+
+    fn setup() -> Maps {
+        let memory_manager = MemoryManager::init(DefaultMemoryImpl::default());
+        Maps {
+            alerts: PriceAlertMap::init(memory_manager.get(MemoryId::new(0))),
+            by_token: PriceAlertTokenIndexMap::init(memory_manager.get(MemoryId::new(1))),
+            inbox: TriggeredPriceAlertMap::init(memory_manager.get(MemoryId::new(2))),
+            history: ExchangeRateHistoryMap::init(memory_manager.get(MemoryId::new(3))),
+        }
+    }
+
+    fn alice() -> Principal {
+        Principal::from_slice(&[1])
+    }
+
+    fn bob() -> Principal {
+        Principal::from_slice(&[2])
+    }
+
+    fn icp() -> StoredTokenId {
+        StoredTokenId(TokenId::IcpNative)
+    }
+
+    fn above(usd: f64) -> PriceAlertCondition {
+        PriceAlertCondition::PriceAbove { usd }
+    }
+
+    fn create(
+        maps: &mut Maps,
+        principal: Principal,
+        token_id: &StoredTokenId,
+        condition: PriceAlertCondition,
+    ) -> u64 {
+        create_alert(
+            &mut maps.alerts,
+            &mut maps.by_token,
+            principal,
+            token_id.0.clone(),
+            condition,
+            0,
+        )
+        .expect("alert should be created")
+        .id
+    }
+
+    fn evaluate(maps: &mut Maps, token_id: &StoredTokenId, price_usd: f64, now: u64) -> u64 {
+        evaluate_alerts(
+            &mut maps.alerts,
+            &maps.by_token,
+            &mut maps.inbox,
+            token_id,
+            ObservedPrice {
+                price_usd,
+                change_24h_pct: None,
+                timestamp_ns: now,
+            },
+            None,
+            |_| true,
+        )
+        .triggered
+    }
+
+    #[test]
+    fn test_create_alert_rejects_invalid_thresholds() {
+        let mut maps = setup();
+        for condition in [
+            above(0.0),
+            PriceAlertCondition::PriceBelow { usd: f64::NAN },
+            PriceAlertCondition::Change24hBelow {
+                pct: f64::NEG_INFINITY,
+            },
+        ] {
+            let result = create_alert(
+                &mut maps.alerts,
+                &mut maps.by_token,
+                alice(),
+                TokenId::IcpNative,
+                condition,
+                0,
+            );
+            assert_eq!(result, Err(PriceAlertError::InvalidThreshold));
+        }
+    }
+
+    #[test]
+    fn test_create_alert_rejects_unpriceable_tokens() {
+        let mut maps = setup();
+
+        let result = create_alert(
+            &mut maps.alerts,
+            &mut maps.by_token,
+            alice(),
+            TokenId::SolNativeDevnet,
+            above(1.0),
+            0,
+        );
+
+        assert_eq!(result, Err(PriceAlertError::TokenNotPriceable));
+    }
+
+    #[test]
+    fn test_create_alert_caps_alerts_per_user() {
+        let mut maps = setup();
+        for _ in 0..MAX_PRICE_ALERTS_PER_USER {
+            create(&mut maps, alice(), &icp(), above(1.0));
+        }
+
+        let result = create_alert(
+            &mut maps.alerts,
+            &mut maps.by_token,
+            alice(),
+            TokenId::IcpNative,
+            above(1.0),
+            0,
+        );
+
+        assert_eq!(
+            result,
+            Err(PriceAlertError::TooManyAlerts {
+                max: MAX_PRICE_ALERTS_PER_USER as u64
+            })
+        );
+        assert_eq!(create(&mut maps, bob(), &icp(), above(1.0)), 0);
+    }
+
+    #[test]
+    fn test_alert_triggers_once_per_crossing() {
+        let mut maps = setup();
+        let id = create(&mut maps, alice(), &icp(), above(15.0));
+
+        assert_eq!(evaluate(&mut maps, &icp(), 14.0, 1), 0);
+        assert_eq!(evaluate(&mut maps, &icp(), 16.0, 2), 1);
+        assert_eq!(evaluate(&mut maps, &icp(), 17.0, 3), 0);
+        assert_eq!(evaluate(&mut maps, &icp(), 14.0, 4), 0);
+        assert_eq!(evaluate(&mut maps, &icp(), 15.5, 5), 1);
+
+        let inbox = triggered_alerts(&maps.inbox, alice());
+        assert_eq!(
+            inbox
+                .iter()
+                .map(|t| (t.alert_id, t.triggered_at_ns))
+                .collect::<Vec<_>>(),
+            vec![(id, 5), (id, 2)]
+        );
+        let alert = &list_alerts(&maps.alerts, alice())[0];
+        assert!(!alert.armed);
+        assert_eq!(alert.last_triggered_at_ns, Some(5));
+    }
+
+    #[test]
+    fn test_refused_trigger_keeps_alert_armed() {
+        let mut maps = setup();
+        create(&mut maps, alice(), &icp(), above(1.0));
+
+        let triggered = evaluate_alerts(
+            &mut maps.alerts,
+            &maps.by_token,
+            &mut maps.inbox,
+            &icp(),
+            ObservedPrice {
+                price_usd: 2.0,
+                change_24h_pct: None,
+                timestamp_ns: 1,
+            },
+            None,
+            |_| false,
+        )
+        .triggered;
+
+        assert_eq!(triggered, 0);
+        assert!(list_alerts(&maps.alerts, alice())[0].armed);
+        assert_eq!(evaluate(&mut maps, &icp(), 2.0, 2), 1);
+    }
+
+    #[test]
+    fn test_only_alerts_of_the_refreshed_token_are_evaluated() {
+        let mut maps = setup();
+        let btc = StoredTokenId(TokenId::BtcNativeMainnet);
+        create(&mut maps, alice(), &icp(), above(1.0));
+        create(&mut maps, bob(), &btc, above(1.0));
+
+        assert_eq!(evaluate(&mut maps, &btc, 2.0, 1), 1);
+
+        assert!(triggered_alerts(&maps.inbox, alice()).is_empty());
+        assert_eq!(triggered_alerts(&maps.inbox, bob()).len(), 1);
+    }
+
+    #[test]
+    fn test_alerts_are_evaluated_in_bounded_batches() {
+        let mut maps = setup();
+        let owners = MAX_ALERTS_EVALUATED_PER_BATCH.div_ceil(MAX_PRICE_ALERTS_PER_USER) + 1;
+        for owner in 0..owners {
+            let owner = Principal::from_slice(&u32::try_from(owner).unwrap().to_be_bytes());
+            for _ in 0..MAX_PRICE_ALERTS_PER_USER {
+                create(&mut maps, owner, &icp(), above(1.0));
+            }
+        }
+        let total = owners * MAX_PRICE_ALERTS_PER_USER;
+        let observed = ObservedPrice {
+            price_usd: 2.0,
+            change_24h_pct: None,
+            timestamp_ns: 1,
+        };
+
+        let mut after = None;
+        let mut batches = Vec::new();
+        loop {
+            let evaluation = evaluate_alerts(
+                &mut maps.alerts,
+                &maps.by_token,
+                &mut maps.inbox,
+                &icp(),
+                observed,
+                after,
+                |_| true,
+            );
+            batches.push(evaluation.triggered);
+            after = evaluation.resume_after;
+            if after.is_none() {
+                break;
+            }
+        }
+
+        assert_eq!(
+            batches.len(),
+            total.div_ceil(MAX_ALERTS_EVALUATED_PER_BATCH)
+        );
+        assert!(batches
+            .iter()
+            .all(|&triggered| triggered <= MAX_ALERTS_EVALUATED_PER_BATCH as u64));
+        assert_eq!(batches.iter().sum::<u64>(), total as u64);
+    }
+
+    #[test]
+    fn test_change_conditions_need_a_known_change() {
+        let mut maps = setup();
+        create(
+            &mut maps,
+            alice(),
+            &icp(),
+            PriceAlertCondition::Change24hBelow { pct: -10.0 },
+        );
+
+        assert_eq!(evaluate(&mut maps, &icp(), 1.0, 1), 0);
+        let triggered = evaluate_alerts(
+            &mut maps.alerts,
+            &maps.by_token,
+            &mut maps.inbox,
+            &icp(),
+            ObservedPrice {
+                price_usd: 1.0,
+                change_24h_pct: Some(-12.5),
+                timestamp_ns: 2,
+            },
+            None,
+            |_| true,
+        )
+        .triggered;
+        assert_eq!(triggered, 1);
+    }
+
+    #[test]
+    fn test_inbox_keeps_the_newest_triggered_alerts() {
+        let mut maps = setup();
+        create(&mut maps, alice(), &icp(), above(1.0));
+        let extra = 5u64;
+        for tick in 0..(MAX_TRIGGERED_PRICE_ALERTS_PER_USER as u64 + extra) {
+            evaluate(&mut maps, &icp(), 2.0, 2 * tick + 1);
+            evaluate(&mut maps, &icp(), 0.5, 2 * tick + 2);
+        }
+
+        let inbox = triggered_alerts(&maps.inbox, alice());
+
+        assert_eq!(inbox.len(), MAX_TRIGGERED_PRICE_ALERTS_PER_USER);
+        assert_eq!(inbox.last().unwrap().triggered_at_ns, 2 * extra + 1);
+        assert_eq!(clear_triggered_alerts(&mut maps.inbox, alice()), 50);
+        assert!(triggered_alerts(&maps.inbox, alice()).is_empty());
+    }
+
+    #[test]
+    fn test_delete_alert_removes_it_from_the_token_index() {
+        let mut maps = setup();
+        let btc = StoredTokenId(TokenId::BtcNativeMainnet);
+        let id = create(&mut maps, alice(), &icp(), above(1.0));
+        create(&mut maps, alice(), &btc, above(1.0));
+        create(&mut maps, bob(), &btc, above(1.0));
+
+        assert_eq!(
+            delete_alert(&mut maps.alerts, &mut maps.by_token, alice(), id),
+            Ok(())
+        );
+
+        assert_eq!(
+            delete_alert(&mut maps.alerts, &mut maps.by_token, alice(), id),
+            Err(PriceAlertError::AlertNotFound)
+        );
+        assert_eq!(alert_token_ids(&maps.by_token), vec![btc]);
+        assert_eq!(evaluate(&mut maps, &icp(), 2.0, 1), 0);
+    }
+
+    #[test]
+    fn test_change_24h_falls_back_to_hourly_history() {
+        let mut maps = setup();
+        let now = 10 * NANOS_PER_DAY;
+        record_price(&mut maps.history, &icp(), now - NANOS_PER_DAY, 10.0);
+        let data = ExchangeData {
+            timestamp_ns: now,
+            price: Some(9.0),
+            price_24h_change_pct: None,
+            market_cap: None,
+            source: None,
+            sources: None,
+            stale: None,
+        };
+
+        let change = change_24h_pct(&maps.history, &icp(), &data).unwrap();
+        let reported = change_24h_pct(
+            &maps.history,
+            &icp(),
+            &ExchangeData {
+                price_24h_change_pct: Some(3.0),
+                ..data
+            },
+        );
+
+        assert!((change + 10.0).abs() < 1e-9);
+        assert_eq!(reported, Some(3.0));
+    }
+}
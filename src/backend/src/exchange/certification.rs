@@ -0,0 +1,47 @@
+//! Certified exchange rates.
+//!
+//! The cached exchange rates are mirrored under `exchange_rates` in the canister's certified tree
+//! (see [`crate::certification`]), so queries can return a rate with a proof that the canister
+//! stored it. They are re-certified from stable memory on init and upgrade.
+
+use ic_stable_structures::Storable;
+use serde_bytes::ByteBuf;
+use shared::types::exchange::{CertifiedExchangeRate, ExchangeRate};
+
+use crate::{
+    certification,
+    types::{
+        maps::ExchangeRateMap,
+        storable::{Candid, StoredTokenId},
+    },
+};
+
+/// Certifies `exchange_rates`, replacing the previously certified rates.
+pub(crate) fn init_certified_exchange_rates(exchange_rates: &ExchangeRateMap) {
+    certification::reset_exchange_rates(exchange_rates.iter().map(|entry| {
+        (
+            entry.key().to_bytes().into_owned(),
+            entry.value().into_bytes(),
+        )
+    }));
+}
+
+/// Adds or replaces the certified rate of `token_id`.
+pub(crate) fn certify_exchange_rate(token_id: &StoredTokenId, rate: &Candid<ExchangeRate>) {
+    certification::set_exchange_rate(
+        token_id.to_bytes().into_owned(),
+        rate.to_bytes().into_owned(),
+    );
+}
+
+/// The witness of `token_id` with `certificate`, the canister's data certificate.
+pub(crate) fn certified_exchange_rate(
+    token_id: &StoredTokenId,
+    certificate: Vec<u8>,
+) -> CertifiedExchangeRate {
+    let witness = certification::exchange_rate_witness(&token_id.to_bytes());
+    CertifiedExchangeRate {
+        certificate: ByteBuf::from(certificate),
+        witness: ByteBuf::from(certification::to_self_describing_cbor(&witness)),
+    }
+}
@@ -3,7 +3,12 @@ use std::collections::BTreeMap;
 use shared::types::exchange::ExchangeData;
 
 use crate::{
-    exchange::{provider::ExchangePriceProvider, supplemental::SupplementalPriceProvider},
+    exchange::{
+        aggregation::{aggregate_quotes, record_agreement, ProviderQuote},
+        overrides::PriceOverrides,
+        provider::ExchangePriceProvider,
+        supplemental::SupplementalPriceProvider,
+    },
     types::storable::StoredTokenId,
 };
 
@@ -12,12 +17,21 @@ pub(crate) fn has_valid_price(data: &ExchangeData) -> bool {
     data.price.is_some_and(|p| p.is_finite() && p > 0.0)
 }
 
+/// `data` recorded as the only quote, from `provider`.
+fn from_provider(mut data: ExchangeData, provider: &'static str) -> ExchangeData {
+    data.source = Some(provider.to_string());
+    data.sources = Some(1);
+    data
+}
+
 fn merge_valid_primary(
     primary: Vec<(StoredTokenId, ExchangeData)>,
+    provider: &'static str,
 ) -> BTreeMap<StoredTokenId, ExchangeData> {
     primary
         .into_iter()
         .filter(|(_, d)| has_valid_price(d))
+        .map(|(id, d)| (id, from_provider(d, provider)))
         .collect()
 }
 
@@ -32,14 +46,33 @@ fn still_missing(
         .collect()
 }
 
-/// Runs the primary provider, then each supplemental in order, merging only valid prices.
+/// Prices `token_ids`, with `overrides` taking precedence over every provider, then the primary
+/// provider, then each supplemental in order, merging only valid prices.
 ///
-/// Later supplementals only see tokens that still lack a valid price after earlier steps.
+/// Overridden tokens are not sent to the providers, which are asked for the targets of their pegs
+/// instead (see [`PriceOverrides`]). Later supplementals only see tokens that still lack a valid
+/// price after earlier steps.
 ///
 /// When `primary_enabled` is `false` the primary is skipped entirely (treated as an empty
 /// result), so every requested token flows straight through to the supplementals. This is the
 /// code-level kill-switch for the primary provider (see `COINGECKO_PROVIDER_ENABLED`).
 pub(crate) async fn fetch_all_prices<P: ExchangePriceProvider>(
+    primary: &P,
+    primary_enabled: bool,
+    supplementals: &[Box<dyn SupplementalPriceProvider>],
+    overrides: &PriceOverrides,
+    token_ids: &[StoredTokenId],
+) -> Vec<(StoredTokenId, ExchangeData)> {
+    let token_ids = overrides.provider_token_ids(token_ids);
+    let prices = if token_ids.is_empty() {
+        Vec::new()
+    } else {
+        fetch_provider_prices(primary, primary_enabled, supplementals, &token_ids).await
+    };
+    overrides.apply(prices)
+}
+
+async fn fetch_provider_prices<P: ExchangePriceProvider>(
     primary: &P,
     primary_enabled: bool,
     supplementals: &[Box<dyn SupplementalPriceProvider>],
@@ -54,7 +87,7 @@ pub(crate) async fn fetch_all_prices<P: ExchangePriceProvider>(
         Vec::new()
     };
 
-    let mut map = merge_valid_primary(primary_rows);
+    let mut map = merge_valid_primary(primary_rows, primary.id());
     let mut missing = still_missing(token_ids, &map);
 
     for provider in supplementals {
@@ -66,7 +99,7 @@ pub(crate) async fn fetch_all_prices<P: ExchangePriceProvider>(
             Ok(filled) => {
                 for (id, data) in filled {
                     if has_valid_price(&data) {
-                        map.insert(id, data);
+                        map.insert(id, from_provider(data, provider.id()));
                     }
                 }
                 missing = still_missing(token_ids, &map);
@@ -86,6 +119,93 @@ pub(crate) async fn fetch_all_prices<P: ExchangePriceProvider>(
         .collect()
 }
 
+/// Asks every enabled provider for every token and aggregates their quotes per token with
+/// [`aggregate_quotes`], rejecting quotes more than `max_deviation_bps` basis points from the
+/// median.
+///
+/// Unlike [`fetch_all_prices`], supplementals also see tokens the primary already priced, so this
+/// mode costs one request per provider and token batch. Tokens whose quotes have no consensus are
+/// left out, keeping their previously cached price. `overrides` take precedence as in
+/// [`fetch_all_prices`].
+pub(crate) async fn fetch_all_prices_median<P: ExchangePriceProvider>(
+    primary: &P,
+    primary_enabled: bool,
+    supplementals: &[Box<dyn SupplementalPriceProvider>],
+    overrides: &PriceOverrides,
+    token_ids: &[StoredTokenId],
+    max_deviation_bps: u32,
+) -> Vec<(StoredTokenId, ExchangeData)> {
+    let token_ids = overrides.provider_token_ids(token_ids);
+    let prices = if token_ids.is_empty() {
+        Vec::new()
+    } else {
+        fetch_provider_prices_median(
+            primary,
+            primary_enabled,
+            supplementals,
+            &token_ids,
+            max_deviation_bps,
+        )
+        .await
+    };
+    overrides.apply(prices)
+}
+
+async fn fetch_provider_prices_median<P: ExchangePriceProvider>(
+    primary: &P,
+    primary_enabled: bool,
+    supplementals: &[Box<dyn SupplementalPriceProvider>],
+    token_ids: &[StoredTokenId],
+    max_deviation_bps: u32,
+) -> Vec<(StoredTokenId, ExchangeData)> {
+    let mut quotes: BTreeMap<StoredTokenId, Vec<ProviderQuote>> = BTreeMap::new();
+    let mut add_quotes = |provider: &'static str, rows: Vec<(StoredTokenId, ExchangeData)>| {
+        for (id, data) in rows {
+            if has_valid_price(&data) && token_ids.contains(&id) {
+                quotes
+                    .entry(id)
+                    .or_default()
+                    .push(ProviderQuote { provider, data });
+            }
+        }
+    };
+
+    if primary_enabled {
+        match primary.fetch_prices(token_ids).await {
+            Ok(rows) => add_quotes(primary.id(), rows),
+            Err(e) => ic_cdk::println!("Primary exchange provider failed: {e}"),
+        }
+    }
+    for provider in supplementals {
+        match provider.supplement(token_ids).await {
+            Ok(rows) => add_quotes(provider.id(), rows),
+            Err(err) => {
+                ic_cdk::println!(
+                    "Supplemental exchange provider {} failed: {err}",
+                    provider.id()
+                );
+            }
+        }
+    }
+
+    token_ids
+        .iter()
+        .filter_map(|t| {
+            let token_quotes = quotes.get(t)?;
+            let aggregated = aggregate_quotes(token_quotes, max_deviation_bps);
+            record_agreement(token_quotes, &aggregated);
+            if !aggregated.outliers.is_empty() {
+                ic_cdk::println!(
+                    "Rejected outlier prices for {:?} from {:?}",
+                    t.0,
+                    aggregated.outliers
+                );
+            }
+            aggregated.data.map(|d| (t.clone(), d))
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use std::{cell::RefCell, rc::Rc};
@@ -93,10 +213,10 @@ mod tests {
     use candid::Principal;
     use futures::executor::block_on;
     use pretty_assertions::assert_eq;
-    use shared::types::token_id::TokenId;
+    use shared::types::{exchange::PriceOverride, token_id::TokenId};
 
     use super::*;
-    use crate::exchange::supplemental::SupplementalPricesFuture;
+    use crate::exchange::{overrides::OVERRIDE_SOURCE, supplemental::SupplementalPricesFuture};
 
     type RequestedTokensLog = Rc<RefCell<Vec<Vec<StoredTokenId>>>>;
 
@@ -106,6 +226,10 @@ mod tests {
     }
 
     impl ExchangePriceProvider for MockPrimaryProvider {
+        fn id(&self) -> &'static str {
+            "mock_primary"
+        }
+
         async fn fetch_prices(
             &self,
             _token_ids: &[StoredTokenId],
@@ -119,6 +243,10 @@ mod tests {
     struct PanickingPrimaryProvider;
 
     impl ExchangePriceProvider for PanickingPrimaryProvider {
+        fn id(&self) -> &'static str {
+            "panicking_primary"
+        }
+
         async fn fetch_prices(
             &self,
             _token_ids: &[StoredTokenId],
@@ -162,6 +290,17 @@ mod tests {
             price,
             price_24h_change_pct: None,
             market_cap: None,
+            source: None,
+            sources: None,
+            stale: None,
+        }
+    }
+
+    fn quoted(price: f64, provider: &'static str, sources: u32) -> ExchangeData {
+        ExchangeData {
+            source: Some(provider.to_string()),
+            sources: Some(sources),
+            ..data(Some(price))
         }
     }
 
@@ -180,6 +319,9 @@ mod tests {
             price: Some(1.5),
             price_24h_change_pct: None,
             market_cap: None,
+            source: None,
+            sources: None,
+            stale: None,
         };
         assert!(has_valid_price(&d));
     }
@@ -191,6 +333,9 @@ mod tests {
             price: None,
             price_24h_change_pct: None,
             market_cap: None,
+            source: None,
+            sources: None,
+            stale: None,
         };
         assert!(!has_valid_price(&none));
 
@@ -199,6 +344,9 @@ mod tests {
             price: Some(0.0),
             price_24h_change_pct: None,
             market_cap: None,
+            source: None,
+            sources: None,
+            stale: None,
         };
         assert!(!has_valid_price(&zero));
 
@@ -207,6 +355,9 @@ mod tests {
             price: Some(f64::NAN),
             price_24h_change_pct: None,
             market_cap: None,
+            source: None,
+            sources: None,
+            stale: None,
         };
         assert!(!has_valid_price(&nan));
     }
@@ -223,6 +374,9 @@ mod tests {
                 price: Some(1.0),
                 price_24h_change_pct: None,
                 market_cap: None,
+                source: None,
+                sources: None,
+                stale: None,
             },
         );
         let requested = vec![t.clone()];
@@ -243,11 +397,20 @@ mod tests {
         ]));
         let supplementals: Vec<Box<dyn SupplementalPriceProvider>> = vec![supplemental];
 
-        let prices = block_on(fetch_all_prices(&primary, true, &supplementals, &requested));
+        let prices = block_on(fetch_all_prices(
+            &primary,
+            true,
+            &supplementals,
+            &PriceOverrides::default(),
+            &requested,
+        ));
 
         assert_eq!(
             prices,
-            vec![(first, data(Some(1.0))), (second, data(Some(2.0)))]
+            vec![
+                (first, quoted(1.0, "mock", 1)),
+                (second, quoted(2.0, "mock", 1))
+            ]
         );
         assert_eq!(*requested_by_supplemental.borrow(), vec![requested]);
     }
@@ -267,7 +430,13 @@ mod tests {
             MockSupplementalProvider::boxed(Ok(vec![(invalid.clone(), data(Some(2.0)))]));
         let supplementals: Vec<Box<dyn SupplementalPriceProvider>> = vec![supplemental];
 
-        let prices = block_on(fetch_all_prices(&primary, true, &supplementals, &requested));
+        let prices = block_on(fetch_all_prices(
+            &primary,
+            true,
+            &supplementals,
+            &PriceOverrides::default(),
+            &requested,
+        ));
 
         assert_eq!(
             *requested_by_supplemental.borrow(),
@@ -275,7 +444,10 @@ mod tests {
         );
         assert_eq!(
             prices,
-            vec![(valid, data(Some(1.0))), (invalid, data(Some(2.0)))]
+            vec![
+                (valid, quoted(1.0, "mock_primary", 1)),
+                (invalid, quoted(2.0, "mock", 1)),
+            ]
         );
     }
 
@@ -291,9 +463,15 @@ mod tests {
             MockSupplementalProvider::boxed(Err("supplemental unavailable".to_string()));
         let supplementals: Vec<Box<dyn SupplementalPriceProvider>> = vec![supplemental];
 
-        let prices = block_on(fetch_all_prices(&primary, true, &supplementals, &requested));
+        let prices = block_on(fetch_all_prices(
+            &primary,
+            true,
+            &supplementals,
+            &PriceOverrides::default(),
+            &requested,
+        ));
 
-        assert_eq!(prices, vec![(valid, data(Some(1.0)))]);
+        assert_eq!(prices, vec![(valid, quoted(1.0, "mock_primary", 1))]);
         assert_eq!(*requested_by_supplemental.borrow(), vec![vec![missing]]);
     }
 
@@ -313,6 +491,7 @@ mod tests {
             &primary,
             false,
             &supplementals,
+            &PriceOverrides::default(),
             &requested,
         ));
 
@@ -321,7 +500,10 @@ mod tests {
         // ... and the result is sourced entirely from the supplemental, not the primary.
         assert_eq!(
             prices,
-            vec![(first, data(Some(1.0))), (second, data(Some(2.0)))]
+            vec![
+                (first, quoted(1.0, "mock", 1)),
+                (second, quoted(2.0, "mock", 1))
+            ]
         );
     }
 
@@ -338,9 +520,123 @@ mod tests {
             &primary,
             false,
             &supplementals,
+            &PriceOverrides::default(),
             &requested,
         ));
 
         assert!(prices.is_empty());
     }
+
+    #[test]
+    fn fetch_all_prices_median_queries_every_provider_for_every_token() {
+        let first = native_token();
+        let second = icrc_token("ryjl3-tyaaa-aaaaa-aaaba-cai");
+        let requested = vec![first.clone(), second.clone()];
+        let primary = MockPrimaryProvider {
+            result: Ok(vec![(first.clone(), data(Some(100.0)))]),
+        };
+        let (supplemental, requested_by_supplemental) = MockSupplementalProvider::boxed(Ok(vec![
+            (first.clone(), data(Some(102.0))),
+            (second.clone(), data(Some(2.0))),
+        ]));
+        let supplementals: Vec<Box<dyn SupplementalPriceProvider>> = vec![supplemental];
+
+        let prices = block_on(fetch_all_prices_median(
+            &primary,
+            true,
+            &supplementals,
+            &PriceOverrides::default(),
+            &requested,
+            500,
+        ));
+
+        // The supplemental is asked for the token the primary already priced, too.
+        assert_eq!(*requested_by_supplemental.borrow(), vec![requested]);
+        assert_eq!(
+            prices,
+            vec![
+                (first, quoted(101.0, "mock_primary", 2)),
+                (second, quoted(2.0, "mock", 1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn fetch_all_prices_median_drops_tokens_without_consensus() {
+        let agreed = native_token();
+        let disputed = icrc_token("ryjl3-tyaaa-aaaaa-aaaba-cai");
+        let requested = vec![agreed.clone(), disputed.clone()];
+        let primary = MockPrimaryProvider {
+            result: Ok(vec![
+                (agreed.clone(), data(Some(1.0))),
+                (disputed.clone(), data(Some(10.0))),
+            ]),
+        };
+        let (supplemental, _) = MockSupplementalProvider::boxed(Ok(vec![
+            (agreed.clone(), data(Some(1.0))),
+            (disputed, data(Some(20.0))),
+        ]));
+        let supplementals: Vec<Box<dyn SupplementalPriceProvider>> = vec![supplemental];
+
+        let prices = block_on(fetch_all_prices_median(
+            &primary,
+            true,
+            &supplementals,
+            &PriceOverrides::default(),
+            &requested,
+            1_000,
+        ));
+
+        assert_eq!(prices, vec![(agreed, quoted(1.0, "mock_primary", 2))]);
+    }
+
+    #[test]
+    fn fetch_all_prices_does_not_ask_providers_for_overridden_tokens() {
+        let fetched = native_token();
+        let overridden = icrc_token("ryjl3-tyaaa-aaaaa-aaaba-cai");
+        let requested = vec![fetched.clone(), overridden.clone()];
+        let primary = MockPrimaryProvider {
+            result: Err("primary unavailable".to_string()),
+        };
+        let (supplemental, requested_by_supplemental) = MockSupplementalProvider::boxed(Ok(vec![
+            (fetched.clone(), data(Some(1.0))),
+            (overridden.clone(), data(Some(5.0))),
+        ]));
+        let supplementals: Vec<Box<dyn SupplementalPriceProvider>> = vec![supplemental];
+        let overrides = PriceOverrides::new(
+            BTreeMap::from([(overridden.clone(), PriceOverride::Static { price: 2.0 })]),
+            7,
+        );
+
+        let prices = block_on(fetch_all_prices(
+            &primary,
+            true,
+            &supplementals,
+            &overrides,
+            &requested,
+        ));
+
+        assert_eq!(
+            *requested_by_supplemental.borrow(),
+            vec![vec![fetched.clone()]]
+        );
+        assert_eq!(
+            prices,
+            vec![
+                (fetched, quoted(1.0, "mock", 1)),
+                (
+                    overridden,
+                    ExchangeData {
+                        timestamp_ns: 7,
+                        price: Some(2.0),
+                        price_24h_change_pct: None,
+                        market_cap: None,
+                        source: Some(OVERRIDE_SOURCE.to_string()),
+                        sources: None,
+                        stale: None,
+                    }
+                ),
+            ]
+        );
+    }
 }
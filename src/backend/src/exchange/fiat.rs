@@ -0,0 +1,185 @@
+//! USD FX rates used to convert cached exchange rates into other fiat currencies.
+//!
+//! FX rates move far slower than token prices, so they are fetched at most every
+//! [`FX_REFRESH_INTERVAL_NS`] and kept on the heap. After an upgrade the first refresh fetches them
+//! again; until then, new exchange rates are cached without fiat prices.
+
+use std::cell::RefCell;
+
+use shared::types::{
+    exchange::{ExchangeRate, FiatCurrency, FiatPrice},
+    Timestamp,
+};
+
+/// How often USD FX rates are refetched (10 minutes).
+const FX_REFRESH_INTERVAL_NS: u64 = 10 * 60 * 1_000_000_000;
+
+/// FX rates older than this are no longer used to convert prices (1 day).
+const FX_MAX_AGE_NS: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+#[derive(Clone, Debug, PartialEq)]
+struct UsdFxRates {
+    fetched_at_ns: Timestamp,
+    /// Units of each currency per USD.
+    rates: Vec<(FiatCurrency, f64)>,
+}
+
+thread_local! {
+    static USD_FX_RATES: RefCell<Option<UsdFxRates>> = const { RefCell::new(None) };
+}
+
+/// Whether the cached FX rates are missing or due for a refetch at `now`.
+pub(crate) fn needs_refresh(now: Timestamp) -> bool {
+    USD_FX_RATES.with(|cache| {
+        cache
+            .borrow()
+            .as_ref()
+            .is_none_or(|fx| now.saturating_sub(fx.fetched_at_ns) >= FX_REFRESH_INTERVAL_NS)
+    })
+}
+
+/// Caches `rates` (units of each currency per USD) as fetched at `now`.
+pub(crate) fn set_usd_fx_rates(now: Timestamp, rates: Vec<(FiatCurrency, f64)>) {
+    USD_FX_RATES.with(|cache| {
+        *cache.borrow_mut() = Some(UsdFxRates {
+            fetched_at_ns: now,
+            rates,
+        });
+    });
+}
+
+/// `usd_price` in every currency with a valid FX rate.
+fn convert(usd_price: f64, rates: &[(FiatCurrency, f64)]) -> Vec<FiatPrice> {
+    rates
+        .iter()
+        .filter(|(_, rate)| rate.is_finite() && *rate > 0.0)
+        .map(|&(currency, rate)| FiatPrice {
+            currency,
+            price: usd_price * rate,
+        })
+        .collect()
+}
+
+/// `usd_price` converted at the cached FX rates, or `None` without a price or with FX rates older
+/// than [`FX_MAX_AGE_NS`].
+pub(crate) fn fiat_prices(usd_price: Option<f64>, now: Timestamp) -> Option<Vec<FiatPrice>> {
+    let usd_price = usd_price?;
+    USD_FX_RATES.with(|cache| {
+        cache
+            .borrow()
+            .as_ref()
+            .filter(|fx| now.saturating_sub(fx.fetched_at_ns) < FX_MAX_AGE_NS)
+            .map(|fx| convert(usd_price, &fx.rates))
+    })
+}
+
+/// `rate` with only its price in `currency` kept next to USD.
+pub(crate) fn for_currency(rate: ExchangeRate, currency: FiatCurrency) -> ExchangeRate {
+    ExchangeRate {
+        fiat: rate.fiat.map(|prices| {
+            prices
+                .into_iter()
+                .filter(|price| price.currency == currency)
+                .collect()
+        }),
+        ..rate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use shared::types::exchange::ExchangeData;
+
+    use super::*;
+
+    const NOW: Timestamp = 1_000 * FX_MAX_AGE_NS;
+
+    #[test]
+    fn convert_skips_invalid_rates() {
+        let rates = [
+            (FiatCurrency::Eur, 0.5),
+            (FiatCurrency::Chf, 0.0),
+            (FiatCurrency::Jpy, 150.0),
+        ];
+
+        assert_eq!(
+            convert(2.0, &rates),
+            vec![
+                FiatPrice {
+                    currency: FiatCurrency::Eur,
+                    price: 1.0,
+                },
+                FiatPrice {
+                    currency: FiatCurrency::Jpy,
+                    price: 300.0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn fx_rates_refresh_after_the_interval() {
+        assert!(needs_refresh(NOW));
+
+        set_usd_fx_rates(NOW, vec![(FiatCurrency::Eur, 0.5)]);
+
+        assert!(!needs_refresh(NOW + FX_REFRESH_INTERVAL_NS - 1));
+        assert!(needs_refresh(NOW + FX_REFRESH_INTERVAL_NS));
+    }
+
+    #[test]
+    fn fiat_prices_ignore_expired_fx_rates() {
+        assert_eq!(fiat_prices(Some(2.0), NOW), None);
+
+        set_usd_fx_rates(NOW, vec![(FiatCurrency::Eur, 0.5)]);
+
+        assert_eq!(
+            fiat_prices(Some(2.0), NOW + 1),
+            Some(vec![FiatPrice {
+                currency: FiatCurrency::Eur,
+                price: 1.0,
+            }])
+        );
+        assert_eq!(fiat_prices(None, NOW + 1), None);
+        assert_eq!(fiat_prices(Some(2.0), NOW + FX_MAX_AGE_NS), None);
+    }
+
+    #[test]
+    fn for_currency_keeps_only_the_preferred_currency() {
+        let usd = ExchangeData {
+            timestamp_ns: 1,
+            price: Some(2.0),
+            price_24h_change_pct: None,
+            market_cap: None,
+            source: None,
+            sources: None,
+            stale: None,
+        };
+        let rate = ExchangeRate {
+            usd: usd.clone(),
+            fiat: Some(convert(
+                2.0,
+                &[(FiatCurrency::Eur, 0.5), (FiatCurrency::Jpy, 150.0)],
+            )),
+        };
+
+        assert_eq!(
+            for_currency(rate.clone(), FiatCurrency::Jpy),
+            ExchangeRate {
+                usd: usd.clone(),
+                fiat: Some(vec![FiatPrice {
+                    currency: FiatCurrency::Jpy,
+                    price: 300.0,
+                }]),
+            }
+        );
+        assert_eq!(
+            for_currency(rate, FiatCurrency::Usd),
+            ExchangeRate {
+                usd,
+                fiat: Some(vec![]),
+            }
+        );
+    }
+}
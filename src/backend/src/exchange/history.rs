@@ -0,0 +1,337 @@
+//! Bounded OHLC history of the USD prices fetched by the exchange-rate refresh.
+//!
+//! Every fetched price is folded into one hourly and one daily bucket of its token. Each
+//! `(token, resolution)` series keeps a fixed number of buckets; older ones are dropped as new
+//! buckets open.
+
+use std::ops::Bound;
+
+use shared::types::exchange::{ExchangeRateResolution, PriceCandle};
+
+use crate::types::{
+    maps::ExchangeRateHistoryMap,
+    storable::{Candid, ExchangeRateHistoryKey, StoredTokenId},
+};
+
+const NANOS_PER_HOUR: u64 = 3_600 * 1_000_000_000;
+const NANOS_PER_DAY: u64 = 24 * NANOS_PER_HOUR;
+
+/// Number of hourly buckets kept per token (30 days).
+pub const HOURLY_HISTORY_BUCKETS: u64 = 30 * 24;
+/// Number of daily buckets kept per token (2 years).
+pub const DAILY_HISTORY_BUCKETS: u64 = 2 * 365;
+
+const RESOLUTIONS: [ExchangeRateResolution; 2] = [
+    ExchangeRateResolution::Hourly,
+    ExchangeRateResolution::Daily,
+];
+
+/// Key component of `resolution` in [`ExchangeRateHistoryKey`].
+fn resolution_code(resolution: ExchangeRateResolution) -> u8 {
+    match resolution {
+        ExchangeRateResolution::Hourly => 0,
+        ExchangeRateResolution::Daily => 1,
+    }
+}
+
+fn bucket_width_ns(resolution: ExchangeRateResolution) -> u64 {
+    match resolution {
+        ExchangeRateResolution::Hourly => NANOS_PER_HOUR,
+        ExchangeRateResolution::Daily => NANOS_PER_DAY,
+    }
+}
+
+fn retained_buckets(resolution: ExchangeRateResolution) -> u64 {
+    match resolution {
+        ExchangeRateResolution::Hourly => HOURLY_HISTORY_BUCKETS,
+        ExchangeRateResolution::Daily => DAILY_HISTORY_BUCKETS,
+    }
+}
+
+fn bucket_start_ns(timestamp_ns: u64, resolution: ExchangeRateResolution) -> u64 {
+    timestamp_ns - timestamp_ns % bucket_width_ns(resolution)
+}
+
+/// Folds a price observed at `timestamp_ns` into the token's hourly and daily buckets and drops
+/// the buckets that fall out of the retention window.
+///
+/// Samples are expected in chronological order per token: the sample becomes the close of its
+/// buckets.
+pub(crate) fn record_price(
+    history: &mut ExchangeRateHistoryMap,
+    token_id: &StoredTokenId,
+    timestamp_ns: u64,
+    price: f64,
+) {
+    for resolution in RESOLUTIONS {
+        let code = resolution_code(resolution);
+        let bucket_start_ns = bucket_start_ns(timestamp_ns, resolution);
+        let key = ExchangeRateHistoryKey::new(token_id, code, bucket_start_ns);
+
+        let candle = match history.get(&key) {
+            Some(Candid(candle)) => PriceCandle {
+                high: candle.high.max(price),
+                low: candle.low.min(price),
+                close: price,
+                ..candle
+            },
+            None => PriceCandle {
+                timestamp_ns: bucket_start_ns,
+                open: price,
+                high: price,
+                low: price,
+                close: price,
+            },
+        };
+        history.insert(key.clone(), Candid(candle));
+
+        let oldest_kept_ns = bucket_start_ns
+            .saturating_sub((retained_buckets(resolution) - 1) * bucket_width_ns(resolution));
+        let expired: Vec<ExchangeRateHistoryKey> = history
+            .keys_range((
+                Bound::Included(ExchangeRateHistoryKey(key.0.clone(), code, 0)),
+                Bound::Excluded(ExchangeRateHistoryKey(key.0, code, oldest_kept_ns)),
+            ))
+            .collect();
+        for key in expired {
+            history.remove(&key);
+        }
+    }
+}
+
+/// The token's buckets at `resolution` that overlap `[from_ns, to_ns]`, oldest first.
+///
+/// The bucket containing `from_ns` is included. At most the retained number of buckets
+/// ([`HOURLY_HISTORY_BUCKETS`] or [`DAILY_HISTORY_BUCKETS`]) is returned.
+pub(crate) fn get_history(
+    history: &ExchangeRateHistoryMap,
+    token_id: &StoredTokenId,
+    from_ns: u64,
+    to_ns: u64,
+    resolution: ExchangeRateResolution,
+) -> Vec<PriceCandle> {
+    if from_ns > to_ns {
+        return Vec::new();
+    }
+    let code = resolution_code(resolution);
+
+    history
+        .range((
+            Bound::Included(ExchangeRateHistoryKey::new(
+                token_id,
+                code,
+                bucket_start_ns(from_ns, resolution),
+            )),
+            Bound::Included(ExchangeRateHistoryKey::new(token_id, code, to_ns)),
+        ))
+        .map(|entry| entry.value().0)
+        .collect()
+}
+
+/// The close of the token's bucket containing `timestamp_ns`, at the finest resolution that has
+/// one, and that resolution.
+pub(crate) fn price_at(
+    history: &ExchangeRateHistoryMap,
+    token_id: &StoredTokenId,
+    timestamp_ns: u64,
+) -> Option<(f64, ExchangeRateResolution)> {
+    RESOLUTIONS.into_iter().find_map(|resolution| {
+        let key = ExchangeRateHistoryKey::new(
+            token_id,
+            resolution_code(resolution),
+            bucket_start_ns(timestamp_ns, resolution),
+        );
+        history
+            .get(&key)
+            .map(|Candid(candle)| (candle.close, resolution))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use ic_stable_structures::{
+        memory_manager::{MemoryId, MemoryManager},
+        DefaultMemoryImpl,
+    };
+    use pretty_assertions::assert_eq;
+    use shared::types::{
+        exchange::{ExchangeRateResolution, PriceCandle},
+        token_id::TokenId,
+    };
+
+    use super::{
+        get_history, price_at, record_price, DAILY_HISTORY_BUCKETS, HOURLY_HISTORY_BUCKETS,
+        NANOS_PER_DAY, NANOS_PER_HOUR,
+    };
+    use crate::types::{maps::ExchangeRateHistoryMap, storable::StoredTokenId};
+
+    /// 2023-11-14T00:00:00Z, the start of a daily bucket.
+    const DAY_START_NS: u64 = 1_699_920_000 * 1_000_000_000;
+
+    fn setup() -> ExchangeRateHistoryMap {
+        let memory_manager = MemoryManager::init(DefaultMemoryImpl::default());
+        ExchangeRateHistoryMap::init(memory_manager.get(MemoryId::new(0)))
+    }
+
+    fn icp() -> StoredTokenId {
+        StoredTokenId(TokenId::IcpNative)
+    }
+
+    fn candle(timestamp_ns: u64, open: f64, high: f64, low: f64, close: f64) -> PriceCandle {
+        PriceCandle {
+            timestamp_ns,
+            open,
+            high,
+            low,
+            close,
+        }
+    }
+
+    #[test]
+    fn samples_fold_into_hourly_and_daily_candles() {
+        let mut history = setup();
+
+        record_price(&mut history, &icp(), DAY_START_NS + 60_000_000_000, 5.0);
+        record_price(&mut history, &icp(), DAY_START_NS + 120_000_000_000, 7.0);
+        record_price(&mut history, &icp(), DAY_START_NS + 180_000_000_000, 4.0);
+        record_price(&mut history, &icp(), DAY_START_NS + NANOS_PER_HOUR, 6.0);
+
+        assert_eq!(
+            get_history(
+                &history,
+                &icp(),
+                DAY_START_NS,
+                DAY_START_NS + NANOS_PER_DAY,
+                ExchangeRateResolution::Hourly,
+            ),
+            vec![
+                candle(DAY_START_NS, 5.0, 7.0, 4.0, 4.0),
+                candle(DAY_START_NS + NANOS_PER_HOUR, 6.0, 6.0, 6.0, 6.0),
+            ]
+        );
+        assert_eq!(
+            get_history(
+                &history,
+                &icp(),
+                DAY_START_NS,
+                DAY_START_NS + NANOS_PER_DAY,
+                ExchangeRateResolution::Daily,
+            ),
+            vec![candle(DAY_START_NS, 5.0, 7.0, 4.0, 6.0)]
+        );
+    }
+
+    #[test]
+    fn range_includes_bucket_containing_from() {
+        let mut history = setup();
+        for hour in 0..5 {
+            record_price(
+                &mut history,
+                &icp(),
+                DAY_START_NS + hour * NANOS_PER_HOUR,
+                1.0,
+            );
+        }
+
+        let candles = get_history(
+            &history,
+            &icp(),
+            DAY_START_NS + NANOS_PER_HOUR + 1,
+            DAY_START_NS + 3 * NANOS_PER_HOUR,
+            ExchangeRateResolution::Hourly,
+        );
+
+        let starts: Vec<u64> = candles.iter().map(|c| c.timestamp_ns).collect();
+        assert_eq!(
+            starts,
+            vec![
+                DAY_START_NS + NANOS_PER_HOUR,
+                DAY_START_NS + 2 * NANOS_PER_HOUR,
+                DAY_START_NS + 3 * NANOS_PER_HOUR,
+            ]
+        );
+    }
+
+    #[test]
+    fn inverted_range_is_empty() {
+        let mut history = setup();
+        record_price(&mut history, &icp(), DAY_START_NS, 1.0);
+
+        assert_eq!(
+            get_history(
+                &history,
+                &icp(),
+                DAY_START_NS + 1,
+                DAY_START_NS,
+                ExchangeRateResolution::Daily,
+            ),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn history_is_per_token() {
+        let mut history = setup();
+        let btc = StoredTokenId(TokenId::BtcNativeMainnet);
+        record_price(&mut history, &icp(), DAY_START_NS, 5.0);
+        record_price(&mut history, &btc, DAY_START_NS, 90_000.0);
+
+        let candles = get_history(&history, &btc, 0, u64::MAX, ExchangeRateResolution::Daily);
+
+        assert_eq!(
+            candles,
+            vec![candle(DAY_START_NS, 90_000.0, 90_000.0, 90_000.0, 90_000.0)]
+        );
+    }
+
+    #[test]
+    fn old_buckets_are_dropped_past_retention() {
+        let mut history = setup();
+        let hours = HOURLY_HISTORY_BUCKETS + 5;
+        for hour in 0..hours {
+            record_price(
+                &mut history,
+                &icp(),
+                DAY_START_NS + hour * NANOS_PER_HOUR,
+                1.0,
+            );
+        }
+
+        let hourly = get_history(
+            &history,
+            &icp(),
+            0,
+            u64::MAX,
+            ExchangeRateResolution::Hourly,
+        );
+        let daily = get_history(&history, &icp(), 0, u64::MAX, ExchangeRateResolution::Daily);
+
+        assert_eq!(hourly.len() as u64, HOURLY_HISTORY_BUCKETS);
+        assert_eq!(hourly[0].timestamp_ns, DAY_START_NS + 5 * NANOS_PER_HOUR);
+        assert_eq!(
+            daily.len() as u64,
+            hours.div_ceil(24).min(DAILY_HISTORY_BUCKETS)
+        );
+    }
+
+    #[test]
+    fn price_at_prefers_hourly_then_daily_close() {
+        let mut history = setup();
+        record_price(&mut history, &icp(), DAY_START_NS, 5.0);
+        record_price(&mut history, &icp(), DAY_START_NS + 60_000_000_000, 6.0);
+        record_price(&mut history, &icp(), DAY_START_NS + 2 * NANOS_PER_HOUR, 7.0);
+
+        assert_eq!(
+            price_at(&history, &icp(), DAY_START_NS + 120_000_000_000),
+            Some((6.0, ExchangeRateResolution::Hourly))
+        );
+        assert_eq!(
+            price_at(&history, &icp(), DAY_START_NS + NANOS_PER_HOUR),
+            Some((7.0, ExchangeRateResolution::Daily))
+        );
+        assert_eq!(
+            price_at(&history, &icp(), DAY_START_NS + NANOS_PER_DAY),
+            None
+        );
+    }
+}
@@ -1,4 +1,10 @@
+pub(crate) mod aggregation;
+pub(crate) mod alerts;
+pub(crate) mod certification;
 mod composite;
+pub(crate) mod fiat;
+pub(crate) mod history;
+pub(crate) mod overrides;
 pub(crate) mod provider;
 mod providers;
 mod supplemental;
@@ -8,16 +14,22 @@ use std::{cell::Cell, time::Duration};
 use ic_cdk::api::time;
 use ic_cdk_timers::{set_timer, set_timer_interval};
 use shared::types::{
+    custom_token::{ChainId, ErcTokenId},
     exchange::{ExchangeData, ExchangeError, ExchangeRate},
+    network::evm_chains::EVM_CHAINS,
     token_id::TokenId,
 };
 
 use crate::{
     exchange::{
-        composite::fetch_all_prices,
+        composite::{fetch_all_prices, fetch_all_prices_median},
+        overrides::PriceOverrides,
         providers::{
             coingecko::{is_priceable_token_id, CoinGeckoProvider},
+            erc4626::{self, AlchemyRpcClient},
             icpswap::IcpSwapProvider,
+            icpswap_pool::IcpSwapPoolProvider,
+            jupiter::JupiterProvider,
         },
         supplemental::SupplementalPriceProvider,
     },
@@ -27,6 +39,7 @@ use crate::{
         storable::{Candid, StoredTokenId},
         StoredPrincipal,
     },
+    utils::rate_limiter::PRICE_ALERT_TRIGGER_RATE_LIMITER,
 };
 
 /// How often exchange rates are refreshed (1 minute).
@@ -56,6 +69,14 @@ const PRICE_FRESHNESS_GRACE_NS: u64 = (PRICE_REFRESH_INTERVAL_SEC / 2) * 1_000_0
 /// honours the contract that prices are at most this many seconds old.
 pub const PRICE_STALENESS_THRESHOLD_SEC: u64 = 2 * 60;
 
+/// Tokens refreshed only because they have price alerts are refreshed at most this often
+/// (15 minutes), so that alerts alone keep outcall costs well below those of tokens in use.
+const PRICE_ALERT_REFRESH_INTERVAL_SEC: u64 = 15 * 60;
+
+/// Upper bound on the number of tokens refreshed per tick only because they have price alerts.
+/// The ones with the oldest cached rates go first.
+const MAX_ALERT_TOKENS_PER_REFRESH: usize = 20;
+
 /// Safety timeout for the cross-refresh in-flight guard. If a spawned refresh
 /// traps before releasing its lock, future ticks can recover instead of
 /// permanently serving stale/missing rates. Set to 5x the refresh interval.
@@ -67,18 +88,19 @@ pub(crate) struct RefreshLock {
     started_at_ns: u64,
 }
 
-/// Native tokens whose prices are always fetched, regardless of user activity.
+/// Native tokens whose prices are always fetched, regardless of user activity: the native token
+/// of every EVM chain in [`EVM_CHAINS`] with a `CoinGecko` coin, plus ICP, SOL and BTC.
 fn native_token_ids() -> Vec<StoredTokenId> {
-    vec![
-        StoredTokenId(TokenId::EvmNative(1)),
-        StoredTokenId(TokenId::EvmNative(56)),
-        StoredTokenId(TokenId::EvmNative(137)),
-        StoredTokenId(TokenId::EvmNative(8453)),
-        StoredTokenId(TokenId::EvmNative(42161)),
-        StoredTokenId(TokenId::IcpNative),
-        StoredTokenId(TokenId::SolNativeMainnet),
-        StoredTokenId(TokenId::BtcNativeMainnet),
-    ]
+    EVM_CHAINS
+        .iter()
+        .filter(|chain| chain.coingecko_native_coin.is_some())
+        .map(|chain| StoredTokenId(TokenId::EvmNative(chain.chain_id())))
+        .chain([
+            StoredTokenId(TokenId::IcpNative),
+            StoredTokenId(TokenId::SolNativeMainnet),
+            StoredTokenId(TokenId::BtcNativeMainnet),
+        ])
+        .collect()
 }
 
 thread_local! {
@@ -230,6 +252,15 @@ fn exchange_rate_is_fresh_enough(rate: &ExchangeRate, freshness_floor_ns: u64) -
     rate.usd.timestamp_ns >= freshness_floor_ns
 }
 
+/// `rate` with [`ExchangeData::stale`] set for a read at `now`.
+pub(crate) fn with_staleness(mut rate: ExchangeRate, now: u64) -> ExchangeRate {
+    rate.usd.stale = Some(!exchange_rate_is_fresh_enough(
+        &rate,
+        staleness_floor_ns(now),
+    ));
+    rate
+}
+
 fn exchange_rate_is_missing_or_older_than(
     rate: Option<&ExchangeRate>,
     freshness_floor_ns: u64,
@@ -252,6 +283,31 @@ fn tokens_missing_or_older_than(
         .collect()
 }
 
+/// The tokens with price alerts whose cached rate is missing or older than
+/// [`PRICE_ALERT_REFRESH_INTERVAL_SEC`], oldest first, at most [`MAX_ALERT_TOKENS_PER_REFRESH`].
+fn alert_tokens_due_for_refresh(
+    alert_token_ids: &[StoredTokenId],
+    mut cached_rate: impl FnMut(&StoredTokenId) -> Option<ExchangeRate>,
+    now: u64,
+) -> Vec<StoredTokenId> {
+    let floor_ns = now.saturating_sub(PRICE_ALERT_REFRESH_INTERVAL_SEC * NANOS_PER_SEC);
+    let mut due: Vec<(Option<u64>, StoredTokenId)> = alert_token_ids
+        .iter()
+        .filter_map(|token_id| {
+            let timestamp_ns = cached_rate(token_id).map(|rate| rate.usd.timestamp_ns);
+            timestamp_ns
+                .is_none_or(|timestamp_ns| timestamp_ns < floor_ns)
+                .then(|| (timestamp_ns, token_id.clone()))
+        })
+        .collect();
+    // `None` sorts first, so tokens without a rate go before stale ones.
+    due.sort_unstable();
+    due.into_iter()
+        .take(MAX_ALERT_TOKENS_PER_REFRESH)
+        .map(|(_, token_id)| token_id)
+        .collect()
+}
+
 fn refresh_candidates(
     active_custom_tokens: impl IntoIterator<Item = StoredTokenId>,
     include_natives: bool,
@@ -290,14 +346,77 @@ pub(crate) fn start_exchange_rate_timer() {
 }
 
 fn update_price(token_id: &StoredTokenId, exchange_data: &ExchangeData) {
+    let now = time();
     mutate_state(|s| {
-        s.exchange_rates.insert(
-            token_id.clone(),
-            Candid(ExchangeRate {
-                usd: exchange_data.clone(),
-            }),
+        // Providers keep reporting the same `last_updated_at` until their price moves, so only
+        // samples newer than the cached one are added to the history.
+        let previous_ns = s
+            .exchange_rates
+            .get(token_id)
+            .map(|rate| rate.0.usd.timestamp_ns);
+        if let Some(price) = exchange_data.price {
+            if previous_ns.is_none_or(|previous_ns| exchange_data.timestamp_ns > previous_ns) {
+                history::record_price(
+                    &mut s.exchange_rate_history,
+                    token_id,
+                    exchange_data.timestamp_ns,
+                    price,
+                );
+            }
+        }
+        let rate = Candid(ExchangeRate {
+            usd: exchange_data.clone(),
+            fiat: fiat::fiat_prices(exchange_data.price, now),
+        });
+        certification::certify_exchange_rate(token_id, &rate);
+        s.exchange_rates.insert(token_id.clone(), rate);
+    });
+
+    if exchange_data.price.is_some() {
+        evaluate_price_alerts(token_id.clone(), None);
+    }
+}
+
+/// Evaluates the next batch of the price alerts of `token_id`, following `after`, against its
+/// stored rate, and schedules the following batch, if any.
+fn evaluate_price_alerts(token_id: StoredTokenId, after: Option<(StoredPrincipal, u64)>) {
+    let now = time();
+    let resume_after = mutate_state(|s| {
+        let data = s.exchange_rates.get(&token_id)?.0.usd;
+        let price_usd = data.price?;
+        let change_24h_pct = alerts::change_24h_pct(&s.exchange_rate_history, &token_id, &data);
+        let evaluation = alerts::evaluate_alerts(
+            &mut s.price_alerts,
+            &s.price_alerts_by_token,
+            &mut s.triggered_price_alerts,
+            &token_id,
+            alerts::ObservedPrice {
+                price_usd,
+                change_24h_pct,
+                timestamp_ns: now,
+            },
+            after,
+            |principal| {
+                PRICE_ALERT_TRIGGER_RATE_LIMITER
+                    .with(|limiter| limiter.check_at(principal, now))
+                    .is_ok()
+            },
         );
+        if evaluation.triggered > 0 {
+            ic_cdk::println!(
+                "Triggered {} price alerts for {:?}",
+                evaluation.triggered,
+                token_id.0
+            );
+        }
+        evaluation.resume_after
     });
+
+    if let Some(after) = resume_after {
+        set_timer(Duration::ZERO, async move {
+            evaluate_price_alerts(token_id, Some(after));
+        });
+    }
 }
 
 /// Per-provider code-level kill-switches. These are hardcoded `const`s by design: they are flipped
@@ -307,6 +426,8 @@ fn update_price(token_id: &StoredTokenId, exchange_data: &ExchangeData) {
 /// wins: when refresh is off, neither provider runs regardless of these flags.
 const COINGECKO_PROVIDER_ENABLED: bool = true;
 const ICPSWAP_PROVIDER_ENABLED: bool = false;
+const ICPSWAP_POOL_PROVIDER_ENABLED: bool = true;
+const JUPITER_PROVIDER_ENABLED: bool = true;
 
 /// Ordered supplemental sources that run after `CoinGecko` for tokens still missing a valid USD
 /// price.
@@ -315,11 +436,24 @@ const ICPSWAP_PROVIDER_ENABLED: bool = false;
 /// variant you support), place it under `exchange/providers/`, and append `Box::new(...)` here in
 /// priority order (first match wins; later providers only see still-missing tokens).
 fn supplemental_price_providers(replicated: bool) -> Vec<Box<dyn SupplementalPriceProvider>> {
+    let mut providers: Vec<Box<dyn SupplementalPriceProvider>> = Vec::new();
+    if ICPSWAP_POOL_PROVIDER_ENABLED {
+        // ICP-quoted pools are valued at the cached ICP rate, refreshed alongside the other
+        // natives on every refresh.
+        let icp_usd_price = read_state(|s| {
+            s.exchange_rates
+                .get(&StoredTokenId(TokenId::IcpNative))
+                .and_then(|rate| rate.0.usd.price)
+        });
+        providers.push(Box::new(IcpSwapPoolProvider::new(icp_usd_price)));
+    }
     if ICPSWAP_PROVIDER_ENABLED {
-        vec![Box::new(IcpSwapProvider::new(replicated))]
-    } else {
-        Vec::new()
+        providers.push(Box::new(IcpSwapProvider::new(replicated)));
+    }
+    if JUPITER_PROVIDER_ENABLED {
+        providers.push(Box::new(JupiterProvider::new(replicated)));
     }
+    providers
 }
 
 /// Whether exchange-rate HTTP outcalls are sent *replicated* (through consensus, every replica
@@ -392,18 +526,81 @@ pub(crate) async fn fetch_and_update_prices(
     let provider = CoinGeckoProvider::new(api_key, replicated);
     let supplementals = supplemental_price_providers(replicated);
 
-    let prices = fetch_all_prices(
-        &provider,
-        COINGECKO_PROVIDER_ENABLED,
-        &supplementals,
-        token_ids,
-    )
-    .await;
+    // ERC-4626 vault shares are priced from their underlying ERC-20, so the underlying assets are
+    // fetched along with the requested tokens. Without an Alchemy key vaults stay unpriced.
+    let vaults: Vec<(ErcTokenId, ChainId)> = token_ids
+        .iter()
+        .filter_map(|StoredTokenId(token_id)| match token_id {
+            TokenId::Erc4626(address, chain_id) => Some((address.clone(), *chain_id)),
+            _ => None,
+        })
+        .collect();
+    let rpc = with_api_keys(|keys| keys.alchemy_api_key.clone())
+        .filter(|_| !vaults.is_empty())
+        .map(|key| AlchemyRpcClient::new(key, replicated));
+    let mut token_ids = token_ids.to_vec();
+    if let Some(rpc) = &rpc {
+        erc4626::resolve_vaults(rpc, &vaults).await;
+        token_ids.extend(erc4626::underlying_token_ids(&vaults));
+        token_ids.sort_unstable();
+        token_ids.dedup();
+    }
+    let token_ids = token_ids.as_slice();
+    let overrides = read_state(|s| PriceOverrides::load(&s.price_overrides, token_ids, time()));
+
+    let prices = match with_api_keys(|keys| keys.exchange_rate_max_deviation_bps) {
+        Some(max_deviation_bps) => {
+            fetch_all_prices_median(
+                &provider,
+                COINGECKO_PROVIDER_ENABLED,
+                &supplementals,
+                &overrides,
+                token_ids,
+                max_deviation_bps,
+            )
+            .await
+        }
+        None => {
+            fetch_all_prices(
+                &provider,
+                COINGECKO_PROVIDER_ENABLED,
+                &supplementals,
+                &overrides,
+                token_ids,
+            )
+            .await
+        }
+    };
+
+    // FX rates come from CoinGecko too, so they share its kill-switch.
+    if COINGECKO_PROVIDER_ENABLED && fiat::needs_refresh(time()) {
+        match provider.fetch_usd_fx_rates().await {
+            Ok(rates) => fiat::set_usd_fx_rates(time(), rates),
+            Err(err) => ic_cdk::println!("USD FX rate fetch failed: {err}"),
+        }
+    }
 
     for (token_id, exchange_data) in prices {
         update_price(&token_id, &exchange_data);
     }
 
+    if let Some(rpc) = &rpc {
+        let freshness_floor_ns = staleness_floor_ns(time());
+        let vault_prices = erc4626::price_vaults(rpc, &vaults, |underlying| {
+            read_state(|s| {
+                s.exchange_rates.get(underlying).and_then(|rate| {
+                    exchange_rate_is_fresh_enough(&rate.0, freshness_floor_ns).then_some(rate.0.usd)
+                })
+            })
+        })
+        .await;
+        for (token_id, exchange_data) in vault_prices {
+            if !overrides.contains(&token_id) {
+                update_price(&token_id, &exchange_data);
+            }
+        }
+    }
+
     Ok(())
 }
 
@@ -411,8 +608,8 @@ pub(crate) async fn fetch_and_update_prices(
 /// in the form expected by the price-fetch helpers.
 ///
 /// "Priceable" means: the token variant + chain combination is something we
-/// can ever fetch a USD rate for via the configured providers. NFT
-/// standards, ERC-4626 vaults, testnets, and EVM chains we don't have a
+/// can ever fetch or derive a USD rate for via the configured providers. NFT
+/// standards, testnets, and EVM chains we don't have a
 /// `CoinGecko` mapping for are filtered out so they don't bloat the request
 /// payload or the activity map. The result is the union of:
 ///
@@ -460,36 +657,49 @@ pub(crate) fn custom_tokens_to_mark(tokens: &[StoredTokenId]) -> Vec<TokenId> {
 }
 
 /// Single-pass read of the cache for `token_ids`. Returns:
-/// - the per-token snapshot in input order — `None` when the cache has no entry or it is older than
-///   [`PRICE_STALENESS_THRESHOLD_SEC`] seconds — and
-/// - the subset whose snapshot entry is `None`, i.e. the tokens that need a refresh outcall.
+/// - the per-token snapshot in input order — `None` when the cache has no entry, and otherwise the
+///   cached rate with `stale` set when it is older than [`PRICE_STALENESS_THRESHOLD_SEC`] seconds —
+///   and
+/// - the tokens that need a refresh outcall: those without an entry or with a stale one.
 ///
-/// The two outputs are exact complements, so `get_exchange_rates` derives both
-/// from one state borrow and one Candid decode per token. (The previous
-/// two-function form read and decoded the cache twice per call: once to find
-/// the stale set, once to build the snapshot.)
+/// `get_exchange_rates` derives both from one state borrow and one Candid decode per token. (The
+/// previous two-function form read and decoded the cache twice per call: once to find the stale
+/// set, once to build the snapshot.)
 pub(crate) fn snapshot_and_stale(
     token_ids: Vec<StoredTokenId>,
 ) -> (Vec<(TokenId, Option<ExchangeRate>)>, Vec<StoredTokenId>) {
-    let freshness_floor_ns = staleness_floor_ns(time());
+    let now = time();
 
     read_state(|s| {
-        let mut snapshot = Vec::with_capacity(token_ids.len());
-        let mut stale = Vec::new();
+        snapshot_of(
+            token_ids,
+            |token_id| s.exchange_rates.get(token_id).map(|c| c.0),
+            now,
+        )
+    })
+}
 
-        for stored in token_ids {
-            let rate = s.exchange_rates.get(&stored).and_then(|c| {
-                exchange_rate_is_fresh_enough(&c.0, freshness_floor_ns).then_some(c.0)
-            });
+fn snapshot_of(
+    token_ids: Vec<StoredTokenId>,
+    cached_rate: impl Fn(&StoredTokenId) -> Option<ExchangeRate>,
+    now: u64,
+) -> (Vec<(TokenId, Option<ExchangeRate>)>, Vec<StoredTokenId>) {
+    let mut snapshot = Vec::with_capacity(token_ids.len());
+    let mut stale = Vec::new();
 
-            if rate.is_none() {
-                stale.push(stored.clone());
-            }
-            snapshot.push((stored.0, rate));
+    for stored in token_ids {
+        let rate = cached_rate(&stored).map(|rate| with_staleness(rate, now));
+
+        if rate
+            .as_ref()
+            .is_none_or(|rate| rate.usd.stale == Some(true))
+        {
+            stale.push(stored.clone());
         }
+        snapshot.push((stored.0, rate));
+    }
 
-        (snapshot, stale)
-    })
+    (snapshot, stale)
 }
 
 pub(crate) async fn refresh_exchange_rates() -> Result<(), ExchangeError> {
@@ -510,13 +720,23 @@ pub(crate) async fn refresh_exchange_rates() -> Result<(), ExchangeError> {
     let tokens_to_fetch = refresh_candidates(active_custom_tokens, include_natives);
 
     let freshness_floor_ns = now.saturating_sub(PRICE_FRESHNESS_GRACE_NS);
-    let tokens_to_fetch = read_state(|s| {
-        tokens_missing_or_older_than(
+    let mut tokens_to_fetch = read_state(|s| {
+        let mut tokens_to_fetch = tokens_missing_or_older_than(
             &tokens_to_fetch,
             |token_id| s.exchange_rates.get(token_id).map(|rate| rate.0),
             freshness_floor_ns,
-        )
+        );
+        // Tokens with price alerts are refreshed even while nobody is requesting rates, so that
+        // the alerts can trigger while their owners are away, but at a lower cadence.
+        tokens_to_fetch.extend(alert_tokens_due_for_refresh(
+            &alerts::alert_token_ids(&s.price_alerts_by_token),
+            |token_id| s.exchange_rates.get(token_id).map(|rate| rate.0),
+            now,
+        ));
+        tokens_to_fetch
     });
+    tokens_to_fetch.sort_unstable();
+    tokens_to_fetch.dedup();
 
     fetch_and_update_prices(&tokens_to_fetch).await
 }
@@ -723,10 +943,57 @@ mod tests {
                 price: Some(1.0),
                 price_24h_change_pct: None,
                 market_cap: None,
+                source: None,
+                sources: None,
+                stale: None,
             },
+            fiat: None,
         }
     }
 
+    #[test]
+    fn with_staleness_flags_rates_past_the_staleness_threshold() {
+        let now = 1_000 * NANOS_PER_SEC;
+        let threshold_ns = PRICE_STALENESS_THRESHOLD_SEC * NANOS_PER_SEC;
+
+        let fresh = with_staleness(exchange_rate(now - threshold_ns), now);
+        let stale = with_staleness(exchange_rate(now - threshold_ns - 1), now);
+
+        assert_eq!(fresh.usd.stale, Some(false));
+        assert_eq!(stale.usd.stale, Some(true));
+    }
+
+    #[test]
+    fn snapshot_flags_stale_cached_rates_and_queues_them_for_refresh() {
+        let now = 1_000 * NANOS_PER_SEC;
+        let threshold_ns = PRICE_STALENESS_THRESHOLD_SEC * NANOS_PER_SEC;
+        let fresh = custom_token(1);
+        let stale = custom_token(2);
+        let missing = custom_token(3);
+
+        let (snapshot, to_refresh) = snapshot_of(
+            vec![fresh.clone(), stale.clone(), missing.clone()],
+            |token_id| {
+                if *token_id == fresh {
+                    Some(exchange_rate(now - threshold_ns))
+                } else if *token_id == stale {
+                    Some(exchange_rate(now - threshold_ns - 1))
+                } else {
+                    None
+                }
+            },
+            now,
+        );
+
+        let flags: Vec<Option<bool>> = snapshot
+            .iter()
+            .map(|(_, rate)| rate.as_ref().and_then(|rate| rate.usd.stale))
+            .collect();
+        assert_eq!(flags, vec![Some(false), Some(true), None]);
+        assert!(snapshot[2].1.is_none());
+        assert_eq!(to_refresh, vec![stale, missing]);
+    }
+
     #[test]
     fn active_custom_tokens_for_refresh_uses_strict_activity_window() {
         let now = 1_000 * NANOS_PER_SEC;
@@ -811,6 +1078,49 @@ mod tests {
         assert_eq!(due, vec![missing, stale]);
     }
 
+    #[test]
+    fn alert_tokens_are_refreshed_at_a_lower_cadence_oldest_first() {
+        let now = 10_000 * NANOS_PER_SEC;
+        let floor = now - PRICE_ALERT_REFRESH_INTERVAL_SEC * NANOS_PER_SEC;
+        let missing = custom_token(1);
+        let oldest = custom_token(2);
+        let stale = custom_token(3);
+        let recent = custom_token(4);
+        let tokens = vec![
+            stale.clone(),
+            recent.clone(),
+            oldest.clone(),
+            missing.clone(),
+        ];
+        let cached_rate = |token_id: &StoredTokenId| {
+            if *token_id == oldest {
+                Some(exchange_rate(floor - 2))
+            } else if *token_id == stale {
+                Some(exchange_rate(floor - 1))
+            } else if *token_id == recent {
+                // Too old for an active token, recent enough for an alert-only one.
+                Some(exchange_rate(now - 2 * PRICE_FRESHNESS_GRACE_NS))
+            } else {
+                None
+            }
+        };
+
+        let due = alert_tokens_due_for_refresh(&tokens, cached_rate, now);
+
+        assert_eq!(due, vec![missing, oldest, stale]);
+    }
+
+    #[test]
+    fn alert_tokens_refreshed_per_tick_are_capped() {
+        let tokens: Vec<StoredTokenId> = (0..=MAX_ALERT_TOKENS_PER_REFRESH)
+            .map(|seed| custom_token(u8::try_from(seed).unwrap()))
+            .collect();
+
+        let due = alert_tokens_due_for_refresh(&tokens, |_| None, NANOS_PER_SEC);
+
+        assert_eq!(due.len(), MAX_ALERT_TOKENS_PER_REFRESH);
+    }
+
     #[test]
     fn staleness_floor_uses_two_minute_caller_freshness_contract() {
         let now = 1_000 * NANOS_PER_SEC;
@@ -828,7 +1138,7 @@ mod tests {
     }
 
     #[test]
-    fn caller_staleness_floor_aligns_refresh_trigger_with_snapshot_filter() {
+    fn caller_staleness_floor_aligns_refresh_trigger_with_snapshot_staleness() {
         let now = 1_000 * NANOS_PER_SEC;
         let floor = staleness_floor_ns(now);
         let missing = custom_token(1);
@@ -855,26 +1165,17 @@ mod tests {
         };
 
         let due = tokens_missing_or_older_than(&tokens, cached_rate, floor);
-        let snapshot: Vec<(StoredTokenId, Option<ExchangeRate>)> = tokens
-            .iter()
-            .map(|token_id| {
-                (
-                    token_id.clone(),
-                    cached_rate(token_id).and_then(|rate| {
-                        exchange_rate_is_fresh_enough(&rate, floor).then_some(rate)
-                    }),
-                )
-            })
-            .collect();
+        let (snapshot, to_refresh) = snapshot_of(tokens, cached_rate, now);
 
         assert_eq!(due, vec![missing.clone(), stale.clone()]);
+        assert_eq!(to_refresh, due);
         assert_eq!(
             snapshot,
             vec![
-                (missing, None),
-                (stale, None),
-                (boundary, Some(exchange_rate(floor))),
-                (fresh, Some(exchange_rate(now)))
+                (missing.0, None),
+                (stale.0, Some(with_staleness(exchange_rate(floor - 1), now))),
+                (boundary.0, Some(with_staleness(exchange_rate(floor), now))),
+                (fresh.0, Some(with_staleness(exchange_rate(now), now)))
             ]
         );
     }
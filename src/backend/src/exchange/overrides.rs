@@ -0,0 +1,320 @@
+//! Controller-set price overrides.
+//!
+//! An override pins a token's price when the providers cannot be relied on for it, either to a
+//! static USD price or to the price of another token (a peg, e.g. ckBTC = BTC). Overridden tokens
+//! are never sent to the providers; the targets of their pegs are fetched in their place. Pegs are
+//! one level deep: a peg target never has an override of its own.
+
+use std::collections::BTreeMap;
+
+use shared::types::{
+    exchange::{ExchangeData, PriceOverride, SetPriceOverrideError},
+    Timestamp,
+};
+
+use crate::{
+    exchange::providers::coingecko::is_priceable_token_id,
+    types::{
+        maps::PriceOverrideMap,
+        storable::{Candid, StoredTokenId},
+    },
+};
+
+/// [`ExchangeData::source`] of a price set by a static override.
+pub(crate) const OVERRIDE_SOURCE: &str = "override";
+/// [`ExchangeData::source`] of a price copied from a peg target.
+pub(crate) const PEG_SOURCE: &str = "peg";
+
+/// The overrides applying to one price refresh.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct PriceOverrides {
+    overrides: BTreeMap<StoredTokenId, PriceOverride>,
+    /// Timestamp given to static prices.
+    now_ns: Timestamp,
+}
+
+impl PriceOverrides {
+    /// The stored overrides of `token_ids`.
+    pub(crate) fn load(
+        map: &PriceOverrideMap,
+        token_ids: &[StoredTokenId],
+        now_ns: Timestamp,
+    ) -> Self {
+        let overrides = token_ids
+            .iter()
+            .filter_map(|token_id| map.get(token_id).map(|o| (token_id.clone(), o.0)))
+            .collect();
+        Self { overrides, now_ns }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn new(
+        overrides: BTreeMap<StoredTokenId, PriceOverride>,
+        now_ns: Timestamp,
+    ) -> Self {
+        Self { overrides, now_ns }
+    }
+
+    pub(crate) fn contains(&self, token_id: &StoredTokenId) -> bool {
+        self.overrides.contains_key(token_id)
+    }
+
+    /// The tokens to ask the providers for: `token_ids` without an override, plus the targets of
+    /// their pegs.
+    pub(crate) fn provider_token_ids(&self, token_ids: &[StoredTokenId]) -> Vec<StoredTokenId> {
+        let mut provider_token_ids: Vec<StoredTokenId> = token_ids
+            .iter()
+            .filter(|token_id| !self.overrides.contains_key(token_id))
+            .cloned()
+            .collect();
+        for o in self.overrides.values() {
+            if let PriceOverride::Peg { token_id } = o {
+                let target = StoredTokenId(token_id.clone());
+                if !provider_token_ids.contains(&target) {
+                    provider_token_ids.push(target);
+                }
+            }
+        }
+        provider_token_ids
+    }
+
+    /// `provider_prices` with the overridden prices added.
+    ///
+    /// A pegged token takes its target's provider price, without the market cap; it stays unpriced
+    /// when the target has none.
+    pub(crate) fn apply(
+        &self,
+        mut provider_prices: Vec<(StoredTokenId, ExchangeData)>,
+    ) -> Vec<(StoredTokenId, ExchangeData)> {
+        let overridden: Vec<(StoredTokenId, ExchangeData)> = self
+            .overrides
+            .iter()
+            .filter_map(|(token_id, o)| {
+                let data = match o {
+                    PriceOverride::Static { price } => ExchangeData {
+                        timestamp_ns: self.now_ns,
+                        price: Some(*price),
+                        price_24h_change_pct: None,
+                        market_cap: None,
+                        source: Some(OVERRIDE_SOURCE.to_string()),
+                        sources: None,
+                        stale: None,
+                    },
+                    PriceOverride::Peg { token_id: target } => {
+                        let (_, target_data) =
+                            provider_prices.iter().find(|(id, _)| id.0 == *target)?;
+                        ExchangeData {
+                            market_cap: None,
+                            source: Some(PEG_SOURCE.to_string()),
+                            ..target_data.clone()
+                        }
+                    }
+                };
+                Some((token_id.clone(), data))
+            })
+            .collect();
+
+        provider_prices.retain(|(token_id, _)| !self.overrides.contains_key(token_id));
+        provider_prices.extend(overridden);
+        provider_prices
+    }
+}
+
+/// Sets or, with `None`, removes the override of `token_id`.
+///
+/// # Errors
+///
+/// - [`SetPriceOverrideError::InvalidPrice`] for a static price that is not finite and positive.
+/// - [`SetPriceOverrideError::InvalidPegTarget`] when `token_id` is the target of a peg, or the
+///   new peg would not be one level deep or targets a token the providers cannot price.
+pub(crate) fn set_price_override(
+    map: &mut PriceOverrideMap,
+    token_id: StoredTokenId,
+    price_override: Option<PriceOverride>,
+) -> Result<(), SetPriceOverrideError> {
+    let Some(price_override) = price_override else {
+        map.remove(&token_id);
+        return Ok(());
+    };
+
+    let is_peg_target = map.iter().any(|entry| {
+        matches!(&entry.value().0, PriceOverride::Peg { token_id: target } if *target == token_id.0)
+    });
+    if is_peg_target {
+        return Err(SetPriceOverrideError::InvalidPegTarget);
+    }
+
+    match &price_override {
+        PriceOverride::Static { price } => {
+            if !(price.is_finite() && *price > 0.0) {
+                return Err(SetPriceOverrideError::InvalidPrice);
+            }
+        }
+        PriceOverride::Peg { token_id: target } => {
+            let target = StoredTokenId(target.clone());
+            if target == token_id || map.contains_key(&target) || !is_priceable_token_id(&target.0)
+            {
+                return Err(SetPriceOverrideError::InvalidPegTarget);
+            }
+        }
+    }
+
+    map.insert(token_id, Candid(price_override));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use candid::Principal;
+    use ic_stable_structures::{
+        memory_manager::{MemoryId, MemoryManager},
+        DefaultMemoryImpl,
+    };
+    use pretty_assertions::assert_eq;
+    use shared::types::token_id::TokenId;
+
+    use super::*;
+
+    const NOW: Timestamp = 1_000;
+
+    fn ck_btc() -> StoredTokenId {
+        StoredTokenId(TokenId::Icrc(
+            Principal::from_text("mxzaz-hqaaa-aaaar-qaada-cai").unwrap(),
+        ))
+    }
+
+    fn new_token() -> StoredTokenId {
+        StoredTokenId(TokenId::Icrc(Principal::anonymous()))
+    }
+
+    fn btc() -> StoredTokenId {
+        StoredTokenId(TokenId::BtcNativeMainnet)
+    }
+
+    fn data(price: f64) -> ExchangeData {
+        ExchangeData {
+            timestamp_ns: 7,
+            price: Some(price),
+            price_24h_change_pct: Some(1.5),
+            market_cap: Some(1e12),
+            source: None,
+            sources: None,
+            stale: None,
+        }
+    }
+
+    fn overrides(entries: Vec<(StoredTokenId, PriceOverride)>) -> PriceOverrides {
+        PriceOverrides::new(entries.into_iter().collect(), NOW)
+    }
+
+    fn peg(target: &StoredTokenId) -> PriceOverride {
+        PriceOverride::Peg {
+            token_id: target.0.clone(),
+        }
+    }
+
+    #[test]
+    fn provider_token_ids_swap_pegged_tokens_for_their_targets() {
+        let overrides = overrides(vec![
+            (ck_btc(), peg(&btc())),
+            (new_token(), PriceOverride::Static { price: 2.0 }),
+        ]);
+        let icp = StoredTokenId(TokenId::IcpNative);
+
+        assert_eq!(
+            overrides.provider_token_ids(&[ck_btc(), new_token(), icp.clone()]),
+            vec![icp, btc()]
+        );
+    }
+
+    #[test]
+    fn apply_takes_precedence_over_provider_prices() {
+        let overrides = overrides(vec![
+            (ck_btc(), peg(&btc())),
+            (new_token(), PriceOverride::Static { price: 2.0 }),
+        ]);
+
+        let prices: BTreeMap<StoredTokenId, ExchangeData> = overrides
+            .apply(vec![(btc(), data(60_000.0)), (new_token(), data(3.0))])
+            .into_iter()
+            .collect();
+
+        assert_eq!(
+            prices,
+            BTreeMap::from([
+                (btc(), data(60_000.0)),
+                (
+                    ck_btc(),
+                    ExchangeData {
+                        market_cap: None,
+                        source: Some(PEG_SOURCE.to_string()),
+                        ..data(60_000.0)
+                    }
+                ),
+                (
+                    new_token(),
+                    ExchangeData {
+                        timestamp_ns: NOW,
+                        price: Some(2.0),
+                        price_24h_change_pct: None,
+                        market_cap: None,
+                        source: Some(OVERRIDE_SOURCE.to_string()),
+                        sources: None,
+                        stale: None,
+                    }
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn pegged_token_without_target_price_stays_unpriced() {
+        let overrides = overrides(vec![(ck_btc(), peg(&btc()))]);
+
+        assert_eq!(overrides.apply(vec![]), vec![]);
+    }
+
+    #[test]
+    fn set_price_override_rejects_invalid_overrides() {
+        let memory_manager = MemoryManager::init(DefaultMemoryImpl::default());
+        let mut map = PriceOverrideMap::init(memory_manager.get(MemoryId::new(0)));
+
+        assert_eq!(
+            set_price_override(
+                &mut map,
+                new_token(),
+                Some(PriceOverride::Static { price: f64::NAN })
+            ),
+            Err(SetPriceOverrideError::InvalidPrice)
+        );
+        assert_eq!(
+            set_price_override(&mut map, ck_btc(), Some(peg(&ck_btc()))),
+            Err(SetPriceOverrideError::InvalidPegTarget)
+        );
+        assert_eq!(
+            set_price_override(
+                &mut map,
+                ck_btc(),
+                Some(peg(&StoredTokenId(TokenId::BtcNativeTestnet)))
+            ),
+            Err(SetPriceOverrideError::InvalidPegTarget)
+        );
+
+        assert_eq!(
+            set_price_override(&mut map, ck_btc(), Some(peg(&btc()))),
+            Ok(())
+        );
+        // Pegs are one level deep in both directions.
+        assert_eq!(
+            set_price_override(&mut map, new_token(), Some(peg(&ck_btc()))),
+            Err(SetPriceOverrideError::InvalidPegTarget)
+        );
+        assert_eq!(
+            set_price_override(&mut map, btc(), Some(PriceOverride::Static { price: 1.0 })),
+            Err(SetPriceOverrideError::InvalidPegTarget)
+        );
+
+        assert_eq!(set_price_override(&mut map, ck_btc(), None), Ok(()));
+        assert!(map.is_empty());
+    }
+}
@@ -7,6 +7,9 @@ use crate::types::storable::StoredTokenId;
 /// Implementations handle all provider-specific concerns such as API
 /// authentication, platform mapping, request batching, and response parsing.
 pub trait ExchangePriceProvider {
+    /// Stable identifier, used to label the provider in logs and metrics.
+    fn id(&self) -> &'static str;
+
     async fn fetch_prices(
         &self,
         token_ids: &[StoredTokenId],
@@ -9,6 +9,9 @@ use crate::utils::http_outcall::get;
 const DEFAULT_BASE_URL: &str = "https://pro-api.coingecko.com/api/v3";
 const SIMPLE_PRICE_PATH: &str = "/simple/price";
 const TOKEN_PRICE_PATH: &str = "/simple/token_price";
+/// Coin quoted in every fiat currency to derive USD FX rates from (a BTC cross rate, like the
+/// frontend's FX conversion).
+const FX_REFERENCE_COIN: &str = "bitcoin";
 
 // `http_request` cycle cost is charged on the *reserved* `max_response_bytes`,
 // not the bytes actually returned, so a uniform cap over-charges small calls.
@@ -51,10 +54,29 @@ impl From<CoinGeckoPrice> for ExchangeData {
             price: p.usd,
             price_24h_change_pct: p.usd_24h_change,
             market_cap: p.usd_market_cap,
+            source: None,
+            sources: None,
+            stale: None,
         }
     }
 }
 
+/// Units of each currency per USD, derived from the reference coin's price in every currency.
+///
+/// Currencies with a missing or non-positive quote are left out.
+fn usd_fx_rates(reference_prices: &HashMap<String, f64>) -> Result<HashMap<String, f64>, String> {
+    let usd = reference_prices
+        .get("usd")
+        .copied()
+        .filter(|p| p.is_finite() && *p > 0.0)
+        .ok_or("CoinGecko FX response has no USD quote")?;
+    Ok(reference_prices
+        .iter()
+        .filter(|(_, price)| price.is_finite() && **price > 0.0)
+        .map(|(currency, price)| (currency.clone(), price / usd))
+        .collect())
+}
+
 pub struct CoinGeckoClient {
     base_url: String,
     api_key: String,
@@ -141,14 +163,79 @@ impl CoinGeckoClient {
         self.fetch_prices(&url, response_bytes_for(addresses.len()))
             .await
     }
+
+    /// Fetches units of each of `currencies` (lowercase ISO 4217 codes) per USD, as cross rates of
+    /// [`FX_REFERENCE_COIN`] from the
+    /// [`/simple/price`](https://docs.coingecko.com/reference/simple-price) endpoint.
+    pub async fn fetch_usd_fx_rates(
+        &self,
+        currencies: &[&str],
+    ) -> Result<HashMap<String, f64>, String> {
+        let vs_currencies = currencies.join(",");
+
+        let url = format!(
+            "{}{SIMPLE_PRICE_PATH}?ids={FX_REFERENCE_COIN}&vs_currencies={vs_currencies}",
+            self.base_url
+        );
+
+        let response = get(
+            &url,
+            vec![self.auth_header()],
+            response_bytes_for(1),
+            self.replicated,
+        )
+        .await?;
+
+        let mut prices: HashMap<String, HashMap<String, f64>> =
+            serde_json::from_slice(&response.body)
+                .map_err(|e| format!("Failed to parse CoinGecko FX response: {e}"))?;
+        let reference_prices = prices
+            .remove(FX_REFERENCE_COIN)
+            .ok_or("CoinGecko FX response has no reference coin")?;
+
+        usd_fx_rates(&reference_prices)
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+
+    use pretty_assertions::assert_eq;
+
     use super::{
-        response_bytes_for, MAX_RESPONSE_BYTES, MIN_RESPONSE_BYTES, PER_ITEM_RESPONSE_BYTES,
+        response_bytes_for, usd_fx_rates, MAX_RESPONSE_BYTES, MIN_RESPONSE_BYTES,
+        PER_ITEM_RESPONSE_BYTES,
     };
 
+    #[test]
+    fn usd_fx_rates_divide_by_the_usd_quote() {
+        let reference_prices = HashMap::from([
+            ("usd".to_string(), 100_000.0),
+            ("eur".to_string(), 90_000.0),
+            ("jpy".to_string(), 15_000_000.0),
+            ("chf".to_string(), 0.0),
+        ]);
+
+        let rates = usd_fx_rates(&reference_prices).unwrap();
+
+        assert_eq!(
+            rates,
+            HashMap::from([
+                ("usd".to_string(), 1.0),
+                ("eur".to_string(), 0.9),
+                ("jpy".to_string(), 150.0),
+            ])
+        );
+    }
+
+    #[test]
+    fn usd_fx_rates_require_a_usd_quote() {
+        let reference_prices = HashMap::from([("eur".to_string(), 90_000.0)]);
+
+        assert!(usd_fx_rates(&reference_prices).is_err());
+    }
+
     #[test]
     fn response_bytes_zero_and_one_item_hit_floor() {
         assert_eq!(response_bytes_for(0), MIN_RESPONSE_BYTES);
@@ -4,7 +4,10 @@ mod platform;
 use std::collections::HashMap;
 
 use futures::future::join_all;
-use shared::types::{exchange::ExchangeData, token_id::TokenId};
+use shared::types::{
+    exchange::{ExchangeData, FiatCurrency},
+    token_id::TokenId,
+};
 
 pub(crate) use self::platform::is_priceable_token_id;
 use self::{
@@ -26,6 +29,18 @@ impl CoinGeckoProvider {
         }
     }
 
+    /// Fetches units of every non-USD [`FiatCurrency`] per USD. Currencies without a quote are
+    /// left out.
+    pub async fn fetch_usd_fx_rates(&self) -> Result<Vec<(FiatCurrency, f64)>, String> {
+        let codes: Vec<&str> = FiatCurrency::ALL.iter().map(|c| c.code()).collect();
+        let rates = self.client.fetch_usd_fx_rates(&codes).await?;
+        Ok(FiatCurrency::ALL
+            .into_iter()
+            .filter(|currency| *currency != FiatCurrency::Usd)
+            .filter_map(|currency| rates.get(currency.code()).map(|rate| (currency, *rate)))
+            .collect())
+    }
+
     #[expect(dead_code)]
     pub fn with_base_url(mut self, base_url: String) -> Self {
         self.client = self.client.with_base_url(base_url);
@@ -150,6 +165,10 @@ enum FetchTag<'a> {
 }
 
 impl ExchangePriceProvider for CoinGeckoProvider {
+    fn id(&self) -> &'static str {
+        "coingecko"
+    }
+
     async fn fetch_prices(
         &self,
         token_ids: &[StoredTokenId],
@@ -1,42 +1,32 @@
-use shared::types::token_id::TokenId;
+use shared::types::{network::evm_chains::EvmChain, token_id::TokenId};
 
 /// Maps an EVM chain ID to the corresponding `CoinGecko` platform identifier
 /// used in the `/simple/token_price` endpoint for contract-based tokens.
 pub fn coingecko_platform(chain_id: u64) -> Option<&'static str> {
-    match chain_id {
-        1 => Some("ethereum"),
-        56 => Some("binance-smart-chain"),
-        137 => Some("polygon-pos"),
-        8453 => Some("base"),
-        42161 => Some("arbitrum-one"),
-        _ => None,
-    }
+    EvmChain::from_chain_id(chain_id)?.coingecko_platform
 }
 
 /// Maps an EVM chain ID to the `CoinGecko` coin identifier for its native token,
 /// used in the `/simple/price` endpoint.
 pub fn coingecko_native_coin(chain_id: u64) -> Option<&'static str> {
-    match chain_id {
-        1 | 8453 | 42161 => Some("ethereum"),
-        56 => Some("binancecoin"),
-        137 => Some("polygon-ecosystem-token"),
-        _ => None,
-    }
+    EvmChain::from_chain_id(chain_id)?.coingecko_native_coin
 }
 
 /// Whether this `TokenId` is something we can ever fetch a USD price for via
 /// the configured providers (`CoinGecko` primary, `ICPSwap` supplemental for
-/// ICRC).
+/// ICRC), or derive one from (ERC-4626 vault tokens, priced from their
+/// underlying ERC-20).
 ///
 /// Returns `false` for testnet variants, NFT standards (ERC-721/1155, EXT,
-/// DIP-721, `ICPunks`, ICRC-7), ERC-4626 vault tokens (priced FE-side from
-/// their underlying ERC-20), and EVM/ERC-20 tokens on chains we don't have
-/// a `CoinGecko` mapping for.
+/// DIP-721, `ICPunks`, ICRC-7), and EVM tokens on chains we don't have a
+/// `CoinGecko` mapping for.
 #[must_use]
 pub fn is_priceable_token_id(token_id: &TokenId) -> bool {
     match token_id {
         TokenId::EvmNative(chain_id) => coingecko_native_coin(*chain_id).is_some(),
-        TokenId::Erc20(_, chain_id) => coingecko_platform(*chain_id).is_some(),
+        TokenId::Erc20(_, chain_id) | TokenId::Erc4626(_, chain_id) => {
+            coingecko_platform(*chain_id).is_some()
+        }
         TokenId::Icrc(_)
         | TokenId::IcpNative
         | TokenId::SolNativeMainnet
@@ -44,7 +34,6 @@ pub fn is_priceable_token_id(token_id: &TokenId) -> bool {
         | TokenId::SplMainnet(_) => true,
         TokenId::Erc721(..)
         | TokenId::Erc1155(..)
-        | TokenId::Erc4626(..)
         | TokenId::SplDevnet(_)
         | TokenId::SolNativeDevnet
         | TokenId::BtcNativeTestnet
@@ -58,6 +47,7 @@ pub fn is_priceable_token_id(token_id: &TokenId) -> bool {
 #[cfg(test)]
 mod tests {
     use candid::Principal;
+    use pretty_assertions::assert_eq;
     use shared::types::custom_token::ChainId;
 
     use super::{coingecko_native_coin, coingecko_platform, is_priceable_token_id, TokenId};
@@ -69,6 +59,16 @@ mod tests {
         assert_eq!(coingecko_platform(137), Some("polygon-pos"));
         assert_eq!(coingecko_platform(8453), Some("base"));
         assert_eq!(coingecko_platform(42161), Some("arbitrum-one"));
+        assert_eq!(coingecko_platform(10), Some("optimistic-ethereum"));
+        assert_eq!(coingecko_platform(43114), Some("avalanche"));
+        assert_eq!(coingecko_platform(59144), Some("linea"));
+        assert_eq!(coingecko_platform(324), Some("zksync"));
+    }
+
+    #[test]
+    fn test_testnets_have_no_platform() {
+        assert_eq!(coingecko_platform(11_155_111), None);
+        assert_eq!(coingecko_native_coin(84_532), None);
     }
 
     #[test]
@@ -82,12 +82,16 @@ mod tests {
         assert_eq!(coingecko_native_coin(1), Some("ethereum"));
         assert_eq!(coingecko_native_coin(8453), Some("ethereum"));
         assert_eq!(coingecko_native_coin(42161), Some("ethereum"));
+        assert_eq!(coingecko_native_coin(10), Some("ethereum"));
+        assert_eq!(coingecko_native_coin(59144), Some("ethereum"));
+        assert_eq!(coingecko_native_coin(324), Some("ethereum"));
     }
 
     #[test]
     fn test_native_coin_other_chains() {
         assert_eq!(coingecko_native_coin(56), Some("binancecoin"));
         assert_eq!(coingecko_native_coin(137), Some("polygon-ecosystem-token"));
+        assert_eq!(coingecko_native_coin(43114), Some("avalanche-2"));
     }
 
     #[test]
@@ -134,16 +138,24 @@ mod tests {
     }
 
     #[test]
-    fn priceable_excludes_nft_and_vault_standards() {
-        assert!(!is_priceable_token_id(&TokenId::Erc721(
+    fn priceable_includes_vaults_on_supported_chains() {
+        assert!(is_priceable_token_id(&TokenId::Erc4626(
             shared::types::custom_token::ErcTokenId("0xabc".to_string()),
             1
         )));
-        assert!(!is_priceable_token_id(&TokenId::Erc1155(
+        assert!(!is_priceable_token_id(&TokenId::Erc4626(
+            shared::types::custom_token::ErcTokenId("0xabc".to_string()),
+            999
+        )));
+    }
+
+    #[test]
+    fn priceable_excludes_nft_standards() {
+        assert!(!is_priceable_token_id(&TokenId::Erc721(
             shared::types::custom_token::ErcTokenId("0xabc".to_string()),
             1
         )));
-        assert!(!is_priceable_token_id(&TokenId::Erc4626(
+        assert!(!is_priceable_token_id(&TokenId::Erc1155(
             shared::types::custom_token::ErcTokenId("0xabc".to_string()),
             1
         )));
@@ -0,0 +1,463 @@
+mod rpc;
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+};
+
+use futures::future::join_all;
+use shared::types::{
+    custom_token::{ChainId, ErcTokenId},
+    exchange::ExchangeData,
+    token_id::TokenId,
+};
+
+use self::rpc::EthCall;
+pub(crate) use self::rpc::{AlchemyRpcClient, EvmRpcClient};
+use crate::types::storable::StoredTokenId;
+
+/// `asset()`: the vault's underlying ERC-20.
+const ASSET_SELECTOR: [u8; 4] = [0x38, 0xd5, 0x2e, 0x0f];
+/// `decimals()`, on both the vault and its underlying asset.
+const DECIMALS_SELECTOR: [u8; 4] = [0x31, 0x3c, 0xe5, 0x67];
+/// `convertToAssets(uint256)`: underlying assets redeemable for a number of shares.
+const CONVERT_TO_ASSETS_SELECTOR: [u8; 4] = [0x07, 0xa2, 0xd1, 0x3a];
+/// Largest share decimals whose `10^decimals` fits the `u128` used to encode one share.
+const MAX_SHARE_DECIMALS: u8 = 38;
+/// [`ExchangeData::source`] of vault share prices.
+const ERC4626_SOURCE: &str = "erc4626";
+
+/// The immutable part of a vault: its underlying asset and both tokens' decimals.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct VaultMetadata {
+    asset: ErcTokenId,
+    share_decimals: u8,
+    asset_decimals: u8,
+}
+
+thread_local! {
+    /// Metadata per `(chain, lowercase vault address)`. Only resolved vaults are cached; lookups
+    /// that failed are retried on the next refresh.
+    static VAULTS: RefCell<HashMap<(ChainId, String), VaultMetadata>> = RefCell::new(HashMap::new());
+}
+
+fn vault_key(address: &ErcTokenId, chain_id: ChainId) -> (ChainId, String) {
+    (chain_id, address.as_str().to_lowercase())
+}
+
+fn cached_vault(address: &ErcTokenId, chain_id: ChainId) -> Option<VaultMetadata> {
+    VAULTS.with(|vaults| vaults.borrow().get(&vault_key(address, chain_id)).cloned())
+}
+
+fn call(to: &ErcTokenId, selector: [u8; 4], args: &[[u8; 32]]) -> EthCall {
+    let mut data = selector.to_vec();
+    for arg in args {
+        data.extend_from_slice(arg);
+    }
+    EthCall {
+        to: to.as_str().to_string(),
+        data,
+    }
+}
+
+fn encode_uint256(value: u128) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[16..].copy_from_slice(&value.to_be_bytes());
+    word
+}
+
+/// The first 32-byte word of ABI-encoded return data.
+fn first_word(data: &[u8]) -> Option<&[u8]> {
+    data.get(..32)
+}
+
+fn decode_address(data: &[u8]) -> Option<ErcTokenId> {
+    let word = first_word(data)?;
+    word[..12]
+        .iter()
+        .all(|b| *b == 0)
+        .then(|| ErcTokenId(format!("0x{}", hex::encode(&word[12..]))))
+}
+
+fn decode_u8(data: &[u8]) -> Option<u8> {
+    let word = first_word(data)?;
+    word[..31].iter().all(|b| *b == 0).then_some(word[31])
+}
+
+/// A `uint256` as the nearest `f64`.
+fn decode_uint256(data: &[u8]) -> Option<f64> {
+    Some(
+        first_word(data)?
+            .iter()
+            .fold(0.0, |acc, b| acc * 256.0 + f64::from(*b)),
+    )
+}
+
+/// Groups `vaults` by chain, keeping their original addresses.
+fn by_chain<'a>(
+    vaults: impl IntoIterator<Item = &'a (ErcTokenId, ChainId)>,
+) -> BTreeMap<ChainId, Vec<ErcTokenId>> {
+    let mut grouped: BTreeMap<ChainId, Vec<ErcTokenId>> = BTreeMap::new();
+    for (address, chain_id) in vaults {
+        grouped.entry(*chain_id).or_default().push(address.clone());
+    }
+    grouped
+}
+
+/// Resolves the metadata of `vaults` on `chain_id` in two batches: `asset()` and `decimals()` of
+/// every vault, then `decimals()` of every asset.
+async fn resolve_chain_vaults<C: EvmRpcClient>(
+    client: &C,
+    chain_id: ChainId,
+    vaults: Vec<ErcTokenId>,
+) -> Result<Vec<(ErcTokenId, VaultMetadata)>, String> {
+    let calls: Vec<EthCall> = vaults
+        .iter()
+        .flat_map(|vault| {
+            [
+                call(vault, ASSET_SELECTOR, &[]),
+                call(vault, DECIMALS_SELECTOR, &[]),
+            ]
+        })
+        .collect();
+    let results = client.eth_calls(chain_id, &calls).await?;
+
+    let shares: Vec<(ErcTokenId, ErcTokenId, u8)> = vaults
+        .into_iter()
+        .zip(results.chunks(2))
+        .filter_map(|(vault, results)| {
+            let asset = decode_address(results[0].as_ref().ok()?)?;
+            let share_decimals = decode_u8(results.get(1)?.as_ref().ok()?)?;
+            Some((vault, asset, share_decimals))
+        })
+        .collect();
+
+    let calls: Vec<EthCall> = shares
+        .iter()
+        .map(|(_, asset, _)| call(asset, DECIMALS_SELECTOR, &[]))
+        .collect();
+    let results = client.eth_calls(chain_id, &calls).await?;
+
+    Ok(shares
+        .into_iter()
+        .zip(results)
+        .filter_map(|((vault, asset, share_decimals), result)| {
+            let asset_decimals = decode_u8(result.as_ref().ok()?)?;
+            Some((
+                vault,
+                VaultMetadata {
+                    asset,
+                    share_decimals,
+                    asset_decimals,
+                },
+            ))
+        })
+        .collect())
+}
+
+/// Looks up and caches the underlying asset and decimals of every vault in `vaults` that isn't
+/// cached yet.
+pub(crate) async fn resolve_vaults<C: EvmRpcClient>(client: &C, vaults: &[(ErcTokenId, ChainId)]) {
+    let unknown = by_chain(
+        vaults
+            .iter()
+            .filter(|(address, chain_id)| cached_vault(address, *chain_id).is_none()),
+    );
+
+    let outcomes = join_all(unknown.into_iter().map(|(chain_id, vaults)| async move {
+        (
+            chain_id,
+            resolve_chain_vaults(client, chain_id, vaults).await,
+        )
+    }))
+    .await;
+
+    for (chain_id, outcome) in outcomes {
+        match outcome {
+            Ok(resolved) => VAULTS.with(|cache| {
+                let mut cache = cache.borrow_mut();
+                for (vault, metadata) in resolved {
+                    cache.insert(vault_key(&vault, chain_id), metadata);
+                }
+            }),
+            Err(err) => ic_cdk::println!("ERC-4626 vault lookup on chain {chain_id} failed: {err}"),
+        }
+    }
+}
+
+/// The underlying ERC-20s of the resolved vaults in `vaults`, whose prices the vault prices are
+/// derived from.
+pub(crate) fn underlying_token_ids(vaults: &[(ErcTokenId, ChainId)]) -> Vec<StoredTokenId> {
+    vaults
+        .iter()
+        .filter_map(|(address, chain_id)| {
+            cached_vault(address, *chain_id)
+                .map(|vault| StoredTokenId(TokenId::Erc20(vault.asset, *chain_id)))
+        })
+        .collect()
+}
+
+/// USD price of one vault share, given the raw assets one share converts to.
+fn share_price(assets_per_share_raw: f64, asset_decimals: u8, underlying_price: f64) -> f64 {
+    assets_per_share_raw / 10f64.powi(i32::from(asset_decimals)) * underlying_price
+}
+
+/// Prices the resolved vaults in `vaults` from their current `convertToAssets` rate and the price
+/// of their underlying asset returned by `underlying_price`.
+///
+/// The result carries the underlying price's timestamp and number of agreeing sources. Vaults without a resolved asset, a
+/// conversion rate or an underlying price are left out.
+pub(crate) async fn price_vaults<C: EvmRpcClient>(
+    client: &C,
+    vaults: &[(ErcTokenId, ChainId)],
+    underlying_price: impl Fn(&StoredTokenId) -> Option<ExchangeData>,
+) -> Vec<(StoredTokenId, ExchangeData)> {
+    let resolved = by_chain(vaults.iter().filter(|(address, chain_id)| {
+        cached_vault(address, *chain_id)
+            .is_some_and(|vault| vault.share_decimals <= MAX_SHARE_DECIMALS)
+    }));
+
+    let outcomes = join_all(resolved.into_iter().map(|(chain_id, vaults)| async move {
+        let calls: Vec<EthCall> = vaults
+            .iter()
+            .filter_map(|vault| {
+                let metadata = cached_vault(vault, chain_id)?;
+                let one_share = 10u128.pow(u32::from(metadata.share_decimals));
+                Some(call(
+                    vault,
+                    CONVERT_TO_ASSETS_SELECTOR,
+                    &[encode_uint256(one_share)],
+                ))
+            })
+            .collect();
+        let outcome = client.eth_calls(chain_id, &calls).await;
+        (chain_id, vaults, outcome)
+    }))
+    .await;
+
+    let mut prices = Vec::new();
+    for (chain_id, vaults, outcome) in outcomes {
+        let results = match outcome {
+            Ok(results) => results,
+            Err(err) => {
+                ic_cdk::println!("ERC-4626 conversion rates on chain {chain_id} failed: {err}");
+                continue;
+            }
+        };
+        for (vault, result) in vaults.into_iter().zip(results) {
+            let Some(metadata) = cached_vault(&vault, chain_id) else {
+                continue;
+            };
+            let Some(assets_per_share) = result.ok().as_deref().and_then(decode_uint256) else {
+                continue;
+            };
+            let underlying =
+                underlying_price(&StoredTokenId(TokenId::Erc20(metadata.asset, chain_id)));
+            let Some((underlying, price)) =
+                underlying.and_then(|data| data.price.map(|price| (data, price)))
+            else {
+                continue;
+            };
+            prices.push((
+                StoredTokenId(TokenId::Erc4626(vault, chain_id)),
+                ExchangeData {
+                    timestamp_ns: underlying.timestamp_ns,
+                    price: Some(share_price(
+                        assets_per_share,
+                        metadata.asset_decimals,
+                        price,
+                    )),
+                    price_24h_change_pct: None,
+                    market_cap: None,
+                    source: Some(ERC4626_SOURCE.to_string()),
+                    sources: underlying.sources,
+                    stale: None,
+                },
+            ));
+        }
+    }
+    prices
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use futures::executor::block_on;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    const VAULT: &str = "0x83F20F44975D03b1b09e64809B757c47f942BEeA";
+    const ASSET: &str = "0x6b175474e89094c44da98b954eedeac495271d0f";
+    const CHAIN: ChainId = 1;
+
+    /// Answers calls from a fixed `(to, data) -> return data` table and records every batch.
+    #[derive(Default)]
+    struct MockRpc {
+        responses: HashMap<(String, Vec<u8>), Vec<u8>>,
+        batches: RefCell<Vec<(ChainId, usize)>>,
+    }
+
+    impl MockRpc {
+        fn with(mut self, to: &str, data: Vec<u8>, response: [u8; 32]) -> Self {
+            self.responses
+                .insert((to.to_lowercase(), data), response.to_vec());
+            self
+        }
+    }
+
+    impl EvmRpcClient for MockRpc {
+        async fn eth_calls(
+            &self,
+            chain_id: ChainId,
+            calls: &[EthCall],
+        ) -> Result<Vec<Result<Vec<u8>, String>>, String> {
+            self.batches.borrow_mut().push((chain_id, calls.len()));
+            Ok(calls
+                .iter()
+                .map(|call| {
+                    self.responses
+                        .get(&(call.to.to_lowercase(), call.data.clone()))
+                        .cloned()
+                        .ok_or_else(|| "execution reverted".to_string())
+                })
+                .collect())
+        }
+    }
+
+    fn address_word(address: &str) -> [u8; 32] {
+        let mut word = [0u8; 32];
+        word[12..].copy_from_slice(&hex::decode(address.trim_start_matches("0x")).unwrap());
+        word
+    }
+
+    fn vault() -> (ErcTokenId, ChainId) {
+        (ErcTokenId(VAULT.to_string()), CHAIN)
+    }
+
+    /// A vault with 18-decimal shares over an 18-decimal asset, where one share is worth 1.25
+    /// assets.
+    fn mock_rpc() -> MockRpc {
+        MockRpc::default()
+            .with(VAULT, ASSET_SELECTOR.to_vec(), address_word(ASSET))
+            .with(VAULT, DECIMALS_SELECTOR.to_vec(), encode_uint256(18))
+            .with(ASSET, DECIMALS_SELECTOR.to_vec(), encode_uint256(18))
+            .with(
+                VAULT,
+                [
+                    CONVERT_TO_ASSETS_SELECTOR.as_slice(),
+                    &encode_uint256(10u128.pow(18)),
+                ]
+                .concat(),
+                encode_uint256(1_250_000_000_000_000_000),
+            )
+    }
+
+    fn underlying_data(price: f64) -> ExchangeData {
+        ExchangeData {
+            timestamp_ns: 7,
+            price: Some(price),
+            price_24h_change_pct: Some(1.0),
+            market_cap: Some(1e9),
+            source: Some("coingecko".to_string()),
+            sources: Some(2),
+            stale: None,
+        }
+    }
+
+    #[test]
+    fn abi_words_round_trip() {
+        assert_eq!(decode_u8(&encode_uint256(18)), Some(18));
+        assert_eq!(decode_u8(&encode_uint256(256)), None);
+        assert_eq!(decode_uint256(&encode_uint256(1_000)), Some(1_000.0));
+        assert_eq!(decode_uint256(&[0; 31]), None);
+        assert_eq!(
+            decode_address(&address_word(ASSET)),
+            Some(ErcTokenId(ASSET.to_string()))
+        );
+        assert_eq!(
+            decode_address(&encode_uint256(u128::MAX)),
+            Some(ErcTokenId(
+                "0x00000000ffffffffffffffffffffffffffffffff".to_string()
+            ))
+        );
+        assert_eq!(decode_address(&[0xff; 32]), None);
+    }
+
+    #[test]
+    fn resolve_vaults_caches_the_underlying_asset() {
+        let rpc = mock_rpc();
+
+        block_on(resolve_vaults(&rpc, &[vault()]));
+        block_on(resolve_vaults(&rpc, &[vault()]));
+
+        assert_eq!(
+            underlying_token_ids(&[vault()]),
+            vec![StoredTokenId(TokenId::Erc20(
+                ErcTokenId(ASSET.to_string()),
+                CHAIN
+            ))]
+        );
+        // Two lookup rounds for the first call, none once cached.
+        assert_eq!(*rpc.batches.borrow(), vec![(CHAIN, 2), (CHAIN, 1)]);
+    }
+
+    #[test]
+    fn failed_lookups_are_not_cached() {
+        let rpc = MockRpc::default();
+
+        block_on(resolve_vaults(&rpc, &[vault()]));
+
+        assert_eq!(underlying_token_ids(&[vault()]), Vec::new());
+    }
+
+    #[test]
+    fn price_vaults_multiplies_the_conversion_rate_by_the_underlying_price() {
+        let rpc = mock_rpc();
+        block_on(resolve_vaults(&rpc, &[vault()]));
+
+        let prices = block_on(price_vaults(&rpc, &[vault()], |id| {
+            (id == &StoredTokenId(TokenId::Erc20(ErcTokenId(ASSET.to_string()), CHAIN)))
+                .then(|| underlying_data(2.0))
+        }));
+
+        assert_eq!(
+            prices,
+            vec![(
+                StoredTokenId(TokenId::Erc4626(ErcTokenId(VAULT.to_string()), CHAIN)),
+                ExchangeData {
+                    timestamp_ns: 7,
+                    price: Some(2.5),
+                    price_24h_change_pct: None,
+                    market_cap: None,
+                    source: Some(ERC4626_SOURCE.to_string()),
+                    sources: Some(2),
+                    stale: None,
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn price_vaults_skips_vaults_without_an_underlying_price() {
+        let rpc = mock_rpc();
+        block_on(resolve_vaults(&rpc, &[vault()]));
+
+        let prices = block_on(price_vaults(&rpc, &[vault()], |_| None));
+
+        assert_eq!(prices, Vec::new());
+    }
+
+    #[test]
+    fn share_price_applies_asset_decimals() {
+        // One share converts to 1.05 USDC (6 decimals) at $1.
+        assert_eq!(
+            ExchangeData {
+                price: Some(share_price(1_050_000.0, 6, 1.0)),
+                ..underlying_data(0.0)
+            },
+            underlying_data(1.05)
+        );
+    }
+}
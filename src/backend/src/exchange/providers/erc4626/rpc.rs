@@ -0,0 +1,217 @@
+use serde::Deserialize;
+use serde_json::{json, Value};
+use shared::types::{custom_token::ChainId, network::evm_chains::EvmChain};
+
+use crate::utils::http_outcall::post;
+
+/// JSON-RPC responses carry one ~150 byte entry per `eth_call` (a 32-byte word hex-encoded plus the
+/// envelope); 512 bytes per call leaves ample headroom over the observed size.
+const PER_CALL_RESPONSE_BYTES: u64 = 512;
+/// Floor for the JSON-RPC envelope and response headers.
+const MIN_RESPONSE_BYTES: u64 = 2_048;
+
+/// One `eth_call` against the latest block.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct EthCall {
+    /// Hex-encoded contract address.
+    pub to: String,
+    /// ABI-encoded call data.
+    pub data: Vec<u8>,
+}
+
+/// An EVM JSON-RPC endpoint able to run a batch of `eth_call`s.
+///
+/// The vault pricing logic only depends on this trait, so it can be tested against a mock.
+pub(crate) trait EvmRpcClient {
+    /// Runs `calls` on `chain_id` and returns each call's return data, in the order of `calls`.
+    ///
+    /// The outer `Err` is a failure of the whole batch; the inner ones are per-call errors (e.g.
+    /// a reverted call).
+    async fn eth_calls(
+        &self,
+        chain_id: ChainId,
+        calls: &[EthCall],
+    ) -> Result<Vec<Result<Vec<u8>, String>>, String>;
+}
+
+/// Alchemy JSON-RPC base URL for `chain_id`, for the chains with an Alchemy network in the
+/// registry.
+fn alchemy_base_url(chain_id: ChainId) -> Option<String> {
+    let network = EvmChain::from_chain_id(chain_id)?.alchemy_network?;
+    Some(format!("https://{network}.g.alchemy.com/v2/"))
+}
+
+fn response_bytes_for(call_count: usize) -> u64 {
+    (call_count as u64)
+        .saturating_mul(PER_CALL_RESPONSE_BYTES)
+        .max(MIN_RESPONSE_BYTES)
+}
+
+/// JSON-RPC batch body running every call in `calls`, with the call's index as its `id`.
+fn batch_body(calls: &[EthCall]) -> Vec<u8> {
+    let requests: Vec<Value> = calls
+        .iter()
+        .enumerate()
+        .map(|(id, call)| {
+            json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": "eth_call",
+                "params": [
+                    { "to": call.to, "data": format!("0x{}", hex::encode(&call.data)) },
+                    "latest"
+                ],
+            })
+        })
+        .collect();
+    Value::Array(requests).to_string().into_bytes()
+}
+
+#[derive(Deserialize)]
+struct RpcError {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct RpcResponse {
+    id: usize,
+    result: Option<String>,
+    error: Option<RpcError>,
+}
+
+/// Matches a JSON-RPC batch response to the `call_count` requests of [`batch_body`].
+///
+/// Servers may answer a batch in any order, so responses are placed by `id`.
+fn parse_batch_response(
+    body: &[u8],
+    call_count: usize,
+) -> Result<Vec<Result<Vec<u8>, String>>, String> {
+    let responses: Vec<RpcResponse> = serde_json::from_slice(body)
+        .map_err(|e| format!("Failed to parse JSON-RPC batch response: {e}"))?;
+
+    let mut results: Vec<Result<Vec<u8>, String>> =
+        vec![Err("missing from JSON-RPC batch response".to_string()); call_count];
+    for response in responses {
+        let Some(slot) = results.get_mut(response.id) else {
+            continue;
+        };
+        *slot = match (response.result, response.error) {
+            (_, Some(error)) => Err(error.message),
+            (Some(result), None) => hex::decode(result.trim_start_matches("0x"))
+                .map_err(|e| format!("invalid eth_call result: {e}")),
+            (None, None) => Err("empty JSON-RPC response".to_string()),
+        };
+    }
+    Ok(results)
+}
+
+/// [`EvmRpcClient`] backed by Alchemy, called through HTTP outcalls.
+pub(crate) struct AlchemyRpcClient {
+    api_key: String,
+    replicated: bool,
+}
+
+impl AlchemyRpcClient {
+    pub(crate) fn new(api_key: String, replicated: bool) -> Self {
+        Self {
+            api_key,
+            replicated,
+        }
+    }
+}
+
+impl EvmRpcClient for AlchemyRpcClient {
+    async fn eth_calls(
+        &self,
+        chain_id: ChainId,
+        calls: &[EthCall],
+    ) -> Result<Vec<Result<Vec<u8>, String>>, String> {
+        if calls.is_empty() {
+            return Ok(Vec::new());
+        }
+        let base_url =
+            alchemy_base_url(chain_id).ok_or_else(|| format!("unsupported chain {chain_id}"))?;
+
+        let response = post(
+            &format!("{base_url}{}", self.api_key),
+            batch_body(calls),
+            vec![],
+            response_bytes_for(calls.len()),
+            self.replicated,
+        )
+        .await?;
+
+        parse_batch_response(&response.body, calls.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn batch_body_numbers_calls_by_index() {
+        let calls = [
+            EthCall {
+                to: "0xabc".to_string(),
+                data: vec![0x38, 0xd5, 0x2e, 0x0f],
+            },
+            EthCall {
+                to: "0xdef".to_string(),
+                data: vec![0x31, 0x3c],
+            },
+        ];
+
+        let body: Value = serde_json::from_slice(&batch_body(&calls)).unwrap();
+
+        assert_eq!(
+            body,
+            json!([
+                {
+                    "jsonrpc": "2.0",
+                    "id": 0,
+                    "method": "eth_call",
+                    "params": [{ "to": "0xabc", "data": "0x38d52e0f" }, "latest"],
+                },
+                {
+                    "jsonrpc": "2.0",
+                    "id": 1,
+                    "method": "eth_call",
+                    "params": [{ "to": "0xdef", "data": "0x313c" }, "latest"],
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_batch_response_orders_by_id_and_keeps_per_call_errors() {
+        let body = br#"[
+            {"jsonrpc":"2.0","id":2,"error":{"code":3,"message":"execution reverted"}},
+            {"jsonrpc":"2.0","id":0,"result":"0x0102"}
+        ]"#;
+
+        let results = parse_batch_response(body, 3).unwrap();
+
+        assert_eq!(
+            results,
+            vec![
+                Ok(vec![1, 2]),
+                Err("missing from JSON-RPC batch response".to_string()),
+                Err("execution reverted".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_batch_response_rejects_non_batch_bodies() {
+        assert!(parse_batch_response(br#"{"error":"unauthorized"}"#, 1).is_err());
+    }
+
+    #[test]
+    fn response_bytes_scale_with_call_count() {
+        assert_eq!(response_bytes_for(1), MIN_RESPONSE_BYTES);
+        assert_eq!(response_bytes_for(10), 10 * PER_CALL_RESPONSE_BYTES);
+    }
+}
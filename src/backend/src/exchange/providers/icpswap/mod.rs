@@ -65,6 +65,9 @@ fn exchange_data_from_icpswap_envelope(
         price: Some(price),
         price_24h_change_pct,
         market_cap: None,
+        source: None,
+        sources: None,
+        stale: None,
     })
 }
 
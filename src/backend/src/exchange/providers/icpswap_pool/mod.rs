@@ -0,0 +1,452 @@
+use std::{cell::RefCell, collections::HashMap};
+
+use candid::{CandidType, Nat, Principal};
+use futures::future::join_all;
+use ic_cdk::{api::time, call::Call};
+use serde::Deserialize;
+use shared::types::{exchange::ExchangeData, token_id::TokenId};
+
+use crate::{
+    exchange::supplemental::{SupplementalPriceProvider, SupplementalPricesFuture},
+    types::storable::StoredTokenId,
+};
+
+/// `ICPSwap` `SwapFactory` canister, which maps token pairs to their pool canisters.
+const ICPSWAP_FACTORY_CANISTER_ID: &str = "4mmnk-kiaaa-aaaag-qbllq-cai";
+const CKUSDC_LEDGER_CANISTER_ID: &str = "xevnm-gaaaa-aaaar-qafnq-cai";
+const CKUSDC_DECIMALS: u8 = 6;
+const ICP_LEDGER_CANISTER_ID: &str = "ryjl3-tyaaa-aaaaa-aaaba-cai";
+const ICP_DECIMALS: u8 = 8;
+/// Fee tier of the pools used for pricing (0.3%, the tier `ICPSwap` creates pools in).
+const POOL_FEE: u32 = 3_000;
+/// Pools whose quote-side reserve is worth at most this much are considered too thin to price and
+/// their prices are discarded. Matches the `tvlUSD` threshold of the HTTP `ICPSwap` provider.
+const MIN_QUOTE_RESERVE_USD: f64 = 500.0;
+/// `2^96`, the fixed-point scale of `sqrtPriceX96`.
+const Q96: f64 = 79_228_162_514_264_337_593_543_950_336.0;
+
+thread_local! {
+    /// Pool canister per `(token ledger, quote ledger)`. Pools never move, so only lookups that
+    /// found a pool are cached; a pair without a pool is looked up again on the next refresh.
+    static POOL_CANISTERS: RefCell<HashMap<(Principal, Principal), Principal>> =
+        RefCell::new(HashMap::new());
+    /// `icrc1_decimals` per ledger.
+    static LEDGER_DECIMALS: RefCell<HashMap<Principal, u8>> = RefCell::new(HashMap::new());
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+struct IcpSwapToken {
+    address: String,
+    standard: String,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct GetPoolArgs {
+    fee: Nat,
+    token0: IcpSwapToken,
+    token1: IcpSwapToken,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct PoolData {
+    #[serde(rename = "canisterId")]
+    canister_id: Principal,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+enum IcpSwapError {
+    CommonError,
+    InternalError(String),
+    UnsupportedToken(String),
+    InsufficientFunds,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+enum IcpSwapResult<T> {
+    #[serde(rename = "ok")]
+    Ok(T),
+    #[serde(rename = "err")]
+    Err(IcpSwapError),
+}
+
+/// The subset of a pool's `metadata` needed to derive a price.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct PoolMetadata {
+    token0: IcpSwapToken,
+    token1: IcpSwapToken,
+    liquidity: Nat,
+    #[serde(rename = "sqrtPriceX96")]
+    sqrt_price_x96: Nat,
+}
+
+/// A token the priced tokens are quoted against.
+#[derive(Clone, Copy, Debug)]
+struct Quote {
+    ledger: &'static str,
+    standard: &'static str,
+    decimals: u8,
+    usd_price: f64,
+}
+
+fn nat_to_f64(n: &Nat) -> f64 {
+    // `Nat` has no lossy float conversion; its decimal digits parse into the nearest `f64`.
+    n.0.to_string().parse().unwrap_or(f64::INFINITY)
+}
+
+/// Price of `base` in units of the pool's other token, and the value of the pool's quote-side
+/// (virtual) reserve in quote tokens.
+///
+/// `sqrtPriceX96` is the square root of the raw token1-per-token0 price, as a Q64.96 fixed-point
+/// number. With liquidity `L`, the virtual reserves are `L / sqrtP` of token0 and `L * sqrtP` of
+/// token1.
+fn price_from_pool(
+    metadata: &PoolMetadata,
+    base: &str,
+    base_decimals: u8,
+    quote_decimals: u8,
+) -> Option<(f64, f64)> {
+    let sqrt_price = nat_to_f64(&metadata.sqrt_price_x96) / Q96;
+    let liquidity = nat_to_f64(&metadata.liquidity);
+    if !sqrt_price.is_finite() || sqrt_price <= 0.0 || !liquidity.is_finite() {
+        return None;
+    }
+    let scale = 10f64.powi(i32::from(base_decimals) - i32::from(quote_decimals));
+
+    let (price, quote_reserve) = if metadata.token0.address == base {
+        (
+            sqrt_price * sqrt_price * scale,
+            liquidity * sqrt_price / 10f64.powi(i32::from(quote_decimals)),
+        )
+    } else if metadata.token1.address == base {
+        (
+            scale / (sqrt_price * sqrt_price),
+            liquidity / sqrt_price / 10f64.powi(i32::from(quote_decimals)),
+        )
+    } else {
+        return None;
+    };
+    (price.is_finite() && price > 0.0).then_some((price, quote_reserve))
+}
+
+/// USD price of `base` from a pool against `quote`, or `None` if the pool is too thin.
+fn usd_price_from_pool(
+    metadata: &PoolMetadata,
+    base: &str,
+    base_decimals: u8,
+    quote: Quote,
+) -> Option<f64> {
+    let (price, quote_reserve) = price_from_pool(metadata, base, base_decimals, quote.decimals)?;
+    (quote_reserve * quote.usd_price > MIN_QUOTE_RESERVE_USD).then_some(price * quote.usd_price)
+}
+
+fn parse_principal(text: &str) -> Principal {
+    Principal::from_text(text).unwrap_or_else(|e| unreachable!("invalid canister id {text}: {e}"))
+}
+
+/// Supplemental USD prices for ICRC ledger tokens, read from `ICPSwap` pool canisters with
+/// inter-canister queries.
+///
+/// Each token is priced from its pool against ckUSDC (taken at $1) or, failing that, against ICP
+/// at the cached ICP rate. Unlike [`super::icpswap::IcpSwapProvider`] this issues no HTTP
+/// outcalls and trusts no off-chain API.
+#[derive(Debug, Clone)]
+pub(crate) struct IcpSwapPoolProvider {
+    icp_usd_price: Option<f64>,
+}
+
+impl IcpSwapPoolProvider {
+    /// `icp_usd_price` is the cached ICP rate; without it, tokens are only priced against ckUSDC.
+    pub(crate) fn new(icp_usd_price: Option<f64>) -> Self {
+        Self { icp_usd_price }
+    }
+
+    fn quotes(&self) -> Vec<Quote> {
+        let mut quotes = vec![Quote {
+            ledger: CKUSDC_LEDGER_CANISTER_ID,
+            standard: "ICRC2",
+            decimals: CKUSDC_DECIMALS,
+            usd_price: 1.0,
+        }];
+        if let Some(usd_price) = self.icp_usd_price.filter(|p| p.is_finite() && *p > 0.0) {
+            quotes.push(Quote {
+                ledger: ICP_LEDGER_CANISTER_ID,
+                standard: "ICP",
+                decimals: ICP_DECIMALS,
+                usd_price,
+            });
+        }
+        quotes
+    }
+
+    async fn pool_canister(ledger: Principal, quote: Quote) -> Result<Option<Principal>, String> {
+        let quote_ledger = parse_principal(quote.ledger);
+        if let Some(pool) =
+            POOL_CANISTERS.with(|c| c.borrow().get(&(ledger, quote_ledger)).copied())
+        {
+            return Ok(Some(pool));
+        }
+
+        let args = GetPoolArgs {
+            fee: Nat::from(POOL_FEE),
+            token0: IcpSwapToken {
+                address: ledger.to_text(),
+                standard: "ICRC1".to_string(),
+            },
+            token1: IcpSwapToken {
+                address: quote.ledger.to_string(),
+                standard: quote.standard.to_string(),
+            },
+        };
+        let result: IcpSwapResult<PoolData> =
+            Call::bounded_wait(parse_principal(ICPSWAP_FACTORY_CANISTER_ID), "getPool")
+                .with_arg(args)
+                .await
+                .map_err(|e| format!("getPool failed: {e}"))?
+                .candid()
+                .map_err(|e| format!("getPool returned an unexpected response: {e}"))?;
+
+        match result {
+            IcpSwapResult::Ok(pool) => {
+                POOL_CANISTERS.with(|c| {
+                    c.borrow_mut()
+                        .insert((ledger, quote_ledger), pool.canister_id)
+                });
+                Ok(Some(pool.canister_id))
+            }
+            IcpSwapResult::Err(_) => Ok(None),
+        }
+    }
+
+    async fn ledger_decimals(ledger: Principal) -> Result<u8, String> {
+        if let Some(decimals) = LEDGER_DECIMALS.with(|c| c.borrow().get(&ledger).copied()) {
+            return Ok(decimals);
+        }
+        let decimals: u8 = Call::bounded_wait(ledger, "icrc1_decimals")
+            .await
+            .map_err(|e| format!("icrc1_decimals failed: {e}"))?
+            .candid()
+            .map_err(|e| format!("icrc1_decimals returned an unexpected response: {e}"))?;
+        LEDGER_DECIMALS.with(|c| c.borrow_mut().insert(ledger, decimals));
+        Ok(decimals)
+    }
+
+    async fn pool_metadata(pool: Principal) -> Result<PoolMetadata, String> {
+        let result: IcpSwapResult<PoolMetadata> = Call::bounded_wait(pool, "metadata")
+            .await
+            .map_err(|e| format!("metadata failed: {e}"))?
+            .candid()
+            .map_err(|e| format!("metadata returned an unexpected response: {e}"))?;
+        match result {
+            IcpSwapResult::Ok(metadata) => Ok(metadata),
+            IcpSwapResult::Err(err) => Err(format!("metadata returned an error: {err:?}")),
+        }
+    }
+
+    /// Prices `ledger` against the first quote token it has a liquid enough pool with.
+    async fn fetch_icrc_token_usd(
+        &self,
+        ledger: Principal,
+    ) -> Result<Option<ExchangeData>, String> {
+        for quote in self.quotes() {
+            let Some(pool) = Self::pool_canister(ledger, quote).await? else {
+                continue;
+            };
+            let base_decimals = Self::ledger_decimals(ledger).await?;
+            let metadata = Self::pool_metadata(pool).await?;
+            if let Some(price) =
+                usd_price_from_pool(&metadata, &ledger.to_text(), base_decimals, quote)
+            {
+                return Ok(Some(ExchangeData {
+                    timestamp_ns: time(),
+                    price: Some(price),
+                    price_24h_change_pct: None,
+                    market_cap: None,
+                    source: None,
+                    sources: None,
+                    stale: None,
+                }));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl SupplementalPriceProvider for IcpSwapPoolProvider {
+    fn id(&self) -> &'static str {
+        "icpswap_pool"
+    }
+
+    fn supplement<'a>(&'a self, missing: &'a [StoredTokenId]) -> SupplementalPricesFuture<'a> {
+        Box::pin(async move {
+            let quote_ledgers =
+                [CKUSDC_LEDGER_CANISTER_ID, ICP_LEDGER_CANISTER_ID].map(parse_principal);
+            let outcomes = join_all(missing.iter().filter_map(|stored| {
+                let StoredTokenId(TokenId::Icrc(ledger_id)) = stored else {
+                    return None;
+                };
+                // The quote tokens themselves have no pool against themselves.
+                if quote_ledgers.contains(ledger_id) {
+                    return None;
+                }
+
+                Some(async move {
+                    let outcome = self.fetch_icrc_token_usd(*ledger_id).await;
+                    (stored.clone(), ledger_id, outcome)
+                })
+            }))
+            .await;
+
+            let mut out = Vec::new();
+
+            for (stored, ledger_id, outcome) in outcomes {
+                match outcome {
+                    Ok(Some(data)) => out.push((stored, data)),
+                    Ok(None) => {}
+                    Err(err) => {
+                        ic_cdk::println!("ICPSwap pool price fetch for {ledger_id} failed: {err}");
+                    }
+                }
+            }
+
+            Ok(out)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    const BASE: &str = "mxzaz-hqaaa-aaaar-qaada-cai";
+
+    fn token(address: &str) -> IcpSwapToken {
+        IcpSwapToken {
+            address: address.to_string(),
+            standard: "ICRC1".to_string(),
+        }
+    }
+
+    /// Pool metadata for a raw token1-per-token0 price of `raw_price` and liquidity `liquidity`.
+    fn metadata(token0: &str, token1: &str, raw_price: f64, liquidity: u128) -> PoolMetadata {
+        #[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let sqrt_price_x96 = (raw_price.sqrt() * Q96) as u128;
+        PoolMetadata {
+            token0: token(token0),
+            token1: token(token1),
+            liquidity: Nat::from(liquidity),
+            sqrt_price_x96: Nat::from(sqrt_price_x96),
+        }
+    }
+
+    fn ckusdc() -> Quote {
+        Quote {
+            ledger: CKUSDC_LEDGER_CANISTER_ID,
+            standard: "ICRC2",
+            decimals: CKUSDC_DECIMALS,
+            usd_price: 1.0,
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            ((actual - expected) / expected).abs() < 1e-9,
+            "{actual} is not close to {expected}"
+        );
+    }
+
+    #[test]
+    fn price_of_token0_applies_decimals() {
+        // 1 base token (8 decimals) = 2.5 ckUSDC (6 decimals): 2.5e6 / 1e8 raw.
+        let pool = metadata(BASE, CKUSDC_LEDGER_CANISTER_ID, 0.025, 1_000_000_000_000);
+
+        let (price, _) = price_from_pool(&pool, BASE, 8, CKUSDC_DECIMALS).unwrap();
+
+        assert_close(price, 2.5);
+    }
+
+    #[test]
+    fn price_of_token1_is_inverted() {
+        // Base is token1: 1 base (8 decimals) = 2.5 ckUSDC, i.e. 1e8 / 2.5e6 = 40 raw base per
+        // raw ckUSDC.
+        let pool = metadata(CKUSDC_LEDGER_CANISTER_ID, BASE, 40.0, 1_000_000_000_000);
+
+        let (price, _) = price_from_pool(&pool, BASE, 8, CKUSDC_DECIMALS).unwrap();
+
+        assert_close(price, 2.5);
+    }
+
+    #[test]
+    fn quote_reserve_uses_virtual_reserves() {
+        // L = 1e12, sqrtP = 0.5 (raw price 0.25): token1 reserve L * sqrtP = 5e11 raw = 500k ckUSDC.
+        let pool = metadata(BASE, CKUSDC_LEDGER_CANISTER_ID, 0.25, 1_000_000_000_000);
+
+        let (_, quote_reserve) = price_from_pool(&pool, BASE, 6, CKUSDC_DECIMALS).unwrap();
+
+        assert_close(quote_reserve, 500_000.0);
+    }
+
+    #[test]
+    fn pool_without_base_is_rejected() {
+        let pool = metadata(ICP_LEDGER_CANISTER_ID, CKUSDC_LEDGER_CANISTER_ID, 1.0, 1);
+
+        assert_eq!(price_from_pool(&pool, BASE, 8, CKUSDC_DECIMALS), None);
+    }
+
+    #[test]
+    fn zero_sqrt_price_is_rejected() {
+        let mut pool = metadata(BASE, CKUSDC_LEDGER_CANISTER_ID, 1.0, 1);
+        pool.sqrt_price_x96 = Nat::from(0u32);
+
+        assert_eq!(price_from_pool(&pool, BASE, 8, CKUSDC_DECIMALS), None);
+    }
+
+    #[test]
+    fn thin_pools_are_not_priced() {
+        // Quote reserve: L * sqrtP = 4e8 * 1 raw = 400 ckUSDC.
+        let thin = metadata(BASE, CKUSDC_LEDGER_CANISTER_ID, 1.0, 400_000_000);
+        let liquid = metadata(BASE, CKUSDC_LEDGER_CANISTER_ID, 1.0, 600_000_000);
+
+        assert_eq!(usd_price_from_pool(&thin, BASE, 6, ckusdc()), None);
+        assert!(usd_price_from_pool(&liquid, BASE, 6, ckusdc()).is_some());
+    }
+
+    #[test]
+    fn icp_quoted_prices_are_converted_to_usd() {
+        // 1 base (8 decimals) = 0.5 ICP (8 decimals), ICP at $10.
+        let pool = metadata(BASE, ICP_LEDGER_CANISTER_ID, 0.5, 1_000_000_000_000_000);
+        let icp = Quote {
+            ledger: ICP_LEDGER_CANISTER_ID,
+            standard: "ICP",
+            decimals: ICP_DECIMALS,
+            usd_price: 10.0,
+        };
+
+        assert_close(usd_price_from_pool(&pool, BASE, 8, icp).unwrap(), 5.0);
+    }
+
+    #[test]
+    fn quotes_include_icp_only_with_a_valid_rate() {
+        let ledgers = |provider: IcpSwapPoolProvider| {
+            provider
+                .quotes()
+                .iter()
+                .map(|q| q.ledger)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            ledgers(IcpSwapPoolProvider::new(Some(10.0))),
+            vec![CKUSDC_LEDGER_CANISTER_ID, ICP_LEDGER_CANISTER_ID]
+        );
+        assert_eq!(
+            ledgers(IcpSwapPoolProvider::new(None)),
+            vec![CKUSDC_LEDGER_CANISTER_ID]
+        );
+        assert_eq!(
+            ledgers(IcpSwapPoolProvider::new(Some(f64::NAN))),
+            vec![CKUSDC_LEDGER_CANISTER_ID]
+        );
+    }
+}
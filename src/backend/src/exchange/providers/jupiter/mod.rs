@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+
+use futures::future::join_all;
+use ic_cdk::{api::time, management_canister::HttpHeader};
+use serde::Deserialize;
+use serde_json::from_slice;
+use shared::types::{exchange::ExchangeData, token_id::TokenId};
+
+use crate::{
+    exchange::supplemental::{SupplementalPriceProvider, SupplementalPricesFuture},
+    types::storable::StoredTokenId,
+    utils::http_outcall,
+};
+
+const DEFAULT_BASE_URL: &str = "https://lite-api.jup.ag";
+const PRICE_PATH: &str = "/price/v3";
+/// The price API accepts at most this many mints per request.
+const CHUNK_SIZE: usize = 50;
+/// Each price entry (mint address plus price, liquidity, 24h change, block id and decimals) is
+/// about 200 bytes; 512 bytes per mint keeps a safe margin.
+const PER_MINT_RESPONSE_BYTES: u64 = 512;
+/// Floor so a single-mint request still tolerates the headers and the JSON envelope.
+const MIN_RESPONSE_BYTES: u64 = 2_048;
+/// Mints whose pools hold at most this much liquidity are considered too thin to price and their
+/// prices are discarded.
+const MIN_LIQUIDITY_USD: f64 = 500.0;
+
+/// Reservation for an outcall fetching `mint_count` prices.
+fn response_bytes_for(mint_count: usize) -> u64 {
+    (mint_count as u64)
+        .saturating_mul(PER_MINT_RESPONSE_BYTES)
+        .max(MIN_RESPONSE_BYTES)
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+struct JupiterPrice {
+    #[serde(rename = "usdPrice")]
+    usd_price: f64,
+    #[serde(default)]
+    liquidity: Option<f64>,
+    #[serde(rename = "priceChange24h", default)]
+    price_change_24h: Option<f64>,
+}
+
+fn exchange_data_from_jupiter_price(
+    price: JupiterPrice,
+    timestamp_ns: u64,
+) -> Option<ExchangeData> {
+    if !price.usd_price.is_finite() || price.usd_price <= 0.0 {
+        return None;
+    }
+    let liquidity = price.liquidity?;
+    if !liquidity.is_finite() || liquidity <= MIN_LIQUIDITY_USD {
+        return None;
+    }
+    Some(ExchangeData {
+        timestamp_ns,
+        price: Some(price.usd_price),
+        price_24h_change_pct: price.price_change_24h.filter(|v| v.is_finite()),
+        market_cap: None,
+        source: None,
+        sources: None,
+        stale: None,
+    })
+}
+
+/// Parses a price response keyed by mint. Mints the API does not know are absent from the
+/// response; mints without a usable price are dropped.
+fn parse_jupiter_body(
+    body: &[u8],
+    timestamp_ns: u64,
+) -> Result<HashMap<String, ExchangeData>, String> {
+    let parsed: HashMap<String, Option<JupiterPrice>> =
+        from_slice(body).map_err(|e| format!("Failed to parse Jupiter response: {e}"))?;
+    Ok(parsed
+        .into_iter()
+        .filter_map(|(mint, price)| {
+            exchange_data_from_jupiter_price(price?, timestamp_ns).map(|data| (mint, data))
+        })
+        .collect())
+}
+
+/// Supplemental USD prices for SPL tokens on Solana mainnet via the
+/// [Jupiter price API](https://dev.jup.ag/docs/price), which derives them from on-chain DEX
+/// liquidity and so also covers long-tail mints unknown to `CoinGecko`.
+#[derive(Debug, Clone)]
+pub(crate) struct JupiterProvider {
+    base_url: String,
+    replicated: bool,
+}
+
+impl Default for JupiterProvider {
+    fn default() -> Self {
+        Self {
+            base_url: DEFAULT_BASE_URL.to_string(),
+            replicated: false,
+        }
+    }
+}
+
+impl JupiterProvider {
+    pub(crate) fn new(replicated: bool) -> Self {
+        Self {
+            replicated,
+            ..Self::default()
+        }
+    }
+
+    async fn fetch_spl_usd(&self, mints: &[&str]) -> Result<HashMap<String, ExchangeData>, String> {
+        let url = format!(
+            "{}{PRICE_PATH}?ids={}",
+            self.base_url.trim_end_matches('/'),
+            mints.join(",")
+        );
+
+        let response = http_outcall::get(
+            &url,
+            vec![HttpHeader {
+                name: "Accept".to_string(),
+                value: "application/json".to_string(),
+            }],
+            response_bytes_for(mints.len()),
+            self.replicated,
+        )
+        .await?;
+
+        parse_jupiter_body(&response.body, time())
+    }
+}
+
+impl SupplementalPriceProvider for JupiterProvider {
+    fn id(&self) -> &'static str {
+        "jupiter"
+    }
+
+    fn supplement<'a>(&'a self, missing: &'a [StoredTokenId]) -> SupplementalPricesFuture<'a> {
+        Box::pin(async move {
+            let mints: Vec<&str> = missing
+                .iter()
+                .filter_map(|stored| match &stored.0 {
+                    TokenId::SplMainnet(mint) => Some(mint.as_str()),
+                    _ => None,
+                })
+                .collect();
+
+            let outcomes = join_all(
+                mints
+                    .chunks(CHUNK_SIZE)
+                    .map(|chunk| self.fetch_spl_usd(chunk)),
+            )
+            .await;
+
+            let mut prices = HashMap::new();
+            for outcome in outcomes {
+                match outcome {
+                    Ok(chunk_prices) => prices.extend(chunk_prices),
+                    Err(err) => ic_cdk::println!("Jupiter price fetch failed: {err}"),
+                }
+            }
+
+            Ok(missing
+                .iter()
+                .filter_map(|stored| match &stored.0 {
+                    TokenId::SplMainnet(mint) => prices
+                        .get(mint.as_str())
+                        .map(|data| (stored.clone(), data.clone())),
+                    _ => None,
+                })
+                .collect())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    const BONK: &str = "DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263";
+    const WIF: &str = "EKpQGSJtjMFqKZ9KQanSqYXRcF8fBopzLHYxdM65zcjm";
+
+    #[test]
+    fn parse_jupiter_body_success() {
+        let json = format!(
+            r#"{{"{BONK}":{{"usdPrice":0.0000213,"blockId":348004023,"decimals":5,"priceChange24h":-3.25,"liquidity":12500000.5}}}}"#
+        );
+
+        let prices = parse_jupiter_body(json.as_bytes(), 99).unwrap();
+
+        let data = &prices[BONK];
+        assert_eq!(data.timestamp_ns, 99);
+        assert_eq!(data.price, Some(0.000_021_3));
+        assert_eq!(data.price_24h_change_pct, Some(-3.25));
+        assert_eq!(data.market_cap, None);
+    }
+
+    #[test]
+    fn parse_jupiter_body_drops_null_and_unknown_mints() {
+        let json = format!(r#"{{"{BONK}":null}}"#);
+
+        let prices = parse_jupiter_body(json.as_bytes(), 0).unwrap();
+
+        assert!(prices.is_empty());
+    }
+
+    #[test]
+    fn parse_jupiter_body_rejects_malformed_json() {
+        assert!(parse_jupiter_body(b"<html>", 0).is_err());
+    }
+
+    #[test]
+    fn parse_jupiter_body_rejects_bad_price() {
+        let json = format!(
+            r#"{{"{BONK}":{{"usdPrice":0,"liquidity":10000}},"{WIF}":{{"usdPrice":-1.5,"liquidity":10000}}}}"#
+        );
+
+        assert!(parse_jupiter_body(json.as_bytes(), 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn parse_jupiter_body_rejects_liquidity_at_or_below_threshold() {
+        let json = format!(
+            r#"{{"{BONK}":{{"usdPrice":1.0,"liquidity":500}},"{WIF}":{{"usdPrice":1.0,"liquidity":499.99}}}}"#
+        );
+
+        assert!(parse_jupiter_body(json.as_bytes(), 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn parse_jupiter_body_accepts_liquidity_above_threshold() {
+        let json = format!(r#"{{"{BONK}":{{"usdPrice":1.0,"liquidity":500.01}}}}"#);
+
+        let prices = parse_jupiter_body(json.as_bytes(), 0).unwrap();
+
+        assert_eq!(prices[BONK].price, Some(1.0));
+        assert_eq!(prices[BONK].price_24h_change_pct, None);
+    }
+
+    #[test]
+    fn parse_jupiter_body_rejects_missing_liquidity() {
+        let json = format!(r#"{{"{BONK}":{{"usdPrice":1.0,"priceChange24h":2.0}}}}"#);
+
+        assert!(parse_jupiter_body(json.as_bytes(), 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn response_bytes_scale_with_mint_count() {
+        assert_eq!(response_bytes_for(1), MIN_RESPONSE_BYTES);
+        assert_eq!(
+            response_bytes_for(CHUNK_SIZE),
+            CHUNK_SIZE as u64 * PER_MINT_RESPONSE_BYTES
+        );
+    }
+}
@@ -1,2 +1,5 @@
 pub(crate) mod coingecko;
+pub(crate) mod erc4626;
 pub(crate) mod icpswap;
+pub(crate) mod icpswap_pool;
+pub(crate) mod jupiter;
@@ -18,32 +18,52 @@ use shared::{
         api_keys::ApiKeys,
         backend_config::{Arg, Config},
         bitcoin::{
-            BtcAddPendingTransactionRequest, BtcGetFeePercentilesRequest,
-            BtcGetPendingTransactionsRequest,
+            BtcAddPendingTransactionRequest, BtcBuildTransactionRequest, BtcBumpFeeRequest,
+            BtcGetFeePercentilesRequest, BtcGetPendingTransactionsRequest,
+            UpdateBtcSettingsRequest,
+        },
+        certification::CertifiedUserData,
+        contact::{
+            BatchContactsRequest, ContactExportFormat, CreateContactRequest,
+            CreateVerifiedContactRequest, ImportContactsRequest, UpdateContactRequest,
         },
-        contact::{CreateContactRequest, UpdateContactRequest},
         custom_token::CustomToken,
         dapp::AddHiddenDappIdRequest,
-        exchange::ExchangeRate,
+        exchange::{
+            CertifiedExchangeRate, ExchangeRate, GetExchangeRateHistoryRequest, PriceCandle,
+            PriceOverride, SetPriceOverrideRequest, UpdateFiatSettingsRequest,
+        },
         experimental_feature::UpdateExperimentalFeaturesSettingsRequest,
         network::{SaveNetworksSettingsRequest, SetShowTestnetsRequest},
         notification::AddDismissedNotificationRequest,
         onramper::SignOnramperWidgetUrlRequest,
         personal_note::{DeletePersonalNoteRequest, SetPersonalNoteRequest},
         personal_note_share::CreatePersonalNoteShareRequest,
+        portfolio::{
+            GetPortfolioSnapshotsRequest, GetPortfolioSnapshotsResponse,
+            SavePortfolioSnapshotRequest, UpdatePortfolioSettingsRequest,
+        },
+        price_alert::{
+            CreatePriceAlertRequest, DeletePriceAlertRequest, PriceAlert, TriggeredPriceAlert,
+        },
         result_types::{
             ActiveUserTransactionResult, AddUserDismissedNotificationResult,
-            AddUserHiddenDappIdResult, AllowSigningResult, BtcAddPendingTransactionResult,
+            AddUserHiddenDappIdResult, AllowSigningResult, BatchContactsResult,
+            BtcAddPendingTransactionResult, BtcBuildTransactionResult, BtcBumpFeeResult,
             BtcGetFeePercentilesResult, BtcGetPendingTransactionsResult,
             ConsumePersonalNoteShareResult, CreateContactResult, CreatePersonalNoteShareResult,
-            CreateUserProfileResult, DeleteActiveUserTransactionResult, DeleteContactResult,
-            DeletePersonalNoteResult, GetActiveUserTransactionsResult, GetAgreementHistoryResult,
-            GetAllowedCyclesResult, GetContactResult, GetContactsResult,
-            GetPersonalNoteShareResult, GetPersonalNoteSharesCountResult,
-            GetPersonalNotesCountResult, GetPersonalNotesResult, GetUserProfileResult,
-            GetUserTransactionsResult, PersonalNotesVetkeyResult, SaveUserTransactionsResult,
-            SetPersonalNoteResult, SetUserShowTestnetsResult, SignOnramperWidgetUrlResult,
+            CreatePriceAlertResult, CreateUserProfileResult, CreateVerifiedContactResult,
+            DeleteActiveUserTransactionResult, DeleteContactResult, DeletePersonalNoteResult,
+            DeletePriceAlertResult, ExportContactsResult, ExportUserTransactionsResult,
+            GetActiveUserTransactionsResult, GetAgreementHistoryResult, GetAllowedCyclesResult,
+            GetContactResult, GetContactsResult, GetPersonalNoteShareResult,
+            GetPersonalNoteSharesCountResult, GetPersonalNotesCountResult, GetPersonalNotesResult,
+            GetUserActivityResult, GetUserProfileResult, GetUserTransactionsResult,
+            ImportContactsResult, PersonalNotesVetkeyResult, SavePortfolioSnapshotResult,
+            SaveUserTransactionsResult, SetPersonalNoteResult, SetPriceOverrideResult,
+            SetUserShowTestnetsResult, SignOnramperWidgetUrlResult, UpdateBtcSettingsResult,
             UpdateContactResult, UpdateExperimentalFeaturesSettingsResult,
+            UpdateFiatSettingsResult, UpdatePortfolioSettingsResult,
             UpdateProviderAgreementsResult, UpdateTransactionFilterSettingsResult,
             UpdateUserAgreementsResult, UpdateUserNetworkSettingsResult,
         },
@@ -54,7 +74,10 @@ use shared::{
         token_id::TokenId,
         transaction_settings::UpdateTransactionFilterSettingsRequest,
         user_profile::HasUserProfileResponse,
-        user_transaction::{GetUserTransactionsRequest, SaveUserTransactionsRequest},
+        user_transaction::{
+            ExportUserTransactionsRequest, GetUserActivityRequest, GetUserTransactionsRequest,
+            SaveUserTransactionsRequest,
+        },
         Stats, Timestamp,
     },
 };
@@ -64,11 +87,13 @@ use crate::state::{ensure_personal_notes, read_state, set_config};
 mod active_user_transactions;
 mod api;
 mod bitcoin;
+mod certification;
 mod contacts;
 mod delegation;
 mod exchange;
 mod onramper;
 mod personal_notes;
+mod portfolio;
 mod signer;
 mod state;
 mod status;
@@ -90,9 +115,11 @@ pub fn init(arg: Arg) {
 
     // Initialize the Bitcoin fee percentiles cache
     bitcoin::api::init_fee_percentiles_cache();
+    bitcoin::pending_tx_tracker::init_pending_transactions_tracker();
 
     utils::housekeeping::start_periodic_housekeeping_timers();
 
+    read_state(|s| exchange::certification::init_certified_exchange_rates(&s.exchange_rates));
     exchange::start_exchange_rate_timer();
 }
 
@@ -122,10 +149,19 @@ pub fn post_upgrade(arg: Option<Arg>) {
 
     // Initialize the Bitcoin fee percentiles cache
     bitcoin::api::init_fee_percentiles_cache();
+    bitcoin::pending_tx_tracker::init_pending_transactions_tracker();
 
     utils::housekeeping::start_periodic_housekeeping_timers();
 
+    read_state(|s| exchange::certification::init_certified_exchange_rates(&s.exchange_rates));
     exchange::start_exchange_rate_timer();
+
+    certification::users::schedule_user_data_certification();
+
+    // TODO: remove once all canisters have been upgraded past the per-entry user transactions
+    // migration.
+    transactions::migration::schedule_legacy_transactions_migration();
+    transactions::migration::schedule_activity_index_backfill();
 }
 
 export_candid!();
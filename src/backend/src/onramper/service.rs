@@ -9,13 +9,17 @@
 //! the signing oracle), and a frontend/backend derivation mismatch fails loudly instead of
 //! producing a URL that pays an unspendable address.
 
-use std::collections::HashMap;
+use std::{collections::HashMap, str::FromStr};
 
 use candid::Principal;
 use ic_cdk::bitcoin_canister::Network as BitcoinNetwork;
-use shared::types::onramper::{
-    OnramperSignedEntry, SignOnramperWidgetUrlError, SignOnramperWidgetUrlRequest,
-    SignOnramperWidgetUrlResponse,
+use shared::types::{
+    account::BtcAddress,
+    bitcoin::BtcAddressType,
+    onramper::{
+        OnramperSignedEntry, SignOnramperWidgetUrlError, SignOnramperWidgetUrlRequest,
+        SignOnramperWidgetUrlResponse,
+    },
 };
 
 use super::model::sign_widget_url;
@@ -69,18 +73,29 @@ async fn verify_caller_network_wallets(
     // management-canister public-key reads. Without this cache an authenticated caller could repeat
     // the same network thousands of times in one rate-limited request and amplify it into that many
     // inter-canister reads (cycle drain). Distinct networks are bounded — only four are recognized
-    // and any other resolves to `None` and fails fast — so caching by normalized id caps the reads
-    // at one per supported network regardless of vector length, while preserving the exact
-    // per-entry signed output (duplicates are still emitted as supplied).
-    let mut derived_cache: HashMap<String, Option<String>> = HashMap::new();
+    // and any other resolves to `None` and fails fast — so caching by normalized id (and BTC
+    // address type) caps the reads at one per supported network and address type regardless of
+    // vector length, while preserving the exact per-entry signed output (duplicates are still
+    // emitted as supplied).
+    let mut derived_cache: HashMap<(String, Option<BtcAddressType>), Option<String>> =
+        HashMap::new();
     let mut verified = Vec::with_capacity(provided.len());
     for entry in provided {
         let network_id = entry.key.to_lowercase();
-        let derived = if let Some(cached) = derived_cache.get(&network_id) {
+        // The caller owns a BTC address of every type, so the supplied one is checked against the
+        // derived address of its own type.
+        let btc_address_type = (network_id == ONRAMPER_NETWORK_BITCOIN)
+            .then(|| BtcAddress::from_str(&entry.value).ok())
+            .flatten()
+            .as_ref()
+            .and_then(BtcAddressType::of);
+        let cache_key = (network_id, btc_address_type);
+        let derived = if let Some(cached) = derived_cache.get(&cache_key) {
             cached.clone()
         } else {
-            let freshly_derived = derive_network_address(&network_id, principal).await;
-            derived_cache.insert(network_id, freshly_derived.clone());
+            let freshly_derived =
+                derive_network_address(&cache_key.0, btc_address_type, principal).await;
+            derived_cache.insert(cache_key, freshly_derived.clone());
             freshly_derived
         }
         .ok_or(SignOnramperWidgetUrlError::AddressDerivationFailed)?;
@@ -95,12 +110,17 @@ async fn verify_caller_network_wallets(
     Ok(verified)
 }
 
-/// Derives the caller's own address for a given `OnRamper` network id, or `None` when the network
-/// is unknown or its derivation failed (both treated as "cannot verify"). `network_id` must already
-/// be normalized to lowercase by the caller (which also keys the per-request derivation cache).
-async fn derive_network_address(id: &str, principal: &Principal) -> Option<String> {
+/// Derives the caller's own address for a given `OnRamper` network id, of `btc_address_type` for
+/// Bitcoin, or `None` when the network or address type is unknown or its derivation failed (all
+/// treated as "cannot verify"). `network_id` must already be normalized to lowercase by the caller
+/// (which also keys the per-request derivation cache).
+async fn derive_network_address(
+    id: &str,
+    btc_address_type: Option<BtcAddressType>,
+    principal: &Principal,
+) -> Option<String> {
     if id == ONRAMPER_NETWORK_BITCOIN {
-        signer::btc_principal_to_p2wpkh_address(BitcoinNetwork::Mainnet, principal)
+        signer::btc_principal_to_address(BitcoinNetwork::Mainnet, principal, btc_address_type?)
             .await
             .ok()
     } else if id == ONRAMPER_NETWORK_ETHEREUM {
@@ -0,0 +1,9 @@
+//! Opt-in daily snapshots of the USD value of a user's portfolio.
+//!
+//! The frontend submits the user's balances; the backend values them at the cached exchange
+//! rates and keeps one snapshot per user and day for [`PORTFOLIO_SNAPSHOT_RETENTION_DAYS`].
+//!
+//! [`PORTFOLIO_SNAPSHOT_RETENTION_DAYS`]: shared::types::portfolio::PORTFOLIO_SNAPSHOT_RETENTION_DAYS
+
+pub(crate) mod model;
+pub(crate) mod service;
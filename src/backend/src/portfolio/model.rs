@@ -0,0 +1,384 @@
+use std::ops::Bound;
+
+use candid::Principal;
+use shared::types::{
+    portfolio::{
+        GetPortfolioSnapshotsRequest, GetPortfolioSnapshotsResponse, PortfolioSnapshot,
+        PortfolioTokenBalance, MAX_GET_PORTFOLIO_SNAPSHOTS_RESULTS,
+    },
+    token_id::TokenId,
+    Timestamp,
+};
+
+use crate::types::{
+    maps::{PortfolioSnapshotDayIndexMap, PortfolioSnapshotMap},
+    Candid, StoredPrincipal,
+};
+
+const NANOS_PER_DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+/// Day of `timestamp_ns`, counted from the epoch.
+pub fn day_of(timestamp_ns: Timestamp) -> u64 {
+    timestamp_ns / NANOS_PER_DAY
+}
+
+/// Values `balances` in USD at the rates returned by `usd_price`.
+///
+/// Balances without a rate are counted in `unpriced_tokens` and left out of the total.
+pub fn value_portfolio(
+    balances: &[PortfolioTokenBalance],
+    usd_price: impl Fn(&TokenId) -> Option<f64>,
+    now: Timestamp,
+) -> PortfolioSnapshot {
+    let mut total_usd = 0.0;
+    let mut unpriced_tokens = 0;
+    for PortfolioTokenBalance {
+        token_id,
+        balance,
+        decimals,
+    } in balances
+    {
+        // `Nat` has no lossy float conversion; its decimal digits parse into the nearest `f64`.
+        let amount = balance
+            .0
+            .to_string()
+            .parse::<f64>()
+            .unwrap_or(f64::INFINITY)
+            / 10f64.powi(i32::from(*decimals));
+        match usd_price(token_id) {
+            Some(price) if price.is_finite() && amount.is_finite() => {
+                total_usd += amount * price;
+            }
+            _ => unpriced_tokens += 1,
+        }
+    }
+
+    PortfolioSnapshot {
+        timestamp_ns: now,
+        total_usd,
+        unpriced_tokens,
+    }
+}
+
+/// Stores `snapshot` as the principal's snapshot for its day, replacing an earlier one of the
+/// same day.
+pub fn save_snapshot(
+    snapshots: &mut PortfolioSnapshotMap,
+    by_day: &mut PortfolioSnapshotDayIndexMap,
+    principal: Principal,
+    snapshot: PortfolioSnapshot,
+) {
+    let principal = StoredPrincipal(principal);
+    let day = day_of(snapshot.timestamp_ns);
+    snapshots.insert((principal, day), Candid(snapshot));
+    by_day.insert((day, principal), ());
+}
+
+/// Reads a page of the principal's snapshots, newest first.
+///
+/// With a `cursor`, the page starts at the day before the cursor's day.
+pub fn get_snapshots(
+    snapshots: &PortfolioSnapshotMap,
+    principal: Principal,
+    GetPortfolioSnapshotsRequest {
+        max_results,
+        cursor,
+    }: GetPortfolioSnapshotsRequest,
+) -> GetPortfolioSnapshotsResponse {
+    let principal = StoredPrincipal(principal);
+    let max_results = usize::try_from(max_results.min(MAX_GET_PORTFOLIO_SNAPSHOTS_RESULTS))
+        .expect("max_results should fit in usize");
+    let upper = match cursor {
+        Some(cursor) => Bound::Excluded((principal, day_of(cursor))),
+        None => Bound::Included((principal, u64::MAX)),
+    };
+
+    let mut page = snapshots
+        .range((Bound::Included((principal, 0)), upper))
+        .rev()
+        .take(max_results + 1)
+        .map(|entry| entry.value().0);
+    let snapshots: Vec<PortfolioSnapshot> = page.by_ref().take(max_results).collect();
+    let next_cursor = page
+        .next()
+        .and(snapshots.last())
+        .map(|snapshot| snapshot.timestamp_ns);
+
+    GetPortfolioSnapshotsResponse {
+        snapshots,
+        next_cursor,
+    }
+}
+
+/// Removes all of the principal's snapshots and returns how many were removed.
+pub fn delete_snapshots(
+    snapshots: &mut PortfolioSnapshotMap,
+    by_day: &mut PortfolioSnapshotDayIndexMap,
+    principal: Principal,
+) -> u64 {
+    let principal = StoredPrincipal(principal);
+    let days: Vec<u64> = snapshots
+        .keys_range((principal, 0)..=(principal, u64::MAX))
+        .map(|(_, day)| day)
+        .collect();
+    for &day in &days {
+        snapshots.remove(&(principal, day));
+        by_day.remove(&(day, principal));
+    }
+    days.len() as u64
+}
+
+/// Removes every snapshot of a day before `first_kept_day` and returns how many were removed.
+pub fn evict_snapshots_before(
+    snapshots: &mut PortfolioSnapshotMap,
+    by_day: &mut PortfolioSnapshotDayIndexMap,
+    first_kept_day: u64,
+) -> u64 {
+    let expired: Vec<(u64, StoredPrincipal)> = by_day
+        .keys()
+        .take_while(|(day, _)| *day < first_kept_day)
+        .collect();
+    for &(day, principal) in &expired {
+        by_day.remove(&(day, principal));
+        snapshots.remove(&(principal, day));
+    }
+    expired.len() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use candid::{Nat, Principal};
+    use ic_stable_structures::{
+        memory_manager::{MemoryId, MemoryManager},
+        DefaultMemoryImpl,
+    };
+    use pretty_assertions::assert_eq;
+    use shared::types::{
+        portfolio::{
+            GetPortfolioSnapshotsRequest, PortfolioSnapshot, PortfolioTokenBalance,
+            MAX_GET_PORTFOLIO_SNAPSHOTS_RESULTS,
+        },
+        token_id::TokenId,
+    };
+
+    use super::{
+        delete_snapshots, evict_snapshots_before, get_snapshots, save_snapshot, value_portfolio,
+        NANOS_PER_DAY,
+    };
+    use crate::types::maps::{PortfolioSnapshotDayIndexMap, PortfolioSnapshotMap};
+
+    const ALICE: &str = "7blps-itamd-lzszp-7lbda-4nngn-fev5u-2jvpn-6y3ap-eunp7-kz57e-fqe";
+
+    struct Maps {
+        snapshots: PortfolioSnapshotMap,
+        by_day: PortfolioSnapshotDayIndexMap,
+    }
+
+    fn setup() -> Maps {
+        let memory_manager = MemoryManager::init(DefaultMemoryImpl::default());
+        Maps {
+            snapshots: PortfolioSnapshotMap::init(memory_manager.get(MemoryId::new(0))),
+            by_day: PortfolioSnapshotDayIndexMap::init(memory_manager.get(MemoryId::new(1))),
+        }
+    }
+
+    fn alice() -> Principal {
+        Principal::from_text(ALICE).unwrap()
+    }
+
+    fn bob() -> Principal {
+        Principal::from_slice(&[2])
+    }
+
+    fn snapshot(day: u64, total_usd: f64) -> PortfolioSnapshot {
+        PortfolioSnapshot {
+            timestamp_ns: day * NANOS_PER_DAY + 1,
+            total_usd,
+            unpriced_tokens: 0,
+        }
+    }
+
+    fn save_days(maps: &mut Maps, principal: Principal, days: impl IntoIterator<Item = u64>) {
+        for day in days {
+            #[expect(clippy::cast_precision_loss)]
+            save_snapshot(
+                &mut maps.snapshots,
+                &mut maps.by_day,
+                principal,
+                snapshot(day, day as f64),
+            );
+        }
+    }
+
+    fn page(maps: &Maps, principal: Principal, cursor: Option<u64>, max_results: u64) -> Vec<u64> {
+        get_snapshots(
+            &maps.snapshots,
+            principal,
+            GetPortfolioSnapshotsRequest {
+                max_results,
+                cursor,
+            },
+        )
+        .snapshots
+        .iter()
+        .map(|snapshot| snapshot.timestamp_ns / NANOS_PER_DAY)
+        .collect()
+    }
+
+    #[test]
+    fn test_value_portfolio_applies_decimals_and_counts_unpriced_tokens() {
+        let balances = [
+            PortfolioTokenBalance {
+                token_id: TokenId::IcpNative,
+                balance: Nat::from(250_000_000u64),
+                decimals: 8,
+            },
+            PortfolioTokenBalance {
+                token_id: TokenId::EvmNative(1),
+                balance: Nat::from(500_000_000_000_000_000u64),
+                decimals: 18,
+            },
+            PortfolioTokenBalance {
+                token_id: TokenId::SolNativeMainnet,
+                balance: Nat::from(1u64),
+                decimals: 9,
+            },
+        ];
+        let usd_price = |token_id: &TokenId| match token_id {
+            TokenId::IcpNative => Some(4.0),
+            TokenId::EvmNative(_) => Some(3_000.0),
+            _ => None,
+        };
+
+        let snapshot = value_portfolio(&balances, usd_price, 42);
+
+        assert_eq!(
+            snapshot,
+            PortfolioSnapshot {
+                timestamp_ns: 42,
+                total_usd: 1_510.0,
+                unpriced_tokens: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_save_keeps_last_snapshot_of_the_day() {
+        let mut maps = setup();
+        save_snapshot(
+            &mut maps.snapshots,
+            &mut maps.by_day,
+            alice(),
+            snapshot(3, 1.0),
+        );
+        save_snapshot(
+            &mut maps.snapshots,
+            &mut maps.by_day,
+            alice(),
+            PortfolioSnapshot {
+                timestamp_ns: 4 * NANOS_PER_DAY - 1,
+                total_usd: 2.0,
+                unpriced_tokens: 0,
+            },
+        );
+
+        let response = get_snapshots(
+            &maps.snapshots,
+            alice(),
+            GetPortfolioSnapshotsRequest {
+                max_results: 10,
+                cursor: None,
+            },
+        );
+
+        assert_eq!(
+            response.snapshots,
+            vec![PortfolioSnapshot {
+                timestamp_ns: 4 * NANOS_PER_DAY - 1,
+                total_usd: 2.0,
+                unpriced_tokens: 0,
+            }]
+        );
+        assert_eq!(maps.by_day.len(), 1);
+    }
+
+    #[test]
+    fn test_get_snapshots_pages_newest_first() {
+        let mut maps = setup();
+        save_days(&mut maps, alice(), 1..=5);
+        save_days(&mut maps, bob(), 1..=5);
+
+        let first = get_snapshots(
+            &maps.snapshots,
+            alice(),
+            GetPortfolioSnapshotsRequest {
+                max_results: 3,
+                cursor: None,
+            },
+        );
+        let second = page(&maps, alice(), first.next_cursor, 3);
+
+        assert_eq!(
+            first
+                .snapshots
+                .iter()
+                .map(|s| s.timestamp_ns / NANOS_PER_DAY)
+                .collect::<Vec<_>>(),
+            vec![5, 4, 3]
+        );
+        assert_eq!(second, vec![2, 1]);
+        assert_eq!(page(&maps, alice(), None, 5), vec![5, 4, 3, 2, 1]);
+        assert!(get_snapshots(
+            &maps.snapshots,
+            alice(),
+            GetPortfolioSnapshotsRequest {
+                max_results: 5,
+                cursor: None,
+            },
+        )
+        .next_cursor
+        .is_none());
+    }
+
+    #[test]
+    fn test_get_snapshots_caps_max_results() {
+        let mut maps = setup();
+        save_days(
+            &mut maps,
+            alice(),
+            0..MAX_GET_PORTFOLIO_SNAPSHOTS_RESULTS + 10,
+        );
+
+        let days = page(&maps, alice(), None, u64::MAX);
+
+        assert_eq!(days.len() as u64, MAX_GET_PORTFOLIO_SNAPSHOTS_RESULTS);
+    }
+
+    #[test]
+    fn test_delete_snapshots_only_removes_the_principal() {
+        let mut maps = setup();
+        save_days(&mut maps, alice(), 1..=3);
+        save_days(&mut maps, bob(), 1..=3);
+
+        let removed = delete_snapshots(&mut maps.snapshots, &mut maps.by_day, alice());
+
+        assert_eq!(removed, 3);
+        assert_eq!(page(&maps, alice(), None, 10), Vec::<u64>::new());
+        assert_eq!(page(&maps, bob(), None, 10), vec![3, 2, 1]);
+        assert_eq!(maps.by_day.len(), 3);
+    }
+
+    #[test]
+    fn test_evict_snapshots_before_removes_expired_days_for_everyone() {
+        let mut maps = setup();
+        save_days(&mut maps, alice(), 1..=5);
+        save_days(&mut maps, bob(), 2..=6);
+
+        let removed = evict_snapshots_before(&mut maps.snapshots, &mut maps.by_day, 4);
+
+        assert_eq!(removed, 5);
+        assert_eq!(page(&maps, alice(), None, 10), vec![5, 4]);
+        assert_eq!(page(&maps, bob(), None, 10), vec![6, 5, 4]);
+        assert_eq!(maps.by_day.len(), 5);
+    }
+}
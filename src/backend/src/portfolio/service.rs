@@ -0,0 +1,92 @@
+use candid::Principal;
+use ic_cdk::api::time;
+use shared::types::{
+    portfolio::{
+        GetPortfolioSnapshotsRequest, GetPortfolioSnapshotsResponse, PortfolioSnapshot,
+        PortfolioSnapshotError, SavePortfolioSnapshotRequest, MAX_PORTFOLIO_SNAPSHOT_BALANCES,
+        PORTFOLIO_SNAPSHOT_RETENTION_DAYS,
+    },
+    token_id::TokenId,
+};
+
+use crate::{
+    portfolio::model,
+    state::{mutate_state, read_state},
+    types::{storable::StoredTokenId, StoredPrincipal},
+    user_profile::{model::UserProfileModel, service::find_profile},
+};
+
+/// Values the submitted balances at the cached exchange rates and stores the result as the
+/// caller's snapshot for today.
+///
+/// # Errors
+/// - `TooManyBalances` if more than [`MAX_PORTFOLIO_SNAPSHOT_BALANCES`] balances are submitted.
+/// - `UserNotFound` if the caller has no user profile.
+/// - `SnapshotsDisabled` if the caller has not enabled portfolio snapshots.
+pub fn save_snapshot(
+    principal: Principal,
+    SavePortfolioSnapshotRequest { balances }: SavePortfolioSnapshotRequest,
+) -> Result<PortfolioSnapshot, PortfolioSnapshotError> {
+    if balances.len() > MAX_PORTFOLIO_SNAPSHOT_BALANCES {
+        return Err(PortfolioSnapshotError::TooManyBalances {
+            max: MAX_PORTFOLIO_SNAPSHOT_BALANCES as u64,
+        });
+    }
+
+    mutate_state(|s| {
+        let user_profile_model =
+            UserProfileModel::new(&mut s.user_profile, &mut s.user_profile_updated);
+        let profile = find_profile(StoredPrincipal(principal), &user_profile_model)
+            .map_err(|_| PortfolioSnapshotError::UserNotFound)?;
+        if !profile.portfolio_snapshots_enabled() {
+            return Err(PortfolioSnapshotError::SnapshotsDisabled);
+        }
+
+        let usd_price = |token_id: &TokenId| {
+            s.exchange_rates
+                .get(&StoredTokenId(token_id.clone()))
+                .and_then(|rate| rate.0.usd.price)
+        };
+        let snapshot = model::value_portfolio(&balances, usd_price, time());
+        model::save_snapshot(
+            &mut s.portfolio_snapshots,
+            &mut s.portfolio_snapshots_by_day,
+            principal,
+            snapshot.clone(),
+        );
+        Ok(snapshot)
+    })
+}
+
+/// Reads a page of the caller's snapshots, newest first.
+pub fn get_snapshots(
+    principal: Principal,
+    request: GetPortfolioSnapshotsRequest,
+) -> GetPortfolioSnapshotsResponse {
+    read_state(|s| model::get_snapshots(&s.portfolio_snapshots, principal, request))
+}
+
+/// Removes all of the principal's snapshots, e.g. when they opt out.
+pub fn delete_snapshots(principal: Principal) -> u64 {
+    mutate_state(|s| {
+        model::delete_snapshots(
+            &mut s.portfolio_snapshots,
+            &mut s.portfolio_snapshots_by_day,
+            principal,
+        )
+    })
+}
+
+/// Evicts the snapshots older than [`PORTFOLIO_SNAPSHOT_RETENTION_DAYS`] and returns how many were
+/// evicted.
+pub fn evict_expired_snapshots() -> u64 {
+    let first_kept_day =
+        model::day_of(time()).saturating_sub(PORTFOLIO_SNAPSHOT_RETENTION_DAYS - 1);
+    mutate_state(|s| {
+        model::evict_snapshots_before(
+            &mut s.portfolio_snapshots,
+            &mut s.portfolio_snapshots_by_day,
+            first_kept_day,
+        )
+    })
+}
@@ -2,7 +2,8 @@ mod canister_ids;
 mod service;
 
 pub(crate) use service::{
-    approve_signing, btc_principal_to_p2wpkh_address, eth_principal_to_address, get_allowed_cycles,
-    has_sufficient_allowance, principal_to_account_identifier_hex, sol_principal_to_address,
-    top_up_cycles_ledger,
+    approve_signing, btc_principal_to_address, btc_principal_to_source_address,
+    eth_principal_to_address, get_allowed_cycles, has_sufficient_allowance,
+    principal_to_account_identifier_hex, sol_principal_to_address, top_up_cycles_ledger,
+    transform_network,
 };
@@ -1,5 +1,5 @@
 //! Code for interacting with the chain fusion signer.
-use bitcoin::{Address, CompressedPublicKey, Network};
+use bitcoin::Network;
 use candid::{Nat, Principal};
 use ic_cdk::{
     api::msg_caller,
@@ -15,17 +15,20 @@ use ic_cycles_ledger_client::{
 };
 use ic_ledger_types::Subaccount;
 use serde_bytes::ByteBuf;
-use shared::types::signer::{
-    topup::{
-        TopUpCyclesLedgerError, TopUpCyclesLedgerRequest, TopUpCyclesLedgerResponse,
-        TopUpCyclesLedgerResult,
+use shared::types::{
+    bitcoin::BtcAddressType,
+    signer::{
+        topup::{
+            TopUpCyclesLedgerError, TopUpCyclesLedgerRequest, TopUpCyclesLedgerResponse,
+            TopUpCyclesLedgerResult,
+        },
+        AllowSigningError, GetAllowedCyclesError,
     },
-    AllowSigningError, GetAllowedCyclesError,
 };
 use tiny_keccak::{Hasher, Keccak};
 
 use super::canister_ids::{CYCLES_LEDGER, SIGNER};
-use crate::state::read_config;
+use crate::{bitcoin::address::SourceAddress, state::read_config};
 
 /// Current ledger fee in cycles.  Historically stable.
 ///
@@ -188,9 +191,21 @@ pub fn principal_to_account_identifier_hex(principal: &Principal) -> String {
 // ECDSA schemas: see <https://github.com/dfinity/chain-fusion-signer/blob/26b683c6de9971fdbf7bd4cebc04d427d1753289/src/signer/canister/src/derivation_path.rs#L6>.
 const SCHEMA_BTC: u8 = 0x00;
 const SCHEMA_ETH: u8 = 0x01;
-// Schnorr (Ed25519) schema used for Solana, mirrored from the frontend's offline derivation
-// (`src/frontend/src/lib/ic-pub-key/src/cli.ts`, `deriveSolAddress`).
-const SCHEMA_SOL: u8 = 0xfe;
+// Schnorr schema, followed by a chain-specific path: used for Solana (Ed25519), mirrored from the
+// frontend's offline derivation (`src/frontend/src/lib/ic-pub-key/src/cli.ts`, `deriveSolAddress`),
+// and for BTC Taproot (BIP-340).
+const SCHEMA_SCHNORR: u8 = 0xfe;
+/// The chain-specific path, under `SCHEMA_SCHNORR`, of the BIP-340 key of a user's BTC Taproot
+/// address: `[SCHEMA_SCHNORR, principal, "BTC"]`.
+///
+/// The signer has no Taproot address type of its own (its `BitcoinAddressType` is only `P2WPKH`,
+/// see `src/declarations/signer/signer.did`), so Taproot goes through its generic
+/// `schnorr_public_key` and `schnorr_sign` endpoints. These use the caller's path as-is under
+/// `[SCHEMA_SCHNORR, caller]`, as the frontend's offline `deriveSolAddress` reproduces. The key is
+/// the untweaked internal key of a BIP-86 key-path-only output: inputs must be signed by
+/// `schnorr_sign` with `Bip340secp256k1` on this path and a `Bip341` aux with an empty
+/// `merkle_root_hash`, which applies the BIP-86 tweak.
+const BTC_P2TR_PATH: &[&[u8]] = &[b"BTC"];
 
 /// Computes the secp256k1 public key the chain-fusion signer would derive for `principal` under the
 /// given `schema` (`SCHEMA_BTC` / `SCHEMA_ETH`), via the management canister — a public-key read,
@@ -214,24 +229,24 @@ async fn cfs_ecdsa_pubkey_of(principal: &Principal, schema: u8) -> Result<Vec<u8
     Ok(key.public_key)
 }
 
-/// Computes the Ed25519 public key the chain-fusion signer would derive for `principal` on the
-/// Solana mainnet path, via the management canister `schnorr_public_key` (a public-key read, not a
-/// signing call). Mirrors the signer's path: `[SCHEMA_SOL, principal, "SOL", "mainnet"]`.
-async fn cfs_ed25519_pubkey_of(principal: &Principal) -> Result<Vec<u8>, String> {
+/// Computes the Schnorr public key the chain-fusion signer would derive for `principal` with
+/// `algorithm` on the chain-specific `path`, via the management canister `schnorr_public_key` (a
+/// public-key read, not a signing call). Mirrors the signer's path:
+/// `[SCHEMA_SCHNORR, principal, ..path]`, e.g. `"SOL", "mainnet"` for the Solana mainnet key.
+async fn cfs_schnorr_pubkey_of(
+    principal: &Principal,
+    algorithm: SchnorrAlgorithm,
+    path: &[&[u8]],
+) -> Result<Vec<u8>, String> {
     let (key_name, maybe_cfs_canister_id) =
         read_config(|s| (s.ecdsa_key_name.clone(), s.cfs_canister_id));
-    let derivation_path = vec![
-        vec![SCHEMA_SOL],
-        principal.as_slice().to_vec(),
-        b"SOL".to_vec(),
-        b"mainnet".to_vec(),
-    ];
+    let derivation_path = cfs_schnorr_derivation_path(principal, path);
     let cfs_canister_id = maybe_cfs_canister_id.ok_or("Missing CFS canister id")?;
     let key = schnorr_public_key(&SchnorrPublicKeyArgs {
         canister_id: Some(cfs_canister_id),
         derivation_path,
         key_id: SchnorrKeyId {
-            algorithm: SchnorrAlgorithm::Ed25519,
+            algorithm,
             name: key_name,
         },
     })
@@ -240,7 +255,15 @@ async fn cfs_ed25519_pubkey_of(principal: &Principal) -> Result<Vec<u8>, String>
     Ok(key.public_key)
 }
 
-fn transform_network(network: BitcoinNetwork) -> Network {
+/// The derivation path of the chain-fusion signer's Schnorr key of `principal` on `path`.
+fn cfs_schnorr_derivation_path(principal: &Principal, path: &[&[u8]]) -> Vec<Vec<u8>> {
+    [vec![SCHEMA_SCHNORR], principal.as_slice().to_vec()]
+        .into_iter()
+        .chain(path.iter().map(|element| element.to_vec()))
+        .collect()
+}
+
+pub(crate) fn transform_network(network: BitcoinNetwork) -> Network {
     match network {
         BitcoinNetwork::Mainnet => Network::Bitcoin,
         BitcoinNetwork::Testnet => Network::Testnet,
@@ -248,20 +271,40 @@ fn transform_network(network: BitcoinNetwork) -> Network {
     }
 }
 
-/// Converts a public key to a P2PKH address.
+/// Derives the caller's BTC address of `address_type` on `network`, from the chain-fusion signer's
+/// ECDSA key for P2WPKH and P2SH-P2WPKH, and from its BIP-340 Schnorr key for P2TR.
+///
+/// # Errors
+/// - The signer public key could not be retrieved, or it is not a valid secp256k1 key.
+pub async fn btc_principal_to_source_address(
+    network: BitcoinNetwork,
+    principal: &Principal,
+    address_type: BtcAddressType,
+) -> Result<SourceAddress, String> {
+    let public_key = match address_type {
+        BtcAddressType::P2wpkh | BtcAddressType::P2shP2wpkh => {
+            cfs_ecdsa_pubkey_of(principal, SCHEMA_BTC).await?
+        }
+        BtcAddressType::P2tr => {
+            cfs_schnorr_pubkey_of(principal, SchnorrAlgorithm::Bip340secp256k1, BTC_P2TR_PATH)
+                .await?
+        }
+    };
+    SourceAddress::new(address_type, &public_key, network)
+}
+
+/// Derives the caller's BTC address of `address_type` on `network`, as a string.
 ///
 /// # Errors
-/// - It was not possible to get the P2WPKH from the public key.
-pub async fn btc_principal_to_p2wpkh_address(
+/// - The signer public key could not be retrieved, or it is not a valid secp256k1 key.
+pub async fn btc_principal_to_address(
     network: BitcoinNetwork,
     principal: &Principal,
+    address_type: BtcAddressType,
 ) -> Result<String, String> {
-    let ecdsa_pubkey = cfs_ecdsa_pubkey_of(principal, SCHEMA_BTC).await?;
-    if let Ok(compressed_public_key) = CompressedPublicKey::from_slice(&ecdsa_pubkey) {
-        Ok(Address::p2wpkh(&compressed_public_key, transform_network(network)).to_string())
-    } else {
-        Err("Error getting P2WPKH from public key".to_string())
-    }
+    btc_principal_to_source_address(network, principal, address_type)
+        .await
+        .map(|source| source.address().to_string())
 }
 
 /// Derives the caller's Ethereum address from their principal, reproducing the chain-fusion
@@ -280,7 +323,8 @@ pub async fn eth_principal_to_address(principal: &Principal) -> Result<String, S
 /// # Errors
 /// - The signer public key could not be retrieved, or it is not a 32-byte Ed25519 key.
 pub async fn sol_principal_to_address(principal: &Principal) -> Result<String, String> {
-    let ed25519_pubkey = cfs_ed25519_pubkey_of(principal).await?;
+    let ed25519_pubkey =
+        cfs_schnorr_pubkey_of(principal, SchnorrAlgorithm::Ed25519, &[b"SOL", b"mainnet"]).await?;
     sol_address_from_ed25519_pubkey(&ed25519_pubkey)
 }
 
@@ -519,13 +563,17 @@ pub async fn top_up_cycles_ledger(request: TopUpCyclesLedgerRequest) -> TopUpCyc
 #[cfg(test)]
 mod tests {
     use candid::{Nat, Principal};
+    use ic_cdk::bitcoin_canister::Network as BitcoinNetwork;
     use ic_ledger_types::{AccountIdentifier, DEFAULT_SUBACCOUNT};
     use pretty_assertions::assert_eq;
+    use shared::types::bitcoin::BtcAddressType;
 
     use super::{
-        cycles_to_send, eth_address_from_ecdsa_pubkey, frozen_reserve_cycles,
-        principal_to_account_identifier_hex, sol_address_from_ed25519_pubkey,
+        cfs_schnorr_derivation_path, cycles_to_send, eth_address_from_ecdsa_pubkey,
+        frozen_reserve_cycles, principal_to_account_identifier_hex,
+        sol_address_from_ed25519_pubkey, BTC_P2TR_PATH,
     };
+    use crate::bitcoin::address::SourceAddress;
 
     /// 1 trillion cycles (1T), the unit the examples are written in.
     const T: u128 = 1_000_000_000_000;
@@ -579,6 +627,38 @@ mod tests {
         );
     }
 
+    #[test]
+    fn btc_p2tr_key_is_derived_on_the_btc_path_of_the_schnorr_schema() {
+        let principal = Principal::from_slice(&[0x01, 0x02, 0x03, 0x04]);
+
+        let derivation_path = cfs_schnorr_derivation_path(&principal, BTC_P2TR_PATH);
+
+        assert_eq!(
+            derivation_path,
+            vec![
+                vec![0xfe],
+                vec![0x01, 0x02, 0x03, 0x04],
+                vec![0x42, 0x54, 0x43]
+            ]
+        );
+    }
+
+    #[test]
+    fn btc_p2tr_address_of_a_bip340_public_key_matches_the_bip86_vector() {
+        // The internal key of the first BIP-86 test vector, in the 33-byte SEC1 encoding returned by
+        // `schnorr_public_key` for `Bip340secp256k1`.
+        let public_key =
+            hex_to_bytes("02cc8a4bc64d897bddc5fbc2f670f7a8ba0b386779106cf1223c6fc5d7cd6fc115");
+
+        let source =
+            SourceAddress::new(BtcAddressType::P2tr, &public_key, BitcoinNetwork::Mainnet).unwrap();
+
+        assert_eq!(
+            source.address().to_string(),
+            "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr"
+        );
+    }
+
     #[test]
     fn sol_address_of_zero_pubkey_is_the_known_base58_string() {
         // base58 of 32 zero bytes is 32 '1's — the Solana System Program id.
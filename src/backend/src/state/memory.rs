@@ -21,7 +21,9 @@ pub(crate) const BTC_USER_PENDING_TRANSACTIONS_MEMORY_ID: MemoryId = MemoryId::n
 pub(crate) const TOKEN_ACTIVITY_MEMORY_ID: MemoryId = MemoryId::new(8);
 pub(crate) const API_KEYS_MEMORY_ID: MemoryId = MemoryId::new(9);
 pub(crate) const EXCHANGE_RATE_MEMORY_ID: MemoryId = MemoryId::new(10);
-pub(crate) const USER_TRANSACTIONS_MEMORY_ID: MemoryId = MemoryId::new(11);
+// Legacy `Vec`-per-token layout of user transactions. Drained into
+// `USER_TRANSACTION_ENTRIES_MEMORY_ID` after upgrade; do NOT reuse this ID.
+pub(crate) const LEGACY_USER_TRANSACTIONS_MEMORY_ID: MemoryId = MemoryId::new(11);
 pub(crate) const AGREEMENT_HISTORY_MEMORY_ID: MemoryId = MemoryId::new(12);
 pub(crate) const ACTIVE_USER_TRANSACTIONS_MEMORY_ID: MemoryId = MemoryId::new(13);
 // Personal notes are stored via the vetKeys `EncryptedMaps` library, which is
@@ -37,6 +39,24 @@ pub(crate) const PERSONAL_NOTES_ENCRYPTED_MAPS_MEMORY_ID: MemoryId = MemoryId::n
 // a by-creator index used only to range-scan a creator's active-share count.
 pub(crate) const PERSONAL_NOTE_SHARES_MEMORY_ID: MemoryId = MemoryId::new(18);
 pub(crate) const PERSONAL_NOTE_SHARES_BY_CREATOR_MEMORY_ID: MemoryId = MemoryId::new(19);
+// User transactions, one entry per transaction, plus the per-`(principal, token)`
+// entry count used for `total_stored` and the per-token cap.
+pub(crate) const USER_TRANSACTION_ENTRIES_MEMORY_ID: MemoryId = MemoryId::new(20);
+pub(crate) const USER_TRANSACTION_COUNTS_MEMORY_ID: MemoryId = MemoryId::new(21);
+// Timestamp-ordered index over the user transaction entries, across tokens.
+pub(crate) const USER_ACTIVITY_INDEX_MEMORY_ID: MemoryId = MemoryId::new(22);
+pub(crate) const EXCHANGE_RATE_HISTORY_MEMORY_ID: MemoryId = MemoryId::new(23);
+// Opt-in daily portfolio snapshots, plus their by-day index used for eviction.
+pub(crate) const PORTFOLIO_SNAPSHOTS_MEMORY_ID: MemoryId = MemoryId::new(24);
+pub(crate) const PORTFOLIO_SNAPSHOTS_BY_DAY_MEMORY_ID: MemoryId = MemoryId::new(25);
+// Price alerts, their by-token index evaluated on each exchange-rate refresh, and the inbox of
+// triggered alerts.
+pub(crate) const PRICE_ALERTS_MEMORY_ID: MemoryId = MemoryId::new(26);
+pub(crate) const PRICE_ALERTS_BY_TOKEN_MEMORY_ID: MemoryId = MemoryId::new(27);
+pub(crate) const TRIGGERED_PRICE_ALERTS_MEMORY_ID: MemoryId = MemoryId::new(28);
+pub(crate) const PRICE_OVERRIDES_MEMORY_ID: MemoryId = MemoryId::new(29);
+// By-id index over the user transaction entries, used to deduplicate saves by transaction id.
+pub(crate) const USER_TRANSACTION_IDS_MEMORY_ID: MemoryId = MemoryId::new(30);
 
 thread_local! {
     pub(crate) static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> = RefCell::new(
@@ -13,19 +13,28 @@ use crate::{
     state::memory::{
         ACTIVE_USER_TRANSACTIONS_MEMORY_ID, AGREEMENT_HISTORY_MEMORY_ID, API_KEYS_MEMORY_ID,
         BTC_USER_PENDING_TRANSACTIONS_MEMORY_ID, CONFIG_MEMORY_ID, CONTACT_MEMORY_ID,
-        EXCHANGE_RATE_MEMORY_ID, MEMORY_MANAGER, PERSONAL_NOTES_ENCRYPTED_MAPS_MEMORY_ID,
-        PERSONAL_NOTES_KEY_MANAGER_ACCESS_MEMORY_ID, PERSONAL_NOTES_KEY_MANAGER_CONFIG_MEMORY_ID,
-        PERSONAL_NOTES_KEY_MANAGER_SHARED_MEMORY_ID, PERSONAL_NOTE_SHARES_BY_CREATOR_MEMORY_ID,
-        PERSONAL_NOTE_SHARES_MEMORY_ID, TOKEN_ACTIVITY_MEMORY_ID, USER_CUSTOM_TOKEN_MEMORY_ID,
-        USER_PROFILE_MEMORY_ID, USER_PROFILE_UPDATED_MEMORY_ID, USER_TOKEN_MEMORY_ID,
-        USER_TRANSACTIONS_MEMORY_ID,
+        EXCHANGE_RATE_HISTORY_MEMORY_ID, EXCHANGE_RATE_MEMORY_ID,
+        LEGACY_USER_TRANSACTIONS_MEMORY_ID, MEMORY_MANAGER,
+        PERSONAL_NOTES_ENCRYPTED_MAPS_MEMORY_ID, PERSONAL_NOTES_KEY_MANAGER_ACCESS_MEMORY_ID,
+        PERSONAL_NOTES_KEY_MANAGER_CONFIG_MEMORY_ID, PERSONAL_NOTES_KEY_MANAGER_SHARED_MEMORY_ID,
+        PERSONAL_NOTE_SHARES_BY_CREATOR_MEMORY_ID, PERSONAL_NOTE_SHARES_MEMORY_ID,
+        PORTFOLIO_SNAPSHOTS_BY_DAY_MEMORY_ID, PORTFOLIO_SNAPSHOTS_MEMORY_ID,
+        PRICE_ALERTS_BY_TOKEN_MEMORY_ID, PRICE_ALERTS_MEMORY_ID, PRICE_OVERRIDES_MEMORY_ID,
+        TOKEN_ACTIVITY_MEMORY_ID, TRIGGERED_PRICE_ALERTS_MEMORY_ID, USER_ACTIVITY_INDEX_MEMORY_ID,
+        USER_CUSTOM_TOKEN_MEMORY_ID, USER_PROFILE_MEMORY_ID, USER_PROFILE_UPDATED_MEMORY_ID,
+        USER_TOKEN_MEMORY_ID, USER_TRANSACTION_COUNTS_MEMORY_ID,
+        USER_TRANSACTION_ENTRIES_MEMORY_ID, USER_TRANSACTION_IDS_MEMORY_ID,
     },
     types::{
         maps::{
             ActiveUserTransactionsMap, AgreementHistoryMap, ApiKeysCell,
-            BtcUserPendingTransactionsMap, ConfigCell, ContactMap, CustomTokenMap, ExchangeRateMap,
-            PersonalNoteShareMap, PersonalNoteSharesByCreatorMap, TokenActivityMap, UserProfileMap,
-            UserProfileUpdatedMap, UserTokenMap, UserTransactionsMap,
+            BtcUserPendingTransactionsMap, ConfigCell, ContactMap, CustomTokenMap,
+            ExchangeRateHistoryMap, ExchangeRateMap, LegacyUserTransactionsMap,
+            PersonalNoteShareMap, PersonalNoteSharesByCreatorMap, PortfolioSnapshotDayIndexMap,
+            PortfolioSnapshotMap, PriceAlertMap, PriceAlertTokenIndexMap, PriceOverrideMap,
+            TokenActivityMap, TriggeredPriceAlertMap, UserActivityIndexMap, UserProfileMap,
+            UserProfileUpdatedMap, UserTokenMap, UserTransactionCountsMap,
+            UserTransactionIdIndexMap, UserTransactionsMap,
         },
         storable::Candid,
     },
@@ -49,7 +58,32 @@ pub(crate) struct State {
     // TODO: limit the map size with an eviction policy
     pub(crate) token_activity: TokenActivityMap,
     pub(crate) exchange_rates: ExchangeRateMap,
+    /// Hourly and daily USD price buckets per token, fed by the exchange-rate refresh.
+    pub(crate) exchange_rate_history: ExchangeRateHistoryMap,
+    /// Controller-set static or pegged prices, taking precedence over the price providers.
+    pub(crate) price_overrides: PriceOverrideMap,
+    /// Finalized user transactions, one entry per transaction.
     pub(crate) user_transactions: UserTransactionsMap,
+    /// Number of entries in `user_transactions` per `(principal, token_id)` pair.
+    pub(crate) user_transaction_counts: UserTransactionCountsMap,
+    /// By-id index over `user_transactions`, deduplicating saves by transaction id.
+    pub(crate) user_transaction_ids: UserTransactionIdIndexMap,
+    /// Timestamp-ordered index over `user_transactions`, across all of a user's tokens.
+    pub(crate) user_activity_index: UserActivityIndexMap,
+    /// Pre-migration `Vec`-per-token transactions, drained into `user_transactions` by
+    /// `transactions::migration` after upgrade.
+    // TODO: remove once all canisters have been upgraded past the per-entry migration.
+    pub(crate) legacy_user_transactions: LegacyUserTransactionsMap,
+    /// Opt-in daily portfolio value per user.
+    pub(crate) portfolio_snapshots: PortfolioSnapshotMap,
+    /// By-day index over `portfolio_snapshots`, used by housekeeping to evict expired days.
+    pub(crate) portfolio_snapshots_by_day: PortfolioSnapshotDayIndexMap,
+    /// Per-user price alert rules.
+    pub(crate) price_alerts: PriceAlertMap,
+    /// By-token index over `price_alerts`, evaluated when a token's exchange rate is refreshed.
+    pub(crate) price_alerts_by_token: PriceAlertTokenIndexMap,
+    /// Per-user inbox of triggered price alerts.
+    pub(crate) triggered_price_alerts: TriggeredPriceAlertMap,
     /// Per-user audit trail of agreement consent/rejection events.
     pub(crate) agreement_history: AgreementHistoryMap,
     /// Per-user in-flight high-level operations (swaps, converts, …). Survives
@@ -85,7 +119,9 @@ impl From<&State> for Stats {
             custom_token_count: state.custom_token.len(),
             token_activity_count: state.token_activity.len(),
             exchange_rates_count: state.exchange_rates.len(),
-            user_transactions_count: state.user_transactions.len(),
+            // Counts `(principal, token)` pairs; a pair lives in exactly one of the two maps.
+            user_transactions_count: state.user_transaction_counts.len()
+                + state.legacy_user_transactions.len(),
             agreement_history_count: state.agreement_history.len(),
             active_user_transactions_count: state.active_user_transactions.len(),
             personal_notes_count: state
@@ -113,7 +149,20 @@ thread_local! {
             ),
             token_activity: TokenActivityMap::init(mm.borrow().get(TOKEN_ACTIVITY_MEMORY_ID)),
             exchange_rates: ExchangeRateMap::init(mm.borrow().get(EXCHANGE_RATE_MEMORY_ID)),
-            user_transactions: UserTransactionsMap::init(mm.borrow().get(USER_TRANSACTIONS_MEMORY_ID)),
+            exchange_rate_history: ExchangeRateHistoryMap::init(mm.borrow().get(EXCHANGE_RATE_HISTORY_MEMORY_ID)),
+            price_overrides: PriceOverrideMap::init(mm.borrow().get(PRICE_OVERRIDES_MEMORY_ID)),
+            user_transactions: UserTransactionsMap::init(mm.borrow().get(USER_TRANSACTION_ENTRIES_MEMORY_ID)),
+            user_transaction_counts: UserTransactionCountsMap::init(mm.borrow().get(USER_TRANSACTION_COUNTS_MEMORY_ID)),
+            user_transaction_ids: UserTransactionIdIndexMap::init(mm.borrow().get(USER_TRANSACTION_IDS_MEMORY_ID)),
+            user_activity_index: UserActivityIndexMap::init(mm.borrow().get(USER_ACTIVITY_INDEX_MEMORY_ID)),
+            legacy_user_transactions: LegacyUserTransactionsMap::init(mm.borrow().get(LEGACY_USER_TRANSACTIONS_MEMORY_ID)),
+            portfolio_snapshots: PortfolioSnapshotMap::init(mm.borrow().get(PORTFOLIO_SNAPSHOTS_MEMORY_ID)),
+            portfolio_snapshots_by_day: PortfolioSnapshotDayIndexMap::init(
+                mm.borrow().get(PORTFOLIO_SNAPSHOTS_BY_DAY_MEMORY_ID),
+            ),
+            price_alerts: PriceAlertMap::init(mm.borrow().get(PRICE_ALERTS_MEMORY_ID)),
+            price_alerts_by_token: PriceAlertTokenIndexMap::init(mm.borrow().get(PRICE_ALERTS_BY_TOKEN_MEMORY_ID)),
+            triggered_price_alerts: TriggeredPriceAlertMap::init(mm.borrow().get(TRIGGERED_PRICE_ALERTS_MEMORY_ID)),
             agreement_history: AgreementHistoryMap::init(mm.borrow().get(AGREEMENT_HISTORY_MEMORY_ID)),
             active_user_transactions: ActiveUserTransactionsMap::init(mm.borrow().get(ACTIVE_USER_TRANSACTIONS_MEMORY_ID)),
             // Initialised lazily on first access (see `ensure_personal_notes`).
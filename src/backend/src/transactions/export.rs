@@ -0,0 +1,545 @@
+//! Rendering of stored transactions as CSV or JSON export chunks.
+//!
+//! Pure formatting only: the caller reads the transactions, the contacts and the exchange rates
+//! from state and hands them in.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+use shared::types::{
+    account::{BtcAddress, EthAddress, Icrcv2AccountId, TokenAccountId},
+    contact::StoredContacts,
+    token_id::TokenId,
+    user_transaction::{
+        IcrcTransactionType, NetworkTransactionData, UserActivityItem, UserTransactionError,
+        UserTransactionExportFormat,
+    },
+};
+
+use crate::utils::csv;
+
+/// Column names, in order, of a CSV export.
+const CSV_HEADER: [&str; 15] = [
+    "date",
+    "timestamp",
+    "token",
+    "id",
+    "block_index",
+    "network",
+    "operation",
+    "from",
+    "from_contact",
+    "to",
+    "to_contact",
+    "value",
+    "fee",
+    "usd_price",
+    "usd_price_source",
+];
+
+/// Contact names of a user, keyed by address.
+///
+/// EVM addresses and ICP account identifiers are case-insensitive and stored lowercased; other
+/// addresses are stored as entered.
+#[derive(Default)]
+pub struct ContactNames(HashMap<String, String>);
+
+impl ContactNames {
+    /// Indexes every address of every contact. If two contacts share an address, the one with
+    /// the higher id wins.
+    #[must_use]
+    pub fn new(contacts: &StoredContacts) -> Self {
+        let mut names = HashMap::new();
+        for contact in contacts.contacts.values() {
+            for address in &contact.addresses {
+                if let Some(key) = address_key(&address.token_account_id) {
+                    names.insert(key, contact.name.clone());
+                }
+            }
+        }
+        Self(names)
+    }
+
+    fn get(&self, address: &str) -> Option<&str> {
+        self.0
+            .get(address)
+            .or_else(|| self.0.get(&address.to_lowercase()))
+            .map(String::as_str)
+    }
+}
+
+/// The textual form in which `account` appears in stored transactions.
+///
+/// ICRC accounts with an explicit subaccount are not indexed: transactions only carry their
+/// redacted account identifier, which cannot be derived here.
+fn address_key(account: &TokenAccountId) -> Option<String> {
+    match account {
+        TokenAccountId::Icrcv2(Icrcv2AccountId::WithPrincipal {
+            owner,
+            subaccount: None,
+        }) => Some(owner.to_text()),
+        TokenAccountId::Icrcv2(Icrcv2AccountId::WithPrincipal { .. }) => None,
+        TokenAccountId::Icrcv2(Icrcv2AccountId::Account(id)) => Some(hex::encode(id.0)),
+        TokenAccountId::Sol(address) => Some(address.0.clone()),
+        TokenAccountId::Btc(
+            BtcAddress::P2PKH(address)
+            | BtcAddress::P2SH(address)
+            | BtcAddress::P2WPKH(address)
+            | BtcAddress::P2WSH(address)
+            | BtcAddress::P2TR(address),
+        ) => Some(address.clone()),
+        TokenAccountId::Eth(EthAddress::Public(address)) => Some(address.to_lowercase()),
+    }
+}
+
+/// Where the USD rate of an exported transaction comes from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UsdPriceSource {
+    /// The close of the token's hourly price bucket containing the transaction.
+    Hourly,
+    /// The close of the token's daily price bucket containing the transaction, when its hour is
+    /// not in the history.
+    Daily,
+    /// The token's rate at export time, when the transaction's day is not in the history.
+    Current,
+}
+
+impl UsdPriceSource {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Hourly => "hourly",
+            Self::Daily => "daily",
+            Self::Current => "current",
+        }
+    }
+}
+
+/// One exported transaction. Amounts are in the token's smallest unit.
+#[derive(Serialize)]
+struct ExportRow<'a> {
+    date: String,
+    timestamp: u64,
+    token: String,
+    id: &'a str,
+    block_index: u64,
+    network: &'static str,
+    operation: Option<&'static str>,
+    from: &'a str,
+    from_contact: Option<&'a str>,
+    to: Option<&'a str>,
+    to_contact: Option<&'a str>,
+    value: String,
+    fee: Option<String>,
+    /// The token's USD rate at the time of the transaction, or at export time as a fallback.
+    usd_price: Option<f64>,
+    usd_price_source: Option<UsdPriceSource>,
+}
+
+impl ExportRow<'_> {
+    fn csv_fields(&self) -> [String; 15] {
+        [
+            self.date.clone(),
+            self.timestamp.to_string(),
+            self.token.clone(),
+            self.id.to_string(),
+            self.block_index.to_string(),
+            self.network.to_string(),
+            self.operation.unwrap_or_default().to_string(),
+            self.from.to_string(),
+            self.from_contact.unwrap_or_default().to_string(),
+            self.to.unwrap_or_default().to_string(),
+            self.to_contact.unwrap_or_default().to_string(),
+            self.value.clone(),
+            self.fee.clone().unwrap_or_default(),
+            self.usd_price
+                .map(|price| price.to_string())
+                .unwrap_or_default(),
+            self.usd_price_source
+                .map(UsdPriceSource::as_str)
+                .unwrap_or_default()
+                .to_string(),
+        ]
+    }
+}
+
+/// Renders `items` as one export chunk.
+///
+/// `usd_price` looks up the USD rate of a token at a transaction timestamp, in seconds since epoch.
+/// With `include_header`, a CSV chunk starts with the header row; JSON chunks are always a
+/// self-contained array.
+///
+/// # Errors
+/// `InternalError` if the rows cannot be serialized.
+pub fn render(
+    format: UserTransactionExportFormat,
+    items: &[UserActivityItem],
+    contacts: &ContactNames,
+    mut usd_price: impl FnMut(&TokenId, u64) -> Option<(f64, UsdPriceSource)>,
+    include_header: bool,
+) -> Result<String, UserTransactionError> {
+    let rows = items.iter().map(|item| {
+        let tx = &item.transaction;
+        let (from, to) = match &tx.network_data {
+            NetworkTransactionData::Sol(data) => (
+                data.from_owner.as_deref().unwrap_or(&tx.from),
+                data.to_owner.as_deref().or(tx.to.as_deref()),
+            ),
+            _ => (tx.from.as_str(), tx.to.as_deref()),
+        };
+        let (network, operation, fee) = match &tx.network_data {
+            NetworkTransactionData::Evm(_) => ("Evm", None, None),
+            NetworkTransactionData::Icrc(data) => (
+                "Icrc",
+                Some(match data.tx_type {
+                    IcrcTransactionType::Transfer => "Transfer",
+                    IcrcTransactionType::Approve { .. } => "Approve",
+                    IcrcTransactionType::Mint => "Mint",
+                    IcrcTransactionType::Burn => "Burn",
+                }),
+                data.fee.as_ref(),
+            ),
+            NetworkTransactionData::Btc(data) => ("Btc", None, data.fee.as_ref()),
+            NetworkTransactionData::Sol(data) => ("Sol", None, data.fee.as_ref()),
+        };
+        let usd_price =
+            usd_price(&item.token_id, tx.timestamp).filter(|(price, _)| price.is_finite());
+        ExportRow {
+            date: format_utc(tx.timestamp),
+            timestamp: tx.timestamp,
+            token: token_label(&item.token_id),
+            id: &tx.id,
+            block_index: tx.block_index,
+            network,
+            operation,
+            from: &tx.from,
+            from_contact: contacts.get(from),
+            to: tx.to.as_deref(),
+            to_contact: to.and_then(|to| contacts.get(to)),
+            value: tx.value.0.to_string(),
+            fee: fee.map(|fee| fee.0.to_string()),
+            usd_price: usd_price.map(|(price, _)| price),
+            usd_price_source: usd_price.map(|(_, source)| source),
+        }
+    });
+
+    match format {
+        UserTransactionExportFormat::Csv => {
+            let mut content = String::new();
+            if include_header {
+                csv::push_line(&mut content, CSV_HEADER.iter().copied());
+            }
+            for row in rows {
+                csv::push_line(&mut content, row.csv_fields().iter().map(String::as_str));
+            }
+            Ok(content)
+        }
+        UserTransactionExportFormat::Json => serde_json::to_string(&rows.collect::<Vec<_>>())
+            .map_err(|err| UserTransactionError::InternalError {
+                msg: format!("Failed to serialize export rows: {err}"),
+            }),
+    }
+}
+
+/// A readable identifier of `token_id`, e.g. `Erc20:1:0xa0b8…` or `Icrc:mxzaz-hqaaa-aaaar-qaada-cai`.
+fn token_label(token_id: &TokenId) -> String {
+    match token_id {
+        TokenId::EvmNative(chain_id) => format!("EvmNative:{chain_id}"),
+        TokenId::Erc20(address, chain_id) => format!("Erc20:{chain_id}:{}", address.0),
+        TokenId::Erc721(address, chain_id) => format!("Erc721:{chain_id}:{}", address.0),
+        TokenId::Erc1155(address, chain_id) => format!("Erc1155:{chain_id}:{}", address.0),
+        TokenId::Erc4626(address, chain_id) => format!("Erc4626:{chain_id}:{}", address.0),
+        TokenId::Icrc(ledger_id) => format!("Icrc:{ledger_id}"),
+        TokenId::IcpNative => "IcpNative".to_string(),
+        TokenId::SplMainnet(address) => format!("SplMainnet:{}", address.0),
+        TokenId::SplDevnet(address) => format!("SplDevnet:{}", address.0),
+        TokenId::SolNativeMainnet => "SolNativeMainnet".to_string(),
+        TokenId::SolNativeDevnet => "SolNativeDevnet".to_string(),
+        TokenId::BtcNativeMainnet => "BtcNativeMainnet".to_string(),
+        TokenId::BtcNativeTestnet => "BtcNativeTestnet".to_string(),
+        TokenId::ExtV2(canister_id) => format!("ExtV2:{canister_id}"),
+        TokenId::Dip721(canister_id) => format!("Dip721:{canister_id}"),
+        TokenId::IcPunks(canister_id) => format!("IcPunks:{canister_id}"),
+        TokenId::Icrc7(canister_id) => format!("Icrc7:{canister_id}"),
+    }
+}
+
+/// Formats seconds since epoch as an ISO 8601 UTC date-time, e.g. `2023-11-14T22:13:20Z`.
+fn format_utc(timestamp: u64) -> String {
+    let days = timestamp / 86_400;
+    let seconds = timestamp % 86_400;
+    // Civil-from-days conversion over 400-year eras of 146_097 days, with years starting on
+    // March 1st so that the leap day is the last day of the year.
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        seconds / 3_600,
+        seconds % 3_600 / 60,
+        seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use candid::{Nat, Principal};
+    use pretty_assertions::assert_eq;
+    use shared::types::{
+        account::{EthAddress, Icrcv2AccountId, TokenAccountId},
+        contact::{Contact, ContactAddressData, StoredContacts},
+        custom_token::ErcTokenId,
+        token_id::TokenId,
+        user_transaction::{
+            EvmTransactionData, IcrcTransactionData, IcrcTransactionType, NetworkTransactionData,
+            UserActivityItem, UserTransaction, UserTransactionExportFormat,
+        },
+    };
+
+    use super::{format_utc, render, ContactNames, UsdPriceSource};
+
+    const PRINCIPAL_TEXT: &str = "7blps-itamd-lzszp-7lbda-4nngn-fev5u-2jvpn-6y3ap-eunp7-kz57e-fqe";
+
+    fn contacts() -> ContactNames {
+        let contact = |id: u64, name: &str, token_account_id: TokenAccountId| {
+            (
+                id,
+                Contact {
+                    id,
+                    name: name.to_string(),
+                    addresses: vec![ContactAddressData {
+                        token_account_id,
+                        label: None,
+                        verified_owner: None,
+                    }],
+                    update_timestamp_ns: 0,
+                    image: None,
+                },
+            )
+        };
+        ContactNames::new(&StoredContacts {
+            contacts: BTreeMap::from([
+                contact(
+                    1,
+                    "Alice",
+                    TokenAccountId::Eth(EthAddress::Public(
+                        "0xAbCd000000000000000000000000000000000001".to_string(),
+                    )),
+                ),
+                contact(
+                    2,
+                    "Bob, Jr.",
+                    TokenAccountId::Icrcv2(Icrcv2AccountId::WithPrincipal {
+                        owner: Principal::from_text(PRINCIPAL_TEXT).unwrap(),
+                        subaccount: None,
+                    }),
+                ),
+                contact(
+                    3,
+                    "=cmd",
+                    TokenAccountId::Eth(EthAddress::Public(
+                        "0x0000000000000000000000000000000000000003".to_string(),
+                    )),
+                ),
+            ]),
+            update_timestamp_ns: 0,
+        })
+    }
+
+    fn evm_item(from: &str, to: &str) -> UserActivityItem {
+        UserActivityItem {
+            token_id: TokenId::Erc20(
+                ErcTokenId("0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48".to_string()),
+                1,
+            ),
+            transaction: UserTransaction {
+                id: "0xhash".to_string(),
+                block_index: 7,
+                timestamp: 1_700_000_000,
+                from: from.to_string(),
+                to: Some(to.to_string()),
+                value: Nat::from(1_500_000u64),
+                network_data: NetworkTransactionData::Evm(EvmTransactionData {
+                    chain_id: Some(1),
+                    nonce: None,
+                    gas_limit: None,
+                    gas_price: None,
+                    gas_used: None,
+                    data: None,
+                    nft_token_id: None,
+                }),
+            },
+        }
+    }
+
+    fn icrc_item() -> UserActivityItem {
+        UserActivityItem {
+            token_id: TokenId::IcpNative,
+            transaction: UserTransaction {
+                id: "42".to_string(),
+                block_index: 42,
+                timestamp: 1_700_000_100,
+                from: PRINCIPAL_TEXT.to_string(),
+                to: None,
+                value: Nat::from(100_000_000u64),
+                network_data: NetworkTransactionData::Icrc(IcrcTransactionData {
+                    fee: Some(Nat::from(10_000u64)),
+                    memo: None,
+                    tx_type: IcrcTransactionType::Burn,
+                }),
+            },
+        }
+    }
+
+    fn icp_price(token_id: &TokenId, _timestamp: u64) -> Option<(f64, UsdPriceSource)> {
+        (*token_id == TokenId::IcpNative).then_some((4.25, UsdPriceSource::Hourly))
+    }
+
+    #[test]
+    fn test_format_utc_handles_epoch_leap_days_and_year_ends() {
+        assert_eq!(format_utc(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_utc(1_700_000_000), "2023-11-14T22:13:20Z");
+        assert_eq!(format_utc(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_utc(1_735_689_599), "2024-12-31T23:59:59Z");
+    }
+
+    #[test]
+    fn test_csv_chunk_resolves_contacts_and_escapes_fields() {
+        let items = [
+            evm_item(
+                "0xabcd000000000000000000000000000000000001",
+                "0x0000000000000000000000000000000000000003",
+            ),
+            icrc_item(),
+        ];
+
+        let csv = render(
+            UserTransactionExportFormat::Csv,
+            &items,
+            &contacts(),
+            icp_price,
+            true,
+        )
+        .unwrap();
+
+        let lines: Vec<&str> = csv.split("\r\n").collect();
+        assert_eq!(
+            lines,
+            vec![
+                "date,timestamp,token,id,block_index,network,operation,from,from_contact,to,\
+                 to_contact,value,fee,usd_price,usd_price_source",
+                "2023-11-14T22:13:20Z,1700000000,Erc20:1:0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48,\
+                 0xhash,7,Evm,,0xabcd000000000000000000000000000000000001,Alice,\
+                 0x0000000000000000000000000000000000000003,\"'=cmd\",1500000,,,",
+                &format!(
+                    "2023-11-14T22:15:00Z,1700000100,IcpNative,42,42,Icrc,Burn,{PRINCIPAL_TEXT},\
+                     \"Bob, Jr.\",,,100000000,10000,4.25,hourly"
+                ),
+                "",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_csv_chunk_without_header_only_has_rows() {
+        let csv = render(
+            UserTransactionExportFormat::Csv,
+            &[icrc_item()],
+            &ContactNames::default(),
+            |_, _| None,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(csv.lines().count(), 1);
+        assert!(csv.starts_with("2023-11-14T22:15:00Z,"));
+    }
+
+    #[test]
+    fn test_csv_chunk_prices_rows_at_their_timestamp_and_labels_fallback() {
+        let price_at = |_: &TokenId, timestamp: u64| {
+            Some(if timestamp == 1_700_000_100 {
+                (4.0, UsdPriceSource::Daily)
+            } else {
+                (5.0, UsdPriceSource::Current)
+            })
+        };
+        let items = [
+            icrc_item(),
+            evm_item(
+                "0xabcd000000000000000000000000000000000001",
+                "0x0000000000000000000000000000000000000003",
+            ),
+        ];
+
+        let csv = render(
+            UserTransactionExportFormat::Csv,
+            &items,
+            &ContactNames::default(),
+            price_at,
+            false,
+        )
+        .unwrap();
+
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with(",4,daily"));
+        assert!(lines[1].ends_with(",5,current"));
+    }
+
+    #[test]
+    fn test_json_chunk_is_an_array_of_rows() {
+        let json = render(
+            UserTransactionExportFormat::Json,
+            &[icrc_item()],
+            &contacts(),
+            icp_price,
+            false,
+        )
+        .unwrap();
+
+        let rows: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            rows,
+            serde_json::json!([{
+                "date": "2023-11-14T22:15:00Z",
+                "timestamp": 1_700_000_100,
+                "token": "IcpNative",
+                "id": "42",
+                "block_index": 42,
+                "network": "Icrc",
+                "operation": "Burn",
+                "from": PRINCIPAL_TEXT,
+                "from_contact": "Bob, Jr.",
+                "to": null,
+                "to_contact": null,
+                "value": "100000000",
+                "fee": "10000",
+                "usd_price": 4.25,
+                "usd_price_source": "hourly",
+            }])
+        );
+    }
+
+    #[test]
+    fn test_empty_chunks_render_empty_content() {
+        let render_empty =
+            |format| render(format, &[], &ContactNames::default(), |_, _| None, false).unwrap();
+
+        assert_eq!(render_empty(UserTransactionExportFormat::Csv), "");
+        assert_eq!(render_empty(UserTransactionExportFormat::Json), "[]");
+    }
+}
@@ -0,0 +1,97 @@
+//! Upgrade-time migration of user transactions from the legacy `Vec`-per-token layout to
+//! per-entry storage.
+//!
+//! The legacy map can hold up to `MAX_USER_TRANSACTIONS_PER_TOKEN` transactions for every
+//! `(principal, token)` pair, which is too much to move within `post_upgrade`. Instead,
+//! `post_upgrade` schedules a chain of timers, each moving a bounded batch, until the legacy map is
+//! empty. Saves migrate the pair they touch on demand, and reads of a pair not migrated yet are
+//! served from the legacy layout (see `transactions::service`), so the per-token endpoints behave
+//! the same while the migration is in progress. The cross-token activity feed and export only
+//! include a pair once it has been migrated.
+//!
+//! The same timer chaining backfills the activity index for entries saved before it existed.
+//!
+//! TODO: remove this module, the `legacy_user_transactions` state field and the `post_upgrade`
+//! wiring once all canisters have been upgraded past this release.
+
+use std::time::Duration;
+
+use ic_cdk_timers::set_timer;
+
+use crate::{
+    state::{mutate_state, read_state},
+    transactions::model,
+    types::UserTransactionEntryKey,
+};
+
+/// Upper bound on the number of transactions moved per timer tick, kept to a few thousand so that a
+/// tick, writing every index of each moved transaction, stays well within the instruction limit of
+/// a message. A single `(principal, token)` pair is always moved as a whole, so a tick may
+/// overshoot by up to `MAX_USER_TRANSACTIONS_PER_TOKEN`, which is what the
+/// `bench_migrate_legacy_user_transactions_10000` benchmark measures.
+const LEGACY_MIGRATION_BATCH_TRANSACTIONS: usize = 2_000;
+
+/// Upper bound on the number of entries added to the activity index per timer tick.
+const ACTIVITY_INDEX_BACKFILL_BATCH_ENTRIES: usize = 2_000;
+
+/// Schedules the next migration batch if legacy transactions remain.
+pub(crate) fn schedule_legacy_transactions_migration() {
+    if read_state(|s| s.legacy_user_transactions.is_empty()) {
+        return;
+    }
+
+    set_timer(Duration::ZERO, async {
+        migrate_legacy_transactions_batch();
+    });
+}
+
+fn migrate_legacy_transactions_batch() {
+    let (migrated, remaining) = mutate_state(|s| {
+        let migrated = model::migrate_legacy_transactions(
+            &mut s.legacy_user_transactions,
+            &mut s.user_transactions,
+            &mut s.user_transaction_counts,
+            &mut s.user_transaction_ids,
+            &mut s.user_activity_index,
+            LEGACY_MIGRATION_BATCH_TRANSACTIONS,
+        );
+        (migrated, s.legacy_user_transactions.len())
+    });
+
+    ic_cdk::println!(
+        "Migrated {migrated} user transactions to per-entry storage, {remaining} legacy token lists remaining"
+    );
+
+    schedule_legacy_transactions_migration();
+}
+
+/// Schedules indexing of the per-entry transactions that are missing from the activity index.
+pub(crate) fn schedule_activity_index_backfill() {
+    if read_state(|s| s.user_activity_index.len() >= s.user_transactions.len()) {
+        return;
+    }
+
+    schedule_activity_index_backfill_batch(None);
+}
+
+fn schedule_activity_index_backfill_batch(after: Option<UserTransactionEntryKey>) {
+    set_timer(Duration::ZERO, async move {
+        backfill_activity_index_batch(after);
+    });
+}
+
+fn backfill_activity_index_batch(after: Option<UserTransactionEntryKey>) {
+    let resume = mutate_state(|s| {
+        model::backfill_activity_index(
+            &s.user_transactions,
+            &mut s.user_activity_index,
+            after,
+            ACTIVITY_INDEX_BACKFILL_BATCH_ENTRIES,
+        )
+    });
+
+    match resume {
+        Some(after) => schedule_activity_index_backfill_batch(Some(after)),
+        None => ic_cdk::println!("Backfilled the user activity index"),
+    }
+}
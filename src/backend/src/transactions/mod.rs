@@ -1 +1,4 @@
+pub(crate) mod export;
+pub(crate) mod migration;
 pub(crate) mod model;
+pub(crate) mod service;
@@ -1,76 +1,222 @@
-use std::collections::HashSet;
+use std::{borrow::Cow, collections::HashSet, ops::Bound};
 
 use candid::Principal;
+use ic_stable_structures::Storable;
 use shared::types::{
     token_id::TokenId,
     user_transaction::{
-        GetUserTransactionsResponse, UserTransaction, UserTransactionError,
-        MAX_GET_USER_TRANSACTIONS_RESULTS, MAX_SAVE_USER_TRANSACTIONS_BATCH,
-        MAX_USER_TRANSACTIONS_PER_TOKEN,
+        GetUserActivityRequest, GetUserActivityResponse, GetUserTransactionsRequest,
+        GetUserTransactionsResponse, IcrcTransactionType, IcrcTransactionTypeFilter,
+        NetworkTransactionData, NetworkTransactionFilter, TransactionDirection,
+        TransactionDirectionFilter, UserActivityCursor, UserActivityItem, UserTransaction,
+        UserTransactionCursor, UserTransactionError, UserTransactionFilter,
+        MAX_EXPORT_USER_TRANSACTIONS_ROWS, MAX_GET_USER_TRANSACTIONS_RESULTS,
+        MAX_SAVE_USER_TRANSACTIONS_BATCH, MAX_SCANNED_USER_TRANSACTIONS,
+        MAX_USER_TRANSACTIONS_PER_TOKEN, RESERVED_BLOCK_INDEX, RESERVED_TIMESTAMP,
     },
 };
 
 use crate::types::{
-    Candid, StoredPrincipal, StoredTokenId, UserTransactionKey, UserTransactionsMap,
+    Candid, LegacyUserTransactionsMap, StoredPrincipal, StoredTokenId, UserActivityIndexMap,
+    UserActivityKey, UserTransactionCountsMap, UserTransactionEntryKey, UserTransactionIdIndexMap,
+    UserTransactionKey, UserTransactionsMap,
 };
 
 /// Read paginated transactions from the map without mutating state.
 ///
-/// The cursor is a positional index into the sorted transaction list, making pagination
-/// correct even when multiple transactions share the same `block_index`.
+/// Entries are stored individually, ordered by `(block_index, id)`, so serving a page only
+/// deserializes the transactions it examines:
+/// - With `cursor`, the page starts strictly below the cursor (`O(page)`).
+/// - Otherwise `start` is a positional index into the oldest-first order, as returned by
+///   `next_start`. Seeking to it skips `total_stored - start` keys without decoding their values.
 ///
-/// **Performance note:** The current storage layout (`StableBTreeMap<Key, Candid<Vec<T>>>`)
-/// requires deserializing the full transaction list to serve any page. If per-token lists grow
-/// large (approaching `MAX_USER_TRANSACTIONS_PER_TOKEN`), consider migrating to individual
-/// entries keyed by `(principal, token_id, block_index)` for `O(page_size)` reads.
+/// With a `filter`, at most [`MAX_SCANNED_USER_TRANSACTIONS`] entries are examined; the returned
+/// cursors then point past the last examined entry, matching or not.
 pub fn get_transactions(
-    map: &UserTransactionsMap,
+    entries: &UserTransactionsMap,
+    counts: &UserTransactionCountsMap,
     principal: Principal,
-    token_id: &TokenId,
-    start: Option<u64>,
-    max_results: u64,
+    request: GetUserTransactionsRequest,
 ) -> GetUserTransactionsResponse {
-    let key = make_key(principal, token_id);
+    let GetUserTransactionsRequest {
+        token_id,
+        start,
+        max_results,
+        cursor,
+        filter,
+    } = request;
+
+    let owner = make_key(principal, &token_id);
+    let total_stored = counts.get(&owner).unwrap_or_default();
+    let range = entry_range(&owner);
+
+    let mut keys = entries.keys_range(range.clone());
+    let oldest_block_index = keys.next().map(|k| k.2);
+    let newest_block_index = keys.next_back().map(|k| k.2).or(oldest_block_index);
+
+    let (page_range, skip, end) = if let Some(cursor) = cursor {
+        (
+            (
+                range.0,
+                Bound::Excluded(entry_key(&owner, cursor.block_index, &cursor.id)),
+            ),
+            0,
+            None,
+        )
+    } else {
+        let end = start.map_or(total_stored, |start| start.min(total_stored));
+        let skip = usize::try_from(total_stored - end).expect("skip should fit in usize");
+        (range, skip, Some(end))
+    };
 
-    let transactions = map.get(&key).map(|c| c.0).unwrap_or_default();
+    let newest_first = entries.range(page_range).rev().skip(skip).map(|entry| {
+        let (key, tx) = entry.into_pair();
+        (key.2, key.3, tx.0)
+    });
+    let page = read_page(newest_first, max_results, filter.as_ref());
 
-    let max_results = usize::try_from(max_results.min(MAX_GET_USER_TRANSACTIONS_RESULTS))
-        .expect("max_results should fit in usize");
+    page.into_response(newest_block_index, oldest_block_index, total_stored, end)
+}
 
-    let newest_block_index = transactions.last().map(|t| t.block_index);
-    let oldest_block_index = transactions.first().map(|t| t.block_index);
+/// Like [`get_transactions`], but reads a `(principal, token_id)` pair still in the legacy
+/// `Vec`-per-token layout, without migrating it, so that queries see the pair before the
+/// background migration reaches it.
+///
+/// Returns `None` if the pair is not in the legacy layout.
+// TODO: remove once all canisters have been upgraded past the per-entry migration.
+pub fn get_legacy_transactions(
+    legacy: &LegacyUserTransactionsMap,
+    principal: Principal,
+    request: &GetUserTransactionsRequest,
+) -> Option<GetUserTransactionsResponse> {
+    let GetUserTransactionsRequest {
+        token_id,
+        start,
+        max_results,
+        cursor,
+        filter,
+    } = request;
+
+    let mut transactions = legacy.get(&make_key(principal, token_id))?.0;
+    // Legacy lists are only sorted by `block_index`; use the per-entry order so that cursors carry
+    // over once the pair is migrated.
+    transactions.sort_unstable_by(|a, b| (a.block_index, &a.id).cmp(&(b.block_index, &b.id)));
+
+    let total_stored = u64::try_from(transactions.len()).expect("len should fit in u64");
+    let oldest_block_index = transactions.first().map(|tx| tx.block_index);
+    let newest_block_index = transactions.last().map(|tx| tx.block_index);
+
+    let (end_index, end) = if let Some(cursor) = cursor {
+        let end_index = transactions.partition_point(|tx| {
+            (tx.block_index, tx.id.as_str()) < (cursor.block_index, cursor.id.as_str())
+        });
+        (end_index, None)
+    } else {
+        let end = start.map_or(total_stored, |start| start.min(total_stored));
+        (
+            usize::try_from(end).expect("end should fit in usize"),
+            Some(end),
+        )
+    };
 
-    let len = transactions.len();
-    let total_stored = u64::try_from(len).expect("len should fit in u64");
+    transactions.truncate(end_index);
+    let newest_first = transactions
+        .into_iter()
+        .rev()
+        .map(|tx| (tx.block_index, tx.id.clone(), tx));
+    let page = read_page(newest_first, *max_results, filter.as_ref());
 
-    let end = match start {
-        None => len,
-        Some(cursor) => usize::try_from(cursor).unwrap_or(len).min(len),
-    };
+    Some(page.into_response(newest_block_index, oldest_block_index, total_stored, end))
+}
 
-    let start = end.saturating_sub(max_results);
+/// A page of a pair's transactions, read newest first.
+struct TransactionsPage {
+    transactions: Vec<UserTransaction>,
+    /// `(block_index, id)` of the last examined entry, matching the filter or not.
+    last_scanned: Option<(u64, String)>,
+    scanned: u64,
+    has_older: bool,
+}
 
-    let page: Vec<UserTransaction> = transactions[start..end].iter().rev().cloned().collect();
+/// Reads up to `max_results` transactions matching `filter` from `newest_first`, examining at most
+/// [`MAX_SCANNED_USER_TRANSACTIONS`] entries.
+fn read_page(
+    newest_first: impl Iterator<Item = (u64, String, UserTransaction)>,
+    max_results: u64,
+    filter: Option<&UserTransactionFilter>,
+) -> TransactionsPage {
+    let max_results = usize::try_from(max_results.min(MAX_GET_USER_TRANSACTIONS_RESULTS))
+        .expect("max_results should fit in usize");
 
-    let next_start = if start > 0 && !page.is_empty() {
-        Some(u64::try_from(start).expect("index should fit in u64"))
-    } else {
-        None
-    };
+    let mut transactions = Vec::with_capacity(max_results);
+    let mut last_scanned = None;
+    let mut scanned: u64 = 0;
+    let mut has_older = false;
+    for (block_index, id, tx) in newest_first {
+        if transactions.len() == max_results || scanned == MAX_SCANNED_USER_TRANSACTIONS {
+            has_older = true;
+            break;
+        }
+        scanned += 1;
+        if filter.is_none_or(|filter| matches_filter(filter, &tx)) {
+            transactions.push(tx);
+        }
+        last_scanned = Some((block_index, id));
+    }
+
+    TransactionsPage {
+        transactions,
+        last_scanned,
+        scanned,
+        has_older,
+    }
+}
 
-    GetUserTransactionsResponse {
-        transactions: page,
-        newest_block_index,
-        oldest_block_index,
-        total_stored,
-        next_start,
+impl TransactionsPage {
+    /// `end` is the positional index the page was read below, if it was not read from a cursor.
+    fn into_response(
+        self,
+        newest_block_index: Option<u64>,
+        oldest_block_index: Option<u64>,
+        total_stored: u64,
+        end: Option<u64>,
+    ) -> GetUserTransactionsResponse {
+        let Self {
+            transactions,
+            last_scanned,
+            scanned,
+            has_older,
+        } = self;
+
+        let next_cursor = last_scanned
+            .filter(|_| has_older)
+            .map(|(block_index, id)| UserTransactionCursor { block_index, id });
+        let next_start = end
+            .map(|end| end.saturating_sub(scanned))
+            .filter(|_| has_older && scanned > 0);
+
+        GetUserTransactionsResponse {
+            transactions,
+            newest_block_index,
+            oldest_block_index,
+            total_stored,
+            next_start,
+            next_cursor,
+        }
     }
 }
 
 /// Save finalized transactions for a user and token.
-/// Transactions are deduplicated by hash and kept sorted by `block_index` ascending.
+///
+/// Transactions are deduplicated by `id`, whatever their `block_index`, and stored individually,
+/// so the cost is proportional to the batch (plus any evicted entries), not to the number already
+/// stored.
+/// When the per-token cap is exceeded, the oldest entries are evicted.
 pub fn save_transactions(
-    map: &mut UserTransactionsMap,
+    entries: &mut UserTransactionsMap,
+    counts: &mut UserTransactionCountsMap,
+    ids: &mut UserTransactionIdIndexMap,
+    activity: &mut UserActivityIndexMap,
     principal: Principal,
     token_id: &TokenId,
     transactions: &[UserTransaction],
@@ -78,86 +224,426 @@ pub fn save_transactions(
     if transactions.len() > MAX_SAVE_USER_TRANSACTIONS_BATCH {
         return Err(UserTransactionError::TooManyTransactions);
     }
+    // Requests carrying reserved values are rejected on deserialization, so reaching these checks
+    // is a bug of the backend rather than invalid input.
+    if transactions
+        .iter()
+        .any(|tx| tx.block_index == RESERVED_BLOCK_INDEX)
+    {
+        return Err(UserTransactionError::InternalError {
+            msg: format!("block_index {RESERVED_BLOCK_INDEX} is reserved"),
+        });
+    }
+    if transactions
+        .iter()
+        .any(|tx| tx.timestamp == RESERVED_TIMESTAMP)
+    {
+        return Err(UserTransactionError::InternalError {
+            msg: format!("timestamp {RESERVED_TIMESTAMP} is reserved"),
+        });
+    }
 
-    let key = make_key(principal, token_id);
-
-    let mut existing = map.get(&key).map(|c| c.0).unwrap_or_default();
-
-    let mut known_ids: HashSet<String> = existing.iter().map(|e| e.id.clone()).collect();
+    let owner = make_key(principal, token_id);
+    let mut count = counts.get(&owner).unwrap_or_default();
 
-    let mut new_txs = Vec::new();
+    let mut batch_ids: HashSet<&str> = HashSet::with_capacity(transactions.len());
+    let mut inserted = false;
     for tx in transactions {
-        if !known_ids.insert(tx.id.clone()) {
+        if !batch_ids.insert(tx.id.as_str()) {
             continue;
         }
-        new_txs.push(tx.clone());
-    }
-
-    if !new_txs.is_empty() {
-        new_txs.sort_unstable_by_key(|t| t.block_index);
-        existing = merge_sorted(existing, new_txs);
-
-        if existing.len() > MAX_USER_TRANSACTIONS_PER_TOKEN {
-            let mut trim_at = existing.len() - MAX_USER_TRANSACTIONS_PER_TOKEN;
-
-            // If the trim lands mid-block, advance to the next complete block boundary
-            // so we avoid storing a partial block at the oldest end.
-            if trim_at < existing.len() {
-                let boundary_block = existing[trim_at].block_index;
-                if trim_at > 0 && existing[trim_at - 1].block_index == boundary_block {
-                    if let Some(offset) = existing[trim_at..]
-                        .iter()
-                        .position(|t| t.block_index != boundary_block)
-                    {
-                        trim_at += offset;
-                    }
-                    // If every remaining tx shares the same block_index, keep the
-                    // original trim_at to avoid dropping everything.
-                }
-            }
-
-            existing = existing.split_off(trim_at);
+        let id = id_key(&owner, &tx.id);
+        if ids.contains_key(&id) {
+            continue;
         }
+        let key = entry_key(&owner, tx.block_index, &tx.id);
+        ids.insert(id, tx.block_index);
+        activity.insert(UserActivityKey::new(&key, tx.timestamp), ());
+        entries.insert(key, Candid(tx.clone()));
+        count += 1;
+        inserted = true;
+    }
+
+    if !inserted {
+        return Ok(());
+    }
+
+    let cap = u64::try_from(MAX_USER_TRANSACTIONS_PER_TOKEN).expect("cap should fit in u64");
+    if count > cap {
+        count -= evict_oldest(entries, ids, activity, &owner, count - cap);
     }
 
-    map.insert(key, Candid(existing));
+    counts.insert(owner, count);
 
     Ok(())
 }
 
-fn merge_sorted(a: Vec<UserTransaction>, b: Vec<UserTransaction>) -> Vec<UserTransaction> {
-    let mut result = Vec::with_capacity(a.len() + b.len());
-    let mut a_iter = a.into_iter().peekable();
-    let mut b_iter = b.into_iter().peekable();
-
-    loop {
-        match (a_iter.peek(), b_iter.peek()) {
-            (Some(a_tx), Some(b_tx)) => {
-                if a_tx.block_index <= b_tx.block_index {
-                    result.push(a_iter.next().unwrap());
-                } else {
-                    result.push(b_iter.next().unwrap());
+/// Removes the `excess` oldest entries of a `(principal, token_id)` pair.
+///
+/// If the trim lands mid-block, it advances to the next complete block boundary so we avoid
+/// storing a partial block at the oldest end. If every remaining entry shares the boundary block,
+/// the original trim point is kept to avoid dropping everything.
+///
+/// Returns the number of removed entries.
+fn evict_oldest(
+    entries: &mut UserTransactionsMap,
+    ids: &mut UserTransactionIdIndexMap,
+    activity: &mut UserActivityIndexMap,
+    owner: &UserTransactionKey,
+    excess: u64,
+) -> u64 {
+    let excess = usize::try_from(excess).expect("excess should fit in usize");
+    let mut keys = entries.keys_range(entry_range(owner));
+
+    let mut to_remove: Vec<UserTransactionEntryKey> = keys.by_ref().take(excess).collect();
+
+    if let Some(first_kept) = keys.next() {
+        let boundary_block = first_kept.2;
+        if to_remove.last().is_some_and(|k| k.2 == boundary_block) {
+            let mut partial_block = vec![first_kept];
+            let mut reached_next_block = false;
+            for key in keys {
+                if key.2 != boundary_block {
+                    reached_next_block = true;
+                    break;
                 }
+                partial_block.push(key);
             }
-            (Some(_), None) => {
-                result.extend(a_iter);
-                break;
+            if reached_next_block {
+                to_remove.extend(partial_block);
             }
-            (None, Some(_)) => {
-                result.extend(b_iter);
-                break;
+        }
+    }
+
+    for key in &to_remove {
+        if let Some(tx) = entries.remove(key) {
+            ids.remove(&id_key(owner, &key.3));
+            activity.remove(&UserActivityKey::new(key, tx.timestamp));
+        }
+    }
+
+    u64::try_from(to_remove.len()).expect("removed count should fit in u64")
+}
+
+/// Moves legacy `Vec`-per-token transactions into per-entry storage, oldest pair first, until
+/// at least `max_transactions` transactions have been moved or the legacy map is empty.
+///
+/// A pair is always moved as a whole. Returns the number of migrated transactions.
+// TODO: remove once all canisters have been upgraded past the per-entry migration.
+pub fn migrate_legacy_transactions(
+    legacy: &mut LegacyUserTransactionsMap,
+    entries: &mut UserTransactionsMap,
+    counts: &mut UserTransactionCountsMap,
+    ids: &mut UserTransactionIdIndexMap,
+    activity: &mut UserActivityIndexMap,
+    max_transactions: usize,
+) -> usize {
+    let mut migrated = 0;
+    while migrated < max_transactions {
+        let Some((owner, transactions)) = legacy.pop_first() else {
+            break;
+        };
+        migrated +=
+            insert_legacy_transactions(entries, counts, ids, activity, owner, transactions.0);
+    }
+    migrated
+}
+
+/// Moves the legacy transactions of a single `(principal, token_id)` pair, if any, into
+/// per-entry storage, so that a read or save for that pair sees them before the background
+/// migration reaches it.
+///
+/// Returns the number of migrated transactions.
+// TODO: remove once all canisters have been upgraded past the per-entry migration.
+pub fn migrate_legacy_token_transactions(
+    legacy: &mut LegacyUserTransactionsMap,
+    entries: &mut UserTransactionsMap,
+    counts: &mut UserTransactionCountsMap,
+    ids: &mut UserTransactionIdIndexMap,
+    activity: &mut UserActivityIndexMap,
+    principal: Principal,
+    token_id: &TokenId,
+) -> usize {
+    let owner = make_key(principal, token_id);
+    legacy.remove(&owner).map_or(0, |transactions| {
+        insert_legacy_transactions(entries, counts, ids, activity, owner, transactions.0)
+    })
+}
+
+fn insert_legacy_transactions(
+    entries: &mut UserTransactionsMap,
+    counts: &mut UserTransactionCountsMap,
+    ids: &mut UserTransactionIdIndexMap,
+    activity: &mut UserActivityIndexMap,
+    owner: UserTransactionKey,
+    transactions: Vec<UserTransaction>,
+) -> usize {
+    let migrated = transactions.len();
+    let mut count = counts.get(&owner).unwrap_or_default();
+    for tx in transactions {
+        let id = id_key(&owner, &tx.id);
+        if ids.contains_key(&id) {
+            continue;
+        }
+        let key = entry_key(&owner, tx.block_index, &tx.id);
+        ids.insert(id, tx.block_index);
+        activity.insert(UserActivityKey::new(&key, tx.timestamp), ());
+        entries.insert(key, Candid(tx));
+        count += 1;
+    }
+    if count > 0 {
+        counts.insert(owner, count);
+    }
+    migrated
+}
+
+/// Read a page of a user's transactions across tokens, newest first by timestamp.
+///
+/// Walks the activity index below `cursor` and only deserializes the transactions it examines.
+/// Index keys of tokens outside `token_ids` are skipped without decoding their values, and the
+/// filter's timestamp bounds narrow the walked range. At most [`MAX_SCANNED_USER_TRANSACTIONS`]
+/// index keys are examined; `next_cursor` then points past the last examined key.
+pub fn get_activity(
+    entries: &UserTransactionsMap,
+    activity: &UserActivityIndexMap,
+    principal: Principal,
+    request: GetUserActivityRequest,
+) -> GetUserActivityResponse {
+    read_activity(
+        entries,
+        activity,
+        principal,
+        request,
+        MAX_GET_USER_TRANSACTIONS_RESULTS,
+    )
+}
+
+/// Like [`get_activity`], but with pages of up to [`MAX_EXPORT_USER_TRANSACTIONS_ROWS`] items.
+pub fn get_activity_for_export(
+    entries: &UserTransactionsMap,
+    activity: &UserActivityIndexMap,
+    principal: Principal,
+    request: GetUserActivityRequest,
+) -> GetUserActivityResponse {
+    read_activity(
+        entries,
+        activity,
+        principal,
+        request,
+        MAX_EXPORT_USER_TRANSACTIONS_ROWS,
+    )
+}
+
+fn read_activity(
+    entries: &UserTransactionsMap,
+    activity: &UserActivityIndexMap,
+    principal: Principal,
+    request: GetUserActivityRequest,
+    results_limit: u64,
+) -> GetUserActivityResponse {
+    let GetUserActivityRequest {
+        token_ids,
+        max_results,
+        cursor,
+        filter,
+    } = request;
+
+    let principal = StoredPrincipal(principal);
+    let token_filter: Option<HashSet<Vec<u8>>> = token_ids.map(|ids| {
+        ids.into_iter()
+            .map(|id| StoredTokenId(id).to_bytes().into_owned())
+            .collect()
+    });
+    let max_results =
+        usize::try_from(max_results.min(results_limit)).expect("max_results should fit in usize");
+
+    let min_timestamp = filter.as_ref().and_then(|f| f.min_timestamp).unwrap_or(0);
+    let lower = UserActivityKey(principal, min_timestamp, Vec::new(), 0, String::new());
+    let end_timestamp = filter
+        .as_ref()
+        .and_then(|f| f.max_timestamp)
+        .map_or(RESERVED_TIMESTAMP, |max| max.saturating_add(1));
+    let mut upper = UserActivityKey(principal, end_timestamp, Vec::new(), 0, String::new());
+    if let Some(cursor) = cursor {
+        upper = upper.min(UserActivityKey(
+            principal,
+            cursor.timestamp,
+            StoredTokenId(cursor.token_id).to_bytes().into_owned(),
+            cursor.block_index,
+            cursor.id,
+        ));
+    }
+    if lower >= upper {
+        return GetUserActivityResponse {
+            items: Vec::new(),
+            next_cursor: None,
+        };
+    }
+
+    let max_scanned =
+        usize::try_from(MAX_SCANNED_USER_TRANSACTIONS).expect("scan limit should fit in usize");
+    let mut items = Vec::with_capacity(max_results);
+    let mut last_scanned = None;
+    let mut has_older = false;
+    for (scanned, key) in activity
+        .keys_range((Bound::Included(lower), Bound::Excluded(upper)))
+        .rev()
+        .enumerate()
+    {
+        if items.len() == max_results || scanned == max_scanned {
+            has_older = true;
+            break;
+        }
+        if token_filter
+            .as_ref()
+            .is_none_or(|tokens| tokens.contains(&key.2))
+        {
+            if let Some(tx) = entries.get(&key.entry_key()) {
+                if filter
+                    .as_ref()
+                    .is_none_or(|filter| matches_filter(filter, &tx))
+                {
+                    items.push(UserActivityItem {
+                        token_id: StoredTokenId::from_bytes(Cow::Borrowed(&key.2)).0,
+                        transaction: tx.0,
+                    });
+                }
             }
-            (None, None) => break,
         }
+        last_scanned = Some(key);
+    }
+
+    let next_cursor = last_scanned
+        .filter(|_| has_older)
+        .map(|key| activity_cursor(&key));
+
+    GetUserActivityResponse { items, next_cursor }
+}
+
+/// Whether `tx` meets every criterion set in `filter`.
+fn matches_filter(filter: &UserTransactionFilter, tx: &UserTransaction) -> bool {
+    let UserTransactionFilter {
+        from,
+        to,
+        direction,
+        min_timestamp,
+        max_timestamp,
+        min_value,
+        max_value,
+        network,
+    } = filter;
+
+    from.as_ref().is_none_or(|from| *from == tx.from)
+        && to.as_ref().is_none_or(|to| tx.to.as_ref() == Some(to))
+        && direction
+            .as_ref()
+            .is_none_or(|direction| matches_direction(direction, tx))
+        && min_timestamp.is_none_or(|min| tx.timestamp >= min)
+        && max_timestamp.is_none_or(|max| tx.timestamp <= max)
+        && min_value.as_ref().is_none_or(|min| tx.value >= *min)
+        && max_value.as_ref().is_none_or(|max| tx.value <= *max)
+        && network
+            .as_ref()
+            .is_none_or(|network| matches_network(network, &tx.network_data))
+}
+
+fn matches_direction(filter: &TransactionDirectionFilter, tx: &UserTransaction) -> bool {
+    match filter.direction {
+        TransactionDirection::Incoming => tx.to.as_ref() == Some(&filter.address),
+        TransactionDirection::Outgoing => tx.from == filter.address,
+    }
+}
+
+fn matches_network(filter: &NetworkTransactionFilter, data: &NetworkTransactionData) -> bool {
+    match (filter, data) {
+        (
+            NetworkTransactionFilter::Evm {
+                chain_id,
+                nft_token_id,
+            },
+            NetworkTransactionData::Evm(data),
+        ) => {
+            chain_id.is_none_or(|chain_id| data.chain_id == Some(chain_id))
+                && nft_token_id
+                    .as_ref()
+                    .is_none_or(|nft_token_id| data.nft_token_id.as_ref() == Some(nft_token_id))
+        }
+        (NetworkTransactionFilter::Icrc { tx_type }, NetworkTransactionData::Icrc(data)) => tx_type
+            .as_ref()
+            .is_none_or(|tx_type| match (tx_type, &data.tx_type) {
+                (IcrcTransactionTypeFilter::Transfer, IcrcTransactionType::Transfer)
+                | (IcrcTransactionTypeFilter::Mint, IcrcTransactionType::Mint)
+                | (IcrcTransactionTypeFilter::Burn, IcrcTransactionType::Burn) => true,
+                (
+                    IcrcTransactionTypeFilter::Approve { spender: expected },
+                    IcrcTransactionType::Approve { spender },
+                ) => expected.as_ref().is_none_or(|expected| expected == spender),
+                _ => false,
+            }),
+        (NetworkTransactionFilter::Btc, NetworkTransactionData::Btc(_))
+        | (NetworkTransactionFilter::Sol, NetworkTransactionData::Sol(_)) => true,
+        _ => false,
+    }
+}
+
+/// Indexes up to `max_entries` entries of the per-entry map in the activity index, starting after
+/// `after`. Indexing is idempotent, so a backfill can be restarted from scratch.
+///
+/// Returns the key to resume from, or `None` once every entry has been indexed.
+// TODO: remove once all canisters have been upgraded past the activity index backfill.
+pub fn backfill_activity_index(
+    entries: &UserTransactionsMap,
+    activity: &mut UserActivityIndexMap,
+    after: Option<UserTransactionEntryKey>,
+    max_entries: usize,
+) -> Option<UserTransactionEntryKey> {
+    let lower = after.map_or(Bound::Unbounded, Bound::Excluded);
+    let mut last = None;
+    for (indexed, entry) in entries.range((lower, Bound::Unbounded)).enumerate() {
+        if indexed == max_entries {
+            return last;
+        }
+        let (key, tx) = entry.into_pair();
+        activity.insert(UserActivityKey::new(&key, tx.timestamp), ());
+        last = Some(key);
     }
+    None
+}
 
-    result
+fn activity_cursor(key: &UserActivityKey) -> UserActivityCursor {
+    UserActivityCursor {
+        timestamp: key.1,
+        token_id: StoredTokenId::from_bytes(Cow::Borrowed(&key.2)).0,
+        block_index: key.3,
+        id: key.4.clone(),
+    }
 }
 
 fn make_key(principal: Principal, token_id: &TokenId) -> UserTransactionKey {
     UserTransactionKey(StoredPrincipal(principal), StoredTokenId(token_id.clone()))
 }
 
+fn entry_key(owner: &UserTransactionKey, block_index: u64, id: &str) -> UserTransactionEntryKey {
+    UserTransactionEntryKey::new(owner.0, &owner.1, block_index, id)
+}
+
+fn id_key(owner: &UserTransactionKey, id: &str) -> (StoredPrincipal, Vec<u8>, String) {
+    (owner.0, owner.1.to_bytes().into_owned(), id.to_string())
+}
+
+/// Key range covering every entry of a `(principal, token_id)` pair.
+fn entry_range(
+    owner: &UserTransactionKey,
+) -> (
+    Bound<UserTransactionEntryKey>,
+    Bound<UserTransactionEntryKey>,
+) {
+    (
+        Bound::Included(entry_key(owner, 0, "")),
+        Bound::Excluded(entry_key(owner, RESERVED_BLOCK_INDEX, "")),
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use std::cell::RefCell;
@@ -171,24 +657,166 @@ mod tests {
     use shared::types::{
         token_id::TokenId,
         user_transaction::{
-            EvmTransactionData, NetworkTransactionData, UserTransaction, UserTransactionError,
+            EvmTransactionData, GetUserActivityRequest, GetUserActivityResponse,
+            GetUserTransactionsRequest, GetUserTransactionsResponse, IcrcTransactionData,
+            IcrcTransactionType, IcrcTransactionTypeFilter, NetworkTransactionData,
+            NetworkTransactionFilter, TransactionDirection, TransactionDirectionFilter,
+            UserActivityCursor, UserTransaction, UserTransactionCursor, UserTransactionError,
+            UserTransactionFilter, MAX_EXPORT_USER_TRANSACTIONS_ROWS,
             MAX_GET_USER_TRANSACTIONS_RESULTS, MAX_SAVE_USER_TRANSACTIONS_BATCH,
-            MAX_USER_TRANSACTIONS_PER_TOKEN,
+            MAX_SCANNED_USER_TRANSACTIONS, MAX_USER_TRANSACTIONS_PER_TOKEN,
         },
     };
 
-    use super::{get_transactions, make_key, save_transactions};
-    use crate::types::{maps::UserTransactionsMap, storable::Candid};
+    use super::{
+        backfill_activity_index, get_activity, get_activity_for_export, get_legacy_transactions,
+        get_transactions, make_key, migrate_legacy_token_transactions, migrate_legacy_transactions,
+        save_transactions,
+    };
+    use crate::types::{
+        maps::{
+            LegacyUserTransactionsMap, UserActivityIndexMap, UserTransactionCountsMap,
+            UserTransactionIdIndexMap, UserTransactionsMap,
+        },
+        storable::Candid,
+    };
 
     const PRINCIPAL_TEXT: &str = "7blps-itamd-lzszp-7lbda-4nngn-fev5u-2jvpn-6y3ap-eunp7-kz57e-fqe";
 
-    fn setup() -> (
-        UserTransactionsMap,
-        RefCell<MemoryManager<DefaultMemoryImpl>>,
-    ) {
+    struct Maps {
+        entries: UserTransactionsMap,
+        counts: UserTransactionCountsMap,
+        ids: UserTransactionIdIndexMap,
+        activity: UserActivityIndexMap,
+        legacy: LegacyUserTransactionsMap,
+    }
+
+    impl Maps {
+        fn init(memory_manager: &RefCell<MemoryManager<DefaultMemoryImpl>>) -> Self {
+            let mm = memory_manager.borrow();
+            Self {
+                entries: UserTransactionsMap::init(mm.get(MemoryId::new(0))),
+                counts: UserTransactionCountsMap::init(mm.get(MemoryId::new(1))),
+                activity: UserActivityIndexMap::init(mm.get(MemoryId::new(2))),
+                legacy: LegacyUserTransactionsMap::init(mm.get(MemoryId::new(3))),
+                ids: UserTransactionIdIndexMap::init(mm.get(MemoryId::new(4))),
+            }
+        }
+    }
+
+    fn setup() -> (Maps, RefCell<MemoryManager<DefaultMemoryImpl>>) {
         let memory_manager = RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
-        let map = UserTransactionsMap::init(memory_manager.borrow().get(MemoryId::new(0)));
-        (map, memory_manager)
+        let maps = Maps::init(&memory_manager);
+        (maps, memory_manager)
+    }
+
+    fn get(
+        maps: &Maps,
+        principal: Principal,
+        token_id: &TokenId,
+        start: Option<u64>,
+        max_results: u64,
+    ) -> GetUserTransactionsResponse {
+        get_transactions(
+            &maps.entries,
+            &maps.counts,
+            principal,
+            GetUserTransactionsRequest {
+                token_id: token_id.clone(),
+                start,
+                max_results,
+                cursor: None,
+                filter: None,
+            },
+        )
+    }
+
+    fn get_after(
+        maps: &Maps,
+        principal: Principal,
+        token_id: &TokenId,
+        cursor: Option<&UserTransactionCursor>,
+        max_results: u64,
+    ) -> GetUserTransactionsResponse {
+        get_filtered(maps, principal, token_id, cursor, None, max_results)
+    }
+
+    fn get_filtered(
+        maps: &Maps,
+        principal: Principal,
+        token_id: &TokenId,
+        cursor: Option<&UserTransactionCursor>,
+        filter: Option<&UserTransactionFilter>,
+        max_results: u64,
+    ) -> GetUserTransactionsResponse {
+        get_transactions(
+            &maps.entries,
+            &maps.counts,
+            principal,
+            GetUserTransactionsRequest {
+                token_id: token_id.clone(),
+                start: None,
+                max_results,
+                cursor: cursor.cloned(),
+                filter: filter.cloned(),
+            },
+        )
+    }
+
+    fn save(
+        maps: &mut Maps,
+        principal: Principal,
+        token_id: &TokenId,
+        transactions: &[UserTransaction],
+    ) -> Result<(), UserTransactionError> {
+        save_transactions(
+            &mut maps.entries,
+            &mut maps.counts,
+            &mut maps.ids,
+            &mut maps.activity,
+            principal,
+            token_id,
+            transactions,
+        )
+    }
+
+    fn activity(
+        maps: &Maps,
+        principal: Principal,
+        token_ids: Option<&[TokenId]>,
+        cursor: Option<&UserActivityCursor>,
+        max_results: u64,
+    ) -> GetUserActivityResponse {
+        activity_filtered(maps, principal, token_ids, cursor, None, max_results)
+    }
+
+    fn activity_filtered(
+        maps: &Maps,
+        principal: Principal,
+        token_ids: Option<&[TokenId]>,
+        cursor: Option<&UserActivityCursor>,
+        filter: Option<&UserTransactionFilter>,
+        max_results: u64,
+    ) -> GetUserActivityResponse {
+        get_activity(
+            &maps.entries,
+            &maps.activity,
+            principal,
+            GetUserActivityRequest {
+                token_ids: token_ids.map(<[TokenId]>::to_vec),
+                max_results,
+                cursor: cursor.cloned(),
+                filter: filter.cloned(),
+            },
+        )
+    }
+
+    fn activity_ids(response: &GetUserActivityResponse) -> Vec<&str> {
+        response
+            .items
+            .iter()
+            .map(|item| item.transaction.id.as_str())
+            .collect()
     }
 
     fn make_tx(id: &str, block_index: u64, timestamp: u64) -> UserTransaction {
@@ -215,10 +843,18 @@ mod tests {
         TokenId::EvmNative(1)
     }
 
-    fn insert_transactions(map: &mut UserTransactionsMap, txs: Vec<UserTransaction>) {
+    fn insert_transactions(maps: &mut Maps, txs: &[UserTransaction]) {
         let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
+        save(maps, principal, &eth_native_token(), txs).unwrap();
+    }
+
+    fn insert_legacy_transactions(
+        maps: &mut Maps,
+        principal: Principal,
+        txs: Vec<UserTransaction>,
+    ) {
         let key = make_key(principal, &eth_native_token());
-        map.insert(key, Candid(txs));
+        maps.legacy.insert(key, Candid(txs));
     }
 
     #[test]
@@ -226,7 +862,7 @@ mod tests {
         let (map, _mm) = setup();
         let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
 
-        let result = get_transactions(&map, principal, &eth_native_token(), None, 10);
+        let result = get(&map, principal, &eth_native_token(), None, 10);
 
         assert!(result.transactions.is_empty());
         assert!(result.newest_block_index.is_none());
@@ -243,9 +879,9 @@ mod tests {
         let txs: Vec<UserTransaction> = (10..15)
             .map(|i| make_tx(&format!("0xhash{i}"), i, i * 10))
             .collect();
-        insert_transactions(&mut map, txs);
+        insert_transactions(&mut map, &txs);
 
-        let result = get_transactions(&map, principal, &eth_native_token(), None, 10);
+        let result = get(&map, principal, &eth_native_token(), None, 10);
 
         let block_indices: Vec<u64> = result.transactions.iter().map(|t| t.block_index).collect();
         assert_eq!(block_indices, vec![14, 13, 12, 11, 10]);
@@ -259,9 +895,9 @@ mod tests {
         let txs: Vec<UserTransaction> = (5..10)
             .map(|i| make_tx(&format!("0xhash{i}"), i, i * 10))
             .collect();
-        insert_transactions(&mut map, txs);
+        insert_transactions(&mut map, &txs);
 
-        let result = get_transactions(&map, principal, &eth_native_token(), None, 10);
+        let result = get(&map, principal, &eth_native_token(), None, 10);
 
         assert_eq!(result.oldest_block_index, Some(5));
         assert_eq!(result.newest_block_index, Some(9));
@@ -277,9 +913,9 @@ mod tests {
         let txs: Vec<UserTransaction> = (0..count)
             .map(|i| make_tx(&format!("0xhash{i}"), i, i * 10))
             .collect();
-        insert_transactions(&mut map, txs);
+        insert_transactions(&mut map, &txs);
 
-        let result = get_transactions(&map, principal, &eth_native_token(), None, count + 1000);
+        let result = get(&map, principal, &eth_native_token(), None, count + 1000);
 
         assert_eq!(
             result.transactions.len(),
@@ -296,22 +932,22 @@ mod tests {
         let txs: Vec<UserTransaction> = (0..7)
             .map(|i| make_tx(&format!("0xhash{i}"), i, i * 10))
             .collect();
-        insert_transactions(&mut map, txs);
+        insert_transactions(&mut map, &txs);
 
         // Page 1: newest 3
-        let page1 = get_transactions(&map, principal, &eth_native_token(), None, 3);
+        let page1 = get(&map, principal, &eth_native_token(), None, 3);
         let p1_indices: Vec<u64> = page1.transactions.iter().map(|t| t.block_index).collect();
         assert_eq!(p1_indices, vec![6, 5, 4]);
         assert!(page1.next_start.is_some());
 
         // Page 2
-        let page2 = get_transactions(&map, principal, &eth_native_token(), page1.next_start, 3);
+        let page2 = get(&map, principal, &eth_native_token(), page1.next_start, 3);
         let p2_indices: Vec<u64> = page2.transactions.iter().map(|t| t.block_index).collect();
         assert_eq!(p2_indices, vec![3, 2, 1]);
         assert!(page2.next_start.is_some());
 
         // Page 3: last remaining
-        let page3 = get_transactions(&map, principal, &eth_native_token(), page2.next_start, 3);
+        let page3 = get(&map, principal, &eth_native_token(), page2.next_start, 3);
         let p3_indices: Vec<u64> = page3.transactions.iter().map(|t| t.block_index).collect();
         assert_eq!(p3_indices, vec![0]);
         assert!(
@@ -328,13 +964,13 @@ mod tests {
         let txs: Vec<UserTransaction> = (0..10)
             .map(|i| make_tx(&format!("0xhash{i}"), i, i * 10))
             .collect();
-        insert_transactions(&mut map, txs);
+        insert_transactions(&mut map, &txs);
 
         let mut all_indices = Vec::new();
         let mut cursor: Option<u64> = None;
 
         loop {
-            let page = get_transactions(&map, principal, &eth_native_token(), cursor, 3);
+            let page = get(&map, principal, &eth_native_token(), cursor, 3);
             let indices: Vec<u64> = page.transactions.iter().map(|t| t.block_index).collect();
             all_indices.extend(indices);
             cursor = page.next_start;
@@ -354,9 +990,9 @@ mod tests {
         let txs: Vec<UserTransaction> = (0..5)
             .map(|i| make_tx(&format!("0xhash{i}"), i, i * 10))
             .collect();
-        insert_transactions(&mut map, txs);
+        insert_transactions(&mut map, &txs);
 
-        let result = get_transactions(&map, principal, &eth_native_token(), Some(0), 10);
+        let result = get(&map, principal, &eth_native_token(), Some(0), 10);
 
         assert!(
             result.transactions.is_empty(),
@@ -374,10 +1010,10 @@ mod tests {
         let txs: Vec<UserTransaction> = (0..5)
             .map(|i| make_tx(&format!("0xhash{i}"), i, i * 10))
             .collect();
-        insert_transactions(&mut map, txs);
+        insert_transactions(&mut map, &txs);
 
-        let result_none = get_transactions(&map, principal, &eth_native_token(), None, 10);
-        let result_len = get_transactions(&map, principal, &eth_native_token(), Some(5), 10);
+        let result_none = get(&map, principal, &eth_native_token(), None, 10);
+        let result_len = get(&map, principal, &eth_native_token(), Some(5), 10);
 
         assert_eq!(
             result_none.transactions, result_len.transactions,
@@ -393,9 +1029,9 @@ mod tests {
         let txs: Vec<UserTransaction> = (0..5)
             .map(|i| make_tx(&format!("0xhash{i}"), i, i * 10))
             .collect();
-        insert_transactions(&mut map, txs);
+        insert_transactions(&mut map, &txs);
 
-        let result = get_transactions(&map, principal, &eth_native_token(), Some(999), 10);
+        let result = get(&map, principal, &eth_native_token(), Some(999), 10);
 
         let block_indices: Vec<u64> = result.transactions.iter().map(|t| t.block_index).collect();
         assert_eq!(
@@ -410,9 +1046,9 @@ mod tests {
         let (mut map, _mm) = setup();
         let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
 
-        insert_transactions(&mut map, vec![make_tx("0xhash42", 42, 420)]);
+        insert_transactions(&mut map, &[make_tx("0xhash42", 42, 420)]);
 
-        let result = get_transactions(&map, principal, &eth_native_token(), None, 10);
+        let result = get(&map, principal, &eth_native_token(), None, 10);
 
         assert_eq!(result.transactions.len(), 1);
         assert_eq!(result.transactions[0].block_index, 42);
@@ -431,7 +1067,7 @@ mod tests {
         let tx2 = make_tx("0xhash2", 200, 2000);
         let tx3 = make_tx("0xhash3", 300, 3000);
 
-        save_transactions(
+        save(
             &mut map,
             principal,
             &eth_native_token(),
@@ -439,7 +1075,7 @@ mod tests {
         )
         .unwrap();
 
-        let result = get_transactions(&map, principal, &eth_native_token(), None, 10);
+        let result = get(&map, principal, &eth_native_token(), None, 10);
 
         assert_eq!(result.transactions.len(), 3);
         assert_eq!(result.transactions[0].id, "0xhash3");
@@ -459,10 +1095,10 @@ mod tests {
             .map(|i| make_tx(&format!("0xhash{i}"), i * 100, i * 1000))
             .collect();
 
-        save_transactions(&mut map, principal, &eth_native_token(), &txs).unwrap();
+        save(&mut map, principal, &eth_native_token(), &txs).unwrap();
 
         // First page: newest 2
-        let page1 = get_transactions(&map, principal, &eth_native_token(), None, 2);
+        let page1 = get(&map, principal, &eth_native_token(), None, 2);
 
         assert_eq!(page1.transactions.len(), 2);
         assert_eq!(page1.transactions[0].block_index, 500);
@@ -471,7 +1107,7 @@ mod tests {
         assert_eq!(page1.newest_block_index, Some(500));
 
         // Second page
-        let page2 = get_transactions(&map, principal, &eth_native_token(), page1.next_start, 2);
+        let page2 = get(&map, principal, &eth_native_token(), page1.next_start, 2);
 
         assert_eq!(page2.transactions.len(), 2);
         assert_eq!(page2.transactions[0].block_index, 300);
@@ -479,7 +1115,7 @@ mod tests {
         assert_eq!(page2.next_start, Some(1));
 
         // Third page: only 1 item remaining
-        let page3 = get_transactions(&map, principal, &eth_native_token(), page2.next_start, 2);
+        let page3 = get(&map, principal, &eth_native_token(), page2.next_start, 2);
 
         assert_eq!(page3.transactions.len(), 1);
         assert_eq!(page3.transactions[0].block_index, 100);
@@ -492,14 +1128,14 @@ mod tests {
         let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
         let tx = make_tx("0xhash1", 100, 1000);
 
-        save_transactions(
+        save(
             &mut map,
             principal,
             &eth_native_token(),
             std::slice::from_ref(&tx),
         )
         .unwrap();
-        save_transactions(
+        save(
             &mut map,
             principal,
             &eth_native_token(),
@@ -507,9 +1143,36 @@ mod tests {
         )
         .unwrap();
 
-        let result = get_transactions(&map, principal, &eth_native_token(), None, 10);
+        let result = get(&map, principal, &eth_native_token(), None, 10);
+
+        assert_eq!(result.transactions.len(), 1);
+    }
+
+    #[test]
+    fn test_deduplication_by_id_ignores_block_index() {
+        let (mut map, _mm) = setup();
+        let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
+
+        save(
+            &mut map,
+            principal,
+            &eth_native_token(),
+            &[make_tx("0xhash1", 100, 1000)],
+        )
+        .unwrap();
+        save(
+            &mut map,
+            principal,
+            &eth_native_token(),
+            &[make_tx("0xhash1", 101, 1000)],
+        )
+        .unwrap();
+
+        let result = get(&map, principal, &eth_native_token(), None, 10);
 
         assert_eq!(result.transactions.len(), 1);
+        assert_eq!(result.transactions[0].block_index, 100);
+        assert_eq!(result.total_stored, 1);
     }
 
     #[test]
@@ -526,14 +1189,14 @@ mod tests {
         );
         let erc20_tx = make_tx("0xerc20_hash", 200, 2000);
 
-        save_transactions(&mut map, principal, &eth_native_token(), &[eth_tx]).unwrap();
-        save_transactions(&mut map, principal, &erc20_token_id, &[erc20_tx]).unwrap();
+        save(&mut map, principal, &eth_native_token(), &[eth_tx]).unwrap();
+        save(&mut map, principal, &erc20_token_id, &[erc20_tx]).unwrap();
 
-        let eth_result = get_transactions(&map, principal, &eth_native_token(), None, 10);
+        let eth_result = get(&map, principal, &eth_native_token(), None, 10);
         assert_eq!(eth_result.transactions.len(), 1);
         assert_eq!(eth_result.transactions[0].id, "0xeth_hash");
 
-        let erc20_result = get_transactions(&map, principal, &erc20_token_id, None, 10);
+        let erc20_result = get(&map, principal, &erc20_token_id, None, 10);
         assert_eq!(erc20_result.transactions.len(), 1);
         assert_eq!(erc20_result.transactions[0].id, "0xerc20_hash");
     }
@@ -549,14 +1212,14 @@ mod tests {
         let tx1 = make_tx("0xuser1_hash", 100, 1000);
         let tx2 = make_tx("0xuser2_hash", 200, 2000);
 
-        save_transactions(&mut map, principal1, &eth_native_token(), &[tx1]).unwrap();
-        save_transactions(&mut map, principal2, &eth_native_token(), &[tx2]).unwrap();
+        save(&mut map, principal1, &eth_native_token(), &[tx1]).unwrap();
+        save(&mut map, principal2, &eth_native_token(), &[tx2]).unwrap();
 
-        let result1 = get_transactions(&map, principal1, &eth_native_token(), None, 10);
+        let result1 = get(&map, principal1, &eth_native_token(), None, 10);
         assert_eq!(result1.transactions.len(), 1);
         assert_eq!(result1.transactions[0].id, "0xuser1_hash");
 
-        let result2 = get_transactions(&map, principal2, &eth_native_token(), None, 10);
+        let result2 = get(&map, principal2, &eth_native_token(), None, 10);
         assert_eq!(result2.transactions.len(), 1);
         assert_eq!(result2.transactions[0].id, "0xuser2_hash");
     }
@@ -570,9 +1233,9 @@ mod tests {
         let tx1 = make_tx("0xhash1", 100, 1000);
         let tx2 = make_tx("0xhash2", 200, 2000);
 
-        save_transactions(&mut map, principal, &eth_native_token(), &[tx3, tx1, tx2]).unwrap();
+        save(&mut map, principal, &eth_native_token(), &[tx3, tx1, tx2]).unwrap();
 
-        let result = get_transactions(&map, principal, &eth_native_token(), None, 10);
+        let result = get(&map, principal, &eth_native_token(), None, 10);
 
         assert_eq!(result.transactions[0].block_index, 300);
         assert_eq!(result.transactions[1].block_index, 200);
@@ -584,7 +1247,7 @@ mod tests {
         let (mut map, _mm) = setup();
         let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
 
-        save_transactions(
+        save(
             &mut map,
             principal,
             &eth_native_token(),
@@ -592,7 +1255,7 @@ mod tests {
         )
         .unwrap();
 
-        save_transactions(
+        save(
             &mut map,
             principal,
             &eth_native_token(),
@@ -600,7 +1263,7 @@ mod tests {
         )
         .unwrap();
 
-        let result = get_transactions(&map, principal, &eth_native_token(), None, 10);
+        let result = get(&map, principal, &eth_native_token(), None, 10);
 
         assert_eq!(result.transactions.len(), 3);
         assert_eq!(result.newest_block_index, Some(300));
@@ -613,9 +1276,8 @@ mod tests {
         let tx = make_tx("0xpersist", 42, 420);
 
         {
-            let memory = memory_manager.borrow().get(MemoryId::new(0));
-            let mut map = UserTransactionsMap::init(memory);
-            save_transactions(
+            let mut map = Maps::init(&memory_manager);
+            save(
                 &mut map,
                 principal,
                 &eth_native_token(),
@@ -626,9 +1288,8 @@ mod tests {
 
         // Re-init with same memory
         {
-            let memory = memory_manager.borrow().get(MemoryId::new(0));
-            let map = UserTransactionsMap::init(memory);
-            let result = get_transactions(&map, principal, &eth_native_token(), None, 10);
+            let map = Maps::init(&memory_manager);
+            let result = get(&map, principal, &eth_native_token(), None, 10);
             assert_eq!(result.transactions.len(), 1);
             assert_eq!(result.transactions[0].id, "0xpersist");
         }
@@ -644,7 +1305,7 @@ mod tests {
         let tx0 = make_tx("0xgenesis", 0, 0);
         let tx1 = make_tx("0xblock1", 1, 10);
 
-        save_transactions(
+        save(
             &mut map,
             principal,
             &eth_native_token(),
@@ -653,13 +1314,13 @@ mod tests {
         .unwrap();
 
         // Page size 1: first page returns block 1
-        let page1 = get_transactions(&map, principal, &eth_native_token(), None, 1);
+        let page1 = get(&map, principal, &eth_native_token(), None, 1);
         assert_eq!(page1.transactions.len(), 1);
         assert_eq!(page1.transactions[0].block_index, 1);
         assert_eq!(page1.next_start, Some(1));
 
         // Second page: block 0
-        let page2 = get_transactions(&map, principal, &eth_native_token(), page1.next_start, 1);
+        let page2 = get(&map, principal, &eth_native_token(), page1.next_start, 1);
         assert_eq!(page2.transactions.len(), 1);
         assert_eq!(page2.transactions[0].block_index, 0);
         assert_eq!(page2.next_start, None);
@@ -670,7 +1331,7 @@ mod tests {
         let (mut map, _mm) = setup();
         let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
 
-        save_transactions(
+        save(
             &mut map,
             principal,
             &eth_native_token(),
@@ -678,7 +1339,7 @@ mod tests {
         )
         .unwrap();
 
-        let result = get_transactions(&map, principal, &eth_native_token(), None, 10);
+        let result = get(&map, principal, &eth_native_token(), None, 10);
         assert_eq!(result.transactions.len(), 1);
         assert_eq!(result.next_start, None);
     }
@@ -692,9 +1353,9 @@ mod tests {
             .map(|i| make_tx(&format!("0xhash{i}"), i * 10, i * 100))
             .collect();
 
-        save_transactions(&mut map, principal, &eth_native_token(), &txs).unwrap();
+        save(&mut map, principal, &eth_native_token(), &txs).unwrap();
 
-        let result = get_transactions(&map, principal, &eth_native_token(), None, 3);
+        let result = get(&map, principal, &eth_native_token(), None, 3);
         assert_eq!(result.transactions.len(), 3);
         assert_eq!(result.next_start, None);
     }
@@ -714,7 +1375,7 @@ mod tests {
             })
             .collect();
 
-        let result = save_transactions(&mut map, principal, &eth_native_token(), &txs);
+        let result = save(&mut map, principal, &eth_native_token(), &txs);
 
         assert_eq!(result, Err(UserTransactionError::TooManyTransactions));
     }
@@ -740,7 +1401,7 @@ mod tests {
                     )
                 })
                 .collect();
-            save_transactions(&mut map, principal, &eth_native_token(), &txs).unwrap();
+            save(&mut map, principal, &eth_native_token(), &txs).unwrap();
         }
 
         if remainder > 0 {
@@ -754,11 +1415,11 @@ mod tests {
                     )
                 })
                 .collect();
-            save_transactions(&mut map, principal, &eth_native_token(), &txs).unwrap();
+            save(&mut map, principal, &eth_native_token(), &txs).unwrap();
         }
 
         // At capacity — adding a newer transaction should succeed and evict the oldest
-        let overflow = save_transactions(
+        let overflow = save(
             &mut map,
             principal,
             &eth_native_token(),
@@ -766,7 +1427,7 @@ mod tests {
         );
         assert!(overflow.is_ok());
 
-        let result = get_transactions(&map, principal, &eth_native_token(), None, 1);
+        let result = get(&map, principal, &eth_native_token(), None, 1);
         assert_eq!(result.transactions[0].id, "0xoverflow");
         assert_eq!(
             result.total_stored,
@@ -775,7 +1436,7 @@ mod tests {
         assert_eq!(result.oldest_block_index, Some(1));
 
         // Duplicates of existing hashes should still succeed (they're skipped, no new insert)
-        let dup_ok = save_transactions(
+        let dup_ok = save(
             &mut map,
             principal,
             &eth_native_token(),
@@ -803,7 +1464,7 @@ mod tests {
                     )
                 })
                 .collect();
-            save_transactions(&mut map, principal, &eth_native_token(), &txs).unwrap();
+            save(&mut map, principal, &eth_native_token(), &txs).unwrap();
         }
 
         let new_txs = vec![
@@ -811,9 +1472,9 @@ mod tests {
             make_tx("0xnew2", 20_002, 200_020),
             make_tx("0xnew3", 20_003, 200_030),
         ];
-        save_transactions(&mut map, principal, &eth_native_token(), &new_txs).unwrap();
+        save(&mut map, principal, &eth_native_token(), &new_txs).unwrap();
 
-        let result = get_transactions(&map, principal, &eth_native_token(), None, 3);
+        let result = get(&map, principal, &eth_native_token(), None, 3);
 
         assert_eq!(result.newest_block_index, Some(20_003));
         assert_eq!(result.transactions[0].id, "0xnew3");
@@ -844,13 +1505,13 @@ mod tests {
                     make_tx(&format!("0xfill{idx}"), bi, bi * 10)
                 })
                 .collect();
-            save_transactions(&mut map, principal, &eth_native_token(), &txs).unwrap();
+            save(&mut map, principal, &eth_native_token(), &txs).unwrap();
         }
 
         let old_txs = vec![make_tx("0xancient1", 1, 10), make_tx("0xancient2", 2, 20)];
-        save_transactions(&mut map, principal, &eth_native_token(), &old_txs).unwrap();
+        save(&mut map, principal, &eth_native_token(), &old_txs).unwrap();
 
-        let result = get_transactions(&map, principal, &eth_native_token(), None, 1);
+        let result = get(&map, principal, &eth_native_token(), None, 1);
 
         assert_eq!(
             result.total_stored,
@@ -879,7 +1540,7 @@ mod tests {
                 )
             })
             .collect();
-        save_transactions(&mut map, principal, &eth_native_token(), &shared_txs).unwrap();
+        save(&mut map, principal, &eth_native_token(), &shared_txs).unwrap();
 
         let remaining = total - txs_in_shared_block;
         for b in 0..(remaining / batch) {
@@ -890,7 +1551,7 @@ mod tests {
                     make_tx(&format!("0xfill{idx}"), bi, bi * 10)
                 })
                 .collect();
-            save_transactions(&mut map, principal, &eth_native_token(), &txs).unwrap();
+            save(&mut map, principal, &eth_native_token(), &txs).unwrap();
         }
         let filled = (remaining / batch) * batch;
         if filled < remaining {
@@ -900,14 +1561,14 @@ mod tests {
                     make_tx(&format!("0xfill{i}"), bi, bi * 10)
                 })
                 .collect();
-            save_transactions(&mut map, principal, &eth_native_token(), &txs).unwrap();
+            save(&mut map, principal, &eth_native_token(), &txs).unwrap();
         }
 
-        let before = get_transactions(&map, principal, &eth_native_token(), None, 1);
+        let before = get(&map, principal, &eth_native_token(), None, 1);
         assert_eq!(before.total_stored, u64::try_from(total).unwrap());
         assert_eq!(before.oldest_block_index, Some(shared_block));
 
-        save_transactions(
+        save(
             &mut map,
             principal,
             &eth_native_token(),
@@ -915,7 +1576,7 @@ mod tests {
         )
         .unwrap();
 
-        let after = get_transactions(&map, principal, &eth_native_token(), None, 1);
+        let after = get(&map, principal, &eth_native_token(), None, 1);
 
         assert_eq!(after.oldest_block_index, Some(2));
         assert_eq!(
@@ -943,16 +1604,16 @@ mod tests {
                     )
                 })
                 .collect();
-            save_transactions(&mut map, principal, &eth_native_token(), &txs).unwrap();
+            save(&mut map, principal, &eth_native_token(), &txs).unwrap();
         }
 
         let new_txs = vec![
             make_tx("0xnew1", 50_001, 500_010),
             make_tx("0xnew2", 50_002, 500_020),
         ];
-        save_transactions(&mut map, principal, &eth_native_token(), &new_txs).unwrap();
+        save(&mut map, principal, &eth_native_token(), &new_txs).unwrap();
 
-        let result = get_transactions(&map, principal, &eth_native_token(), None, 1);
+        let result = get(&map, principal, &eth_native_token(), None, 1);
 
         assert_eq!(
             result.total_stored,
@@ -971,14 +1632,14 @@ mod tests {
         let batch = MAX_SAVE_USER_TRANSACTIONS_BATCH;
 
         let b1_txs = vec![make_tx("0xb1_a", 1, 10), make_tx("0xb1_b", 1, 11)];
-        save_transactions(&mut map, principal, &eth_native_token(), &b1_txs).unwrap();
+        save(&mut map, principal, &eth_native_token(), &b1_txs).unwrap();
 
         let b2_txs = vec![
             make_tx("0xb2_a", 2, 20),
             make_tx("0xb2_b", 2, 21),
             make_tx("0xb2_c", 2, 22),
         ];
-        save_transactions(&mut map, principal, &eth_native_token(), &b2_txs).unwrap();
+        save(&mut map, principal, &eth_native_token(), &b2_txs).unwrap();
 
         let filled_so_far = 5;
         let remaining = total - filled_so_far;
@@ -990,7 +1651,7 @@ mod tests {
                     make_tx(&format!("0xfill{idx}"), bi, bi * 10)
                 })
                 .collect();
-            save_transactions(&mut map, principal, &eth_native_token(), &txs).unwrap();
+            save(&mut map, principal, &eth_native_token(), &txs).unwrap();
         }
         let full_batches = (remaining / batch) * batch;
         if full_batches < remaining {
@@ -1000,10 +1661,10 @@ mod tests {
                     make_tx(&format!("0xfill{i}"), bi, bi * 10)
                 })
                 .collect();
-            save_transactions(&mut map, principal, &eth_native_token(), &txs).unwrap();
+            save(&mut map, principal, &eth_native_token(), &txs).unwrap();
         }
 
-        let before = get_transactions(&map, principal, &eth_native_token(), None, 1);
+        let before = get(&map, principal, &eth_native_token(), None, 1);
         assert_eq!(before.total_stored, u64::try_from(total).unwrap());
         assert_eq!(before.oldest_block_index, Some(1));
 
@@ -1012,9 +1673,9 @@ mod tests {
             make_tx("0xnew2", 200_002, 2_000_020),
             make_tx("0xnew3", 200_003, 2_000_030),
         ];
-        save_transactions(&mut map, principal, &eth_native_token(), &new_txs).unwrap();
+        save(&mut map, principal, &eth_native_token(), &new_txs).unwrap();
 
-        let after = get_transactions(&map, principal, &eth_native_token(), None, 1);
+        let after = get(&map, principal, &eth_native_token(), None, 1);
 
         assert_eq!(after.oldest_block_index, Some(3));
         assert_eq!(after.total_stored, u64::try_from(total - 5 + 3).unwrap());
@@ -1035,12 +1696,12 @@ mod tests {
             })
             .collect();
 
-        save_transactions(&mut map, principal, &eth_native_token(), &txs).unwrap();
+        save(&mut map, principal, &eth_native_token(), &txs).unwrap();
 
         // Re-save the same batch — all should be deduplicated
-        save_transactions(&mut map, principal, &eth_native_token(), &txs).unwrap();
+        save(&mut map, principal, &eth_native_token(), &txs).unwrap();
 
-        let result = get_transactions(
+        let result = get(
             &map,
             principal,
             &eth_native_token(),
@@ -1059,7 +1720,7 @@ mod tests {
         let (mut map, _mm) = setup();
         let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
 
-        save_transactions(
+        save(
             &mut map,
             principal,
             &eth_native_token(),
@@ -1067,7 +1728,7 @@ mod tests {
         )
         .unwrap();
 
-        let result = get_transactions(&map, principal, &eth_native_token(), None, 0);
+        let result = get(&map, principal, &eth_native_token(), None, 0);
 
         assert!(result.transactions.is_empty());
         assert_eq!(result.newest_block_index, Some(100));
@@ -1081,7 +1742,7 @@ mod tests {
         let (mut map, _mm) = setup();
         let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
 
-        let result = save_transactions(&mut map, principal, &eth_native_token(), &[]);
+        let result = save(&mut map, principal, &eth_native_token(), &[]);
 
         assert!(result.is_ok());
     }
@@ -1095,7 +1756,7 @@ mod tests {
         let tx_b = make_tx("0xhash_b", 100, 1001);
         let tx_c = make_tx("0xhash_c", 100, 1002);
 
-        save_transactions(
+        save(
             &mut map,
             principal,
             &eth_native_token(),
@@ -1103,7 +1764,7 @@ mod tests {
         )
         .unwrap();
 
-        let result = get_transactions(&map, principal, &eth_native_token(), None, 10);
+        let result = get(&map, principal, &eth_native_token(), None, 10);
 
         assert_eq!(result.transactions.len(), 3);
         assert_eq!(result.newest_block_index, Some(100));
@@ -1119,18 +1780,18 @@ mod tests {
             .map(|i| make_tx(&format!("0xhash{i}"), 100, 1000 + i))
             .collect();
 
-        save_transactions(&mut map, principal, &eth_native_token(), &txs).unwrap();
+        save(&mut map, principal, &eth_native_token(), &txs).unwrap();
 
         // All 5 txs at block 100, page size 2
-        let page1 = get_transactions(&map, principal, &eth_native_token(), None, 2);
+        let page1 = get(&map, principal, &eth_native_token(), None, 2);
         assert_eq!(page1.transactions.len(), 2);
         assert!(page1.next_start.is_some());
 
-        let page2 = get_transactions(&map, principal, &eth_native_token(), page1.next_start, 2);
+        let page2 = get(&map, principal, &eth_native_token(), page1.next_start, 2);
         assert_eq!(page2.transactions.len(), 2);
         assert!(page2.next_start.is_some());
 
-        let page3 = get_transactions(&map, principal, &eth_native_token(), page2.next_start, 2);
+        let page3 = get(&map, principal, &eth_native_token(), page2.next_start, 2);
         assert_eq!(page3.transactions.len(), 1);
         assert_eq!(page3.next_start, None);
 
@@ -1157,7 +1818,7 @@ mod tests {
                     )
                 })
                 .collect();
-            save_transactions(&mut map, principal, &eth_native_token(), &txs).unwrap();
+            save(&mut map, principal, &eth_native_token(), &txs).unwrap();
         }
 
         let mut all_hashes = Vec::new();
@@ -1165,7 +1826,7 @@ mod tests {
         let page_size = 30u64;
 
         loop {
-            let page = get_transactions(&map, principal, &eth_native_token(), cursor, page_size);
+            let page = get(&map, principal, &eth_native_token(), cursor, page_size);
 
             all_hashes.extend(page.transactions.iter().map(|t| t.id.clone()));
 
@@ -1189,4 +1850,1194 @@ mod tests {
             assert!(window[0] >= window[1], "blocks should be newest-first");
         }
     }
+
+    #[test]
+    fn test_cursor_pagination_walks_all_transactions() {
+        let (mut map, _mm) = setup();
+        let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
+
+        let txs: Vec<UserTransaction> = (0..7)
+            .map(|i| make_tx(&format!("0xhash{i}"), i, i * 10))
+            .collect();
+        insert_transactions(&mut map, &txs);
+
+        let mut all_indices = Vec::new();
+        let mut cursor: Option<UserTransactionCursor> = None;
+        loop {
+            let page = get_after(&map, principal, &eth_native_token(), cursor.as_ref(), 3);
+            assert!(page.next_start.is_none() || cursor.is_none());
+            all_indices.extend(page.transactions.iter().map(|t| t.block_index));
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+
+        assert_eq!(all_indices, vec![6, 5, 4, 3, 2, 1, 0]);
+    }
+
+    #[test]
+    fn test_cursor_is_stable_when_newer_transactions_arrive() {
+        let (mut map, _mm) = setup();
+        let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
+
+        let txs: Vec<UserTransaction> = (0..5)
+            .map(|i| make_tx(&format!("0xhash{i}"), i, i * 10))
+            .collect();
+        insert_transactions(&mut map, &txs);
+
+        let page1 = get(&map, principal, &eth_native_token(), None, 2);
+        assert_eq!(
+            page1.next_cursor,
+            Some(UserTransactionCursor {
+                block_index: 3,
+                id: "0xhash3".to_string(),
+            })
+        );
+
+        insert_transactions(&mut map, &[make_tx("0xnewer", 10, 100)]);
+
+        let page2 = get_after(
+            &map,
+            principal,
+            &eth_native_token(),
+            page1.next_cursor.as_ref(),
+            2,
+        );
+        let p2_indices: Vec<u64> = page2.transactions.iter().map(|t| t.block_index).collect();
+        assert_eq!(p2_indices, vec![2, 1]);
+        assert_eq!(page2.next_start, None);
+        assert_eq!(page2.total_stored, 6);
+    }
+
+    #[test]
+    fn test_cursor_orders_same_block_by_id() {
+        let (mut map, _mm) = setup();
+        let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
+
+        let txs: Vec<UserTransaction> = ["0xc", "0xa", "0xb"]
+            .iter()
+            .map(|id| make_tx(id, 100, 1000))
+            .collect();
+        insert_transactions(&mut map, &txs);
+
+        let page1 = get(&map, principal, &eth_native_token(), None, 2);
+        let p1_ids: Vec<&str> = page1.transactions.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(p1_ids, vec!["0xc", "0xb"]);
+
+        let page2 = get_after(
+            &map,
+            principal,
+            &eth_native_token(),
+            page1.next_cursor.as_ref(),
+            2,
+        );
+        let p2_ids: Vec<&str> = page2.transactions.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(p2_ids, vec!["0xa"]);
+        assert_eq!(page2.next_cursor, None);
+    }
+
+    #[test]
+    fn test_save_rejects_reserved_block_index() {
+        let (mut map, _mm) = setup();
+        let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
+
+        let result = save(
+            &mut map,
+            principal,
+            &eth_native_token(),
+            &[make_tx("0xhash1", u64::MAX, 1000)],
+        );
+
+        assert!(matches!(
+            result,
+            Err(UserTransactionError::InternalError { .. })
+        ));
+        assert_eq!(
+            get(&map, principal, &eth_native_token(), None, 10).total_stored,
+            0
+        );
+    }
+
+    #[test]
+    fn test_save_deduplicates_within_batch() {
+        let (mut map, _mm) = setup();
+        let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
+        let tx = make_tx("0xhash1", 100, 1000);
+
+        save(&mut map, principal, &eth_native_token(), &[tx.clone(), tx]).unwrap();
+
+        let result = get(&map, principal, &eth_native_token(), None, 10);
+        assert_eq!(result.transactions.len(), 1);
+        assert_eq!(result.total_stored, 1);
+    }
+
+    #[test]
+    fn test_migrate_legacy_transactions_moves_all_pairs() {
+        let (mut map, _mm) = setup();
+        let principal1 = Principal::from_text(PRINCIPAL_TEXT).unwrap();
+        let principal2 =
+            Principal::from_text("xzg7k-thc6c-idntg-knmtz-2fbhh-utt3e-snqw6-5xph3-54pbp-7axl5-tae")
+                .unwrap();
+
+        let txs1: Vec<UserTransaction> = (0..5)
+            .map(|i| make_tx(&format!("0xuser1_{i}"), i, i * 10))
+            .collect();
+        let txs2: Vec<UserTransaction> = (0..3)
+            .map(|i| make_tx(&format!("0xuser2_{i}"), i, i * 10))
+            .collect();
+        insert_legacy_transactions(&mut map, principal1, txs1);
+        insert_legacy_transactions(&mut map, principal2, txs2);
+
+        // A batch budget of 1 still moves one whole pair per call.
+        let first = migrate_legacy_transactions(
+            &mut map.legacy,
+            &mut map.entries,
+            &mut map.counts,
+            &mut map.ids,
+            &mut map.activity,
+            1,
+        );
+        assert!(first == 5 || first == 3);
+        assert_eq!(map.legacy.len(), 1);
+
+        let second = migrate_legacy_transactions(
+            &mut map.legacy,
+            &mut map.entries,
+            &mut map.counts,
+            &mut map.ids,
+            &mut map.activity,
+            1,
+        );
+        assert_eq!(first + second, 8);
+        assert!(map.legacy.is_empty());
+
+        let result1 = get(&map, principal1, &eth_native_token(), None, 10);
+        let ids1: Vec<&str> = result1.transactions.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(
+            ids1,
+            vec![
+                "0xuser1_4",
+                "0xuser1_3",
+                "0xuser1_2",
+                "0xuser1_1",
+                "0xuser1_0"
+            ]
+        );
+        assert_eq!(result1.total_stored, 5);
+        assert_eq!(result1.oldest_block_index, Some(0));
+        assert_eq!(result1.newest_block_index, Some(4));
+
+        let result2 = get(&map, principal2, &eth_native_token(), None, 10);
+        assert_eq!(result2.total_stored, 3);
+    }
+
+    #[test]
+    fn test_migrate_legacy_transactions_is_noop_when_empty() {
+        let (mut map, _mm) = setup();
+
+        let migrated = migrate_legacy_transactions(
+            &mut map.legacy,
+            &mut map.entries,
+            &mut map.counts,
+            &mut map.ids,
+            &mut map.activity,
+            100,
+        );
+
+        assert_eq!(migrated, 0);
+        assert!(map.entries.is_empty());
+        assert!(map.counts.is_empty());
+    }
+
+    #[test]
+    fn test_migrate_legacy_token_transactions_then_save() {
+        let (mut map, _mm) = setup();
+        let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
+
+        insert_legacy_transactions(
+            &mut map,
+            principal,
+            vec![make_tx("0xhash1", 100, 1000), make_tx("0xhash2", 200, 2000)],
+        );
+
+        let migrated = migrate_legacy_token_transactions(
+            &mut map.legacy,
+            &mut map.entries,
+            &mut map.counts,
+            &mut map.ids,
+            &mut map.activity,
+            principal,
+            &eth_native_token(),
+        );
+        assert_eq!(migrated, 2);
+        assert!(map.legacy.is_empty());
+
+        // Migrating again is a no-op.
+        let migrated_again = migrate_legacy_token_transactions(
+            &mut map.legacy,
+            &mut map.entries,
+            &mut map.counts,
+            &mut map.ids,
+            &mut map.activity,
+            principal,
+            &eth_native_token(),
+        );
+        assert_eq!(migrated_again, 0);
+
+        save(
+            &mut map,
+            principal,
+            &eth_native_token(),
+            &[make_tx("0xhash2", 200, 2000), make_tx("0xhash3", 300, 3000)],
+        )
+        .unwrap();
+
+        let result = get(&map, principal, &eth_native_token(), None, 10);
+        let ids: Vec<&str> = result.transactions.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids, vec!["0xhash3", "0xhash2", "0xhash1"]);
+        assert_eq!(result.total_stored, 3);
+    }
+
+    #[test]
+    fn test_evicted_id_can_be_saved_again() {
+        let (mut map, _mm) = setup();
+        let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
+
+        let txs: Vec<UserTransaction> = (0..MAX_USER_TRANSACTIONS_PER_TOKEN)
+            .map(|i| make_tx(&format!("0x{i}"), i as u64 + 1, 1000))
+            .collect();
+        for chunk in txs.chunks(MAX_SAVE_USER_TRANSACTIONS_BATCH) {
+            insert_transactions(&mut map, chunk);
+        }
+        insert_transactions(&mut map, &[make_tx("0xnew", 1_000_000, 1000)]);
+        assert_eq!(map.ids.len(), map.entries.len());
+
+        // "0x0" was evicted, so it is no longer a duplicate.
+        insert_transactions(&mut map, &[make_tx("0x0", 2_000_000, 1000)]);
+
+        let result = get(&map, principal, &eth_native_token(), None, 1);
+        assert_eq!(result.transactions[0].id, "0x0");
+        assert_eq!(map.ids.len(), map.entries.len());
+    }
+
+    #[test]
+    fn test_migrate_legacy_transactions_deduplicates_by_id() {
+        let (mut map, _mm) = setup();
+        let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
+
+        insert_legacy_transactions(
+            &mut map,
+            principal,
+            vec![make_tx("0xhash1", 100, 1000), make_tx("0xhash1", 101, 1000)],
+        );
+        migrate_legacy_transactions(
+            &mut map.legacy,
+            &mut map.entries,
+            &mut map.counts,
+            &mut map.ids,
+            &mut map.activity,
+            usize::MAX,
+        );
+
+        let result = get(&map, principal, &eth_native_token(), None, 10);
+        assert_eq!(result.transactions.len(), 1);
+        assert_eq!(result.total_stored, 1);
+    }
+
+    #[test]
+    fn test_get_legacy_transactions_matches_migrated_pages() {
+        let (mut map, _mm) = setup();
+        let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
+        // Legacy lists are sorted by `block_index` only.
+        let txs = vec![
+            make_tx("0xc", 100, 1000),
+            make_tx("0xa", 100, 1000),
+            make_tx("0xd", 200, 2000),
+            make_tx("0xb", 300, 3000),
+        ];
+        insert_legacy_transactions(&mut map, principal, txs);
+
+        let request = |start, cursor: Option<&UserTransactionCursor>| GetUserTransactionsRequest {
+            token_id: eth_native_token(),
+            start,
+            max_results: 2,
+            cursor: cursor.cloned(),
+            filter: None,
+        };
+
+        let legacy_page1 =
+            get_legacy_transactions(&map.legacy, principal, &request(None, None)).unwrap();
+        let legacy_page2 = get_legacy_transactions(
+            &map.legacy,
+            principal,
+            &request(None, legacy_page1.next_cursor.as_ref()),
+        )
+        .unwrap();
+        let legacy_positional = get_legacy_transactions(
+            &map.legacy,
+            principal,
+            &request(legacy_page1.next_start, None),
+        )
+        .unwrap();
+        assert!(
+            get_legacy_transactions(&map.legacy, Principal::anonymous(), &request(None, None))
+                .is_none()
+        );
+
+        migrate_legacy_transactions(
+            &mut map.legacy,
+            &mut map.entries,
+            &mut map.counts,
+            &mut map.ids,
+            &mut map.activity,
+            usize::MAX,
+        );
+
+        let page1 = get(&map, principal, &eth_native_token(), None, 2);
+        let page2 = get_after(
+            &map,
+            principal,
+            &eth_native_token(),
+            page1.next_cursor.as_ref(),
+            2,
+        );
+        let positional = get(&map, principal, &eth_native_token(), page1.next_start, 2);
+        assert_eq!(legacy_page1, page1);
+        assert_eq!(legacy_page2, page2);
+        assert_eq!(legacy_positional, positional);
+        let ids: Vec<&str> = page1
+            .transactions
+            .iter()
+            .chain(&page2.transactions)
+            .map(|t| t.id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["0xb", "0xd", "0xc", "0xa"]);
+    }
+
+    #[test]
+    fn test_get_activity_merges_tokens_by_timestamp() {
+        let (mut map, _mm) = setup();
+        let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
+
+        save(
+            &mut map,
+            principal,
+            &eth_native_token(),
+            &[make_tx("0xeth1", 10, 100), make_tx("0xeth2", 20, 300)],
+        )
+        .unwrap();
+        save(
+            &mut map,
+            principal,
+            &TokenId::IcpNative,
+            &[make_tx("icp1", 5_000, 200), make_tx("icp2", 6_000, 400)],
+        )
+        .unwrap();
+
+        let result = activity(&map, principal, None, None, 10);
+
+        assert_eq!(
+            activity_ids(&result),
+            vec!["icp2", "0xeth2", "icp1", "0xeth1"]
+        );
+        assert_eq!(result.items[0].token_id, TokenId::IcpNative);
+        assert_eq!(result.items[1].token_id, eth_native_token());
+        assert!(result.next_cursor.is_none());
+    }
+
+    #[test]
+    fn test_get_activity_empty_for_unknown_principal() {
+        let (map, _mm) = setup();
+        let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
+
+        let result = activity(&map, principal, None, None, 10);
+
+        assert!(result.items.is_empty());
+        assert!(result.next_cursor.is_none());
+    }
+
+    #[test]
+    fn test_get_activity_for_export_uses_export_page_size() {
+        let (mut map, _mm) = setup();
+        let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
+
+        let count = MAX_EXPORT_USER_TRANSACTIONS_ROWS + 10;
+        let txs: Vec<UserTransaction> = (0..count)
+            .map(|i| make_tx(&format!("0xhash{i}"), i, i * 10))
+            .collect();
+        for batch in txs.chunks(MAX_SAVE_USER_TRANSACTIONS_BATCH) {
+            save(&mut map, principal, &eth_native_token(), batch).unwrap();
+        }
+        let request = |cursor| GetUserActivityRequest {
+            token_ids: None,
+            max_results: u64::MAX,
+            cursor,
+            filter: None,
+        };
+
+        let first = get_activity_for_export(&map.entries, &map.activity, principal, request(None));
+        let second = get_activity_for_export(
+            &map.entries,
+            &map.activity,
+            principal,
+            request(first.next_cursor.clone()),
+        );
+
+        assert_eq!(
+            first.items.len(),
+            usize::try_from(MAX_EXPORT_USER_TRANSACTIONS_ROWS).unwrap()
+        );
+        assert!(first.next_cursor.is_some());
+        assert_eq!(
+            activity_ids(&second),
+            (0..10)
+                .rev()
+                .map(|i| format!("0xhash{i}"))
+                .collect::<Vec<_>>()
+        );
+        assert!(second.next_cursor.is_none());
+    }
+
+    #[test]
+    fn test_get_activity_cursor_walks_all_pages() {
+        let (mut map, _mm) = setup();
+        let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
+
+        let eth: Vec<UserTransaction> = (0..5)
+            .map(|i| make_tx(&format!("0xeth{i}"), i, i * 2))
+            .collect();
+        let icp: Vec<UserTransaction> = (0..5)
+            .map(|i| make_tx(&format!("icp{i}"), i, i * 2 + 1))
+            .collect();
+        save(&mut map, principal, &eth_native_token(), &eth).unwrap();
+        save(&mut map, principal, &TokenId::IcpNative, &icp).unwrap();
+
+        let mut cursor = None;
+        let mut timestamps = Vec::new();
+        loop {
+            let page = activity(&map, principal, None, cursor.as_ref(), 3);
+            assert!(page.items.len() <= 3);
+            timestamps.extend(page.items.iter().map(|item| item.transaction.timestamp));
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+
+        assert_eq!(timestamps, (0..10).rev().collect::<Vec<u64>>());
+    }
+
+    #[test]
+    fn test_get_activity_cursor_stable_when_newer_transactions_arrive() {
+        let (mut map, _mm) = setup();
+        let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
+
+        let txs: Vec<UserTransaction> = (1..=4)
+            .map(|i| make_tx(&format!("0xhash{i}"), i, i * 100))
+            .collect();
+        save(&mut map, principal, &eth_native_token(), &txs).unwrap();
+
+        let first = activity(&map, principal, None, None, 2);
+        assert_eq!(activity_ids(&first), vec!["0xhash4", "0xhash3"]);
+
+        save(
+            &mut map,
+            principal,
+            &TokenId::IcpNative,
+            &[make_tx("icp-new", 1, 10_000)],
+        )
+        .unwrap();
+
+        let second = activity(&map, principal, None, first.next_cursor.as_ref(), 2);
+        assert_eq!(activity_ids(&second), vec!["0xhash2", "0xhash1"]);
+        assert!(second.next_cursor.is_none());
+    }
+
+    #[test]
+    fn test_get_activity_orders_equal_timestamps_deterministically() {
+        let (mut map, _mm) = setup();
+        let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
+
+        save(
+            &mut map,
+            principal,
+            &eth_native_token(),
+            &[make_tx("0xa", 1, 100), make_tx("0xb", 1, 100)],
+        )
+        .unwrap();
+        save(
+            &mut map,
+            principal,
+            &TokenId::IcpNative,
+            &[make_tx("icp", 1, 100)],
+        )
+        .unwrap();
+
+        let all = activity(&map, principal, None, None, 10);
+        let first = activity(&map, principal, None, None, 1);
+        let rest = activity(&map, principal, None, first.next_cursor.as_ref(), 10);
+
+        let paged: Vec<&str> = activity_ids(&first)
+            .into_iter()
+            .chain(activity_ids(&rest))
+            .collect();
+        assert_eq!(paged, activity_ids(&all));
+        assert_eq!(all.items.len(), 3);
+    }
+
+    #[test]
+    fn test_get_activity_filters_tokens() {
+        let (mut map, _mm) = setup();
+        let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
+
+        save(
+            &mut map,
+            principal,
+            &eth_native_token(),
+            &[make_tx("0xeth", 1, 100)],
+        )
+        .unwrap();
+        save(
+            &mut map,
+            principal,
+            &TokenId::IcpNative,
+            &[make_tx("icp", 1, 200)],
+        )
+        .unwrap();
+        save(
+            &mut map,
+            principal,
+            &TokenId::BtcNativeMainnet,
+            &[make_tx("btc", 1, 300)],
+        )
+        .unwrap();
+
+        let filter = [eth_native_token(), TokenId::BtcNativeMainnet];
+        let result = activity(&map, principal, Some(&filter), None, 1);
+        assert_eq!(activity_ids(&result), vec!["btc"]);
+
+        let next = activity(
+            &map,
+            principal,
+            Some(&filter),
+            result.next_cursor.as_ref(),
+            1,
+        );
+        assert_eq!(activity_ids(&next), vec!["0xeth"]);
+        assert!(next.next_cursor.is_none());
+
+        let none = activity(&map, principal, Some(&[]), None, 10);
+        assert!(none.items.is_empty());
+    }
+
+    #[test]
+    fn test_get_activity_token_filter_stops_after_scan_limit() {
+        let (mut map, _mm) = setup();
+        let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
+
+        save(
+            &mut map,
+            principal,
+            &TokenId::IcpNative,
+            &[make_tx("icp", 1, 1)],
+        )
+        .unwrap();
+        let txs: Vec<UserTransaction> = (0..MAX_SCANNED_USER_TRANSACTIONS)
+            .map(|i| make_tx(&format!("0x{i:06}"), i, i + 2))
+            .collect();
+        for batch in txs.chunks(MAX_SAVE_USER_TRANSACTIONS_BATCH) {
+            save(&mut map, principal, &eth_native_token(), batch).unwrap();
+        }
+
+        let filter = [TokenId::IcpNative];
+        let first = activity(&map, principal, Some(&filter), None, 10);
+        assert!(first.items.is_empty());
+        assert_eq!(
+            first.next_cursor.as_ref().map(|cursor| cursor.id.as_str()),
+            Some("0x000000")
+        );
+
+        let second = activity(
+            &map,
+            principal,
+            Some(&filter),
+            first.next_cursor.as_ref(),
+            10,
+        );
+        assert_eq!(activity_ids(&second), vec!["icp"]);
+        assert!(second.next_cursor.is_none());
+    }
+
+    #[test]
+    fn test_get_activity_is_principal_scoped() {
+        let (mut map, _mm) = setup();
+        let principal1 = Principal::from_text(PRINCIPAL_TEXT).unwrap();
+        let principal2 =
+            Principal::from_text("xzg7k-thc6c-idntg-knmtz-2fbhh-utt3e-snqw6-5xph3-54pbp-7axl5-tae")
+                .unwrap();
+
+        save(
+            &mut map,
+            principal1,
+            &eth_native_token(),
+            &[make_tx("0xuser1", 1, 100)],
+        )
+        .unwrap();
+        save(
+            &mut map,
+            principal2,
+            &eth_native_token(),
+            &[make_tx("0xuser2", 1, 200)],
+        )
+        .unwrap();
+
+        assert_eq!(
+            activity_ids(&activity(&map, principal1, None, None, 10)),
+            vec!["0xuser1"]
+        );
+        assert_eq!(
+            activity_ids(&activity(&map, principal2, None, None, 10)),
+            vec!["0xuser2"]
+        );
+    }
+
+    #[test]
+    fn test_get_activity_drops_evicted_transactions() {
+        let (mut map, _mm) = setup();
+        let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
+
+        let txs: Vec<UserTransaction> = (0..MAX_USER_TRANSACTIONS_PER_TOKEN as u64)
+            .map(|i| make_tx(&format!("0x{i:06}"), i, i))
+            .collect();
+        for batch in txs.chunks(MAX_SAVE_USER_TRANSACTIONS_BATCH) {
+            save(&mut map, principal, &eth_native_token(), batch).unwrap();
+        }
+        let newest = MAX_USER_TRANSACTIONS_PER_TOKEN as u64;
+        save(
+            &mut map,
+            principal,
+            &eth_native_token(),
+            &[make_tx("0xnewest", newest, newest)],
+        )
+        .unwrap();
+
+        assert_eq!(map.activity.len(), map.entries.len());
+        let mut cursor = None;
+        let mut oldest = None;
+        loop {
+            let page = activity(
+                &map,
+                principal,
+                None,
+                cursor.as_ref(),
+                MAX_GET_USER_TRANSACTIONS_RESULTS,
+            );
+            oldest = page
+                .items
+                .last()
+                .map(|item| item.transaction.id.clone())
+                .or(oldest);
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        assert_eq!(oldest.as_deref(), Some("0x000001"));
+    }
+
+    #[test]
+    fn test_save_rejects_reserved_timestamp() {
+        let (mut map, _mm) = setup();
+        let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
+
+        let result = save(
+            &mut map,
+            principal,
+            &eth_native_token(),
+            &[make_tx("0xhash", 1, u64::MAX)],
+        );
+
+        assert!(matches!(
+            result,
+            Err(UserTransactionError::InternalError { .. })
+        ));
+        assert!(map.entries.is_empty());
+        assert!(map.activity.is_empty());
+    }
+
+    #[test]
+    fn test_backfill_activity_index_resumes_in_batches() {
+        let (mut map, _mm) = setup();
+        let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
+
+        let txs: Vec<UserTransaction> = (0..5)
+            .map(|i| make_tx(&format!("0xhash{i}"), i, i * 10))
+            .collect();
+        save(&mut map, principal, &eth_native_token(), &txs).unwrap();
+        map.activity.clear_new();
+
+        let mut after = None;
+        let mut batches = 0;
+        loop {
+            batches += 1;
+            after = backfill_activity_index(&map.entries, &mut map.activity, after, 2);
+            if after.is_none() {
+                break;
+            }
+        }
+
+        assert_eq!(batches, 3);
+        assert_eq!(map.activity.len(), 5);
+        assert_eq!(
+            activity_ids(&activity(&map, principal, None, None, 10)),
+            vec!["0xhash4", "0xhash3", "0xhash2", "0xhash1", "0xhash0"]
+        );
+    }
+
+    fn make_icrc_tx(id: &str, block_index: u64, tx_type: IcrcTransactionType) -> UserTransaction {
+        UserTransaction {
+            network_data: NetworkTransactionData::Icrc(IcrcTransactionData {
+                fee: None,
+                memo: None,
+                tx_type,
+            }),
+            ..make_tx(id, block_index, block_index * 10)
+        }
+    }
+
+    fn transfer(id: &str, block_index: u64, from: &str, to: &str, value: u64) -> UserTransaction {
+        UserTransaction {
+            from: from.to_string(),
+            to: Some(to.to_string()),
+            value: Nat::from(value),
+            ..make_tx(id, block_index, block_index * 10)
+        }
+    }
+
+    fn filtered_ids(
+        maps: &Maps,
+        principal: Principal,
+        filter: &UserTransactionFilter,
+    ) -> Vec<String> {
+        get_filtered(
+            maps,
+            principal,
+            &eth_native_token(),
+            None,
+            Some(filter),
+            100,
+        )
+        .transactions
+        .into_iter()
+        .map(|tx| tx.id)
+        .collect()
+    }
+
+    #[test]
+    fn test_filter_by_from_and_to() {
+        let (mut map, _mm) = setup();
+        let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
+        save(
+            &mut map,
+            principal,
+            &eth_native_token(),
+            &[
+                transfer("0x1", 1, "alice", "bob", 10),
+                transfer("0x2", 2, "bob", "alice", 20),
+                transfer("0x3", 3, "alice", "carol", 30),
+            ],
+        )
+        .unwrap();
+
+        let from_alice = UserTransactionFilter {
+            from: Some("alice".to_string()),
+            ..UserTransactionFilter::default()
+        };
+        assert_eq!(
+            filtered_ids(&map, principal, &from_alice),
+            vec!["0x3", "0x1"]
+        );
+
+        let alice_to_carol = UserTransactionFilter {
+            to: Some("carol".to_string()),
+            ..from_alice
+        };
+        assert_eq!(filtered_ids(&map, principal, &alice_to_carol), vec!["0x3"]);
+    }
+
+    #[test]
+    fn test_filter_by_direction() {
+        let (mut map, _mm) = setup();
+        let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
+        save(
+            &mut map,
+            principal,
+            &eth_native_token(),
+            &[
+                transfer("0xout", 1, "me", "bob", 10),
+                transfer("0xin", 2, "bob", "me", 20),
+                transfer("0xself", 3, "me", "me", 30),
+                transfer("0xother", 4, "bob", "carol", 40),
+            ],
+        )
+        .unwrap();
+
+        let direction = |direction| UserTransactionFilter {
+            direction: Some(TransactionDirectionFilter {
+                address: "me".to_string(),
+                direction,
+            }),
+            ..UserTransactionFilter::default()
+        };
+
+        assert_eq!(
+            filtered_ids(&map, principal, &direction(TransactionDirection::Incoming)),
+            vec!["0xself", "0xin"]
+        );
+        assert_eq!(
+            filtered_ids(&map, principal, &direction(TransactionDirection::Outgoing)),
+            vec!["0xself", "0xout"]
+        );
+    }
+
+    #[test]
+    fn test_filter_by_timestamp_and_value_range() {
+        let (mut map, _mm) = setup();
+        let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
+        let txs: Vec<UserTransaction> = (1..=5)
+            .map(|i| transfer(&format!("0x{i}"), i, "alice", "bob", i * 100))
+            .collect();
+        save(&mut map, principal, &eth_native_token(), &txs).unwrap();
+
+        let filter = UserTransactionFilter {
+            min_timestamp: Some(20),
+            max_timestamp: Some(40),
+            ..UserTransactionFilter::default()
+        };
+        assert_eq!(
+            filtered_ids(&map, principal, &filter),
+            vec!["0x4", "0x3", "0x2"]
+        );
+
+        let filter = UserTransactionFilter {
+            min_value: Some(Nat::from(300u64)),
+            max_value: Some(Nat::from(450u64)),
+            ..filter
+        };
+        assert_eq!(filtered_ids(&map, principal, &filter), vec!["0x4", "0x3"]);
+    }
+
+    #[test]
+    fn test_filter_by_evm_chain_and_nft_token_id() {
+        let (mut map, _mm) = setup();
+        let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
+        let mut nft = make_tx("0xnft", 2, 20);
+        if let NetworkTransactionData::Evm(data) = &mut nft.network_data {
+            data.nft_token_id = Some(Nat::from(7u64));
+        }
+        let mut other_chain = make_tx("0xchain", 3, 30);
+        if let NetworkTransactionData::Evm(data) = &mut other_chain.network_data {
+            data.chain_id = Some(137);
+        }
+        save(
+            &mut map,
+            principal,
+            &eth_native_token(),
+            &[make_tx("0xplain", 1, 10), nft, other_chain],
+        )
+        .unwrap();
+
+        let chain_1 = UserTransactionFilter {
+            network: Some(NetworkTransactionFilter::Evm {
+                chain_id: Some(1),
+                nft_token_id: None,
+            }),
+            ..UserTransactionFilter::default()
+        };
+        assert_eq!(
+            filtered_ids(&map, principal, &chain_1),
+            vec!["0xnft", "0xplain"]
+        );
+
+        let nft_7 = UserTransactionFilter {
+            network: Some(NetworkTransactionFilter::Evm {
+                chain_id: None,
+                nft_token_id: Some(Nat::from(7u64)),
+            }),
+            ..UserTransactionFilter::default()
+        };
+        assert_eq!(filtered_ids(&map, principal, &nft_7), vec!["0xnft"]);
+
+        let icrc = UserTransactionFilter {
+            network: Some(NetworkTransactionFilter::Icrc { tx_type: None }),
+            ..UserTransactionFilter::default()
+        };
+        assert!(filtered_ids(&map, principal, &icrc).is_empty());
+    }
+
+    #[test]
+    fn test_filter_by_icrc_tx_type() {
+        let (mut map, _mm) = setup();
+        let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
+        save(
+            &mut map,
+            principal,
+            &eth_native_token(),
+            &[
+                make_icrc_tx("1", 1, IcrcTransactionType::Transfer),
+                make_icrc_tx(
+                    "2",
+                    2,
+                    IcrcTransactionType::Approve {
+                        spender: "dex".to_string(),
+                    },
+                ),
+                make_icrc_tx(
+                    "3",
+                    3,
+                    IcrcTransactionType::Approve {
+                        spender: "bridge".to_string(),
+                    },
+                ),
+                make_icrc_tx("4", 4, IcrcTransactionType::Mint),
+            ],
+        )
+        .unwrap();
+
+        let tx_type = |tx_type| UserTransactionFilter {
+            network: Some(NetworkTransactionFilter::Icrc {
+                tx_type: Some(tx_type),
+            }),
+            ..UserTransactionFilter::default()
+        };
+
+        assert_eq!(
+            filtered_ids(
+                &map,
+                principal,
+                &tx_type(IcrcTransactionTypeFilter::Approve { spender: None })
+            ),
+            vec!["3", "2"]
+        );
+        assert_eq!(
+            filtered_ids(
+                &map,
+                principal,
+                &tx_type(IcrcTransactionTypeFilter::Approve {
+                    spender: Some("dex".to_string())
+                })
+            ),
+            vec!["2"]
+        );
+        assert_eq!(
+            filtered_ids(&map, principal, &tx_type(IcrcTransactionTypeFilter::Mint)),
+            vec!["4"]
+        );
+        assert!(
+            filtered_ids(&map, principal, &tx_type(IcrcTransactionTypeFilter::Burn)).is_empty()
+        );
+    }
+
+    #[test]
+    fn test_filter_pages_by_cursor() {
+        let (mut map, _mm) = setup();
+        let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
+        let txs: Vec<UserTransaction> = (1..=10)
+            .map(|i| {
+                let from = if i % 2 == 0 { "alice" } else { "bob" };
+                transfer(&format!("0x{i:02}"), i, from, "carol", 1)
+            })
+            .collect();
+        save(&mut map, principal, &eth_native_token(), &txs).unwrap();
+        let filter = UserTransactionFilter {
+            from: Some("alice".to_string()),
+            ..UserTransactionFilter::default()
+        };
+
+        let mut cursor = None;
+        let mut ids = Vec::new();
+        loop {
+            let page = get_filtered(
+                &map,
+                principal,
+                &eth_native_token(),
+                cursor.as_ref(),
+                Some(&filter),
+                2,
+            );
+            ids.extend(page.transactions.into_iter().map(|tx| tx.id));
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+
+        assert_eq!(ids, vec!["0x10", "0x08", "0x06", "0x04", "0x02"]);
+    }
+
+    #[test]
+    fn test_filter_positional_next_start_skips_scanned_entries() {
+        let (mut map, _mm) = setup();
+        let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
+        let txs: Vec<UserTransaction> = (1..=6)
+            .map(|i| {
+                let from = if i <= 2 { "alice" } else { "bob" };
+                transfer(&format!("0x{i}"), i, from, "carol", 1)
+            })
+            .collect();
+        save(&mut map, principal, &eth_native_token(), &txs).unwrap();
+
+        let request = |start| GetUserTransactionsRequest {
+            token_id: eth_native_token(),
+            start,
+            max_results: 1,
+            cursor: None,
+            filter: Some(UserTransactionFilter {
+                from: Some("alice".to_string()),
+                ..UserTransactionFilter::default()
+            }),
+        };
+
+        let first = get_transactions(&map.entries, &map.counts, principal, request(None));
+        let ids: Vec<&str> = first.transactions.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids, vec!["0x2"]);
+        // The four newer non-matching entries and the match were examined.
+        assert_eq!(first.next_start, Some(1));
+
+        let second = get_transactions(
+            &map.entries,
+            &map.counts,
+            principal,
+            request(first.next_start),
+        );
+        let ids: Vec<&str> = second.transactions.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids, vec!["0x1"]);
+        assert!(second.next_start.is_none());
+    }
+
+    #[test]
+    fn test_filter_stops_after_scan_limit() {
+        let (mut map, _mm) = setup();
+        let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
+        let scan_limit = MAX_SCANNED_USER_TRANSACTIONS;
+        let txs: Vec<UserTransaction> = (0..=scan_limit)
+            .map(|i| {
+                let from = if i == 0 { "alice" } else { "bob" };
+                transfer(&format!("0x{i:06}"), i, from, "carol", 1)
+            })
+            .collect();
+        for batch in txs.chunks(MAX_SAVE_USER_TRANSACTIONS_BATCH) {
+            save(&mut map, principal, &eth_native_token(), batch).unwrap();
+        }
+        let filter = UserTransactionFilter {
+            from: Some("alice".to_string()),
+            ..UserTransactionFilter::default()
+        };
+
+        let first = get_filtered(
+            &map,
+            principal,
+            &eth_native_token(),
+            None,
+            Some(&filter),
+            10,
+        );
+        assert!(first.transactions.is_empty());
+        assert_eq!(
+            first.next_cursor,
+            Some(UserTransactionCursor {
+                block_index: 1,
+                id: "0x000001".to_string(),
+            })
+        );
+
+        let second = get_filtered(
+            &map,
+            principal,
+            &eth_native_token(),
+            first.next_cursor.as_ref(),
+            Some(&filter),
+            10,
+        );
+        let ids: Vec<&str> = second.transactions.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids, vec!["0x000000"]);
+        assert!(second.next_cursor.is_none());
+    }
+
+    #[test]
+    fn test_activity_filter_applies_across_tokens() {
+        let (mut map, _mm) = setup();
+        let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
+        save(
+            &mut map,
+            principal,
+            &eth_native_token(),
+            &[
+                transfer("0xeth1", 1, "me", "bob", 1),
+                transfer("0xeth5", 5, "bob", "me", 1),
+            ],
+        )
+        .unwrap();
+        save(
+            &mut map,
+            principal,
+            &TokenId::IcpNative,
+            &[
+                transfer("icp2", 2, "bob", "me", 1),
+                transfer("icp3", 3, "me", "bob", 1),
+                transfer("icp4", 4, "bob", "me", 1),
+            ],
+        )
+        .unwrap();
+
+        let filter = UserTransactionFilter {
+            direction: Some(TransactionDirectionFilter {
+                address: "me".to_string(),
+                direction: TransactionDirection::Incoming,
+            }),
+            min_timestamp: Some(20),
+            max_timestamp: Some(40),
+            ..UserTransactionFilter::default()
+        };
+
+        let result = activity_filtered(&map, principal, None, None, Some(&filter), 10);
+        assert_eq!(activity_ids(&result), vec!["icp4", "icp2"]);
+        assert!(result.next_cursor.is_none());
+
+        let first = activity_filtered(&map, principal, None, None, Some(&filter), 1);
+        assert_eq!(activity_ids(&first), vec!["icp4"]);
+        let second = activity_filtered(
+            &map,
+            principal,
+            None,
+            first.next_cursor.as_ref(),
+            Some(&filter),
+            1,
+        );
+        assert_eq!(activity_ids(&second), vec!["icp2"]);
+    }
+
+    #[test]
+    fn test_activity_empty_timestamp_range() {
+        let (mut map, _mm) = setup();
+        let principal = Principal::from_text(PRINCIPAL_TEXT).unwrap();
+        save(
+            &mut map,
+            principal,
+            &eth_native_token(),
+            &[make_tx("0xhash", 1, 100)],
+        )
+        .unwrap();
+
+        let filter = UserTransactionFilter {
+            min_timestamp: Some(200),
+            max_timestamp: Some(100),
+            ..UserTransactionFilter::default()
+        };
+
+        let result = activity_filtered(&map, principal, None, None, Some(&filter), 10);
+        assert!(result.items.is_empty());
+        assert!(result.next_cursor.is_none());
+    }
 }
@@ -0,0 +1,179 @@
+use std::collections::BTreeMap;
+
+use candid::Principal;
+use shared::types::{
+    exchange::ExchangeRateResolution,
+    token_id::TokenId,
+    user_transaction::{
+        ExportUserTransactionsRequest, ExportUserTransactionsResponse, GetUserActivityRequest,
+        GetUserActivityResponse, GetUserTransactionsRequest, GetUserTransactionsResponse,
+        SaveUserTransactionsRequest, UserTransactionError, UserTransactionFilter,
+    },
+};
+
+use crate::{
+    exchange::history,
+    state::{mutate_state, read_state},
+    transactions::{
+        export::{self, ContactNames, UsdPriceSource},
+        model,
+    },
+    types::{StoredPrincipal, StoredTokenId},
+};
+
+/// Reads a page of the caller's stored transactions for a token.
+///
+/// A `(principal, token)` pair still in the legacy layout is read from it as is, leaving its
+/// migration to the background migration.
+pub fn get_transactions(
+    principal: Principal,
+    request: GetUserTransactionsRequest,
+) -> GetUserTransactionsResponse {
+    read_state(|state| {
+        model::get_legacy_transactions(&state.legacy_user_transactions, principal, &request)
+            .unwrap_or_else(|| {
+                model::get_transactions(
+                    &state.user_transactions,
+                    &state.user_transaction_counts,
+                    principal,
+                    request,
+                )
+            })
+    })
+}
+
+/// Saves finalized transactions for the caller, migrating the `(principal, token)` pair out of
+/// the legacy layout first if needed.
+///
+/// # Errors
+/// Errors are enumerated by: `UserTransactionError`.
+pub fn save_transactions(
+    principal: Principal,
+    request: SaveUserTransactionsRequest,
+) -> Result<(), UserTransactionError> {
+    let SaveUserTransactionsRequest {
+        token_id,
+        transactions,
+    } = request;
+
+    mutate_state(|state| {
+        model::migrate_legacy_token_transactions(
+            &mut state.legacy_user_transactions,
+            &mut state.user_transactions,
+            &mut state.user_transaction_counts,
+            &mut state.user_transaction_ids,
+            &mut state.user_activity_index,
+            principal,
+            &token_id,
+        );
+        model::save_transactions(
+            &mut state.user_transactions,
+            &mut state.user_transaction_counts,
+            &mut state.user_transaction_ids,
+            &mut state.user_activity_index,
+            principal,
+            &token_id,
+            &transactions,
+        )
+    })
+}
+
+/// Reads a page of the caller's stored transactions across tokens, newest first.
+///
+/// Pairs still in the legacy layout are only included once the background migration has moved
+/// them.
+pub fn get_activity(
+    principal: Principal,
+    request: GetUserActivityRequest,
+) -> GetUserActivityResponse {
+    read_state(|state| {
+        model::get_activity(
+            &state.user_transactions,
+            &state.user_activity_index,
+            principal,
+            request,
+        )
+    })
+}
+
+/// Renders one chunk of the caller's stored transactions across tokens, newest first.
+///
+/// Counterparties are resolved to the caller's contact names. Rows carry the USD exchange rate of
+/// their token at the time of the transaction from the exchange-rate history, or the current rate
+/// when the history does not cover it, where known. Like [`get_activity`], pairs still in the legacy
+/// layout are only included once the background migration has moved them.
+///
+/// # Errors
+/// Errors are enumerated by: `UserTransactionError`.
+pub fn export_transactions(
+    principal: Principal,
+    request: ExportUserTransactionsRequest,
+) -> Result<ExportUserTransactionsResponse, UserTransactionError> {
+    let ExportUserTransactionsRequest {
+        format,
+        token_ids,
+        min_timestamp,
+        max_timestamp,
+        cursor,
+    } = request;
+    let include_header = cursor.is_none();
+
+    read_state(|state| {
+        let page = model::get_activity_for_export(
+            &state.user_transactions,
+            &state.user_activity_index,
+            principal,
+            GetUserActivityRequest {
+                token_ids,
+                max_results: u64::MAX,
+                cursor,
+                filter: Some(UserTransactionFilter {
+                    min_timestamp,
+                    max_timestamp,
+                    ..UserTransactionFilter::default()
+                }),
+            },
+        );
+
+        let contacts = state
+            .contact
+            .get(&StoredPrincipal(principal))
+            .map(|contacts| ContactNames::new(&contacts.0))
+            .unwrap_or_default();
+        let mut current_prices = BTreeMap::new();
+        let usd_price = |token_id: &TokenId, timestamp: u64| {
+            let stored_token_id = StoredTokenId(token_id.clone());
+            let historical = history::price_at(
+                &state.exchange_rate_history,
+                &stored_token_id,
+                timestamp.saturating_mul(1_000_000_000),
+            )
+            .map(|(price, resolution)| {
+                let source = match resolution {
+                    ExchangeRateResolution::Hourly => UsdPriceSource::Hourly,
+                    ExchangeRateResolution::Daily => UsdPriceSource::Daily,
+                };
+                (price, source)
+            });
+            historical.or_else(|| {
+                current_prices
+                    .entry(token_id.clone())
+                    .or_insert_with(|| {
+                        state
+                            .exchange_rates
+                            .get(&stored_token_id)
+                            .and_then(|rate| rate.0.usd.price)
+                    })
+                    .map(|price| (price, UsdPriceSource::Current))
+            })
+        };
+
+        let content = export::render(format, &page.items, &contacts, usd_price, include_header)?;
+
+        Ok(ExportUserTransactionsResponse {
+            content,
+            row_count: page.items.len() as u64,
+            next_cursor: page.next_cursor,
+        })
+    })
+}
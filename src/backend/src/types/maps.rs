@@ -4,17 +4,28 @@ use ic_stable_structures::{
     memory_manager::VirtualMemory, DefaultMemoryImpl, StableBTreeMap, StableCell,
 };
 use shared::types::{
-    active_user_transaction::ActiveUserTransaction, agreement::AgreementHistoryEntry,
-    api_keys::ApiKeys, backend_config::Config, bitcoin::StoredPendingTransaction,
-    contact::StoredContacts, custom_token::CustomToken, exchange::ExchangeRate, token::UserToken,
-    user_profile::StoredUserProfile, user_transaction::UserTransaction, Timestamp,
+    active_user_transaction::ActiveUserTransaction,
+    agreement::AgreementHistoryEntry,
+    api_keys::ApiKeys,
+    backend_config::Config,
+    bitcoin::StoredPendingTransaction,
+    contact::StoredContacts,
+    custom_token::CustomToken,
+    exchange::{ExchangeRate, PriceCandle, PriceOverride},
+    portfolio::PortfolioSnapshot,
+    price_alert::{PriceAlert, TriggeredPriceAlert},
+    token::UserToken,
+    user_profile::StoredUserProfile,
+    user_transaction::UserTransaction,
+    Timestamp,
 };
 
 use crate::{
     personal_notes::share::model::PersonalNoteShareRecord,
     types::storable::{
-        ActiveUserTransactionKey, Candid, PersonalNoteShareCreatorKey, PersonalNoteShareToken,
-        StoredPrincipal, StoredTokenId, UserTransactionKey,
+        ActiveUserTransactionKey, Candid, ExchangeRateHistoryKey, PersonalNoteShareCreatorKey,
+        PersonalNoteShareToken, StoredPrincipal, StoredTokenId, UserActivityKey,
+        UserTransactionEntryKey, UserTransactionKey,
     },
 };
 
@@ -47,9 +58,57 @@ pub type TokenActivityMap = StableBTreeMap<StoredTokenId, Timestamp, VMem>;
 
 pub type ExchangeRateMap = StableBTreeMap<StoredTokenId, Candid<ExchangeRate>, VMem>;
 
-/// Per-user, per-token storage of finalized transactions.
-/// Key: (user principal, token identifier), Value: sorted Vec of finalized transactions.
+/// Controller-set price overrides, consulted before every price provider.
+pub type PriceOverrideMap = StableBTreeMap<StoredTokenId, Candid<PriceOverride>, VMem>;
+
+/// Bounded USD price history per token, in hourly and daily OHLC buckets.
+pub type ExchangeRateHistoryMap = StableBTreeMap<ExchangeRateHistoryKey, Candid<PriceCandle>, VMem>;
+
+/// Daily portfolio snapshots. Key: `(principal, day)`, with `day` counted from the epoch.
+pub type PortfolioSnapshotMap =
+    StableBTreeMap<(StoredPrincipal, u64), Candid<PortfolioSnapshot>, VMem>;
+
+/// Index of [`PortfolioSnapshotMap`] by day, so that housekeeping evicts expired snapshots with
+/// a range scan instead of a full scan.
+pub type PortfolioSnapshotDayIndexMap = StableBTreeMap<(u64, StoredPrincipal), (), VMem>;
+
+/// Price alerts. Key: `(principal, alert_id)`.
+pub type PriceAlertMap = StableBTreeMap<(StoredPrincipal, u64), Candid<PriceAlert>, VMem>;
+
+/// Index of [`PriceAlertMap`] by token, so that a refreshed price is only checked against the
+/// alerts of its token. Key: `(token_id, principal, alert_id)`, with the token id kept as its
+/// Candid encoding (see [`StoredTokenId`]).
+pub type PriceAlertTokenIndexMap = StableBTreeMap<(Vec<u8>, StoredPrincipal, u64), (), VMem>;
+
+/// Inbox of triggered price alerts. Key: `(principal, triggered_at_ns, alert_id)`.
+pub type TriggeredPriceAlertMap =
+    StableBTreeMap<(StoredPrincipal, u64, u64), Candid<TriggeredPriceAlert>, VMem>;
+
+/// Per-entry storage of finalized transactions.
+/// Key: `(principal, token_id, block_index, id)`, Value: the finalized transaction. One row per
+/// transaction so that reads and saves only touch the requested page / batch.
 pub type UserTransactionsMap =
+    StableBTreeMap<UserTransactionEntryKey, Candid<UserTransaction>, VMem>;
+
+/// Number of entries stored in [`UserTransactionsMap`] per `(principal, token_id)` pair, so that
+/// `total_stored` and the per-token cap do not require a range scan.
+pub type UserTransactionCountsMap = StableBTreeMap<UserTransactionKey, u64, VMem>;
+
+/// By-id index over [`UserTransactionsMap`], used to deduplicate saves by transaction id whatever
+/// their `block_index`. Key: `(principal, token_id, id)`, with the token id kept as its Candid
+/// encoding (see [`StoredTokenId`]), Value: the `block_index` of the stored entry.
+pub type UserTransactionIdIndexMap = StableBTreeMap<(StoredPrincipal, Vec<u8>, String), u64, VMem>;
+
+/// Per-user activity index over [`UserTransactionsMap`], ordered by transaction timestamp across
+/// all tokens. Key: `(principal, timestamp, token_id, block_index, id)`.
+pub type UserActivityIndexMap = StableBTreeMap<UserActivityKey, (), VMem>;
+
+/// Legacy per-user, per-token storage of finalized transactions.
+/// Key: (user principal, token identifier), Value: sorted Vec of finalized transactions.
+///
+/// Drained into [`UserTransactionsMap`] after upgrade; see `transactions::migration`.
+// TODO: remove once all canisters have been upgraded past the per-entry migration.
+pub type LegacyUserTransactionsMap =
     StableBTreeMap<UserTransactionKey, Candid<Vec<UserTransaction>>, VMem>;
 
 /// Per-user audit trail of agreement consent/rejection events.
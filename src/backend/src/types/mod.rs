@@ -4,9 +4,11 @@ pub(crate) mod storable;
 pub(crate) use self::{
     maps::{
         ActiveUserTransactionsMap, AgreementHistoryMap, BtcUserPendingTransactionsMap,
-        UserProfileMap, UserProfileUpdatedMap, UserTransactionsMap, VMem,
+        LegacyUserTransactionsMap, UserActivityIndexMap, UserProfileMap, UserProfileUpdatedMap,
+        UserTransactionCountsMap, UserTransactionIdIndexMap, UserTransactionsMap, VMem,
     },
     storable::{
-        ActiveUserTransactionKey, Candid, StoredPrincipal, StoredTokenId, UserTransactionKey,
+        ActiveUserTransactionKey, Candid, StoredPrincipal, StoredTokenId, UserActivityKey,
+        UserTransactionEntryKey, UserTransactionKey,
     },
 };
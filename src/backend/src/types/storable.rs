@@ -122,6 +122,233 @@ impl Storable for UserTransactionKey {
     }
 }
 
+/// Composite key for per-entry transaction storage: `(principal, token_id, block_index, id)`.
+///
+/// The token id is kept as its Candid encoding (see [`StoredTokenId`]) and compared as raw bytes:
+/// the map decodes keys on every comparison, and only grouping by token matters, not `TokenId`
+/// order. All entries of a `(principal, token_id)` pair are therefore contiguous and sorted
+/// oldest-first by `(block_index, id)`.
+///
+/// Encoding: `[u32 BE principal_len][principal_bytes][u32 BE token_id_len][token_id_bytes]
+/// [u64 BE block_index][id_bytes]`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct UserTransactionEntryKey(pub StoredPrincipal, pub Vec<u8>, pub u64, pub String);
+
+impl UserTransactionEntryKey {
+    pub fn new(
+        principal: StoredPrincipal,
+        token_id: &StoredTokenId,
+        block_index: u64,
+        id: &str,
+    ) -> Self {
+        Self(
+            principal,
+            token_id.to_bytes().into_owned(),
+            block_index,
+            id.to_string(),
+        )
+    }
+}
+
+impl Storable for UserTransactionEntryKey {
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let principal_bytes = self.0.to_bytes();
+        let id_bytes = self.3.as_bytes();
+        let principal_len =
+            u32::try_from(principal_bytes.len()).expect("principal length should fit in u32");
+        let token_id_len = u32::try_from(self.1.len()).expect("token id length should fit in u32");
+        let mut buf =
+            Vec::with_capacity(16 + principal_bytes.len() + self.1.len() + id_bytes.len());
+        buf.extend_from_slice(&principal_len.to_be_bytes());
+        buf.extend_from_slice(&principal_bytes);
+        buf.extend_from_slice(&token_id_len.to_be_bytes());
+        buf.extend_from_slice(&self.1);
+        buf.extend_from_slice(&self.2.to_be_bytes());
+        buf.extend_from_slice(id_bytes);
+        Cow::Owned(buf)
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        self.to_bytes().to_vec()
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        let principal_len = u32::from_be_bytes(
+            bytes[..4]
+                .try_into()
+                .expect("failed to decode principal length"),
+        ) as usize;
+        let principal = StoredPrincipal::from_bytes(Cow::Borrowed(&bytes[4..4 + principal_len]));
+        let offset = 4 + principal_len;
+        let token_id_len = u32::from_be_bytes(
+            bytes[offset..offset + 4]
+                .try_into()
+                .expect("failed to decode token id length"),
+        ) as usize;
+        let offset = offset + 4;
+        let token_id = bytes[offset..offset + token_id_len].to_vec();
+        let offset = offset + token_id_len;
+        let block_index = u64::from_be_bytes(
+            bytes[offset..offset + 8]
+                .try_into()
+                .expect("failed to decode block index"),
+        );
+        let id = std::str::from_utf8(&bytes[offset + 8..])
+            .expect("user transaction id should be valid UTF-8")
+            .to_owned();
+        Self(principal, token_id, block_index, id)
+    }
+}
+
+/// Key of the per-user activity index: `(principal, timestamp, token_id, block_index, id)`.
+///
+/// Orders a user's transactions across all tokens by timestamp; the `(token_id, block_index, id)`
+/// suffix identifies the entry in [`UserTransactionEntryKey`] order and breaks timestamp ties.
+///
+/// Encoding: `[u32 BE principal_len][principal_bytes][u64 BE timestamp][u32 BE token_id_len]
+/// [token_id_bytes][u64 BE block_index][id_bytes]`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct UserActivityKey(
+    pub StoredPrincipal,
+    pub u64,
+    pub Vec<u8>,
+    pub u64,
+    pub String,
+);
+
+impl UserActivityKey {
+    pub fn new(entry: &UserTransactionEntryKey, timestamp: u64) -> Self {
+        Self(
+            entry.0,
+            timestamp,
+            entry.1.clone(),
+            entry.2,
+            entry.3.clone(),
+        )
+    }
+
+    /// The key of the indexed entry in the per-entry transaction map.
+    pub fn entry_key(&self) -> UserTransactionEntryKey {
+        UserTransactionEntryKey(self.0, self.2.clone(), self.3, self.4.clone())
+    }
+}
+
+impl Storable for UserActivityKey {
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let principal_bytes = self.0.to_bytes();
+        let id_bytes = self.4.as_bytes();
+        let principal_len =
+            u32::try_from(principal_bytes.len()).expect("principal length should fit in u32");
+        let token_id_len = u32::try_from(self.2.len()).expect("token id length should fit in u32");
+        let mut buf =
+            Vec::with_capacity(24 + principal_bytes.len() + self.2.len() + id_bytes.len());
+        buf.extend_from_slice(&principal_len.to_be_bytes());
+        buf.extend_from_slice(&principal_bytes);
+        buf.extend_from_slice(&self.1.to_be_bytes());
+        buf.extend_from_slice(&token_id_len.to_be_bytes());
+        buf.extend_from_slice(&self.2);
+        buf.extend_from_slice(&self.3.to_be_bytes());
+        buf.extend_from_slice(id_bytes);
+        Cow::Owned(buf)
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        self.to_bytes().to_vec()
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        let principal_len = u32::from_be_bytes(
+            bytes[..4]
+                .try_into()
+                .expect("failed to decode principal length"),
+        ) as usize;
+        let principal = StoredPrincipal::from_bytes(Cow::Borrowed(&bytes[4..4 + principal_len]));
+        let offset = 4 + principal_len;
+        let timestamp = u64::from_be_bytes(
+            bytes[offset..offset + 8]
+                .try_into()
+                .expect("failed to decode timestamp"),
+        );
+        let offset = offset + 8;
+        let token_id_len = u32::from_be_bytes(
+            bytes[offset..offset + 4]
+                .try_into()
+                .expect("failed to decode token id length"),
+        ) as usize;
+        let offset = offset + 4;
+        let token_id = bytes[offset..offset + token_id_len].to_vec();
+        let offset = offset + token_id_len;
+        let block_index = u64::from_be_bytes(
+            bytes[offset..offset + 8]
+                .try_into()
+                .expect("failed to decode block index"),
+        );
+        let id = std::str::from_utf8(&bytes[offset + 8..])
+            .expect("user transaction id should be valid UTF-8")
+            .to_owned();
+        Self(principal, timestamp, token_id, block_index, id)
+    }
+}
+
+/// Key of the exchange-rate history: `(token_id, resolution, bucket_start_ns)`.
+///
+/// Like [`UserTransactionEntryKey`], the token id is kept as its Candid encoding so that
+/// comparisons don't decode it. All buckets of a `(token_id, resolution)` pair are contiguous and
+/// sorted oldest-first.
+///
+/// Encoding: `[u32 BE token_id_len][token_id_bytes][u8 resolution][u64 BE bucket_start_ns]`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ExchangeRateHistoryKey(pub Vec<u8>, pub u8, pub u64);
+
+impl ExchangeRateHistoryKey {
+    pub fn new(token_id: &StoredTokenId, resolution: u8, bucket_start_ns: u64) -> Self {
+        Self(
+            token_id.to_bytes().into_owned(),
+            resolution,
+            bucket_start_ns,
+        )
+    }
+}
+
+impl Storable for ExchangeRateHistoryKey {
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let token_id_len = u32::try_from(self.0.len()).expect("token id length should fit in u32");
+        let mut buf = Vec::with_capacity(13 + self.0.len());
+        buf.extend_from_slice(&token_id_len.to_be_bytes());
+        buf.extend_from_slice(&self.0);
+        buf.push(self.1);
+        buf.extend_from_slice(&self.2.to_be_bytes());
+        Cow::Owned(buf)
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        self.to_bytes().to_vec()
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        let token_id_len = u32::from_be_bytes(
+            bytes[..4]
+                .try_into()
+                .expect("failed to decode token id length"),
+        ) as usize;
+        let token_id = bytes[4..4 + token_id_len].to_vec();
+        let offset = 4 + token_id_len;
+        let resolution = bytes[offset];
+        let bucket_start_ns = u64::from_be_bytes(
+            bytes[offset + 1..offset + 9]
+                .try_into()
+                .expect("failed to decode bucket start"),
+        );
+        Self(token_id, resolution, bucket_start_ns)
+    }
+}
+
 /// Composite key for per-user active-transaction storage.
 ///
 /// Encoding mirrors [`UserTransactionKey`]: `[u32 BE principal_len][principal_bytes][id_bytes]`.
@@ -1,6 +1,9 @@
 use shared::types::{user_profile::StoredUserProfile, Timestamp};
 
-use crate::types::{Candid, StoredPrincipal, UserProfileMap, UserProfileUpdatedMap};
+use crate::{
+    certification::users::certify_user_profile,
+    types::{Candid, StoredPrincipal, UserProfileMap, UserProfileUpdatedMap},
+};
 
 pub struct UserProfileModel<'a> {
     user_profile_map: &'a mut UserProfileMap,
@@ -44,6 +47,7 @@ impl<'a> UserProfileModel<'a> {
             .insert(user_principal, timestamp);
         self.user_profile_map
             .insert((timestamp, user_principal), Candid(new_user.clone()));
+        certify_user_profile(user_principal, new_user);
     }
 
     #[cfg(test)]
@@ -6,12 +6,18 @@ use shared::types::{
         AgreementHistoryEntry, AgreementType, ProviderAgreementType, UpdateAgreementsError,
         UserAgreement, UserAgreements,
     },
+    bitcoin::{BtcAddressType, BtcSettings, UpdateBtcSettingsError},
     dapp::AddDappSettingsError,
+    exchange::{FiatCurrency, FiatSettings, UpdateFiatSettingsError},
     experimental_feature::{
         ExperimentalFeatureSettingsMap, UpdateExperimentalFeaturesSettingsError,
     },
-    network::{NetworkSettingsMap, SetTestnetsSettingsError, UpdateNetworksSettingsError},
+    network::{
+        evm_chains::set_evm_testnet_flags, NetworkSettingsMap, SetTestnetsSettingsError,
+        UpdateNetworksSettingsError,
+    },
     notification::{AddDismissedNotificationError, DismissedNotification},
+    portfolio::{PortfolioSettings, UpdatePortfolioSettingsError},
     transaction_settings::{TransactionFilterSettings, UpdateTransactionFilterSettingsError},
     user_profile::{GetUserProfileError, StoredUserProfile},
     Timestamp, Version,
@@ -57,7 +63,8 @@ pub fn create_profile(
 /// # Arguments
 /// * `principal` - The principal of the user.
 /// * `profile_version` - The version of the user's profile.
-/// * `networks` - The new network settings to save.
+/// * `networks` - The new network settings to save. The testnet flag of EVM networks is taken
+///   from the chain registry rather than from the caller.
 /// * `user_profile_model` - The user profile model.
 ///
 /// # Returns
@@ -68,11 +75,12 @@ pub fn create_profile(
 pub fn update_network_settings(
     principal: StoredPrincipal,
     profile_version: Option<Version>,
-    networks: NetworkSettingsMap,
+    mut networks: NetworkSettingsMap,
     user_profile_model: &mut UserProfileModel,
 ) -> Result<(), UpdateNetworksSettingsError> {
     let user_profile = find_profile(principal, user_profile_model)
         .map_err(|_| UpdateNetworksSettingsError::UserNotFound)?;
+    set_evm_testnet_flags(&mut networks);
     let now = time();
     let new_profile = user_profile.with_networks(profile_version, now, networks, false)?;
     user_profile_model.store_new(principal, now, &new_profile);
@@ -349,3 +357,106 @@ pub fn update_transaction_filter_settings(
     user_profile_model.store_new(principal, now, &new_profile);
     Ok(())
 }
+
+/// Updates the user's portfolio snapshot settings.
+///
+/// # Arguments
+/// * `principal` - The principal of the user.
+/// * `profile_version` - The version of the user's profile.
+/// * `portfolio` - The new portfolio settings to save.
+/// * `user_profile_model` - The user profile model.
+///
+/// # Returns
+/// - Returns `Ok(())` if the settings were successfully updated.
+///
+/// # Errors
+/// - Returns `Err` if the user profile is not found, or the user profile version is not up-to-date.
+pub fn update_portfolio_settings(
+    principal: StoredPrincipal,
+    profile_version: Option<Version>,
+    portfolio: PortfolioSettings,
+    user_profile_model: &mut UserProfileModel,
+) -> Result<(), UpdatePortfolioSettingsError> {
+    let user_profile = find_profile(principal, user_profile_model)
+        .map_err(|_| UpdatePortfolioSettingsError::UserNotFound)?;
+    let now = time();
+    let new_profile = user_profile.with_portfolio_settings(profile_version, now, portfolio)?;
+    user_profile_model.store_new(principal, now, &new_profile);
+    Ok(())
+}
+
+/// Updates the user's preferred fiat currency.
+///
+/// # Arguments
+/// * `principal` - The principal of the user.
+/// * `profile_version` - The version of the user's profile.
+/// * `fiat` - The new fiat settings to save.
+/// * `user_profile_model` - The user profile model.
+///
+/// # Returns
+/// - Returns `Ok(())` if the settings were successfully updated.
+///
+/// # Errors
+/// - Returns `Err` if the user profile is not found, or the user profile version is not up-to-date.
+pub fn update_fiat_settings(
+    principal: StoredPrincipal,
+    profile_version: Option<Version>,
+    fiat: FiatSettings,
+    user_profile_model: &mut UserProfileModel,
+) -> Result<(), UpdateFiatSettingsError> {
+    let user_profile = find_profile(principal, user_profile_model)
+        .map_err(|_| UpdateFiatSettingsError::UserNotFound)?;
+    let now = time();
+    let new_profile = user_profile.with_fiat_settings(profile_version, now, fiat)?;
+    user_profile_model.store_new(principal, now, &new_profile);
+    Ok(())
+}
+
+/// Returns the user's preferred fiat currency, USD for users without a profile.
+#[must_use]
+pub fn preferred_fiat_currency(
+    principal: StoredPrincipal,
+    user_profile_model: &UserProfileModel,
+) -> FiatCurrency {
+    find_profile(principal, user_profile_model)
+        .map(|profile| profile.preferred_fiat_currency())
+        .unwrap_or_default()
+}
+
+/// Updates the user's preferred BTC address type.
+///
+/// # Arguments
+/// * `principal` - The principal of the user.
+/// * `profile_version` - The version of the user's profile.
+/// * `btc` - The new BTC settings to save.
+/// * `user_profile_model` - The user profile model.
+///
+/// # Returns
+/// - Returns `Ok(())` if the settings were successfully updated.
+///
+/// # Errors
+/// - Returns `Err` if the user profile is not found, or the user profile version is not up-to-date.
+pub fn update_btc_settings(
+    principal: StoredPrincipal,
+    profile_version: Option<Version>,
+    btc: BtcSettings,
+    user_profile_model: &mut UserProfileModel,
+) -> Result<(), UpdateBtcSettingsError> {
+    let user_profile = find_profile(principal, user_profile_model)
+        .map_err(|_| UpdateBtcSettingsError::UserNotFound)?;
+    let now = time();
+    let new_profile = user_profile.with_btc_settings(profile_version, now, btc)?;
+    user_profile_model.store_new(principal, now, &new_profile);
+    Ok(())
+}
+
+/// Returns the user's preferred BTC address type, P2WPKH for users without a profile.
+#[must_use]
+pub fn preferred_btc_address_type(
+    principal: StoredPrincipal,
+    user_profile_model: &UserProfileModel,
+) -> BtcAddressType {
+    find_profile(principal, user_profile_model)
+        .map(|profile| profile.preferred_btc_address_type())
+        .unwrap_or_default()
+}
@@ -0,0 +1,169 @@
+//! Reading and writing RFC 4180 CSV.
+
+/// Appends `fields` as one CSV row.
+pub(crate) fn push_line<'a>(content: &mut String, fields: impl Iterator<Item = &'a str>) {
+    for (i, field) in fields.enumerate() {
+        if i > 0 {
+            content.push(',');
+        }
+        push_field(content, field);
+    }
+    content.push_str("\r\n");
+}
+
+/// Appends `field` quoted as per RFC 4180 when needed.
+///
+/// Fields that a spreadsheet would evaluate as a formula are prefixed with `'`, since addresses,
+/// ids and contact names are not under the exporting user's control. [`parse`] removes the prefix.
+fn push_field(content: &mut String, field: &str) {
+    let is_formula = is_formula(field);
+    let needs_quotes = is_formula || field.contains([',', '"', '\r', '\n']);
+    if needs_quotes {
+        content.push('"');
+    }
+    if is_formula {
+        content.push('\'');
+    }
+    for c in field.chars() {
+        if c == '"' {
+            content.push('"');
+        }
+        content.push(c);
+    }
+    if needs_quotes {
+        content.push('"');
+    }
+}
+
+fn is_formula(field: &str) -> bool {
+    field.starts_with(['=', '+', '-', '@', '\t', '\r'])
+}
+
+/// A row of `content` that is not valid CSV, such as one with an unterminated quoted field.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct MalformedRow;
+
+/// A CSV row and the 1-based line it starts on.
+pub(crate) type Row = (u64, Result<Vec<String>, MalformedRow>);
+
+/// Splits `content` into rows of fields. Empty lines are skipped and the `'` prefix that
+/// [`push_line`] adds to formula-like fields is removed.
+///
+/// A malformed row does not affect the following ones, except for an unterminated quoted field,
+/// which extends to the end of `content`.
+pub(crate) fn parse(content: &str) -> Vec<Row> {
+    let mut rows = Vec::new();
+    let mut chars = content.chars().peekable();
+    let mut line = 1;
+
+    while chars.peek().is_some() {
+        let start_line = line;
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        let mut malformed = false;
+
+        loop {
+            let Some(c) = chars.next() else {
+                if quoted {
+                    malformed = true;
+                }
+                break;
+            };
+            if quoted {
+                match c {
+                    '"' if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    '"' => quoted = false,
+                    _ => {
+                        if c == '\n' {
+                            line += 1;
+                        }
+                        field.push(c);
+                    }
+                }
+                continue;
+            }
+            match c {
+                '"' if field.is_empty() => quoted = true,
+                // A quote inside an unquoted field, or text after a closing quote.
+                '"' => malformed = true,
+                ',' => fields.push(unescape(std::mem::take(&mut field))),
+                '\r' if chars.peek() == Some(&'\n') => {}
+                '\n' => {
+                    line += 1;
+                    break;
+                }
+                _ => field.push(c),
+            }
+        }
+        fields.push(unescape(field));
+
+        if fields.len() == 1 && fields[0].is_empty() && !malformed {
+            continue;
+        }
+        rows.push((
+            start_line,
+            if malformed {
+                Err(MalformedRow)
+            } else {
+                Ok(fields)
+            },
+        ));
+    }
+    rows
+}
+
+fn unescape(field: String) -> String {
+    match field.strip_prefix('\'') {
+        Some(formula) if is_formula(formula) => formula.to_string(),
+        _ => field,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn fields(fields: &[&str]) -> Vec<String> {
+        fields.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn parse_reads_back_written_rows() {
+        let rows: [&[&str]; 3] = [
+            &["id", "name", "address"],
+            &["1", "Doe, \"Jane\"", "multi\r\nline"],
+            &["2", "=cmd", ""],
+        ];
+        let mut content = String::new();
+        for row in rows {
+            push_line(&mut content, row.iter().copied());
+        }
+
+        assert_eq!(
+            parse(&content),
+            vec![
+                (1, Ok(fields(rows[0]))),
+                (2, Ok(fields(rows[1]))),
+                (4, Ok(fields(rows[2]))),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_reports_malformed_rows_and_skips_empty_lines() {
+        assert_eq!(
+            parse("a,b\n\nc\"d,e\nf,\"g\n"),
+            vec![
+                (1, Ok(fields(&["a", "b"]))),
+                (3, Err(MalformedRow)),
+                (4, Err(MalformedRow)),
+            ]
+        );
+    }
+}
@@ -7,6 +7,7 @@ use shared::types::signer::topup::TopUpCyclesLedgerResult;
 use crate::{
     api,
     personal_notes::share::service::prune_expired_shares,
+    portfolio::service::evict_expired_snapshots,
     token::{evict_inactive_tokens, TOKEN_ACTIVITY_RETENTION_SEC},
 };
 
@@ -76,6 +77,7 @@ pub(crate) fn start_periodic_housekeeping_timers() {
 /// - Top up the cycles ledger.
 /// - Evict `token_activity` entries older than [`TOKEN_ACTIVITY_RETENTION_SEC`].
 /// - Prune expired `personal_note_shares` entries.
+/// - Evict portfolio snapshots older than `PORTFOLIO_SNAPSHOT_RETENTION_DAYS`.
 async fn hourly_housekeeping_tasks() {
     // Tops up the account on the cycles ledger
     {
@@ -96,6 +98,11 @@ async fn hourly_housekeeping_tasks() {
     if pruned > 0 {
         ic_cdk::println!("Pruned {pruned} expired personal_note_shares entries");
     }
+
+    let evicted = evict_expired_snapshots();
+    if evicted > 0 {
+        ic_cdk::println!("Evicted {evicted} expired portfolio_snapshots entries");
+    }
 }
 
 #[cfg(test)]
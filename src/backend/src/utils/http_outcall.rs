@@ -136,9 +136,9 @@ pub(crate) async fn get(
 ///
 /// # Idempotency
 ///
-/// On the IC every replica in the subnet executes the outcall independently,
-/// so the remote server will receive the request *n* times (once per
-/// replica). For endpoints that are **not** inherently idempotent, pass an
+/// In replicated mode every replica in the subnet executes the outcall
+/// independently, so the remote server will receive the request *n* times
+/// (once per replica). For endpoints that are **not** inherently idempotent, pass an
 /// `Idempotency-Key` header via `headers` so the server can deduplicate:
 ///
 /// ```ignore
@@ -150,6 +150,7 @@ pub(crate) async fn get(
 ///         value: unique_key,
 ///     }],
 ///     max_response_bytes,
+///     true,
 /// )
 /// ```
 ///
@@ -162,12 +163,14 @@ pub(crate) async fn get(
 /// * `headers` - Additional headers appended after `User-Agent` and `Content-Type`.
 /// * `max_response_bytes` - Upper bound on the response size in bytes. Keep this as low as possible
 ///   to minimise cycle costs.
-#[expect(dead_code)]
+/// * `replicated` - When `true`, every replica issues the request and they reach consensus on the
+///   response; when `false`, a single replica handles it. See [`get`].
 pub(crate) async fn post(
     url: &str,
     body: Vec<u8>,
     headers: Vec<HttpHeader>,
     max_response_bytes: u64,
+    replicated: bool,
 ) -> Result<HttpRequestResult, String> {
     let mut post_headers = vec![HttpHeader {
         name: "Content-Type".to_string(),
@@ -184,6 +187,7 @@ pub(crate) async fn post(
         max_response_bytes,
     );
 
+    request.is_replicated = Some(replicated);
     request.transform = Some(transform_context_from_query(
         "http_request_transform".to_string(),
         vec![],
@@ -1,3 +1,4 @@
+pub(crate) mod csv;
 pub(crate) mod guards;
 pub(crate) mod housekeeping;
 pub(crate) mod http_outcall;
@@ -1,5 +1,7 @@
 use std::{convert::TryInto, mem::size_of};
 
+use sha2::{Digest, Sha256};
+
 /// Generates a cryptographically secure random `u64` number using the Internet Computer's
 /// Management Canister API `raw_rand()`.
 ///
@@ -36,3 +38,30 @@ pub(crate) async fn generate_random_u64() -> Result<u64, String> {
 
     Ok(random_id)
 }
+
+/// Generates `count` random `u64` numbers from a single `raw_rand()` call, each the first 8 bytes
+/// of the SHA-256 of the random seed and its index.
+///
+/// # Errors
+/// - Returns error string when `raw_rand()` call fails, with the error details.
+pub(crate) async fn generate_random_u64s(count: usize) -> Result<Vec<u64>, String> {
+    if count == 0 {
+        return Ok(Vec::new());
+    }
+    let seed: Vec<u8> = ic_cdk::management_canister::raw_rand()
+        .await
+        .map_err(|e| format!("raw_rand failed: {e:?}"))?;
+
+    Ok((0..count as u64)
+        .map(|index| {
+            let hash = Sha256::new()
+                .chain_update(&seed)
+                .chain_update(index.to_le_bytes())
+                .finalize();
+            let (int_bytes, _rest) = hash.split_at(size_of::<u64>());
+            u64::from_le_bytes(int_bytes.try_into().unwrap_or_else(|_| {
+                unreachable!("A SHA-256 hash has 32 bytes, so this cannot fail.")
+            }))
+        })
+        .collect())
+}
@@ -58,6 +58,16 @@ thread_local! {
     pub(crate) static CREATE_PERSONAL_NOTE_SHARE_RATE_LIMITER: RateLimiter =
         RateLimiter::new(20, 60 * 1_000_000_000);
 
+    /// Rate-limits `create_price_alert`: max 10 calls per caller per minute.
+    pub(crate) static CREATE_PRICE_ALERT_RATE_LIMITER: RateLimiter =
+        RateLimiter::new(10, 60 * 1_000_000_000);
+
+    /// Limits how many price alerts trigger per user: max 10 per hour. Checked by the
+    /// exchange-rate refresh rather than an endpoint; an alert over the limit stays armed and
+    /// triggers on a later refresh if its condition still holds.
+    pub(crate) static PRICE_ALERT_TRIGGER_RATE_LIMITER: RateLimiter =
+        RateLimiter::new(10, 60 * 60 * 1_000_000_000);
+
     /// Coarse **global** limiter for `consume_personal_note_share`: max 600
     /// calls total per minute, across *every* anonymous caller. An anonymous
     /// update call has no distinguishing principal — `msg_caller()` is always
@@ -3,9 +3,10 @@ use ic_cdk::bitcoin_canister::{Network as BitcoinNetwork, Outpoint, Utxo};
 use pretty_assertions::assert_eq;
 use shared::types::{
     bitcoin::{
-        BtcAddPendingTransactionError, BtcAddPendingTransactionRequest,
-        BtcGetPendingTransactionsError, BtcGetPendingTransactionsReponse,
-        BtcGetPendingTransactionsRequest,
+        BtcAddPendingTransactionError, BtcAddPendingTransactionRequest, BtcBuildTransactionError,
+        BtcBuildTransactionRequest, BtcBuildTransactionResponse, BtcBumpFeeError,
+        BtcBumpFeeRequest, BtcBumpFeeResponse, BtcFeeBumpMethod, BtcGetPendingTransactionsError,
+        BtcGetPendingTransactionsReponse, BtcGetPendingTransactionsRequest,
     },
     signer::RateLimitError,
 };
@@ -35,6 +36,7 @@ fn test_add_pending_transaction_requires_delegation_chain() {
         txid: vec![],
         utxos: vec![UTXO_1],
         network: BitcoinNetwork::Regtest,
+        address_type: None,
         ii_delegation_chain: None,
     };
 
@@ -66,6 +68,7 @@ fn test_add_pending_transaction_enforces_guard_under_production_config() {
         txid: vec![],
         utxos: vec![UTXO_1],
         network: BitcoinNetwork::Regtest,
+        address_type: None,
         ii_delegation_chain: None,
     };
 
@@ -107,6 +110,7 @@ fn test_get_pending_transactions_returns_empty_for_new_user() {
 
     let read_request = BtcGetPendingTransactionsRequest {
         network: BitcoinNetwork::Regtest,
+        address_type: None,
         ii_delegation_chain: Some(delegation_chain),
     };
     let read_response = pic_setup.update::<Result<
@@ -145,6 +149,7 @@ fn test_add_pending_transaction_with_valid_delegation() {
         txid: vec![],
         utxos: vec![UTXO_1],
         network: BitcoinNetwork::Regtest,
+        address_type: None,
         ii_delegation_chain: Some(delegation_chain),
     };
 
@@ -173,6 +178,7 @@ fn test_controller_bypasses_delegation_check() {
         txid: vec![],
         utxos: vec![UTXO_1],
         network: BitcoinNetwork::Regtest,
+        address_type: None,
         ii_delegation_chain: None,
     };
 
@@ -193,6 +199,93 @@ fn test_controller_bypasses_delegation_check() {
     );
 }
 
+// -------------------------------------------------------------------------------------------------
+// - Integration tests for btc_build_transaction
+// -------------------------------------------------------------------------------------------------
+
+fn build_request(recipient: &str) -> BtcBuildTransactionRequest {
+    BtcBuildTransactionRequest {
+        network: BitcoinNetwork::Regtest,
+        recipient: recipient.to_string(),
+        amount_satoshis: 10_000,
+        fee_percentile: 50,
+        coin_selection: None,
+        address_type: None,
+        ii_delegation_chain: None,
+    }
+}
+
+#[test]
+fn test_build_transaction_requires_delegation_chain() {
+    let pic_setup = setup();
+
+    let caller = Principal::from_text(CALLER).unwrap();
+    pic_setup.ensure_user_profile(caller);
+
+    let response = pic_setup
+        .update::<Result<BtcBuildTransactionResponse, BtcBuildTransactionError>>(
+            caller,
+            "btc_build_transaction",
+            build_request("bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080"),
+        )
+        .expect("Canister call failed");
+
+    assert!(
+        matches!(
+            response,
+            Err(BtcBuildTransactionError::InvalidDelegationChain { .. })
+        ),
+        "Expected InvalidDelegationChain error, got: {response:?}"
+    );
+}
+
+#[test]
+fn test_build_transaction_rejects_recipient_on_other_network() {
+    let pic_setup = setup();
+    pic_setup.ensure_user_profile(controller());
+
+    let response = pic_setup
+        .update::<Result<BtcBuildTransactionResponse, BtcBuildTransactionError>>(
+            controller(),
+            "btc_build_transaction",
+            build_request("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"),
+        )
+        .expect("Canister call failed");
+
+    assert_eq!(response, Err(BtcBuildTransactionError::InvalidRecipient));
+}
+
+#[test]
+fn test_bump_fee_requires_delegation_chain() {
+    let pic_setup = setup();
+
+    let caller = Principal::from_text(CALLER).unwrap();
+    pic_setup.ensure_user_profile(caller);
+
+    let response = pic_setup
+        .update::<Result<BtcBumpFeeResponse, BtcBumpFeeError>>(
+            caller,
+            "btc_bump_fee",
+            BtcBumpFeeRequest {
+                network: BitcoinNetwork::Regtest,
+                txid: vec![1; 32],
+                method: BtcFeeBumpMethod::ReplaceByFee,
+                fee_percentile: 90,
+                address_type: None,
+                ii_delegation_chain: None,
+            },
+        )
+        .expect("Canister call failed");
+
+    assert!(
+        matches!(
+            response,
+            Err(BtcBumpFeeError::InvalidDelegationChain { .. })
+        ),
+        "Expected InvalidDelegationChain error, got: {response:?}"
+    );
+}
+
 // -------------------------------------------------------------------------------------------------
 // - Delegation chain integration tests for btc_get_pending_transactions
 // -------------------------------------------------------------------------------------------------
@@ -205,6 +298,7 @@ fn test_get_pending_transactions_requires_delegation_chain() {
 
     let request = BtcGetPendingTransactionsRequest {
         network: BitcoinNetwork::Regtest,
+        address_type: None,
         ii_delegation_chain: None,
     };
 
@@ -233,6 +327,7 @@ fn test_get_pending_transactions_enforces_guard_under_production_config() {
 
     let request = BtcGetPendingTransactionsRequest {
         network: BitcoinNetwork::Regtest,
+        address_type: None,
         ii_delegation_chain: None,
     };
 
@@ -260,6 +355,7 @@ fn test_get_pending_transactions_controller_bypasses_delegation_check() {
 
     let request = BtcGetPendingTransactionsRequest {
         network: BitcoinNetwork::Regtest,
+        address_type: None,
         ii_delegation_chain: None,
     };
 
@@ -302,6 +398,7 @@ fn test_get_pending_transactions_with_valid_delegation() {
 
     let request = BtcGetPendingTransactionsRequest {
         network: BitcoinNetwork::Regtest,
+        address_type: None,
         ii_delegation_chain: Some(delegation_chain),
     };
 
@@ -330,6 +427,7 @@ fn call_btc_add_pending_transaction(
         txid: vec![],
         utxos: vec![UTXO_1],
         network: BitcoinNetwork::Regtest,
+        address_type: None,
         ii_delegation_chain: None,
     };
     pic_setup
@@ -382,6 +480,7 @@ fn call_btc_get_pending_transactions(
 ) -> Result<BtcGetPendingTransactionsReponse, BtcGetPendingTransactionsError> {
     let request = BtcGetPendingTransactionsRequest {
         network: BitcoinNetwork::Regtest,
+        address_type: None,
         ii_delegation_chain: None,
     };
     pic_setup
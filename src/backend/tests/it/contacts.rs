@@ -1,18 +1,24 @@
 use std::time::Duration;
 
 use candid::Principal;
+use ic_canister_sig_creation::extract_raw_root_pk_from_der;
 use pretty_assertions::assert_eq;
 use serde_bytes::ByteBuf;
 use shared::types::{
+    account::{conversion::ParseError, Icrcv2AccountId, TokenAccountId},
+    certification::{verify_certified_contacts, CertifiedUserData},
     contact::{
-        Contact, ContactError, ContactImage, CreateContactRequest, ImageMimeType,
-        UpdateContactRequest,
+        BatchContactsError, BatchContactsRequest, Contact, ContactAddressData, ContactError,
+        ContactExportFormat, ContactImage, ContactImportError, ContactImportRowError,
+        ContactOperation, ContactOperationError, ContactOperationResult, CreateContactRequest,
+        CreateVerifiedContactError, CreateVerifiedContactRequest, ImageMimeType,
+        ImportContactsRequest, ImportContactsResponse, UpdateContactRequest,
     },
     user_profile::OisyUser,
 };
 
 use crate::utils::{
-    mock::CALLER,
+    mock::{CALLER, USER_1},
     pocketic::{setup, PicBackend, PicCanisterTrait},
 };
 
@@ -26,12 +32,32 @@ pub fn call_create_contact(
     name: String,
 ) -> Result<Contact, ContactError> {
     pic_setup.ensure_user_profile(caller);
-    let request = CreateContactRequest { name, image: None };
+    let request = CreateContactRequest {
+        name,
+        addresses: None,
+        image: None,
+    };
     let wrapped_result =
         pic_setup.update::<Result<Contact, ContactError>>(caller, "create_contact", request);
     wrapped_result.expect("that create_contact succeeds")
 }
 
+pub fn call_create_verified_contact(
+    pic_setup: &PicBackend,
+    caller: Principal,
+    name: String,
+    principal: Principal,
+) -> Result<Contact, CreateVerifiedContactError> {
+    pic_setup.ensure_user_profile(caller);
+    let request = CreateVerifiedContactRequest { name, principal };
+    let wrapped_result = pic_setup.update::<Result<Contact, CreateVerifiedContactError>>(
+        caller,
+        "create_verified_contact",
+        request,
+    );
+    wrapped_result.expect("that create_verified_contact succeeds")
+}
+
 pub fn call_get_contacts(pic_setup: &PicBackend, caller: Principal) -> Vec<Contact> {
     let wrapped_result =
         pic_setup.query::<Result<Vec<Contact>, ContactError>>(caller, "get_contacts", ());
@@ -98,6 +124,7 @@ fn test_create_contact_requires_authenticated_user() {
     // Try to create a contact as anonymous user
     let request = CreateContactRequest {
         name: "Test Contact".to_string(),
+        addresses: None,
         image: None,
     };
     let result = pic_setup.update::<Result<Contact, ContactError>>(
@@ -130,6 +157,7 @@ fn test_create_contact_requires_registered_user() {
 
     let request = CreateContactRequest {
         name: "Test Contact".to_string(),
+        addresses: None,
         image: None,
     };
     let result =
@@ -207,6 +235,7 @@ fn test_create_contact_should_fail_with_whitespace_name() {
         "create_contact",
         CreateContactRequest {
             name: String::new(),
+            addresses: None,
             image: None,
         },
     );
@@ -218,6 +247,7 @@ fn test_create_contact_should_fail_with_whitespace_name() {
         "create_contact",
         CreateContactRequest {
             name: "  ".to_string(),
+            addresses: None,
             image: None,
         },
     );
@@ -239,6 +269,7 @@ fn test_create_contact_should_fail_with_leading_and_trailing_whitespace_name() {
         "create_contact",
         CreateContactRequest {
             name: "   Leading Whitespace".to_string(),
+            addresses: None,
             image: None,
         },
     );
@@ -253,6 +284,7 @@ fn test_create_contact_should_fail_with_leading_and_trailing_whitespace_name() {
         "create_contact",
         CreateContactRequest {
             name: "Trailing Whitespace   ".to_string(),
+            addresses: None,
             image: None,
         },
     );
@@ -267,6 +299,7 @@ fn test_create_contact_should_fail_with_leading_and_trailing_whitespace_name() {
         "create_contact",
         CreateContactRequest {
             name: "   Leading and Trailing Whitespace   ".to_string(),
+            addresses: None,
             image: None,
         },
     );
@@ -281,6 +314,7 @@ fn test_create_contact_should_fail_with_leading_and_trailing_whitespace_name() {
         "create_contact",
         CreateContactRequest {
             name: "Valid Name With Spaces".to_string(),
+            addresses: None,
             image: None,
         },
     );
@@ -295,6 +329,7 @@ fn test_create_contact_should_fail_with_leading_and_trailing_whitespace_name() {
         "create_contact",
         CreateContactRequest {
             name: "Valid Name  With  Multiple  Spaces".to_string(),
+            addresses: None,
             image: None,
         },
     );
@@ -886,6 +921,288 @@ fn test_update_contact_image_jpeg() {
     assert_eq!(contact_with_image.image, Some(jpeg_image));
 }
 
+#[test]
+fn test_create_verified_contact_requires_an_oisy_user() {
+    let pic_setup = setup();
+    let caller: Principal = Principal::from_text(CALLER).unwrap();
+    let stranger: Principal = Principal::from_text(USER_1).unwrap();
+
+    let result = call_create_verified_contact(&pic_setup, caller, "Stranger".to_string(), stranger);
+
+    assert_eq!(result, Err(CreateVerifiedContactError::NotAnOisyUser));
+    assert!(call_get_contacts(&pic_setup, caller).is_empty());
+}
+
+#[test]
+fn test_update_contact_rejects_forged_verified_addresses() {
+    let pic_setup = setup();
+    let caller: Principal = Principal::from_text(CALLER).unwrap();
+    let friend: Principal = Principal::from_text(USER_1).unwrap();
+
+    let contact = call_create_contact(&pic_setup, caller, "Friend".to_string()).unwrap();
+    let forged = Contact {
+        addresses: vec![ContactAddressData {
+            token_account_id: TokenAccountId::Icrcv2(Icrcv2AccountId::WithPrincipal {
+                owner: friend,
+                subaccount: None,
+            }),
+            label: None,
+            verified_owner: Some(friend),
+        }],
+        ..contact.clone()
+    };
+
+    let result = call_update_contact(&pic_setup, caller, forged);
+
+    assert_eq!(result, Err(ContactError::InvalidContactData));
+    assert_eq!(
+        call_get_contact(&pic_setup, caller, contact.id),
+        Ok(contact)
+    );
+}
+
+#[test]
+fn test_import_contacts_reports_invalid_rows_and_exports_the_imported_ones() {
+    let pic_setup = setup();
+    let caller: Principal = Principal::from_text(CALLER).unwrap();
+    pic_setup.ensure_user_profile(caller);
+
+    let request = ImportContactsRequest {
+        format: ContactExportFormat::Csv,
+        content: "id,name,address,label\n\
+                  1,Alice,un4fu-tqaaa-aaaab-qadjq-cai,Main\n\
+                  2,Bob,not-an-address,\n"
+            .to_string(),
+    };
+    let response = pic_setup
+        .update::<Result<ImportContactsResponse, ContactError>>(caller, "import_contacts", request)
+        .expect("that import_contacts succeeds")
+        .expect("that the import runs");
+
+    assert_eq!(response.imported.len(), 1);
+    assert_eq!(response.imported[0].name, "Alice");
+    assert_eq!(
+        response.errors,
+        vec![ContactImportRowError {
+            line: 3,
+            error: ContactImportError::InvalidAddress {
+                address: "not-an-address".to_string(),
+                error: ParseError::UnsupportedFormat,
+            },
+        }]
+    );
+    assert_eq!(call_get_contacts(&pic_setup, caller), response.imported);
+
+    let exported = pic_setup
+        .query::<Result<String, ContactError>>(caller, "export_contacts", ContactExportFormat::Csv)
+        .expect("that export_contacts succeeds")
+        .expect("that the export succeeds");
+    assert_eq!(
+        exported,
+        format!(
+            "id,name,address,label\r\n{},Alice,un4fu-tqaaa-aaaab-qadjq-cai,Main\r\n",
+            response.imported[0].id
+        )
+    );
+}
+
+fn icrc_address(owner: Principal) -> ContactAddressData {
+    ContactAddressData {
+        token_account_id: TokenAccountId::Icrcv2(Icrcv2AccountId::WithPrincipal {
+            owner,
+            subaccount: None,
+        }),
+        label: Some("Main".to_string()),
+        verified_owner: None,
+    }
+}
+
+fn call_batch_contacts(
+    pic_setup: &PicBackend,
+    caller: Principal,
+    operations: Vec<ContactOperation>,
+) -> Result<Vec<ContactOperationResult>, BatchContactsError> {
+    pic_setup
+        .update::<Result<Vec<ContactOperationResult>, BatchContactsError>>(
+            caller,
+            "batch_contacts",
+            BatchContactsRequest { operations },
+        )
+        .expect("that batch_contacts succeeds")
+}
+
+#[test]
+fn test_create_contact_with_addresses_and_image() {
+    let pic_setup = setup();
+    let caller: Principal = Principal::from_text(CALLER).unwrap();
+    pic_setup.ensure_user_profile(caller);
+    let address = icrc_address(Principal::from_text(USER_1).unwrap());
+    let image = create_test_png_image();
+
+    let contact = pic_setup
+        .update::<Result<Contact, ContactError>>(
+            caller,
+            "create_contact",
+            CreateContactRequest {
+                name: "Friend".to_string(),
+                addresses: Some(vec![address.clone()]),
+                image: Some(image.clone()),
+            },
+        )
+        .expect("that create_contact succeeds")
+        .expect("that the contact is created");
+
+    assert_eq!(contact.addresses, vec![address]);
+    assert_eq!(contact.image, Some(image));
+    assert_eq!(
+        call_get_contact(&pic_setup, caller, contact.id),
+        Ok(contact)
+    );
+}
+
+#[test]
+fn test_create_contact_rejects_verified_addresses() {
+    let pic_setup = setup();
+    let caller: Principal = Principal::from_text(CALLER).unwrap();
+    pic_setup.ensure_user_profile(caller);
+    let friend = Principal::from_text(USER_1).unwrap();
+
+    let result = pic_setup
+        .update::<Result<Contact, ContactError>>(
+            caller,
+            "create_contact",
+            CreateContactRequest {
+                name: "Friend".to_string(),
+                addresses: Some(vec![ContactAddressData {
+                    verified_owner: Some(friend),
+                    ..icrc_address(friend)
+                }]),
+                image: None,
+            },
+        )
+        .expect("that create_contact succeeds");
+
+    assert_eq!(result, Err(ContactError::InvalidContactData));
+}
+
+#[test]
+fn test_batch_contacts_applies_all_operations() {
+    let pic_setup = setup();
+    let caller: Principal = Principal::from_text(CALLER).unwrap();
+    let kept = call_create_contact(&pic_setup, caller, "Kept".to_string()).unwrap();
+    let deleted = call_create_contact(&pic_setup, caller, "Deleted".to_string()).unwrap();
+    let address = icrc_address(Principal::from_text(USER_1).unwrap());
+
+    let results = call_batch_contacts(
+        &pic_setup,
+        caller,
+        vec![
+            ContactOperation::Create(CreateContactRequest {
+                name: "Created".to_string(),
+                addresses: Some(vec![address.clone()]),
+                image: None,
+            }),
+            ContactOperation::Update(UpdateContactRequest {
+                id: kept.id,
+                name: "Renamed".to_string(),
+                addresses: vec![address.clone()],
+                update_timestamp_ns: kept.update_timestamp_ns,
+                image: None,
+            }),
+            ContactOperation::Delete {
+                id: deleted.id,
+                update_timestamp_ns: deleted.update_timestamp_ns,
+            },
+        ],
+    )
+    .expect("that the batch is applied");
+
+    let [ContactOperationResult::Created(created), ContactOperationResult::Updated(renamed), ContactOperationResult::Deleted(deleted_id)] =
+        results.as_slice()
+    else {
+        panic!("unexpected results: {results:?}");
+    };
+    assert_eq!(created.name, "Created");
+    assert_eq!(created.addresses, vec![address.clone()]);
+    assert_eq!(renamed.name, "Renamed");
+    assert_eq!(*deleted_id, deleted.id);
+
+    let mut contacts = call_get_contacts(&pic_setup, caller);
+    contacts.sort_by_key(|contact| contact.name.clone());
+    assert_eq!(contacts, vec![created.clone(), renamed.clone()]);
+}
+
+#[test]
+fn test_batch_contacts_applies_nothing_if_a_contact_is_outdated() {
+    let pic_setup = setup();
+    let caller: Principal = Principal::from_text(CALLER).unwrap();
+    let contact = call_create_contact(&pic_setup, caller, "Friend".to_string()).unwrap();
+    let stale_timestamp_ns = contact.update_timestamp_ns;
+    let renamed = call_update_contact(
+        &pic_setup,
+        caller,
+        Contact {
+            name: "Renamed".to_string(),
+            ..contact
+        },
+    )
+    .unwrap();
+
+    let result = call_batch_contacts(
+        &pic_setup,
+        caller,
+        vec![
+            ContactOperation::Create(CreateContactRequest {
+                name: "Created".to_string(),
+                addresses: None,
+                image: None,
+            }),
+            ContactOperation::Delete {
+                id: renamed.id,
+                update_timestamp_ns: stale_timestamp_ns,
+            },
+        ],
+    );
+
+    assert_eq!(
+        result,
+        Err(BatchContactsError {
+            operation: Some(1),
+            error: ContactOperationError::OutdatedContact,
+        })
+    );
+    assert_eq!(call_get_contacts(&pic_setup, caller), vec![renamed]);
+}
+
+#[test]
+fn test_get_certified_contacts_verifies() {
+    let pic_setup = setup();
+    let caller: Principal = Principal::from_text(CALLER).unwrap();
+
+    let contact = call_create_contact(&pic_setup, caller, "Certified".to_string()).unwrap();
+
+    let certified = pic_setup
+        .query::<Option<CertifiedUserData>>(caller, "get_certified_contacts", ())
+        .expect("that get_certified_contacts succeeds")
+        .expect("that the contacts are certified");
+
+    let root_key = pic_setup
+        .pic
+        .root_key()
+        .expect("PocketIC exposes its root key");
+    assert_eq!(
+        verify_certified_contacts(
+            &certified,
+            caller,
+            pic_setup.canister_id,
+            &extract_raw_root_pk_from_der(&root_key).unwrap(),
+            pic_setup.pic.get_time().as_nanos_since_unix_epoch(),
+            60 * 1_000_000_000,
+        ),
+        Ok(vec![contact])
+    );
+}
+
 #[cfg(test)]
 mod tests {
     use pretty_assertions::assert_eq;
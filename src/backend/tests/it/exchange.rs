@@ -1,6 +1,16 @@
 use candid::Principal;
+use ic_canister_sig_creation::extract_raw_root_pk_from_der;
 use pretty_assertions::assert_eq;
-use shared::types::api_keys::ApiKeys;
+use shared::types::{
+    api_keys::ApiKeys,
+    exchange::{
+        certification::verify_certified_exchange_rate, CertifiedExchangeRate,
+        ExchangeRateResolution, GetExchangeRateHistoryRequest, PriceCandle, PriceOverride,
+        SetPriceOverrideError, SetPriceOverrideRequest,
+    },
+    result_types::SetPriceOverrideResult,
+    token_id::TokenId,
+};
 
 use crate::utils::{
     mock::USER_1,
@@ -281,3 +291,184 @@ fn set_exchange_rate_enabled_rejects_authenticated_non_controller() {
         "Rejected non-controller calls must not mutate the stored refresh setting."
     );
 }
+
+fn history_request() -> GetExchangeRateHistoryRequest {
+    GetExchangeRateHistoryRequest {
+        token_id: TokenId::IcpNative,
+        from_ns: 0,
+        to_ns: u64::MAX,
+        resolution: ExchangeRateResolution::Daily,
+    }
+}
+
+#[test]
+fn get_exchange_rate_history_rejects_anonymous() {
+    let pic_setup = setup();
+
+    assert!(
+        pic_setup
+            .query::<Vec<PriceCandle>>(
+                Principal::anonymous(),
+                "get_exchange_rate_history",
+                history_request(),
+            )
+            .is_err(),
+        "Anonymous caller must not be able to read the exchange-rate history."
+    );
+}
+
+#[test]
+fn get_exchange_rate_history_is_empty_while_refresh_is_disabled() {
+    let pic_setup = setup();
+    let caller = Principal::from_text(USER_1).expect("valid principal");
+
+    assert_eq!(
+        pic_setup.query::<Vec<PriceCandle>>(caller, "get_exchange_rate_history", history_request()),
+        Ok(vec![]),
+        "No history is recorded while the exchange-rate refresh is disabled."
+    );
+}
+
+#[test]
+fn set_exchange_rate_max_deviation_bps_survives_key_rotation_and_can_be_cleared() {
+    let pic_setup = setup();
+
+    assert_eq!(
+        pic_setup.update::<()>(
+            controller(),
+            "set_exchange_rate_max_deviation_bps",
+            Some(500u32)
+        ),
+        Ok(())
+    );
+    assert_eq!(
+        pic_setup.update::<()>(controller(), "set_api_keys", api_keys_with_coingecko()),
+        Ok(())
+    );
+    let stored = pic_setup
+        .query::<ApiKeys>(controller(), "get_api_keys", ())
+        .expect("controller can read API keys");
+    assert_eq!(
+        stored.exchange_rate_max_deviation_bps,
+        Some(500),
+        "Key rotation that omits exchange_rate_max_deviation_bps must not change the aggregation mode."
+    );
+
+    assert_eq!(
+        pic_setup.update::<()>(
+            controller(),
+            "set_exchange_rate_max_deviation_bps",
+            None::<u32>
+        ),
+        Ok(())
+    );
+    let stored = pic_setup
+        .query::<ApiKeys>(controller(), "get_api_keys", ())
+        .expect("controller can read API keys");
+    assert_eq!(stored.exchange_rate_max_deviation_bps, None);
+
+    assert!(
+        pic_setup
+            .update::<()>(
+                Principal::anonymous(),
+                "set_exchange_rate_max_deviation_bps",
+                Some(1u32)
+            )
+            .is_err(),
+        "Anonymous caller must not be able to change the aggregation mode."
+    );
+}
+
+#[test]
+fn set_price_override_is_controller_only_and_validated() {
+    let pic_setup = setup();
+    let caller = Principal::from_text(USER_1).expect("valid principal");
+    let ck_usdc = TokenId::Icrc(Principal::from_text("xevnm-gaaaa-aaaar-qafnq-cai").unwrap());
+    let peg = SetPriceOverrideRequest {
+        token_id: ck_usdc.clone(),
+        price_override: Some(PriceOverride::Peg {
+            token_id: TokenId::BtcNativeMainnet,
+        }),
+    };
+
+    assert!(
+        pic_setup
+            .update::<SetPriceOverrideResult>(caller, "set_price_override", peg.clone())
+            .is_err(),
+        "Only controllers may override prices."
+    );
+    assert_eq!(
+        pic_setup.update::<SetPriceOverrideResult>(controller(), "set_price_override", peg),
+        Ok(SetPriceOverrideResult::Ok(()))
+    );
+    assert_eq!(
+        pic_setup.update::<SetPriceOverrideResult>(
+            controller(),
+            "set_price_override",
+            SetPriceOverrideRequest {
+                token_id: TokenId::IcpNative,
+                price_override: Some(PriceOverride::Static { price: -1.0 }),
+            }
+        ),
+        Ok(SetPriceOverrideResult::Err(
+            SetPriceOverrideError::InvalidPrice
+        ))
+    );
+    assert_eq!(
+        pic_setup.query::<Vec<(TokenId, PriceOverride)>>(controller(), "list_price_overrides", ()),
+        Ok(vec![(
+            ck_usdc.clone(),
+            PriceOverride::Peg {
+                token_id: TokenId::BtcNativeMainnet,
+            }
+        )])
+    );
+
+    assert_eq!(
+        pic_setup.update::<SetPriceOverrideResult>(
+            controller(),
+            "set_price_override",
+            SetPriceOverrideRequest {
+                token_id: ck_usdc,
+                price_override: None,
+            }
+        ),
+        Ok(SetPriceOverrideResult::Ok(()))
+    );
+    assert_eq!(
+        pic_setup.query::<Vec<(TokenId, PriceOverride)>>(controller(), "list_price_overrides", ()),
+        Ok(vec![])
+    );
+}
+
+#[test]
+fn get_certified_exchange_rate_proves_missing_rates() {
+    let pic_setup = setup();
+    let caller = Principal::from_text(USER_1).unwrap();
+
+    let certified = pic_setup
+        .query::<Option<CertifiedExchangeRate>>(
+            caller,
+            "get_certified_exchange_rate",
+            TokenId::IcpNative,
+        )
+        .expect("query should succeed")
+        .expect("a query comes with a certificate");
+
+    // No refresh has run, so the canister certifies that it has no ICP rate.
+    let root_key = pic_setup
+        .pic
+        .root_key()
+        .expect("PocketIC exposes its root key");
+    assert_eq!(
+        verify_certified_exchange_rate(
+            &certified,
+            &TokenId::IcpNative,
+            pic_setup.canister_id,
+            &extract_raw_root_pk_from_der(&root_key).unwrap(),
+            pic_setup.pic.get_time().as_nanos_since_unix_epoch(),
+            60 * 1_000_000_000,
+        ),
+        Ok(None)
+    );
+}
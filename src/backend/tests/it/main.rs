@@ -8,6 +8,8 @@ mod exchange;
 mod onramper;
 mod personal_note_shares;
 mod personal_notes;
+mod portfolio;
+mod price_alerts;
 mod settings;
 mod signer;
 mod stats;
@@ -0,0 +1,181 @@
+use candid::{Nat, Principal};
+use pretty_assertions::assert_eq;
+use shared::types::{
+    portfolio::{
+        GetPortfolioSnapshotsRequest, GetPortfolioSnapshotsResponse, PortfolioSettings,
+        PortfolioSnapshot, PortfolioSnapshotError, PortfolioTokenBalance,
+        SavePortfolioSnapshotRequest, UpdatePortfolioSettingsError, UpdatePortfolioSettingsRequest,
+        MAX_PORTFOLIO_SNAPSHOT_BALANCES,
+    },
+    token_id::TokenId,
+    user_profile::{CreateUserProfileError, GetUserProfileError, UserProfile},
+};
+
+use crate::utils::{
+    mock::CALLER,
+    pocketic::{setup, PicBackend, PicCanisterTrait},
+};
+
+fn create_profile(pic_setup: &PicBackend, caller: Principal) -> UserProfile {
+    pic_setup
+        .update::<Result<UserProfile, CreateUserProfileError>>(caller, "create_user_profile", ())
+        .expect("Create call failed")
+        .expect("Signups should be open")
+}
+
+fn set_snapshots_enabled(
+    pic_setup: &PicBackend,
+    caller: Principal,
+    profile: &UserProfile,
+    snapshots_enabled: bool,
+) -> Result<(), UpdatePortfolioSettingsError> {
+    pic_setup
+        .update::<Result<(), UpdatePortfolioSettingsError>>(
+            caller,
+            "update_user_portfolio_settings",
+            UpdatePortfolioSettingsRequest {
+                settings: PortfolioSettings { snapshots_enabled },
+                current_user_version: profile.version,
+            },
+        )
+        .expect("Update call failed")
+}
+
+fn save_snapshot(
+    pic_setup: &PicBackend,
+    caller: Principal,
+    balances: Vec<PortfolioTokenBalance>,
+) -> Result<PortfolioSnapshot, PortfolioSnapshotError> {
+    pic_setup
+        .update::<Result<PortfolioSnapshot, PortfolioSnapshotError>>(
+            caller,
+            "save_portfolio_snapshot",
+            SavePortfolioSnapshotRequest { balances },
+        )
+        .expect("Save call failed")
+}
+
+fn get_snapshots(pic_setup: &PicBackend, caller: Principal) -> GetPortfolioSnapshotsResponse {
+    pic_setup
+        .query::<GetPortfolioSnapshotsResponse>(
+            caller,
+            "get_portfolio_snapshots",
+            GetPortfolioSnapshotsRequest {
+                max_results: 10,
+                cursor: None,
+            },
+        )
+        .expect("Get call failed")
+}
+
+fn unpriced_balance() -> PortfolioTokenBalance {
+    PortfolioTokenBalance {
+        token_id: TokenId::SolNativeDevnet,
+        balance: Nat::from(1_000_000_000u64),
+        decimals: 9,
+    }
+}
+
+#[test]
+fn test_save_portfolio_snapshot_requires_opt_in() {
+    let pic_setup = setup();
+    let caller = Principal::from_text(CALLER).unwrap();
+    create_profile(&pic_setup, caller);
+
+    let result = save_snapshot(&pic_setup, caller, vec![unpriced_balance()]);
+
+    assert_eq!(result, Err(PortfolioSnapshotError::SnapshotsDisabled));
+}
+
+#[test]
+fn test_save_portfolio_snapshot_stores_one_snapshot_per_day() {
+    let pic_setup = setup();
+    let caller = Principal::from_text(CALLER).unwrap();
+    let profile = create_profile(&pic_setup, caller);
+    assert_eq!(
+        set_snapshots_enabled(&pic_setup, caller, &profile, true),
+        Ok(())
+    );
+
+    let first = save_snapshot(&pic_setup, caller, vec![unpriced_balance()])
+        .expect("Snapshot should be saved");
+    let second = save_snapshot(
+        &pic_setup,
+        caller,
+        vec![unpriced_balance(), unpriced_balance()],
+    )
+    .expect("Snapshot should be saved");
+
+    assert_eq!(first.unpriced_tokens, 1);
+    assert_eq!(second.unpriced_tokens, 2);
+    assert_eq!(
+        get_snapshots(&pic_setup, caller),
+        GetPortfolioSnapshotsResponse {
+            snapshots: vec![second],
+            next_cursor: None,
+        }
+    );
+}
+
+#[test]
+fn test_disabling_portfolio_snapshots_deletes_them() {
+    let pic_setup = setup();
+    let caller = Principal::from_text(CALLER).unwrap();
+    let profile = create_profile(&pic_setup, caller);
+    assert_eq!(
+        set_snapshots_enabled(&pic_setup, caller, &profile, true),
+        Ok(())
+    );
+    save_snapshot(&pic_setup, caller, vec![unpriced_balance()]).expect("Snapshot should be saved");
+
+    let profile = pic_setup
+        .update::<Result<UserProfile, GetUserProfileError>>(caller, "get_user_profile", ())
+        .expect("Call to get profile failed")
+        .expect("Get profile failed");
+    assert_eq!(
+        set_snapshots_enabled(&pic_setup, caller, &profile, false),
+        Ok(())
+    );
+
+    assert_eq!(get_snapshots(&pic_setup, caller).snapshots, vec![]);
+}
+
+#[test]
+fn test_save_portfolio_snapshot_rejects_too_many_balances() {
+    let pic_setup = setup();
+    let caller = Principal::from_text(CALLER).unwrap();
+    let profile = create_profile(&pic_setup, caller);
+    assert_eq!(
+        set_snapshots_enabled(&pic_setup, caller, &profile, true),
+        Ok(())
+    );
+
+    let result = save_snapshot(
+        &pic_setup,
+        caller,
+        vec![unpriced_balance(); MAX_PORTFOLIO_SNAPSHOT_BALANCES + 1],
+    );
+
+    assert_eq!(
+        result,
+        Err(PortfolioSnapshotError::TooManyBalances {
+            max: MAX_PORTFOLIO_SNAPSHOT_BALANCES as u64,
+        })
+    );
+}
+
+#[test]
+fn test_update_user_portfolio_settings_cannot_update_wrong_version() {
+    let pic_setup = setup();
+    let caller = Principal::from_text(CALLER).unwrap();
+    let profile = create_profile(&pic_setup, caller);
+    assert_eq!(
+        set_snapshots_enabled(&pic_setup, caller, &profile, true),
+        Ok(())
+    );
+
+    assert_eq!(
+        set_snapshots_enabled(&pic_setup, caller, &profile, false),
+        Err(UpdatePortfolioSettingsError::VersionMismatch)
+    );
+}
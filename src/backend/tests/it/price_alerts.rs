@@ -0,0 +1,152 @@
+use candid::Principal;
+use pretty_assertions::assert_eq;
+use shared::types::{
+    price_alert::{
+        CreatePriceAlertRequest, DeletePriceAlertRequest, PriceAlert, PriceAlertCondition,
+        PriceAlertError, TriggeredPriceAlert,
+    },
+    token_id::TokenId,
+    user_profile::{CreateUserProfileError, UserProfile},
+};
+
+use crate::utils::{
+    mock::CALLER,
+    pocketic::{setup, PicBackend, PicCanisterTrait},
+};
+
+fn setup_user() -> (PicBackend, Principal) {
+    let pic_setup = setup();
+    let caller = Principal::from_text(CALLER).unwrap();
+    pic_setup
+        .update::<Result<UserProfile, CreateUserProfileError>>(caller, "create_user_profile", ())
+        .expect("Create call failed")
+        .expect("Signups should be open");
+    (pic_setup, caller)
+}
+
+fn create_alert(
+    pic_setup: &PicBackend,
+    caller: Principal,
+    token_id: TokenId,
+    condition: PriceAlertCondition,
+) -> Result<PriceAlert, PriceAlertError> {
+    pic_setup
+        .update::<Result<PriceAlert, PriceAlertError>>(
+            caller,
+            "create_price_alert",
+            CreatePriceAlertRequest {
+                token_id,
+                condition,
+            },
+        )
+        .expect("Create call failed")
+}
+
+fn get_alerts(pic_setup: &PicBackend, caller: Principal) -> Vec<PriceAlert> {
+    pic_setup
+        .query::<Vec<PriceAlert>>(caller, "get_price_alerts", ())
+        .expect("Get call failed")
+}
+
+#[test]
+fn test_create_and_delete_price_alert() {
+    let (pic_setup, caller) = setup_user();
+
+    let alert = create_alert(
+        &pic_setup,
+        caller,
+        TokenId::IcpNative,
+        PriceAlertCondition::PriceAbove { usd: 15.0 },
+    )
+    .expect("Alert should be created");
+
+    assert!(alert.armed);
+    assert_eq!(get_alerts(&pic_setup, caller), vec![alert.clone()]);
+
+    let delete = pic_setup.update::<Result<(), PriceAlertError>>(
+        caller,
+        "delete_price_alert",
+        DeletePriceAlertRequest { alert_id: alert.id },
+    );
+    assert_eq!(delete, Ok(Ok(())));
+    assert_eq!(get_alerts(&pic_setup, caller), vec![]);
+
+    let delete_again = pic_setup.update::<Result<(), PriceAlertError>>(
+        caller,
+        "delete_price_alert",
+        DeletePriceAlertRequest { alert_id: alert.id },
+    );
+    assert_eq!(delete_again, Ok(Err(PriceAlertError::AlertNotFound)));
+}
+
+#[test]
+fn test_create_price_alert_rejects_unpriceable_token_and_invalid_threshold() {
+    let (pic_setup, caller) = setup_user();
+
+    assert_eq!(
+        create_alert(
+            &pic_setup,
+            caller,
+            TokenId::SolNativeDevnet,
+            PriceAlertCondition::PriceAbove { usd: 1.0 },
+        ),
+        Err(PriceAlertError::TokenNotPriceable)
+    );
+    assert_eq!(
+        create_alert(
+            &pic_setup,
+            caller,
+            TokenId::IcpNative,
+            PriceAlertCondition::PriceBelow { usd: -1.0 },
+        ),
+        Err(PriceAlertError::InvalidThreshold)
+    );
+}
+
+#[test]
+fn test_create_price_alert_is_rate_limited() {
+    let (pic_setup, caller) = setup_user();
+
+    for _ in 0..10 {
+        create_alert(
+            &pic_setup,
+            caller,
+            TokenId::IcpNative,
+            PriceAlertCondition::Change24hBelow { pct: -10.0 },
+        )
+        .expect("Alert should be created");
+    }
+
+    let result = create_alert(
+        &pic_setup,
+        caller,
+        TokenId::IcpNative,
+        PriceAlertCondition::Change24hBelow { pct: -10.0 },
+    );
+
+    assert!(matches!(result, Err(PriceAlertError::RateLimited(_))));
+    assert_eq!(get_alerts(&pic_setup, caller).len(), 10);
+}
+
+#[test]
+fn test_price_alerts_require_registered_user() {
+    let pic_setup = setup();
+    let caller = Principal::from_text(CALLER).unwrap();
+
+    let result = pic_setup.update::<Result<PriceAlert, PriceAlertError>>(
+        caller,
+        "create_price_alert",
+        CreatePriceAlertRequest {
+            token_id: TokenId::IcpNative,
+            condition: PriceAlertCondition::PriceAbove { usd: 15.0 },
+        },
+    );
+
+    assert!(result.is_err());
+    assert_eq!(
+        pic_setup
+            .query::<Vec<TriggeredPriceAlert>>(caller, "get_triggered_price_alerts", ())
+            .expect("Get call failed"),
+        vec![]
+    );
+}
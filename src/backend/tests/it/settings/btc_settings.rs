@@ -0,0 +1,102 @@
+use candid::Principal;
+use pretty_assertions::assert_eq;
+use shared::types::{
+    bitcoin::{BtcAddressType, BtcSettings, UpdateBtcSettingsError, UpdateBtcSettingsRequest},
+    user_profile::{CreateUserProfileError, GetUserProfileError, UserProfile},
+};
+
+use crate::utils::{
+    mock::CALLER,
+    pocketic::{setup, PicCanisterTrait},
+};
+
+#[test]
+fn test_update_user_btc_settings_saves_settings() {
+    let pic_setup = setup();
+
+    let caller = Principal::from_text(CALLER).unwrap();
+
+    let profile = pic_setup
+        .update::<Result<UserProfile, CreateUserProfileError>>(caller, "create_user_profile", ())
+        .expect("Create call failed")
+        .expect("Signups should be open");
+
+    assert_eq!(profile.settings.as_ref().unwrap().btc, None);
+
+    let update_request = UpdateBtcSettingsRequest {
+        settings: BtcSettings {
+            address_type: BtcAddressType::P2tr,
+        },
+        current_user_version: profile.version,
+    };
+
+    let update_response = pic_setup.update::<Result<(), UpdateBtcSettingsError>>(
+        caller,
+        "update_user_btc_settings",
+        update_request,
+    );
+
+    assert_eq!(update_response, Ok(Ok(())));
+
+    let user_profile = pic_setup
+        .update::<Result<UserProfile, GetUserProfileError>>(caller, "get_user_profile", ())
+        .expect("Call to get profile failed")
+        .expect("Get profile failed");
+
+    assert_eq!(
+        user_profile.settings.unwrap().btc,
+        Some(BtcSettings {
+            address_type: BtcAddressType::P2tr,
+        })
+    );
+}
+
+#[test]
+fn test_update_user_btc_settings_cannot_update_wrong_version() {
+    let pic_setup = setup();
+
+    let caller = Principal::from_text(CALLER).unwrap();
+
+    let profile = pic_setup
+        .update::<Result<UserProfile, CreateUserProfileError>>(caller, "create_user_profile", ())
+        .expect("Create call failed")
+        .expect("Signups should be open");
+
+    let update = |address_type, current_user_version| {
+        pic_setup.update::<Result<(), UpdateBtcSettingsError>>(
+            caller,
+            "update_user_btc_settings",
+            UpdateBtcSettingsRequest {
+                settings: BtcSettings { address_type },
+                current_user_version,
+            },
+        )
+    };
+
+    assert_eq!(
+        update(BtcAddressType::P2shP2wpkh, profile.version),
+        Ok(Ok(()))
+    );
+    assert_eq!(
+        update(BtcAddressType::P2tr, profile.version),
+        Ok(Err(UpdateBtcSettingsError::VersionMismatch))
+    );
+}
+
+#[test]
+fn test_update_user_btc_settings_requires_a_profile() {
+    let pic_setup = setup();
+
+    let caller = Principal::from_text(CALLER).unwrap();
+
+    let update_response = pic_setup.update::<Result<(), UpdateBtcSettingsError>>(
+        caller,
+        "update_user_btc_settings",
+        UpdateBtcSettingsRequest {
+            settings: BtcSettings::default(),
+            current_user_version: None,
+        },
+    );
+
+    assert!(update_response.is_err());
+}
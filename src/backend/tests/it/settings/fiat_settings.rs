@@ -0,0 +1,99 @@
+use candid::Principal;
+use pretty_assertions::assert_eq;
+use shared::types::{
+    exchange::{FiatCurrency, FiatSettings, UpdateFiatSettingsError, UpdateFiatSettingsRequest},
+    user_profile::{CreateUserProfileError, GetUserProfileError, UserProfile},
+};
+
+use crate::utils::{
+    mock::CALLER,
+    pocketic::{setup, PicCanisterTrait},
+};
+
+#[test]
+fn test_update_user_fiat_settings_saves_settings() {
+    let pic_setup = setup();
+
+    let caller = Principal::from_text(CALLER).unwrap();
+
+    let profile = pic_setup
+        .update::<Result<UserProfile, CreateUserProfileError>>(caller, "create_user_profile", ())
+        .expect("Create call failed")
+        .expect("Signups should be open");
+
+    assert_eq!(profile.settings.as_ref().unwrap().fiat, None);
+
+    let update_request = UpdateFiatSettingsRequest {
+        settings: FiatSettings {
+            currency: FiatCurrency::Chf,
+        },
+        current_user_version: profile.version,
+    };
+
+    let update_response = pic_setup.update::<Result<(), UpdateFiatSettingsError>>(
+        caller,
+        "update_user_fiat_settings",
+        update_request,
+    );
+
+    assert_eq!(update_response, Ok(Ok(())));
+
+    let user_profile = pic_setup
+        .update::<Result<UserProfile, GetUserProfileError>>(caller, "get_user_profile", ())
+        .expect("Call to get profile failed")
+        .expect("Get profile failed");
+
+    assert_eq!(
+        user_profile.settings.unwrap().fiat,
+        Some(FiatSettings {
+            currency: FiatCurrency::Chf,
+        })
+    );
+}
+
+#[test]
+fn test_update_user_fiat_settings_cannot_update_wrong_version() {
+    let pic_setup = setup();
+
+    let caller = Principal::from_text(CALLER).unwrap();
+
+    let profile = pic_setup
+        .update::<Result<UserProfile, CreateUserProfileError>>(caller, "create_user_profile", ())
+        .expect("Create call failed")
+        .expect("Signups should be open");
+
+    let update = |currency, current_user_version| {
+        pic_setup.update::<Result<(), UpdateFiatSettingsError>>(
+            caller,
+            "update_user_fiat_settings",
+            UpdateFiatSettingsRequest {
+                settings: FiatSettings { currency },
+                current_user_version,
+            },
+        )
+    };
+
+    assert_eq!(update(FiatCurrency::Eur, profile.version), Ok(Ok(())));
+    assert_eq!(
+        update(FiatCurrency::Jpy, profile.version),
+        Ok(Err(UpdateFiatSettingsError::VersionMismatch))
+    );
+}
+
+#[test]
+fn test_update_user_fiat_settings_requires_a_profile() {
+    let pic_setup = setup();
+
+    let caller = Principal::from_text(CALLER).unwrap();
+
+    let update_response = pic_setup.update::<Result<(), UpdateFiatSettingsError>>(
+        caller,
+        "update_user_fiat_settings",
+        UpdateFiatSettingsRequest {
+            settings: FiatSettings::default(),
+            current_user_version: None,
+        },
+    );
+
+    assert!(update_response.is_err());
+}
@@ -1,5 +1,7 @@
+mod btc_settings;
 mod dapp_settings;
 mod experimental_features_settings;
+mod fiat_settings;
 mod networks_settings;
 mod testnets_settings;
 mod transaction_filter_settings;
@@ -1,13 +1,30 @@
-use candid::Principal;
+use std::time::Duration;
+
+use candid::{Nat, Principal};
+use pretty_assertions::assert_eq;
 use shared::types::{
-    result_types::GetUserTransactionsResult,
+    account::{Icrcv2AccountId, TokenAccountId},
+    contact::ContactAddressData,
+    result_types::{
+        ExportUserTransactionsResult, GetUserActivityResult, GetUserTransactionsResult,
+        SaveUserTransactionsResult,
+    },
     token_id::TokenId,
-    user_transaction::{GetUserTransactionsRequest, GetUserTransactionsResponse},
+    user_transaction::{
+        ExportUserTransactionsRequest, GetUserActivityRequest, GetUserTransactionsRequest,
+        GetUserTransactionsResponse, IcrcTransactionData, IcrcTransactionType,
+        IcrcTransactionTypeFilter, NetworkTransactionData, NetworkTransactionFilter,
+        SaveUserTransactionsRequest, UserTransaction, UserTransactionCursor,
+        UserTransactionExportFormat, UserTransactionFilter,
+    },
 };
 
-use crate::utils::{
-    mock::CALLER,
-    pocketic::{setup, PicCanisterTrait},
+use crate::{
+    contacts::{call_create_contact, call_update_contact},
+    utils::{
+        mock::CALLER,
+        pocketic::{setup, BackendBuilder, PicBackend, PicCanisterTrait},
+    },
 };
 
 fn eth_native_token() -> TokenId {
@@ -22,6 +39,8 @@ fn test_get_user_transactions_rejects_anonymous() {
         token_id: eth_native_token(),
         start: None,
         max_results: 10,
+        cursor: None,
+        filter: None,
     };
 
     let result = pic_setup.query::<GetUserTransactionsResult>(
@@ -52,6 +71,8 @@ fn test_get_user_transactions_returns_empty_for_new_caller() {
         token_id: eth_native_token(),
         start: None,
         max_results: 10,
+        cursor: None,
+        filter: None,
     };
 
     let result =
@@ -65,15 +86,351 @@ fn test_get_user_transactions_returns_empty_for_new_caller() {
             oldest_block_index,
             total_stored,
             next_start,
+            next_cursor,
         }) => {
             assert!(transactions.is_empty());
             assert!(newest_block_index.is_none());
             assert!(oldest_block_index.is_none());
             assert_eq!(total_stored, 0);
             assert!(next_start.is_none());
+            assert!(next_cursor.is_none());
         }
         GetUserTransactionsResult::Err(err) => {
             panic!("Expected Ok, got Err: {err:?}");
         }
     }
 }
+
+fn make_tx(id: &str, block_index: u64) -> UserTransaction {
+    UserTransaction {
+        id: id.to_string(),
+        block_index,
+        timestamp: 1_700_000_000 + block_index,
+        from: "sender".to_string(),
+        to: Some("recipient".to_string()),
+        value: Nat::from(1_000u64),
+        network_data: NetworkTransactionData::Icrc(IcrcTransactionData {
+            fee: None,
+            memo: None,
+            tx_type: IcrcTransactionType::Transfer,
+        }),
+    }
+}
+
+fn save_transactions(
+    pic_setup: &PicBackend,
+    caller: Principal,
+    token_id: TokenId,
+    transactions: Vec<UserTransaction>,
+) {
+    let result = pic_setup
+        .update::<SaveUserTransactionsResult>(
+            caller,
+            "save_user_transactions",
+            SaveUserTransactionsRequest {
+                token_id,
+                transactions,
+            },
+        )
+        .expect("Canister update failed");
+    assert_eq!(result, SaveUserTransactionsResult::Ok(()));
+}
+
+fn get_page(
+    pic_setup: &PicBackend,
+    caller: Principal,
+    cursor: Option<UserTransactionCursor>,
+    max_results: u64,
+) -> GetUserTransactionsResponse {
+    let result = pic_setup
+        .query::<GetUserTransactionsResult>(
+            caller,
+            "get_user_transactions",
+            GetUserTransactionsRequest {
+                token_id: eth_native_token(),
+                start: None,
+                max_results,
+                cursor,
+                filter: None,
+            },
+        )
+        .expect("Canister query failed");
+    match result {
+        GetUserTransactionsResult::Ok(response) => response,
+        GetUserTransactionsResult::Err(err) => panic!("Expected Ok, got Err: {err:?}"),
+    }
+}
+
+#[test]
+fn test_get_user_transactions_paginates_by_cursor() {
+    let pic_setup = setup();
+    let caller = Principal::from_text(CALLER).unwrap();
+    pic_setup.ensure_user_profile(caller);
+
+    save_transactions(
+        &pic_setup,
+        caller,
+        eth_native_token(),
+        (1..=5).map(|i| make_tx(&format!("tx-{i}"), i)).collect(),
+    );
+
+    let first = get_page(&pic_setup, caller, None, 2);
+    assert_eq!(first.total_stored, 5);
+    assert_eq!(first.newest_block_index, Some(5));
+    assert_eq!(first.oldest_block_index, Some(1));
+    let ids: Vec<&str> = first.transactions.iter().map(|tx| tx.id.as_str()).collect();
+    assert_eq!(ids, vec!["tx-5", "tx-4"]);
+
+    // A newer transaction arriving between pages must not shift the cursor.
+    save_transactions(
+        &pic_setup,
+        caller,
+        eth_native_token(),
+        vec![make_tx("tx-6", 6)],
+    );
+
+    let second = get_page(&pic_setup, caller, first.next_cursor, 2);
+    let ids: Vec<&str> = second
+        .transactions
+        .iter()
+        .map(|tx| tx.id.as_str())
+        .collect();
+    assert_eq!(ids, vec!["tx-3", "tx-2"]);
+    assert!(second.next_start.is_none());
+
+    let third = get_page(&pic_setup, caller, second.next_cursor, 2);
+    let ids: Vec<&str> = third.transactions.iter().map(|tx| tx.id.as_str()).collect();
+    assert_eq!(ids, vec!["tx-1"]);
+    assert!(third.next_cursor.is_none());
+}
+
+#[test]
+fn test_user_transactions_survive_canister_upgrade() {
+    let pic_setup = setup();
+    let caller = Principal::from_text(CALLER).unwrap();
+    pic_setup.ensure_user_profile(caller);
+
+    save_transactions(
+        &pic_setup,
+        caller,
+        eth_native_token(),
+        (1..=3).map(|i| make_tx(&format!("tx-{i}"), i)).collect(),
+    );
+
+    pic_setup.pic.advance_time(Duration::from_mins(1));
+    for _ in 0..20 {
+        pic_setup.pic.tick();
+    }
+
+    pic_setup
+        .upgrade_with_wasm(&BackendBuilder::default_wasm_path(), None)
+        .expect("canister upgrade should succeed");
+
+    let page = get_page(&pic_setup, caller, None, 10);
+    assert_eq!(page.total_stored, 3);
+    let ids: Vec<&str> = page.transactions.iter().map(|tx| tx.id.as_str()).collect();
+    assert_eq!(ids, vec!["tx-3", "tx-2", "tx-1"]);
+}
+
+#[test]
+fn test_get_user_activity_rejects_anonymous() {
+    let pic_setup = setup();
+
+    let result = pic_setup.query::<GetUserActivityResult>(
+        Principal::anonymous(),
+        "get_user_activity",
+        GetUserActivityRequest {
+            token_ids: None,
+            max_results: 10,
+            cursor: None,
+            filter: None,
+        },
+    );
+
+    assert!(result
+        .unwrap_err()
+        .contains("Anonymous caller not authorized"));
+}
+
+#[test]
+fn test_get_user_activity_merges_tokens_by_timestamp() {
+    let pic_setup = setup();
+    let caller = Principal::from_text(CALLER).unwrap();
+    pic_setup.ensure_user_profile(caller);
+
+    // `make_tx` derives the timestamp from the block index.
+    save_transactions(
+        &pic_setup,
+        caller,
+        eth_native_token(),
+        vec![make_tx("eth-1", 1), make_tx("eth-3", 3)],
+    );
+    save_transactions(
+        &pic_setup,
+        caller,
+        TokenId::IcpNative,
+        vec![make_tx("icp-2", 2), make_tx("icp-4", 4)],
+    );
+
+    let request = |cursor| GetUserActivityRequest {
+        token_ids: None,
+        max_results: 3,
+        cursor,
+        filter: None,
+    };
+    let page = |cursor| match pic_setup
+        .query::<GetUserActivityResult>(caller, "get_user_activity", request(cursor))
+        .expect("Canister query failed")
+    {
+        GetUserActivityResult::Ok(response) => response,
+        GetUserActivityResult::Err(err) => panic!("Expected Ok, got Err: {err:?}"),
+    };
+
+    let first = page(None);
+    let ids: Vec<&str> = first
+        .items
+        .iter()
+        .map(|item| item.transaction.id.as_str())
+        .collect();
+    assert_eq!(ids, vec!["icp-4", "eth-3", "icp-2"]);
+    assert_eq!(first.items[0].token_id, TokenId::IcpNative);
+
+    let second = page(first.next_cursor);
+    let ids: Vec<&str> = second
+        .items
+        .iter()
+        .map(|item| item.transaction.id.as_str())
+        .collect();
+    assert_eq!(ids, vec!["eth-1"]);
+    assert!(second.next_cursor.is_none());
+}
+
+#[test]
+fn test_get_user_transactions_applies_filter() {
+    let pic_setup = setup();
+    let caller = Principal::from_text(CALLER).unwrap();
+    pic_setup.ensure_user_profile(caller);
+
+    let mut mint = make_tx("tx-mint", 2);
+    mint.network_data = NetworkTransactionData::Icrc(IcrcTransactionData {
+        fee: None,
+        memo: None,
+        tx_type: IcrcTransactionType::Mint,
+    });
+    save_transactions(
+        &pic_setup,
+        caller,
+        eth_native_token(),
+        vec![make_tx("tx-1", 1), mint, make_tx("tx-3", 3)],
+    );
+
+    let result = pic_setup
+        .query::<GetUserTransactionsResult>(
+            caller,
+            "get_user_transactions",
+            GetUserTransactionsRequest {
+                token_id: eth_native_token(),
+                start: None,
+                max_results: 10,
+                cursor: None,
+                filter: Some(UserTransactionFilter {
+                    network: Some(NetworkTransactionFilter::Icrc {
+                        tx_type: Some(IcrcTransactionTypeFilter::Mint),
+                    }),
+                    ..UserTransactionFilter::default()
+                }),
+            },
+        )
+        .expect("Canister query failed");
+
+    match result {
+        GetUserTransactionsResult::Ok(response) => {
+            let ids: Vec<&str> = response
+                .transactions
+                .iter()
+                .map(|tx| tx.id.as_str())
+                .collect();
+            assert_eq!(ids, vec!["tx-mint"]);
+            assert_eq!(response.total_stored, 3);
+        }
+        GetUserTransactionsResult::Err(err) => panic!("Expected Ok, got Err: {err:?}"),
+    }
+}
+
+#[test]
+fn test_export_user_transactions_rejects_anonymous() {
+    let pic_setup = setup();
+
+    let result = pic_setup.query::<ExportUserTransactionsResult>(
+        Principal::anonymous(),
+        "export_user_transactions",
+        ExportUserTransactionsRequest {
+            format: UserTransactionExportFormat::Csv,
+            token_ids: None,
+            min_timestamp: None,
+            max_timestamp: None,
+            cursor: None,
+        },
+    );
+
+    assert!(result
+        .unwrap_err()
+        .contains("Anonymous caller not authorized"));
+}
+
+#[test]
+fn test_export_user_transactions_as_csv() {
+    let pic_setup = setup();
+    let caller = Principal::from_text(CALLER).unwrap();
+    pic_setup.ensure_user_profile(caller);
+
+    let friend = Principal::from_slice(&[7; 29]);
+    let mut contact = call_create_contact(&pic_setup, caller, "Friend".to_string()).unwrap();
+    contact.addresses = vec![ContactAddressData {
+        token_account_id: TokenAccountId::Icrcv2(Icrcv2AccountId::WithPrincipal {
+            owner: friend,
+            subaccount: None,
+        }),
+        label: None,
+        verified_owner: None,
+    }];
+    call_update_contact(&pic_setup, caller, contact).unwrap();
+
+    // `make_tx` derives the timestamp from the block index.
+    let received = UserTransaction {
+        from: friend.to_text(),
+        ..make_tx("icp-2", 2)
+    };
+    save_transactions(
+        &pic_setup,
+        caller,
+        TokenId::IcpNative,
+        vec![make_tx("icp-1", 1), received, make_tx("icp-3", 3)],
+    );
+
+    let result = pic_setup
+        .query::<ExportUserTransactionsResult>(
+            caller,
+            "export_user_transactions",
+            ExportUserTransactionsRequest {
+                format: UserTransactionExportFormat::Csv,
+                token_ids: None,
+                min_timestamp: Some(1_700_000_001),
+                max_timestamp: Some(1_700_000_002),
+                cursor: None,
+            },
+        )
+        .expect("Canister query failed");
+    let ExportUserTransactionsResult::Ok(response) = result else {
+        panic!("Expected Ok, got {result:?}");
+    };
+
+    let lines: Vec<&str> = response.content.lines().collect();
+    assert_eq!(response.row_count, 2);
+    assert!(response.next_cursor.is_none());
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("date,timestamp,token,id,"));
+    assert!(lines[1].starts_with("2023-11-14T22:13:22Z,1700000002,IcpNative,icp-2,"));
+    assert!(lines[1].contains(&format!(",{},Friend,", friend.to_text())));
+    assert!(lines[2].starts_with("2023-11-14T22:13:21Z,1700000001,IcpNative,icp-1,"));
+}
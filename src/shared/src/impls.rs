@@ -10,15 +10,20 @@ use crate::{
             Agreements, ProviderAgreementType, UpdateAgreementsError, UserAgreement, UserAgreements,
         },
         backend_config::{Config, InitArg},
+        bitcoin::{BtcAddressType, BtcSettings, UpdateBtcSettingsError},
         contact::{
-            Contact, ContactAddressData, ContactImage, CreateContactRequest, UpdateContactRequest,
+            BatchContactsRequest, Contact, ContactAddressData, ContactImage, ContactOperation,
+            CreateContactRequest, CreateVerifiedContactRequest, ImportContactsRequest,
+            UpdateContactRequest, MAX_CONTACT_IMPORT_BYTES, MAX_CONTACT_OPERATIONS_PER_BATCH,
         },
         custom_token::{
             CustomToken, CustomTokenId, Dip721Token, ErcToken, ErcTokenId, ExtV2Token,
             IcPunksToken, Icrc7Token, IcrcToken, SplToken, SplTokenId, Token,
         },
         dapp::{AddDappSettingsError, DappCarouselSettings, DappSettings, MAX_DAPP_ID_LIST_LENGTH},
-        exchange::{ExchangeData, ExchangeRate},
+        exchange::{
+            ExchangeData, ExchangeRate, FiatCurrency, FiatSettings, UpdateFiatSettingsError,
+        },
         experimental_feature::{
             ExperimentalFeatureSettingsMap, ExperimentalFeaturesSettings,
             UpdateExperimentalFeaturesSettingsError,
@@ -31,12 +36,14 @@ use crate::{
             AddDismissedNotificationError, DismissedNotification, NotificationSettings,
             MAX_DISMISSED_NOTIFICATIONS_LIST_LENGTH,
         },
+        portfolio::{PortfolioSettings, UpdatePortfolioSettingsError},
         settings::Settings,
         token::{UserToken, EVM_CONTRACT_ADDRESS_LENGTH},
         transaction_settings::{
             TransactionFilterSettings, TransactionSettings, UpdateTransactionFilterSettingsError,
         },
         user_profile::{OisyUser, StoredUserProfile, UserProfile},
+        user_transaction::{SaveUserTransactionsRequest, RESERVED_BLOCK_INDEX, RESERVED_TIMESTAMP},
         Timestamp, TokenVersion, Version, MAX_SYMBOL_LENGTH,
     },
     validate::{validate_on_deserialize, Validate},
@@ -46,6 +53,7 @@ use crate::{
 const CONTACT_MAX_NAME_LENGTH: usize = 100;
 const CONTACT_MAX_ADDRESSES: usize = 40;
 const CONTACT_MAX_LABEL_LENGTH: usize = 50;
+const EXCHANGE_DATA_SOURCE_MAX_LENGTH: usize = 32;
 /// Maximum image size in bytes (100 KB)
 pub const MAX_IMAGE_SIZE_BYTES: usize = 100 * 1024;
 
@@ -243,6 +251,9 @@ impl StoredUserProfile {
             transactions: Some(TransactionSettings {
                 filter: Some(TransactionFilterSettings::default()),
             }),
+            portfolio: None,
+            fiat: None,
+            btc: None,
         };
         let agreements = Agreements::default();
         StoredUserProfile {
@@ -601,6 +612,125 @@ impl StoredUserProfile {
         new_profile.updated_timestamp = now;
         Ok(new_profile)
     }
+
+    /// Returns a copy with the portfolio settings updated.
+    ///
+    /// # Errors
+    ///
+    /// Will return Err if there is a version mismatch.
+    pub fn with_portfolio_settings(
+        &self,
+        profile_version: Option<Version>,
+        now: Timestamp,
+        portfolio: PortfolioSettings,
+    ) -> Result<StoredUserProfile, UpdatePortfolioSettingsError> {
+        if profile_version != self.version {
+            return Err(UpdatePortfolioSettingsError::VersionMismatch);
+        }
+
+        let settings = self.settings.clone().unwrap_or_default();
+        if settings.portfolio.unwrap_or_default() == portfolio {
+            return Ok(self.clone());
+        }
+
+        let mut new_profile = self.with_incremented_version();
+        new_profile.settings = {
+            let mut settings = new_profile.settings.unwrap_or_default();
+            settings.portfolio = Some(portfolio);
+            Some(settings)
+        };
+        new_profile.updated_timestamp = now;
+        Ok(new_profile)
+    }
+
+    /// Returns a copy with the fiat currency settings updated.
+    ///
+    /// # Errors
+    ///
+    /// Will return Err if there is a version mismatch.
+    pub fn with_fiat_settings(
+        &self,
+        profile_version: Option<Version>,
+        now: Timestamp,
+        fiat: FiatSettings,
+    ) -> Result<StoredUserProfile, UpdateFiatSettingsError> {
+        if profile_version != self.version {
+            return Err(UpdateFiatSettingsError::VersionMismatch);
+        }
+
+        let settings = self.settings.clone().unwrap_or_default();
+        if settings.fiat.unwrap_or_default() == fiat {
+            return Ok(self.clone());
+        }
+
+        let mut new_profile = self.with_incremented_version();
+        new_profile.settings = {
+            let mut settings = new_profile.settings.unwrap_or_default();
+            settings.fiat = Some(fiat);
+            Some(settings)
+        };
+        new_profile.updated_timestamp = now;
+        Ok(new_profile)
+    }
+
+    /// The user's preferred fiat currency, USD unless set otherwise.
+    #[must_use]
+    pub fn preferred_fiat_currency(&self) -> FiatCurrency {
+        self.settings
+            .as_ref()
+            .and_then(|settings| settings.fiat.as_ref())
+            .map(|fiat| fiat.currency)
+            .unwrap_or_default()
+    }
+
+    /// Returns a copy with the BTC settings updated.
+    ///
+    /// # Errors
+    ///
+    /// Will return Err if there is a version mismatch.
+    pub fn with_btc_settings(
+        &self,
+        profile_version: Option<Version>,
+        now: Timestamp,
+        btc: BtcSettings,
+    ) -> Result<StoredUserProfile, UpdateBtcSettingsError> {
+        if profile_version != self.version {
+            return Err(UpdateBtcSettingsError::VersionMismatch);
+        }
+
+        let settings = self.settings.clone().unwrap_or_default();
+        if settings.btc.unwrap_or_default() == btc {
+            return Ok(self.clone());
+        }
+
+        let mut new_profile = self.with_incremented_version();
+        new_profile.settings = {
+            let mut settings = new_profile.settings.unwrap_or_default();
+            settings.btc = Some(btc);
+            Some(settings)
+        };
+        new_profile.updated_timestamp = now;
+        Ok(new_profile)
+    }
+
+    /// The user's preferred BTC address type, P2WPKH unless set otherwise.
+    #[must_use]
+    pub fn preferred_btc_address_type(&self) -> BtcAddressType {
+        self.settings
+            .as_ref()
+            .and_then(|settings| settings.btc.as_ref())
+            .map(|btc| btc.address_type)
+            .unwrap_or_default()
+    }
+
+    /// Whether the user has opted in to portfolio snapshots.
+    #[must_use]
+    pub fn portfolio_snapshots_enabled(&self) -> bool {
+        self.settings
+            .as_ref()
+            .and_then(|settings| settings.portfolio.as_ref())
+            .is_some_and(|portfolio| portfolio.snapshots_enabled)
+    }
 }
 
 impl From<&StoredUserProfile> for UserProfile {
@@ -899,6 +1029,81 @@ impl Validate for CreateContactRequest {
             "CreateContactRequest.name",
         )?;
 
+        // Validate that the number of addresses is not greater than the max allowed
+        if let Some(addresses) = &self.addresses {
+            validate_collection_size(
+                addresses,
+                CONTACT_MAX_ADDRESSES,
+                "CreateContactRequest.addresses",
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Validate for CreateVerifiedContactRequest {
+    fn validate(&self) -> Result<(), Error> {
+        validate_string_length(
+            &self.name,
+            CONTACT_MAX_NAME_LENGTH,
+            "CreateVerifiedContactRequest.name",
+        )?;
+        validate_string_whitespace_padding(&self.name, "CreateVerifiedContactRequest.name")?;
+
+        if self.principal == Principal::anonymous() {
+            return Err(Error::msg(
+                "CreateVerifiedContactRequest.principal must not be anonymous",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl Validate for ImportContactsRequest {
+    fn validate(&self) -> Result<(), Error> {
+        if self.content.len() > MAX_CONTACT_IMPORT_BYTES {
+            return Err(Error::msg(format!(
+                "ImportContactsRequest.content exceeds max size of {MAX_CONTACT_IMPORT_BYTES} bytes"
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Validate for SaveUserTransactionsRequest {
+    fn validate(&self) -> Result<(), Error> {
+        for (index, transaction) in self.transactions.iter().enumerate() {
+            if transaction.block_index == RESERVED_BLOCK_INDEX {
+                return Err(Error::msg(format!(
+                    "SaveUserTransactionsRequest.transactions[{index}].block_index is reserved"
+                )));
+            }
+            if transaction.timestamp == RESERVED_TIMESTAMP {
+                return Err(Error::msg(format!(
+                    "SaveUserTransactionsRequest.transactions[{index}].timestamp is reserved"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Validate for BatchContactsRequest {
+    fn validate(&self) -> Result<(), Error> {
+        validate_collection_size(
+            &self.operations,
+            MAX_CONTACT_OPERATIONS_PER_BATCH,
+            "BatchContactsRequest.operations",
+        )?;
+        for operation in &self.operations {
+            match operation {
+                ContactOperation::Create(request) => request.validate()?,
+                ContactOperation::Update(request) => request.validate()?,
+                ContactOperation::Delete { .. } => {}
+            }
+        }
         Ok(())
     }
 }
@@ -943,13 +1148,69 @@ impl Validate for ExchangeData {
         if let Some(market_cap) = self.market_cap {
             validate_non_negative_float(market_cap, "market_cap")?;
         }
+        if let Some(source) = &self.source {
+            validate_string_length(source, EXCHANGE_DATA_SOURCE_MAX_LENGTH, "source")?;
+        }
         Ok(())
     }
 }
 
 impl Validate for ExchangeRate {
     fn validate(&self) -> Result<(), Error> {
-        self.usd.validate()
+        self.usd.validate()?;
+        for fiat_price in self.fiat.iter().flatten() {
+            validate_non_negative_float(fiat_price.price, "fiat price")?;
+        }
+        Ok(())
+    }
+}
+
+impl FiatCurrency {
+    /// Every supported currency, USD first.
+    pub const ALL: [FiatCurrency; 18] = [
+        FiatCurrency::Usd,
+        FiatCurrency::Aed,
+        FiatCurrency::Brl,
+        FiatCurrency::Cad,
+        FiatCurrency::Chf,
+        FiatCurrency::Cny,
+        FiatCurrency::Czk,
+        FiatCurrency::Eur,
+        FiatCurrency::Gbp,
+        FiatCurrency::Inr,
+        FiatCurrency::Jpy,
+        FiatCurrency::Krw,
+        FiatCurrency::Ngn,
+        FiatCurrency::Pln,
+        FiatCurrency::Rub,
+        FiatCurrency::Sar,
+        FiatCurrency::Sgd,
+        FiatCurrency::Vnd,
+    ];
+
+    /// Lowercase ISO 4217 code, as used by `CoinGecko`'s `vs_currencies`.
+    #[must_use]
+    pub fn code(self) -> &'static str {
+        match self {
+            FiatCurrency::Usd => "usd",
+            FiatCurrency::Aed => "aed",
+            FiatCurrency::Brl => "brl",
+            FiatCurrency::Cad => "cad",
+            FiatCurrency::Chf => "chf",
+            FiatCurrency::Cny => "cny",
+            FiatCurrency::Czk => "czk",
+            FiatCurrency::Eur => "eur",
+            FiatCurrency::Gbp => "gbp",
+            FiatCurrency::Inr => "inr",
+            FiatCurrency::Jpy => "jpy",
+            FiatCurrency::Krw => "krw",
+            FiatCurrency::Ngn => "ngn",
+            FiatCurrency::Pln => "pln",
+            FiatCurrency::Rub => "rub",
+            FiatCurrency::Sar => "sar",
+            FiatCurrency::Sgd => "sgd",
+            FiatCurrency::Vnd => "vnd",
+        }
     }
 }
 
@@ -1267,6 +1528,10 @@ mod tests {
 validate_on_deserialize!(Contact);
 validate_on_deserialize!(ContactAddressData);
 validate_on_deserialize!(CreateContactRequest);
+validate_on_deserialize!(CreateVerifiedContactRequest);
+validate_on_deserialize!(ImportContactsRequest);
+validate_on_deserialize!(BatchContactsRequest);
+validate_on_deserialize!(SaveUserTransactionsRequest);
 validate_on_deserialize!(UpdateContactRequest);
 validate_on_deserialize!(ContactImage);
 validate_on_deserialize!(CustomToken);
@@ -17,13 +17,21 @@ const GIBIBYTE: u32 = 1 << 30;
 /// Returns the metrics in the Prometheus format.
 #[must_use]
 pub fn get_metrics() -> HttpResponse {
+    get_metrics_with(|_| Ok(()))
+}
+
+/// Returns the metrics in the Prometheus format, followed by the canister-specific metrics written
+/// by `encode_extra`.
+pub fn get_metrics_with(
+    encode_extra: impl FnOnce(&mut MetricsEncoder<Vec<u8>>) -> std::io::Result<()>,
+) -> HttpResponse {
     let now = ic_cdk::api::time();
     let mut writer = MetricsEncoder::new(
         vec![],
         i64::try_from(now / 1_000_000)
             .unwrap_or_else(|_| unreachable!("u64::MAX / 1_000_000 is smaller than i64::MAX")),
     );
-    match encode_metrics(&mut writer) {
+    match encode_metrics(&mut writer).and_then(|()| encode_extra(&mut writer)) {
         Ok(()) => {
             let body = writer.into_inner();
             HttpResponse {
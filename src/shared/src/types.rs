@@ -10,6 +10,7 @@ pub mod agreement;
 pub mod api_keys;
 pub mod backend_config;
 pub mod bitcoin;
+pub mod certification;
 pub mod contact;
 pub mod custom_token;
 pub mod dapp;
@@ -22,7 +23,9 @@ pub mod number;
 pub mod onramper;
 pub mod personal_note;
 pub mod personal_note_share;
+pub mod portfolio;
 pub mod pow;
+pub mod price_alert;
 pub mod result_types;
 pub mod settings;
 pub mod signer;
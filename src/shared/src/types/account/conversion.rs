@@ -1,5 +1,5 @@
 //! Conversion functions for account identifiers.
-use std::str::FromStr;
+use std::{fmt, str::FromStr};
 
 use candid::{CandidType, Deserialize, Principal};
 use serde::Serialize;
@@ -59,11 +59,106 @@ impl From<Icrcv2AccountId> for TokenAccountId {
     }
 }
 
+impl fmt::Display for TokenAccountId {
+    /// The textual form parsed by [`TokenAccountId::from_str`].
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenAccountId::Icrcv2(account) => account.fmt(f),
+            TokenAccountId::Sol(SolPrincipal(address))
+            | TokenAccountId::Eth(EthAddress::Public(address))
+            | TokenAccountId::Btc(
+                BtcAddress::P2PKH(address)
+                | BtcAddress::P2SH(address)
+                | BtcAddress::P2WPKH(address)
+                | BtcAddress::P2WSH(address)
+                | BtcAddress::P2TR(address),
+            ) => f.write_str(address),
+        }
+    }
+}
+
+impl fmt::Display for Icrcv2AccountId {
+    /// A principal, the ICRC-1 textual encoding of an account with a subaccount, or the hex of a
+    /// redacted account identifier.
+    ///
+    /// See <https://github.com/dfinity/ICRC-1/blob/main/standards/ICRC-1/TextualEncoding.md>.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Icrcv2AccountId::WithPrincipal {
+                owner,
+                subaccount: Some(subaccount),
+            } if subaccount.0 != [0; 32] => {
+                let subaccount_hex = hex::encode(subaccount.0);
+                write!(
+                    f,
+                    "{owner}-{}.{}",
+                    icrc1_account_checksum(owner, subaccount),
+                    subaccount_hex.trim_start_matches('0')
+                )
+            }
+            Icrcv2AccountId::WithPrincipal { owner, .. } => write!(f, "{owner}"),
+            Icrcv2AccountId::Account(id) => f.write_str(&hex::encode(id.0)),
+        }
+    }
+}
+
+/// The checksum of an ICRC-1 textual account: the unpadded lowercase base32 of the big-endian
+/// CRC-32 of the owner's bytes followed by the subaccount.
+fn icrc1_account_checksum(owner: &Principal, subaccount: &IcrcSubaccountId) -> String {
+    const ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+    let checksum = u64::from(crc32([owner.as_slice(), &subaccount.0].concat().as_slice()));
+    // 32 bits are 7 base32 digits, the last one padded with 3 zero bits.
+    let bits = checksum << 3;
+    (0..7)
+        .rev()
+        .map(|digit| char::from(ALPHABET[((bits >> (digit * 5)) & 0x1f) as usize]))
+        .collect()
+}
+
+/// CRC-32 (IEEE 802.3), as used by ICRC-1 textual accounts.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
 impl FromStr for Icrcv2AccountId {
     type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.contains('-') {
+        if let Some((owner_and_checksum, subaccount_hex)) = s.split_once('.') {
+            let (owner, checksum) = owner_and_checksum
+                .rsplit_once('-')
+                .ok_or(ParseError::InvalidEncoding)?;
+            let owner = Principal::from_text(owner).map_err(|_| ParseError::InvalidEncoding)?;
+            if subaccount_hex.is_empty()
+                || subaccount_hex.len() > 64
+                || subaccount_hex.starts_with('0')
+            {
+                return Err(ParseError::InvalidEncoding);
+            }
+            let subaccount = IcrcSubaccountId::from_str(&format!("{subaccount_hex:0>64}"))?;
+            if subaccount.0 == [0; 32] {
+                return Err(ParseError::InvalidEncoding);
+            }
+            if checksum != icrc1_account_checksum(&owner, &subaccount) {
+                return Err(ParseError::InvalidChecksum);
+            }
+            Ok(Icrcv2AccountId::WithPrincipal {
+                owner,
+                subaccount: Some(subaccount),
+            })
+        } else if s.contains('-') {
             Ok(Icrcv2AccountId::WithPrincipal {
                 owner: Principal::from_text(s).map_err(|_| ParseError::InvalidEncoding)?,
                 subaccount: None,
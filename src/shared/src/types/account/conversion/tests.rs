@@ -71,6 +71,42 @@ fn icrc2_test_vectors() -> Vec<TestVector<Icrcv2AccountId>> {
                 .expect("Test setup err: Failed to parse subaccount ID"),
             )),
         },
+        TestVector {
+            name: "ICRC: Principal with subaccount",
+            input: "k2t6j-2nvnp-4zjm3-25dtz-6xhaa-c7boj-5gayf-oj3xs-i43lp-teztq-6ae-dfxgiyy.102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f20",
+            expected: Ok(Icrcv2AccountId::WithPrincipal {
+                owner: Principal::from_text(
+                    "k2t6j-2nvnp-4zjm3-25dtz-6xhaa-c7boj-5gayf-oj3xs-i43lp-teztq-6ae",
+                )
+                .unwrap(),
+                subaccount: Some(IcrcSubaccountId(std::array::from_fn(|i| {
+                    u8::try_from(i + 1).unwrap()
+                }))),
+            }),
+        },
+        TestVector {
+            name: "ICRC: Principal with short subaccount",
+            input: "k2t6j-2nvnp-4zjm3-25dtz-6xhaa-c7boj-5gayf-oj3xs-i43lp-teztq-6ae-6cc627i.1",
+            expected: Ok(Icrcv2AccountId::WithPrincipal {
+                owner: Principal::from_text(
+                    "k2t6j-2nvnp-4zjm3-25dtz-6xhaa-c7boj-5gayf-oj3xs-i43lp-teztq-6ae",
+                )
+                .unwrap(),
+                subaccount: Some(IcrcSubaccountId(std::array::from_fn(|i| {
+                    u8::from(i == 31)
+                }))),
+            }),
+        },
+        TestVector {
+            name: "ICRC: Principal with subaccount and wrong checksum",
+            input: "k2t6j-2nvnp-4zjm3-25dtz-6xhaa-c7boj-5gayf-oj3xs-i43lp-teztq-6ae-6cc627j.1",
+            expected: Err(ParseError::InvalidChecksum),
+        },
+        TestVector {
+            name: "ICRC: Principal with non-canonical subaccount",
+            input: "k2t6j-2nvnp-4zjm3-25dtz-6xhaa-c7boj-5gayf-oj3xs-i43lp-teztq-6ae-6cc627i.01",
+            expected: Err(ParseError::InvalidEncoding),
+        },
         TestVector {
             name: "ICRC: Invalid principal",
             input: "invalid",
@@ -305,3 +341,12 @@ fn all_test_vectors_can_be_parsed() {
         assert_eq!(vector.expected, vector.input.parse(), "{}", vector.name);
     }
 }
+
+#[test]
+fn all_test_vectors_round_trip_through_display() {
+    for vector in all_test_vectors() {
+        if let Ok(account) = vector.expected {
+            assert_eq!(account.to_string(), vector.input, "{}", vector.name);
+        }
+    }
+}
@@ -16,6 +16,11 @@ pub struct ApiKeys {
     /// issues the request) or *non-replicated* (a single replica). Replicated only when explicitly
     /// `Some(true)`; `None` (the default) and `Some(false)` both mean non-replicated.
     pub exchange_rate_replicated: Option<bool>,
+    /// Opts exchange-rate refresh into median aggregation. When set, every provider is asked for
+    /// every token, the USD price is the median of their quotes, and quotes deviating from that
+    /// median by more than this many basis points are rejected as outliers. `None` (the default)
+    /// keeps the first valid price in provider priority order.
+    pub exchange_rate_max_deviation_bps: Option<u32>,
     /// HMAC-SHA256 secret used to sign `OnRamper` widget URLs. Provided by `OnRamper` support and
     /// provisioned/rotated via the dedicated `set_onramper_signing_secret` endpoint (which
     /// preserves the other keys). When `None`, the signing endpoint reports the secret as
@@ -34,6 +39,10 @@ impl Debug for ApiKeys {
             .field("coingecko_api_key", &redact(&self.coingecko_api_key))
             .field("exchange_rate_enabled", &self.exchange_rate_enabled)
             .field("exchange_rate_replicated", &self.exchange_rate_replicated)
+            .field(
+                "exchange_rate_max_deviation_bps",
+                &self.exchange_rate_max_deviation_bps,
+            )
             .field(
                 "onramper_signing_secret",
                 &redact(&self.onramper_signing_secret),
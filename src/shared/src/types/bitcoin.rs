@@ -7,7 +7,7 @@ use ic_cdk::bitcoin_canister::{MillisatoshiPerByte, Network as BitcoinNetwork, U
 use serde::Deserialize;
 
 use super::delegation::IIDelegationChain;
-use crate::types::signer::RateLimitError;
+use crate::types::{signer::RateLimitError, Version};
 
 /// The maximum length of a single `txid`:
 ///
@@ -21,6 +21,37 @@ pub const MAX_TXID_BYTES: usize = 32;
 /// - Consolidation transactions typically take many more, however that doesn't apply to this API.
 pub const MAX_UTXOS_LEN: usize = 128;
 
+/// The type of a BTC address derived for the user by the chain-fusion signer.
+#[derive(CandidType, Deserialize, Clone, Copy, Eq, PartialEq, Hash, Debug, Default)]
+pub enum BtcAddressType {
+    /// Native `SegWit` (BIP-84), derived from the signer's ECDSA key.
+    #[default]
+    P2wpkh,
+    /// `SegWit` nested in P2SH (BIP-49), derived from the signer's ECDSA key.
+    P2shP2wpkh,
+    /// Taproot key path (BIP-86), derived from the signer's Schnorr key.
+    P2tr,
+}
+
+/// The user's preferred BTC address type, used by the BTC endpoints when a request does not name
+/// one.
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq, Default)]
+pub struct BtcSettings {
+    pub address_type: BtcAddressType,
+}
+
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub enum UpdateBtcSettingsError {
+    UserNotFound,
+    VersionMismatch,
+}
+
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub struct UpdateBtcSettingsRequest {
+    pub settings: BtcSettings,
+    pub current_user_version: Option<Version>,
+}
+
 /// Delay before the first async fee update, giving the canister time to settle after
 /// `init` or `post_upgrade` (stable memory deserialization uses heap).
 pub const FEE_PERCENTILES_INITIAL_DELAY: Duration = Duration::from_secs(10);
@@ -33,6 +64,15 @@ pub const FEE_PERCENTILES_UPDATE_INTERVAL: Duration = Duration::from_mins(1);
 pub const FEE_UPDATE_TIMEOUT_NS: u64 =
     5 * FEE_PERCENTILES_UPDATE_INTERVAL.as_secs() * 1_000_000_000;
 
+/// Timer interval for checking the confirmation status of pending transactions (10 minutes, the
+/// expected block interval)
+pub const PENDING_TRANSACTIONS_CHECK_INTERVAL: Duration = Duration::from_mins(10);
+
+/// Safety timeout: if a check has been "in progress" for longer than this,
+/// assume it was lost to a trap and allow a new one. Set to 3× the check interval.
+pub const PENDING_TRANSACTIONS_CHECK_TIMEOUT_NS: u64 =
+    3 * PENDING_TRANSACTIONS_CHECK_INTERVAL.as_secs() * 1_000_000_000;
+
 #[derive(CandidType, Deserialize, Clone, Copy, Eq, PartialEq, Debug)]
 pub struct BtcGetFeePercentilesRequest {
     pub network: BitcoinNetwork,
@@ -54,6 +94,8 @@ pub struct BtcAddPendingTransactionRequest {
     pub txid: Vec<u8>,
     pub utxos: Vec<Utxo>,
     pub network: BitcoinNetwork,
+    /// The type of the caller's address. Defaults to the caller's preferred address type.
+    pub address_type: Option<BtcAddressType>,
     pub ii_delegation_chain: Option<IIDelegationChain>,
 }
 
@@ -75,9 +117,136 @@ pub enum BtcAddPendingTransactionError {
     InvalidDelegationChain { msg: String },
 }
 
+/// Maximum length, in bytes, of the recipient address of a built transaction.
+pub const MAX_BTC_ADDRESS_BYTES: usize = 100;
+
+/// The strategy choosing the UTXOs a built transaction spends.
+#[derive(CandidType, Deserialize, Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum BtcCoinSelection {
+    /// Spends the largest UTXOs first, so that the transaction has as few inputs as possible.
+    LargestFirst,
+    /// Looks for UTXOs matching the amount and the fee closely enough to need no change output,
+    /// falling back to `LargestFirst`.
+    #[default]
+    BranchAndBound,
+    /// Avoids spending, in the same transaction, coins received in different transactions.
+    PrivacyPreserving,
+    /// Also spends small UTXOs while fees are low, to reduce the fees of later transactions.
+    Consolidate,
+}
+
+/// Builds a transaction from the caller's address and reserves its UTXOs.
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+#[serde(remote = "Self")]
+pub struct BtcBuildTransactionRequest {
+    pub network: BitcoinNetwork,
+    /// The recipient's address, on `network`.
+    pub recipient: String,
+    pub amount_satoshis: u64,
+    /// The percentile, from 0 to 99, of the recent fee rates returned by
+    /// `btc_get_current_fee_percentiles` to pay.
+    pub fee_percentile: u8,
+    /// Defaults to `BranchAndBound`.
+    pub coin_selection: Option<BtcCoinSelection>,
+    /// The type of the caller's address. Defaults to the caller's preferred address type.
+    pub address_type: Option<BtcAddressType>,
+    pub ii_delegation_chain: Option<IIDelegationChain>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub struct BtcBuildTransactionResponse {
+    /// The unsigned transaction as a BIP-174 PSBT, with the spent outputs of its inputs.
+    pub psbt: Vec<u8>,
+    /// The id of the transaction, in the byte order of `Utxo` outpoints. Signing a `SegWit`
+    /// transaction does not change it.
+    pub txid: Vec<u8>,
+    /// The UTXOs the transaction spends, reserved as a pending transaction with `txid`.
+    pub utxos: Vec<Utxo>,
+    pub fee_satoshis: u64,
+    /// The change sent back to the caller's address, `0` if the transaction has no change output.
+    pub change_satoshis: u64,
+    /// The virtual size the fee was computed for, assuming signatures of maximal length.
+    pub vsize: u64,
+}
+
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub enum BtcBuildTransactionError {
+    /// The recipient is not a valid address on the requested network.
+    InvalidRecipient,
+    /// The amount is below the dust limit of the recipient's output.
+    AmountTooLow { min_satoshis: u64 },
+    /// The caller's unreserved UTXOs do not cover the amount and the fee.
+    InsufficientFunds { available_satoshis: u64 },
+    /// Server-side / unexpected
+    InternalError { msg: String },
+    /// The caller has exceeded the call rate limit.
+    RateLimited(RateLimitError),
+    /// The provided II delegation chain is missing or failed verification.
+    InvalidDelegationChain { msg: String },
+}
+
+#[derive(CandidType, Deserialize, Clone, Copy, Eq, PartialEq, Debug)]
+pub enum BtcFeeBumpMethod {
+    /// Replaces the transaction with one spending the same UTXOs with a higher fee (BIP-125).
+    ReplaceByFee,
+    /// Spends the change output of the transaction in a child paying the fee of both.
+    ChildPaysForParent,
+}
+
+/// Bumps the fee of a pending transaction built by `btc_build_transaction`.
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+#[serde(remote = "Self")]
+pub struct BtcBumpFeeRequest {
+    pub network: BitcoinNetwork,
+    /// The id of the pending transaction, as returned by `btc_build_transaction`.
+    pub txid: Vec<u8>,
+    pub method: BtcFeeBumpMethod,
+    /// The percentile, from 0 to 99, of the recent fee rates to pay. For CPFP this is the fee
+    /// rate of the parent and child together.
+    pub fee_percentile: u8,
+    /// The type of the caller's address. Defaults to the caller's preferred address type.
+    pub address_type: Option<BtcAddressType>,
+    pub ii_delegation_chain: Option<IIDelegationChain>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub struct BtcBumpFeeResponse {
+    /// The unsigned replacement or child transaction as a BIP-174 PSBT.
+    pub psbt: Vec<u8>,
+    /// The id of the replacement or child transaction, in the byte order of `Utxo` outpoints.
+    pub txid: Vec<u8>,
+    /// The fee of the new transaction alone.
+    pub fee_satoshis: u64,
+    pub vsize: u64,
+}
+
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub enum BtcBumpFeeError {
+    /// The caller has no pending transaction with this txid.
+    PendingTransactionNotFound,
+    /// The pending transaction was not built by `btc_build_transaction`.
+    NotBumpable,
+    /// The transaction is already confirmed or has been dropped.
+    NotPending,
+    /// CPFP was requested for a transaction without a change output.
+    NoChangeOutput,
+    /// The transaction has already been replaced `MAX_REPLACED_TXIDS` times.
+    TooManyReplacements,
+    /// The spent UTXOs, or the change output for CPFP, do not cover the higher fee.
+    InsufficientFunds { available_satoshis: u64 },
+    /// Server-side / unexpected
+    InternalError { msg: String },
+    /// The caller has exceeded the call rate limit.
+    RateLimited(RateLimitError),
+    /// The provided II delegation chain is missing or failed verification.
+    InvalidDelegationChain { msg: String },
+}
+
 #[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
 pub struct BtcGetPendingTransactionsRequest {
     pub network: BitcoinNetwork,
+    /// The type of the caller's address. Defaults to the caller's preferred address type.
+    pub address_type: Option<BtcAddressType>,
     pub ii_delegation_chain: Option<IIDelegationChain>,
 }
 
@@ -86,6 +255,23 @@ pub struct BtcGetPendingTransactionsRequest {
 pub struct PendingTransaction {
     pub txid: Vec<u8>,
     pub utxos: Vec<Utxo>,
+    pub status: PendingTransactionStatus,
+    /// The number of blocks on top of and including the one the transaction was mined in, `0`
+    /// while it is not `Confirmed`.
+    pub confirmations: u32,
+}
+
+/// The confirmation status of a pending transaction, as observed in the UTXO set of its address.
+#[derive(CandidType, Deserialize, Clone, Copy, Eq, PartialEq, Debug)]
+pub enum PendingTransactionStatus {
+    /// Not mined yet. Its UTXOs stay reserved.
+    Pending,
+    /// Mined in the block at `height`. If the transaction has no output to the address, this is
+    /// the height at which its UTXOs were first seen spent, which may be later.
+    Confirmed { height: u32 },
+    /// Conflicts with a mined transaction, or was not mined in time. Its UTXOs are no longer
+    /// reserved.
+    Dropped { timestamp_ns: u64 },
 }
 
 #[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
@@ -94,6 +280,36 @@ pub struct StoredPendingTransaction {
     pub txid: Vec<u8>,
     pub utxos: Vec<Utxo>,
     pub created_at_timestamp_ns: u64,
+    /// Set for transactions built by `btc_build_transaction`, whose fee can be bumped.
+    pub details: Option<StoredTransactionDetails>,
+    /// `None` for transactions stored before their status was tracked, which are `Pending`.
+    pub status: Option<PendingTransactionStatus>,
+}
+
+/// Maximum number of RBF replacements tracked for a pending transaction.
+pub const MAX_REPLACED_TXIDS: usize = 16;
+
+/// The outputs and fee of a pending transaction built by the backend.
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub struct StoredTransactionDetails {
+    pub recipient: String,
+    pub amount_satoshis: u64,
+    pub fee_satoshis: u64,
+    /// The value of the second output, paying back to the source address, or `0` if the
+    /// transaction has no change output.
+    pub change_satoshis: u64,
+    pub vsize: u64,
+    /// The txids of the transactions this one replaced through RBF, oldest first.
+    pub replaced_txids: Vec<Vec<u8>>,
+    /// The latest child spending the change output to bump the fee through CPFP.
+    pub cpfp_child: Option<StoredCpfpChild>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub struct StoredCpfpChild {
+    pub txid: Vec<u8>,
+    pub fee_satoshis: u64,
+    pub vsize: u64,
 }
 
 #[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
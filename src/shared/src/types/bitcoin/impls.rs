@@ -5,10 +5,14 @@ use ic_cdk::bitcoin_canister::Utxo;
 use serde::{de, Deserializer};
 
 use super::{
-    BtcAddPendingTransactionRequest, PendingTransaction, StoredPendingTransaction, MAX_TXID_BYTES,
-    MAX_UTXOS_LEN,
+    BtcAddPendingTransactionRequest, BtcAddressType, BtcBuildTransactionRequest, BtcBumpFeeRequest,
+    PendingTransaction, StoredPendingTransaction, StoredTransactionDetails, MAX_BTC_ADDRESS_BYTES,
+    MAX_REPLACED_TXIDS, MAX_TXID_BYTES, MAX_UTXOS_LEN,
+};
+use crate::{
+    types::account::BtcAddress,
+    validate::{validate_on_deserialize, Validate},
 };
-use crate::validate::{validate_on_deserialize, Validate};
 
 fn validate_utxo(utxo: &Utxo) -> Result<(), candid::Error> {
     let len = utxo.outpoint.txid.len();
@@ -49,6 +53,39 @@ impl Validate for BtcAddPendingTransactionRequest {
 }
 validate_on_deserialize!(BtcAddPendingTransactionRequest);
 
+impl Validate for BtcBuildTransactionRequest {
+    fn validate(&self) -> Result<(), candid::Error> {
+        if self.recipient.len() > MAX_BTC_ADDRESS_BYTES {
+            return Err(candid::Error::msg(format!(
+                "Recipient address has too many bytes: {} > {MAX_BTC_ADDRESS_BYTES}",
+                self.recipient.len()
+            )));
+        }
+        if self.fee_percentile > 99 {
+            return Err(candid::Error::msg(format!(
+                "Fee percentile must be between 0 and 99, got {}",
+                self.fee_percentile
+            )));
+        }
+        Ok(())
+    }
+}
+validate_on_deserialize!(BtcBuildTransactionRequest);
+
+impl Validate for BtcBumpFeeRequest {
+    fn validate(&self) -> Result<(), candid::Error> {
+        validate_txid_bytes(&self.txid)?;
+        if self.fee_percentile > 99 {
+            return Err(candid::Error::msg(format!(
+                "Fee percentile must be between 0 and 99, got {}",
+                self.fee_percentile
+            )));
+        }
+        Ok(())
+    }
+}
+validate_on_deserialize!(BtcBumpFeeRequest);
+
 impl Validate for PendingTransaction {
     fn validate(&self) -> Result<(), candid::Error> {
         validate_txid_bytes(&self.txid)?;
@@ -57,10 +94,49 @@ impl Validate for PendingTransaction {
 }
 validate_on_deserialize!(PendingTransaction);
 
+fn validate_transaction_details(details: &StoredTransactionDetails) -> Result<(), candid::Error> {
+    if details.recipient.len() > MAX_BTC_ADDRESS_BYTES {
+        return Err(candid::Error::msg(format!(
+            "Recipient address has too many bytes: {} > {MAX_BTC_ADDRESS_BYTES}",
+            details.recipient.len()
+        )));
+    }
+    if details.replaced_txids.len() > MAX_REPLACED_TXIDS {
+        return Err(candid::Error::msg(format!(
+            "Too many replaced transactions: {} > {MAX_REPLACED_TXIDS}",
+            details.replaced_txids.len()
+        )));
+    }
+    for txid in &details.replaced_txids {
+        validate_txid_bytes(txid)?;
+    }
+    if let Some(child) = &details.cpfp_child {
+        validate_txid_bytes(&child.txid)?;
+    }
+    Ok(())
+}
 impl Validate for StoredPendingTransaction {
     fn validate(&self) -> Result<(), candid::Error> {
         validate_txid_bytes(&self.txid)?;
-        validate_utxo_vec(&self.utxos)
+        validate_utxo_vec(&self.utxos)?;
+        if let Some(details) = &self.details {
+            validate_transaction_details(details)?;
+        }
+        Ok(())
     }
 }
 validate_on_deserialize!(StoredPendingTransaction);
+
+impl BtcAddressType {
+    /// The type an address of the user would have to be of to be `address`, or `None` if the
+    /// signer derives no address of that kind. Any P2SH address is taken to be P2SH-P2WPKH.
+    #[must_use]
+    pub fn of(address: &BtcAddress) -> Option<Self> {
+        match address {
+            BtcAddress::P2WPKH(_) => Some(Self::P2wpkh),
+            BtcAddress::P2SH(_) => Some(Self::P2shP2wpkh),
+            BtcAddress::P2TR(_) => Some(Self::P2tr),
+            BtcAddress::P2PKH(_) | BtcAddress::P2WSH(_) => None,
+        }
+    }
+}
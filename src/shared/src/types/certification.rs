@@ -0,0 +1,558 @@
+//! Certification of the backend's state.
+//!
+//! The backend keeps the data it certifies in one hash tree whose root hash is its certified data:
+//!
+//! ```text
+//! ├── exchange_rates
+//! │   └── <Candid-encoded TokenId> → <Candid-encoded ExchangeRate>
+//! └── users
+//!     └── <principal>
+//!         ├── contacts → SHA-256 of the Candid-encoded Vec<Contact>
+//!         ├── custom_tokens → SHA-256 of the Candid-encoded Vec<CustomToken>
+//!         └── profile → SHA-256 of the Candid-encoded UserProfile
+//! ```
+//!
+//! Certified queries return an entry with the IC certificate and a witness of the tree, pruned
+//! everywhere but at the entry. The functions here check both against the IC root key, so a
+//! client can trust the entry without trusting the replica that answered the query. See
+//! [`crate::types::exchange::certification`] for exchange rates.
+
+use candid::{decode_one, CandidType, Deserialize, Principal};
+use ic_canister_sig_creation::extract_raw_root_pk_from_der;
+use ic_certification::{
+    Certificate, Delegation, HashTree, HashTreeNode, LookupResult, SubtreeLookupResult,
+};
+use ic_verify_bls_signature::verify_bls_signature;
+use serde::de::DeserializeOwned;
+use serde_bytes::ByteBuf;
+use sha2::{Digest, Sha256};
+
+use crate::types::{
+    contact::Contact, custom_token::CustomToken, user_profile::UserProfile, Timestamp,
+};
+
+/// Label of the subtree holding the per-user entries.
+pub const USERS_LABEL: &[u8] = b"users";
+/// Label of a user's contacts.
+pub const CONTACTS_LABEL: &[u8] = b"contacts";
+/// Label of a user's custom tokens.
+pub const CUSTOM_TOKENS_LABEL: &[u8] = b"custom_tokens";
+/// Label of a user's profile.
+pub const USER_PROFILE_LABEL: &[u8] = b"profile";
+
+const IC_STATE_ROOT_DOMAIN_SEPARATOR: &[u8] = b"\x0Dic-state-root";
+
+/// Self-describing CBOR tag the IC interface spec requires in front of certificates.
+const CBOR_SELF_DESCRIBING_TAG: [u8; 3] = [0xd9, 0xd9, 0xf7];
+
+/// A user's record with the proof that the backend certified it.
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct CertifiedUserData {
+    /// Candid encoding of the record, as certified.
+    pub data: ByteBuf,
+    /// CBOR-encoded IC certificate of the backend's certified data.
+    pub certificate: ByteBuf,
+    /// CBOR-encoded hash tree revealing the hash of the record.
+    pub witness: ByteBuf,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum VerifyCertifiedDataError {
+    /// The certificate cannot be decoded, is not signed by the IC, or does not cover the canister.
+    InvalidCertificate(String),
+    /// The certificate is older than the accepted age.
+    CertificateTooOld { certificate_time_ns: Timestamp },
+    /// The witness cannot be decoded, does not match the certified data or does not cover the
+    /// entry.
+    InvalidWitness(String),
+    /// The data does not match the certified entry or cannot be decoded.
+    InvalidData(String),
+}
+
+/// Checks that `witness` is the tree `canister_id` certified in `certificate`, and that the
+/// certificate was issued by the IC within the last `max_certificate_age_ns`. Returns the decoded
+/// witness, whose entries can then be trusted.
+///
+/// `ic_root_public_key_raw` is the raw 96-byte IC root key; see
+/// [`ic_canister_sig_creation::IC_ROOT_PK_DER`] and [`extract_raw_root_pk_from_der`].
+///
+/// # Errors
+///
+/// A [`VerifyCertifiedDataError`] if the certificate or the witness is invalid.
+pub fn verify_witness(
+    certificate: &[u8],
+    witness: &[u8],
+    canister_id: Principal,
+    ic_root_public_key_raw: &[u8],
+    now_ns: Timestamp,
+    max_certificate_age_ns: u64,
+) -> Result<HashTree, VerifyCertifiedDataError> {
+    use VerifyCertifiedDataError::{CertificateTooOld, InvalidCertificate, InvalidWitness};
+
+    let certificate: Certificate = parse_cbor(certificate).map_err(InvalidCertificate)?;
+    verify_certificate(&certificate, canister_id, ic_root_public_key_raw)
+        .map_err(InvalidCertificate)?;
+
+    let LookupResult::Found(time) = certificate.tree.lookup_path([b"time".as_slice()]) else {
+        return Err(InvalidCertificate("time not found".to_string()));
+    };
+    let certificate_time_ns =
+        decode_leb128(time).ok_or_else(|| InvalidCertificate("invalid time".to_string()))?;
+    if now_ns.saturating_sub(certificate_time_ns) > max_certificate_age_ns {
+        return Err(CertificateTooOld {
+            certificate_time_ns,
+        });
+    }
+
+    let witness: HashTree = parse_cbor(witness).map_err(InvalidWitness)?;
+    let certified_data_path = [
+        b"canister".as_slice(),
+        canister_id.as_slice(),
+        b"certified_data".as_slice(),
+    ];
+    let LookupResult::Found(certified_data) = certificate.tree.lookup_path(certified_data_path)
+    else {
+        return Err(InvalidCertificate("certified_data not found".to_string()));
+    };
+    if certified_data != witness.digest() {
+        return Err(InvalidWitness(
+            "witness does not match the certified data".to_string(),
+        ));
+    }
+    Ok(witness)
+}
+
+/// Verifies `certified` as `user`'s profile; see [`verify_witness`] for the other arguments.
+///
+/// # Errors
+///
+/// A [`VerifyCertifiedDataError`] if any part of the proof is invalid.
+pub fn verify_certified_user_profile(
+    certified: &CertifiedUserData,
+    user: Principal,
+    canister_id: Principal,
+    ic_root_public_key_raw: &[u8],
+    now_ns: Timestamp,
+    max_certificate_age_ns: u64,
+) -> Result<UserProfile, VerifyCertifiedDataError> {
+    let witness = verify_witness(
+        &certified.certificate,
+        &certified.witness,
+        canister_id,
+        ic_root_public_key_raw,
+        now_ns,
+        max_certificate_age_ns,
+    )?;
+    decode_user_data(&witness, certified, user, USER_PROFILE_LABEL)
+}
+
+/// Verifies `certified` as `user`'s contacts; see [`verify_witness`] for the other arguments.
+///
+/// # Errors
+///
+/// A [`VerifyCertifiedDataError`] if any part of the proof is invalid.
+pub fn verify_certified_contacts(
+    certified: &CertifiedUserData,
+    user: Principal,
+    canister_id: Principal,
+    ic_root_public_key_raw: &[u8],
+    now_ns: Timestamp,
+    max_certificate_age_ns: u64,
+) -> Result<Vec<Contact>, VerifyCertifiedDataError> {
+    let witness = verify_witness(
+        &certified.certificate,
+        &certified.witness,
+        canister_id,
+        ic_root_public_key_raw,
+        now_ns,
+        max_certificate_age_ns,
+    )?;
+    decode_user_data(&witness, certified, user, CONTACTS_LABEL)
+}
+
+/// Verifies `certified` as `user`'s custom tokens; see [`verify_witness`] for the other
+/// arguments.
+///
+/// # Errors
+///
+/// A [`VerifyCertifiedDataError`] if any part of the proof is invalid.
+pub fn verify_certified_custom_tokens(
+    certified: &CertifiedUserData,
+    user: Principal,
+    canister_id: Principal,
+    ic_root_public_key_raw: &[u8],
+    now_ns: Timestamp,
+    max_certificate_age_ns: u64,
+) -> Result<Vec<CustomToken>, VerifyCertifiedDataError> {
+    let witness = verify_witness(
+        &certified.certificate,
+        &certified.witness,
+        canister_id,
+        ic_root_public_key_raw,
+        now_ns,
+        max_certificate_age_ns,
+    )?;
+    decode_user_data(&witness, certified, user, CUSTOM_TOKENS_LABEL)
+}
+
+/// SHA-256 of `data`, the value certified for a user's record.
+#[must_use]
+pub fn user_data_hash(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+/// `certified.data` decoded, once checked against its hash under `users/<user>/<label>` in the
+/// verified `witness`.
+fn decode_user_data<T: CandidType + DeserializeOwned>(
+    witness: &HashTree,
+    certified: &CertifiedUserData,
+    user: Principal,
+    label: &[u8],
+) -> Result<T, VerifyCertifiedDataError> {
+    match witness.lookup_path([USERS_LABEL, user.as_slice(), label]) {
+        LookupResult::Found(hash) if hash == user_data_hash(&certified.data) => {
+            decode_one(&certified.data)
+                .map_err(|e| VerifyCertifiedDataError::InvalidData(e.to_string()))
+        }
+        LookupResult::Found(_) => Err(VerifyCertifiedDataError::InvalidData(
+            "data does not match the certified hash".to_string(),
+        )),
+        LookupResult::Absent | LookupResult::Unknown | LookupResult::Error => {
+            Err(VerifyCertifiedDataError::InvalidWitness(
+                "witness does not cover the entry".to_string(),
+            ))
+        }
+    }
+}
+
+fn parse_cbor<T: DeserializeOwned>(cbor: &[u8]) -> Result<T, String> {
+    if !cbor.starts_with(&CBOR_SELF_DESCRIBING_TAG) {
+        return Err("CBOR doesn't have a self-describing tag".to_string());
+    }
+    serde_cbor::from_slice(cbor).map_err(|e| format!("failed to parse CBOR: {e}"))
+}
+
+/// Checks the signature of `certificate`, following its delegation if it has one.
+fn verify_certificate(
+    certificate: &Certificate,
+    canister_id: Principal,
+    root_public_key_raw: &[u8],
+) -> Result<(), String> {
+    let public_key_raw = match &certificate.delegation {
+        Some(delegation) => verify_delegation(delegation, canister_id, root_public_key_raw)?,
+        None => root_public_key_raw.to_vec(),
+    };
+    check_bls_signature(certificate, &public_key_raw)
+}
+
+/// Checks that the root key delegated to a subnet hosting `canister_id` and returns the subnet's
+/// raw public key.
+fn verify_delegation(
+    delegation: &Delegation,
+    canister_id: Principal,
+    root_public_key_raw: &[u8],
+) -> Result<Vec<u8>, String> {
+    let certificate: Certificate = parse_cbor(&delegation.certificate)
+        .map_err(|e| format!("invalid delegation certificate: {e}"))?;
+    if certificate.delegation.is_some() {
+        return Err("nested delegations are not allowed".to_string());
+    }
+    check_bls_signature(&certificate, root_public_key_raw)?;
+
+    let ranges = canister_ranges(&certificate, &delegation.subnet_id)?;
+    if !ranges
+        .iter()
+        .any(|(start, end)| (start..=end).contains(&&canister_id))
+    {
+        return Err("canister is not in the subnet's canister ranges".to_string());
+    }
+
+    let public_key_path = [
+        b"subnet".as_slice(),
+        delegation.subnet_id.as_slice(),
+        b"public_key".as_slice(),
+    ];
+    let LookupResult::Found(public_key_der) = certificate.tree.lookup_path(public_key_path) else {
+        return Err("subnet public key not found".to_string());
+    };
+    extract_raw_root_pk_from_der(public_key_der)
+}
+
+/// The canister ranges of `subnet_id` revealed by `certificate`: the shards under
+/// `/canister_ranges/<subnet_id>`, or the legacy `/subnet/<subnet_id>/canister_ranges` entry.
+fn canister_ranges(
+    certificate: &Certificate,
+    subnet_id: &[u8],
+) -> Result<Vec<(Principal, Principal)>, String> {
+    match certificate
+        .tree
+        .lookup_subtree([b"canister_ranges".as_slice(), subnet_id])
+    {
+        SubtreeLookupResult::Found(shards) => {
+            let mut leaves = Vec::new();
+            collect_leaves(shards.as_ref(), &mut leaves);
+            leaves
+                .into_iter()
+                .try_fold(Vec::new(), |mut ranges, shard| {
+                    ranges.extend(decode_canister_ranges(shard)?);
+                    Ok(ranges)
+                })
+        }
+        SubtreeLookupResult::Absent | SubtreeLookupResult::Unknown => {
+            let legacy_path = [
+                b"subnet".as_slice(),
+                subnet_id,
+                b"canister_ranges".as_slice(),
+            ];
+            let LookupResult::Found(ranges) = certificate.tree.lookup_path(legacy_path) else {
+                return Err("canister ranges not found".to_string());
+            };
+            decode_canister_ranges(ranges)
+        }
+    }
+}
+
+fn decode_canister_ranges(cbor: &[u8]) -> Result<Vec<(Principal, Principal)>, String> {
+    serde_cbor::from_slice(cbor).map_err(|e| format!("invalid canister ranges: {e}"))
+}
+
+fn collect_leaves<'a>(node: &'a HashTreeNode, leaves: &mut Vec<&'a [u8]>) {
+    match node {
+        HashTreeNode::Empty() | HashTreeNode::Pruned(_) => {}
+        HashTreeNode::Leaf(data) => leaves.push(data),
+        HashTreeNode::Labeled(_, node) => collect_leaves(node, leaves),
+        HashTreeNode::Fork(children) => {
+            collect_leaves(&children.0, leaves);
+            collect_leaves(&children.1, leaves);
+        }
+    }
+}
+
+fn check_bls_signature(certificate: &Certificate, public_key_raw: &[u8]) -> Result<(), String> {
+    let message = [
+        IC_STATE_ROOT_DOMAIN_SEPARATOR,
+        certificate.tree.digest().as_slice(),
+    ]
+    .concat();
+    verify_bls_signature(&certificate.signature, &message, public_key_raw)
+        .map_err(|()| "invalid BLS signature".to_string())
+}
+
+/// Decodes the unsigned LEB128 encoding the IC uses for `/time`.
+fn decode_leb128(bytes: &[u8]) -> Option<u64> {
+    let mut value = 0_u64;
+    for (i, byte) in bytes.iter().enumerate() {
+        let shift = 7 * u32::try_from(i).ok()?;
+        if shift >= u64::BITS {
+            return None;
+        }
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    //! Certificates signed by a test root key, as the IC would issue them.
+
+    use ic_certification::{fork, labeled, leaf, Hash};
+    use ic_verify_bls_signature::PrivateKey;
+    use serde::Serialize;
+
+    use super::*;
+
+    pub(crate) const NOW: Timestamp = 1_700_000_000_000_000_000;
+    pub(crate) const MAX_AGE_NS: u64 = 5 * 60 * 1_000_000_000;
+
+    pub(crate) fn canister_id() -> Principal {
+        Principal::from_text("doked-biaaa-aaaar-qag2a-cai").unwrap()
+    }
+
+    pub(crate) fn root_key() -> PrivateKey {
+        PrivateKey::deserialize(&[0x11; 32]).unwrap()
+    }
+
+    pub(crate) fn root_public_key() -> Vec<u8> {
+        root_key().public_key().serialize().to_vec()
+    }
+
+    pub(crate) fn to_cbor<T: Serialize>(value: &T) -> ByteBuf {
+        let mut serializer = serde_cbor::Serializer::new(Vec::new());
+        serializer.self_describe().unwrap();
+        value.serialize(&mut serializer).unwrap();
+        ByteBuf::from(serializer.into_inner())
+    }
+
+    fn encode_leb128(mut value: u64) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        loop {
+            let byte = u8::try_from(value & 0x7f).unwrap();
+            value >>= 7;
+            if value == 0 {
+                bytes.push(byte);
+                return bytes;
+            }
+            bytes.push(byte | 0x80);
+        }
+    }
+
+    /// A certificate of [`canister_id`]'s `certified_data`, signed by `signer` at `time_ns`.
+    pub(crate) fn certificate(
+        certified_data: Hash,
+        signer: &PrivateKey,
+        time_ns: Timestamp,
+    ) -> ByteBuf {
+        let state = fork(
+            labeled(
+                "canister",
+                labeled(
+                    canister_id().as_slice(),
+                    labeled("certified_data", leaf(certified_data)),
+                ),
+            ),
+            labeled("time", leaf(encode_leb128(time_ns))),
+        );
+        let message = [IC_STATE_ROOT_DOMAIN_SEPARATOR, state.digest().as_slice()].concat();
+        to_cbor(&Certificate {
+            signature: signer.sign(&message).serialize().to_vec(),
+            tree: state,
+            delegation: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use candid::encode_one;
+    use ic_certification::{fork, labeled, leaf, pruned};
+    use ic_verify_bls_signature::PrivateKey;
+    use pretty_assertions::assert_eq;
+
+    use super::{testing::*, *};
+
+    fn user() -> Principal {
+        Principal::from_text("xzg7k-thc6c-idntg-knmtz-2fbhh-utt3e-snqw6-5xph3-54pbp-7axl5-tae")
+            .unwrap()
+    }
+
+    fn contacts() -> Vec<Contact> {
+        vec![Contact {
+            id: 1,
+            name: "Alice".to_string(),
+            addresses: vec![],
+            update_timestamp_ns: NOW,
+            image: None,
+        }]
+    }
+
+    /// `data` certified as the user's contacts next to a pruned profile.
+    fn certify_contacts(data: &[u8], signer: &PrivateKey, time_ns: Timestamp) -> CertifiedUserData {
+        let witness = labeled(
+            USERS_LABEL,
+            labeled(
+                user().as_slice(),
+                fork(
+                    labeled(CONTACTS_LABEL, leaf(user_data_hash(data).to_vec())),
+                    pruned([7; 32]),
+                ),
+            ),
+        );
+        CertifiedUserData {
+            data: ByteBuf::from(data),
+            certificate: certificate(witness.digest(), signer, time_ns),
+            witness: to_cbor(&witness),
+        }
+    }
+
+    #[test]
+    fn verifies_certified_user_data() {
+        let certified = certify_contacts(&encode_one(contacts()).unwrap(), &root_key(), NOW);
+
+        assert_eq!(
+            verify_certified_contacts(
+                &certified,
+                user(),
+                canister_id(),
+                &root_public_key(),
+                NOW,
+                MAX_AGE_NS
+            ),
+            Ok(contacts())
+        );
+        // The witness proves nothing about the profile or another user.
+        assert!(matches!(
+            verify_certified_user_profile(
+                &certified,
+                user(),
+                canister_id(),
+                &root_public_key(),
+                NOW,
+                MAX_AGE_NS
+            ),
+            Err(VerifyCertifiedDataError::InvalidWitness(_))
+        ));
+        assert!(matches!(
+            verify_certified_contacts(
+                &certified,
+                canister_id(),
+                canister_id(),
+                &root_public_key(),
+                NOW,
+                MAX_AGE_NS
+            ),
+            Err(VerifyCertifiedDataError::InvalidWitness(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_data_other_than_the_certified_one() {
+        let certified = CertifiedUserData {
+            data: ByteBuf::from(encode_one(Vec::<Contact>::new()).unwrap()),
+            ..certify_contacts(&encode_one(contacts()).unwrap(), &root_key(), NOW)
+        };
+
+        assert!(matches!(
+            verify_certified_contacts(
+                &certified,
+                user(),
+                canister_id(),
+                &root_public_key(),
+                NOW,
+                MAX_AGE_NS
+            ),
+            Err(VerifyCertifiedDataError::InvalidData(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_certificates_not_signed_by_the_root_key_or_too_old() {
+        let data = encode_one(contacts()).unwrap();
+        let verify = |certified: &CertifiedUserData| {
+            verify_certified_contacts(
+                certified,
+                user(),
+                canister_id(),
+                &root_public_key(),
+                NOW,
+                MAX_AGE_NS,
+            )
+        };
+
+        let forged_key = PrivateKey::deserialize(&[0x22; 32]).unwrap();
+        assert!(matches!(
+            verify(&certify_contacts(&data, &forged_key, NOW)),
+            Err(VerifyCertifiedDataError::InvalidCertificate(_))
+        ));
+
+        let old = NOW - MAX_AGE_NS - 1;
+        assert_eq!(
+            verify(&certify_contacts(&data, &root_key(), old)),
+            Err(VerifyCertifiedDataError::CertificateTooOld {
+                certificate_time_ns: old
+            })
+        );
+    }
+}
@@ -1,9 +1,9 @@
 use std::collections::BTreeMap;
 
-use candid::{CandidType, Deserialize};
+use candid::{CandidType, Deserialize, Principal};
 use serde_bytes::ByteBuf;
 
-use super::account::TokenAccountId;
+use super::account::{conversion::ParseError, TokenAccountId};
 // Re-export image size limit used by validation so tests and external modules can reference it
 pub use crate::impls::MAX_IMAGE_SIZE_BYTES;
 
@@ -16,6 +16,9 @@ pub const MAX_IMAGES_PER_PRINCIPAL: usize = 100;
 /// Memory usage threshold (80%) above which new images cannot be added
 pub const MEMORY_USAGE_THRESHOLD: f64 = 0.8;
 
+/// Maximum size, in bytes, of the content of a contact import.
+pub const MAX_CONTACT_IMPORT_BYTES: usize = 1_000_000;
+
 pub type ImageId = u64;
 
 /// Represents the MIME type of image.
@@ -72,6 +75,10 @@ pub struct ContactImage {
 pub struct ContactAddressData {
     pub token_account_id: TokenAccountId,
     pub label: Option<String>,
+    /// The OISY user the backend derived this address from, for addresses added with
+    /// `create_verified_contact`. Only the backend sets it: `update_contact` rejects verified
+    /// addresses that are not already stored on the contact.
+    pub verified_owner: Option<Principal>,
 }
 
 #[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
@@ -84,9 +91,20 @@ pub struct StoredContacts {
 #[serde(remote = "Self")]
 pub struct CreateContactRequest {
     pub name: String,
+    /// The contact's initial addresses. They cannot be verified addresses.
+    pub addresses: Option<Vec<ContactAddressData>>,
     pub image: Option<ContactImage>,
 }
 
+/// Creates a contact for another OISY user, with the addresses the backend derives from their
+/// principal.
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[serde(remote = "Self")]
+pub struct CreateVerifiedContactRequest {
+    pub name: String,
+    pub principal: Principal,
+}
+
 #[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
 #[serde(remote = "Self")]
 pub struct UpdateContactRequest {
@@ -97,6 +115,108 @@ pub struct UpdateContactRequest {
     pub image: Option<ContactImage>,
 }
 
+/// The file format of a contact import or export.
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ContactExportFormat {
+    /// One row per address, with the columns `id,name,address,label`. Rows with the same
+    /// non-empty `id` belong to the same contact; a row with an empty `id` is a contact of its own.
+    Csv,
+    /// vCard 4.0 cards with the contact name as `FN` and one `X-OISY-ADDRESS` property, with an
+    /// optional `LABEL` parameter, per address.
+    VCard,
+}
+
+/// Contacts to add to the caller's contacts, in a file produced by `export_contacts` or edited by
+/// hand. Images and address verifications are not imported.
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[serde(remote = "Self")]
+pub struct ImportContactsRequest {
+    pub format: ContactExportFormat,
+    pub content: String,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct ImportContactsResponse {
+    /// The contacts that were created.
+    pub imported: Vec<Contact>,
+    /// The contacts that were skipped, and why.
+    pub errors: Vec<ContactImportRowError>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct ContactImportRowError {
+    /// The 1-based line of the content the error was found on.
+    pub line: u64,
+    pub error: ContactImportError,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub enum ContactImportError {
+    /// The line is not a valid CSV row or vCard property, or a card is not terminated.
+    MalformedRecord,
+    InvalidName,
+    InvalidAddress {
+        address: String,
+        error: ParseError,
+    },
+    InvalidLabel,
+    TooManyAddresses,
+    /// The caller reached `MAX_CONTACTS_PER_USER`.
+    TooManyContacts,
+}
+
+/// Maximum number of operations in a `batch_contacts` call.
+pub const MAX_CONTACT_OPERATIONS_PER_BATCH: usize = 100;
+
+/// One operation of a `batch_contacts` call.
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub enum ContactOperation {
+    Create(CreateContactRequest),
+    /// Updates the contact if its `update_timestamp_ns` is still the given one.
+    Update(UpdateContactRequest),
+    /// Deletes the contact if its `update_timestamp_ns` is still the given one.
+    Delete {
+        id: u64,
+        update_timestamp_ns: u64,
+    },
+}
+
+/// Contact operations applied in order, all or none of them.
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[serde(remote = "Self")]
+pub struct BatchContactsRequest {
+    pub operations: Vec<ContactOperation>,
+}
+
+/// The outcome of a successful `ContactOperation`, at the same index.
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub enum ContactOperationResult {
+    Created(Contact),
+    Updated(Contact),
+    Deleted(u64),
+}
+
+/// Why a `batch_contacts` call was not applied.
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct BatchContactsError {
+    /// The index of the operation that failed, or `None` if the batch failed as a whole.
+    pub operation: Option<u64>,
+    pub error: ContactOperationError,
+}
+
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub enum ContactOperationError {
+    ContactError(ContactError),
+    /// The contact changed since the `update_timestamp_ns` given with the operation.
+    OutdatedContact,
+}
+
+impl From<ContactError> for ContactOperationError {
+    fn from(error: ContactError) -> Self {
+        ContactOperationError::ContactError(error)
+    }
+}
+
 #[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
 pub enum ContactError {
     ContactNotFound,
@@ -111,6 +231,22 @@ pub enum ContactError {
     ImageExceedsMaxSize,
 }
 
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub enum CreateVerifiedContactError {
+    /// The contact could not be stored.
+    ContactError(ContactError),
+    /// The principal has no OISY user profile.
+    NotAnOisyUser,
+    /// The addresses of the contact could not be derived from its principal.
+    AddressDerivationFailed,
+}
+
+impl From<ContactError> for CreateVerifiedContactError {
+    fn from(error: ContactError) -> Self {
+        CreateVerifiedContactError::ContactError(error)
+    }
+}
+
 // Helper struct for serialization
 #[derive(serde::Serialize)]
 pub struct ContactImageTemp<'a> {
@@ -165,3 +301,95 @@ pub fn validate_principal_memory_limit(
 
     Ok(())
 }
+
+/// Checks that `addresses`, the new addresses of `stored`, only carry verified addresses that
+/// `stored` already has: a verified address may be relabeled or removed, but not added or altered.
+#[must_use]
+pub fn verified_addresses_preserved(stored: &Contact, addresses: &[ContactAddressData]) -> bool {
+    addresses
+        .iter()
+        .filter(|address| address.verified_owner.is_some())
+        .all(|address| {
+            stored.addresses.iter().any(|existing| {
+                existing.verified_owner == address.verified_owner
+                    && existing.token_account_id == address.token_account_id
+            })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::account::{EthAddress, Icrcv2AccountId};
+
+    fn address(
+        token_account_id: TokenAccountId,
+        verified_owner: Option<Principal>,
+    ) -> ContactAddressData {
+        ContactAddressData {
+            token_account_id,
+            label: None,
+            verified_owner,
+        }
+    }
+
+    fn eth(address: &str) -> TokenAccountId {
+        TokenAccountId::Eth(EthAddress::Public(address.to_string()))
+    }
+
+    #[test]
+    fn verified_addresses_can_be_kept_relabeled_or_removed_but_not_forged() {
+        let owner = Principal::from_slice(&[1; 29]);
+        let verified = address(
+            eth("0x1D1479C185d32EB90533a08b36B3CFa5F84A0E6B"),
+            Some(owner),
+        );
+        let stored = Contact {
+            id: 1,
+            name: "Friend".to_string(),
+            addresses: vec![verified.clone()],
+            update_timestamp_ns: 0,
+            image: None,
+        };
+        let unverified = address(
+            TokenAccountId::Icrcv2(Icrcv2AccountId::WithPrincipal {
+                owner,
+                subaccount: None,
+            }),
+            None,
+        );
+
+        assert!(verified_addresses_preserved(
+            &stored,
+            &[verified.clone(), unverified.clone()]
+        ));
+        assert!(verified_addresses_preserved(
+            &stored,
+            &[ContactAddressData {
+                label: Some("Main".to_string()),
+                ..verified.clone()
+            }]
+        ));
+        assert!(verified_addresses_preserved(
+            &stored,
+            std::slice::from_ref(&unverified)
+        ));
+
+        // A lookalike address cannot claim the owner's verification.
+        assert!(!verified_addresses_preserved(
+            &stored,
+            &[address(
+                eth("0x1D1479C185d32EB90533a08b36B3CFa5F84A0E6C"),
+                Some(owner)
+            )]
+        ));
+        // Nor can an unverified address be marked as verified.
+        assert!(!verified_addresses_preserved(
+            &stored,
+            &[ContactAddressData {
+                verified_owner: Some(owner),
+                ..unverified
+            }]
+        ));
+    }
+}
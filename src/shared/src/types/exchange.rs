@@ -1,6 +1,9 @@
 use candid::{CandidType, Deserialize};
+use serde_bytes::ByteBuf;
 
-use crate::types::Timestamp;
+use crate::types::{token_id::TokenId, Timestamp, Version};
+
+pub mod certification;
 
 #[derive(CandidType, Deserialize, Clone, PartialEq, Debug)]
 #[serde(remote = "Self")]
@@ -9,12 +12,111 @@ pub struct ExchangeData {
     pub price: Option<f64>,
     pub price_24h_change_pct: Option<f64>,
     pub market_cap: Option<f64>,
+    /// Id of the provider the price came from (e.g. `coingecko`, `icpswap_pool`), or `override`
+    /// or `peg` for controller-set prices. `None` for prices cached before it was recorded.
+    pub source: Option<String>,
+    /// Number of providers that agreed on the price: always 1 unless median aggregation is on.
+    /// `None` for static overrides.
+    pub sources: Option<u32>,
+    /// Whether the price is older than the backend's staleness threshold. Only set on prices
+    /// returned by the exchange-rate endpoints.
+    pub stale: Option<bool>,
 }
 
 #[derive(CandidType, Deserialize, Clone, PartialEq, Debug)]
 #[serde(remote = "Self")]
 pub struct ExchangeRate {
     pub usd: ExchangeData,
+    /// `usd.price` converted to other fiat currencies at the cached USD FX rates. `None` for rates
+    /// cached before FX rates were available.
+    pub fiat: Option<Vec<FiatPrice>>,
+}
+
+/// A cached exchange rate with the proof that the backend certified it.
+///
+/// Verify it with [`certification::verify_certified_exchange_rate`] before trusting the rate.
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct CertifiedExchangeRate {
+    /// CBOR-encoded IC certificate of the backend's certified data.
+    pub certificate: ByteBuf,
+    /// CBOR-encoded hash tree revealing the token's entry in the certified exchange rates (or
+    /// proving its absence) and pruned elsewhere.
+    pub witness: ByteBuf,
+}
+
+/// Fiat currencies the backend converts exchange rates to.
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Default)]
+pub enum FiatCurrency {
+    #[default]
+    Usd,
+    Aed,
+    Brl,
+    Cad,
+    Chf,
+    Cny,
+    Czk,
+    Eur,
+    Gbp,
+    Inr,
+    Jpy,
+    Krw,
+    Ngn,
+    Pln,
+    Rub,
+    Sar,
+    Sgd,
+    Vnd,
+}
+
+/// A token price in a fiat currency other than USD.
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct FiatPrice {
+    pub currency: FiatCurrency,
+    pub price: f64,
+}
+
+/// The user's preferred fiat currency. `get_exchange_rates` only returns prices in this currency
+/// next to USD.
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq, Default)]
+pub struct FiatSettings {
+    pub currency: FiatCurrency,
+}
+
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub enum UpdateFiatSettingsError {
+    UserNotFound,
+    VersionMismatch,
+}
+
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub struct UpdateFiatSettingsRequest {
+    pub settings: FiatSettings,
+    pub current_user_version: Option<Version>,
+}
+
+/// A controller-set price for a token, taking precedence over every price provider.
+#[derive(CandidType, Deserialize, Clone, PartialEq, Debug)]
+pub enum PriceOverride {
+    /// A fixed USD price.
+    Static { price: f64 },
+    /// The price of another token, e.g. ckUSDC pegged to USDC.
+    Peg { token_id: TokenId },
+}
+
+#[derive(CandidType, Deserialize, Clone, PartialEq, Debug)]
+pub struct SetPriceOverrideRequest {
+    pub token_id: TokenId,
+    /// `None` removes the override, handing the token back to the price providers.
+    pub price_override: Option<PriceOverride>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub enum SetPriceOverrideError {
+    /// The static price is not a finite, positive number.
+    InvalidPrice,
+    /// The peg target is the token itself, has an override of its own or cannot be priced, or
+    /// the token is itself a peg target. Pegs are only one level deep.
+    InvalidPegTarget,
 }
 
 #[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
@@ -22,3 +124,36 @@ pub enum ExchangeError {
     ApiKeyNotSet,
     Disabled,
 }
+
+/// Bucket width of the exchange-rate history.
+#[derive(CandidType, Deserialize, Clone, Copy, Eq, PartialEq, Debug)]
+pub enum ExchangeRateResolution {
+    Hourly,
+    Daily,
+}
+
+/// Open/high/low/close USD price of a token over one history bucket.
+///
+/// Built from the prices fetched by the exchange-rate refresh, so it only covers the periods in
+/// which the token was being refreshed.
+#[derive(CandidType, Deserialize, Clone, PartialEq, Debug)]
+pub struct PriceCandle {
+    /// Start of the bucket, in nanoseconds since epoch.
+    pub timestamp_ns: Timestamp,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+}
+
+/// Request for the USD price history of a token.
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub struct GetExchangeRateHistoryRequest {
+    pub token_id: TokenId,
+    /// Inclusive start of the range, in nanoseconds since epoch. The bucket containing it is
+    /// included.
+    pub from_ns: Timestamp,
+    /// Inclusive end of the range, in nanoseconds since epoch.
+    pub to_ns: Timestamp,
+    pub resolution: ExchangeRateResolution,
+}
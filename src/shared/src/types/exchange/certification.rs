@@ -0,0 +1,165 @@
+//! Certification of the backend's exchange rates.
+//!
+//! The cached exchange rates are certified under `exchange_rates` in the backend's certified tree
+//! (see [`crate::types::certification`]), keyed by token id, with the Candid-encoded rate as the
+//! leaf. `get_certified_exchange_rate` returns a token's entry, or a proof of its absence.
+
+use candid::{decode_one, encode_one, Principal};
+use ic_certification::LookupResult;
+
+use crate::types::{
+    certification::{verify_witness, VerifyCertifiedDataError},
+    exchange::{CertifiedExchangeRate, ExchangeRate},
+    token_id::TokenId,
+    Timestamp,
+};
+
+/// Label of the subtree holding the exchange rates.
+pub const EXCHANGE_RATES_LABEL: &[u8] = b"exchange_rates";
+
+/// Label of `token_id` in the exchange-rates subtree: its Candid encoding, which is also the
+/// backend's stable-memory key of the rate.
+///
+/// # Panics
+///
+/// Never: a `TokenId` always Candid-encodes.
+#[must_use]
+pub fn exchange_rate_label(token_id: &TokenId) -> Vec<u8> {
+    encode_one(token_id).expect("failed to candid-encode TokenId")
+}
+
+/// Verifies `certified` and returns the certified rate of `token_id`, or `None` if the canister
+/// certified that it has no rate for the token. See [`verify_witness`] for the other arguments.
+///
+/// # Errors
+///
+/// A [`VerifyCertifiedDataError`] if any part of the proof is invalid.
+pub fn verify_certified_exchange_rate(
+    certified: &CertifiedExchangeRate,
+    token_id: &TokenId,
+    canister_id: Principal,
+    ic_root_public_key_raw: &[u8],
+    now_ns: Timestamp,
+    max_certificate_age_ns: u64,
+) -> Result<Option<ExchangeRate>, VerifyCertifiedDataError> {
+    let witness = verify_witness(
+        &certified.certificate,
+        &certified.witness,
+        canister_id,
+        ic_root_public_key_raw,
+        now_ns,
+        max_certificate_age_ns,
+    )?;
+
+    let label = exchange_rate_label(token_id);
+    match witness.lookup_path([EXCHANGE_RATES_LABEL, label.as_slice()]) {
+        LookupResult::Found(rate) => decode_one(rate)
+            .map(Some)
+            .map_err(|e| VerifyCertifiedDataError::InvalidData(e.to_string())),
+        LookupResult::Absent => Ok(None),
+        LookupResult::Unknown | LookupResult::Error => {
+            Err(VerifyCertifiedDataError::InvalidWitness(
+                "witness does not cover the token".to_string(),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ic_certification::{fork, labeled, pruned, RbTree};
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::types::{certification::testing::*, exchange::ExchangeData};
+
+    fn rate(price: f64) -> ExchangeRate {
+        ExchangeRate {
+            usd: ExchangeData {
+                timestamp_ns: NOW,
+                price: Some(price),
+                price_24h_change_pct: None,
+                market_cap: None,
+                source: Some("coingecko".to_string()),
+                sources: Some(1),
+                stale: None,
+            },
+            fiat: None,
+        }
+    }
+
+    /// The backend's tree holding `rates`, certified, and the witness of `token_id`.
+    fn certify(rates: &[(TokenId, ExchangeRate)], token_id: &TokenId) -> CertifiedExchangeRate {
+        let mut tree: RbTree<Vec<u8>, Vec<u8>> = RbTree::new();
+        for (id, rate) in rates {
+            tree.insert(exchange_rate_label(id), encode_one(rate).unwrap());
+        }
+        let witness = fork(
+            labeled(
+                EXCHANGE_RATES_LABEL,
+                tree.witness(&exchange_rate_label(token_id)),
+            ),
+            pruned([0; 32]),
+        );
+
+        CertifiedExchangeRate {
+            certificate: certificate(witness.digest(), &root_key(), NOW),
+            witness: to_cbor(&witness),
+        }
+    }
+
+    fn verify(
+        certified: &CertifiedExchangeRate,
+        token_id: &TokenId,
+    ) -> Result<Option<ExchangeRate>, VerifyCertifiedDataError> {
+        verify_certified_exchange_rate(
+            certified,
+            token_id,
+            canister_id(),
+            &root_public_key(),
+            NOW,
+            MAX_AGE_NS,
+        )
+    }
+
+    #[test]
+    fn verifies_present_and_absent_rates() {
+        let rates = [
+            (TokenId::IcpNative, rate(10.0)),
+            (TokenId::BtcNativeMainnet, rate(60_000.0)),
+        ];
+
+        assert_eq!(
+            verify(
+                &certify(&rates, &TokenId::BtcNativeMainnet),
+                &TokenId::BtcNativeMainnet
+            ),
+            Ok(Some(rate(60_000.0)))
+        );
+        assert_eq!(
+            verify(
+                &certify(&rates, &TokenId::SolNativeMainnet),
+                &TokenId::SolNativeMainnet
+            ),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn rejects_witnesses_of_other_rates() {
+        let certified = certify(&[(TokenId::IcpNative, rate(10.0))], &TokenId::IcpNative);
+        let forged_witness =
+            certify(&[(TokenId::IcpNative, rate(1_000.0))], &TokenId::IcpNative).witness;
+
+        assert!(matches!(
+            verify(
+                &CertifiedExchangeRate {
+                    witness: forged_witness,
+                    ..certified
+                },
+                &TokenId::IcpNative
+            ),
+            Err(VerifyCertifiedDataError::InvalidWitness(_))
+        ));
+    }
+}
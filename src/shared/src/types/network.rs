@@ -6,6 +6,8 @@ use candid::{CandidType, Deserialize};
 
 use crate::types::{network::marker_trait::Network, Version};
 
+pub mod evm_chains;
+
 #[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq, Default)]
 pub struct NetworkSettings {
     pub enabled: bool,
@@ -13,7 +15,7 @@ pub struct NetworkSettings {
 }
 
 /// A flat list of logical networks.
-#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq, Default, Ord, PartialOrd)]
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, Eq, PartialEq, Default, Ord, PartialOrd)]
 pub enum NetworkSettingsFor {
     #[default]
     InternetComputer,
@@ -33,6 +35,14 @@ pub enum NetworkSettingsFor {
     PolygonAmoy,
     ArbitrumMainnet,
     ArbitrumSepolia,
+    OptimismMainnet,
+    OptimismSepolia,
+    AvalancheMainnet,
+    AvalancheFuji,
+    LineaMainnet,
+    LineaSepolia,
+    ZkSyncMainnet,
+    ZkSyncSepolia,
 }
 
 /// A list of logical networks grouped by type.
@@ -70,7 +80,10 @@ impl Network for BitcoinNetworkId {}
 ///
 /// Note: This supercedes the `UserToken ChainId` type that specifies an integer but not the
 /// corresponding network name.
-#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq, Default)]
+///
+/// Per-chain metadata (settings key, testnet flag, pricing identifiers) lives in
+/// [`evm_chains::EVM_CHAINS`].
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, Eq, PartialEq, Default)]
 #[repr(u64)]
 #[non_exhaustive] // Note: This allows chain IDs to be used that are not yet included in this list.
 pub enum EthereumNetworkId {
@@ -85,6 +98,14 @@ pub enum EthereumNetworkId {
     Sepolia = 11_155_111,
     ArbitrumMainnet = 42_161,
     ArbitrumSepolia = 421_614,
+    OptimismMainnet = 10,
+    OptimismSepolia = 11_155_420,
+    AvalancheMainnet = 43_114,
+    AvalancheFuji = 43_113,
+    LineaMainnet = 59_144,
+    LineaSepolia = 59_141,
+    ZkSyncMainnet = 324,
+    ZkSyncSepolia = 300,
 }
 impl Network for EthereumNetworkId {}
 /// Solana networks, or "clusters".
@@ -0,0 +1,265 @@
+//! Registry of the EVM chains OISY knows about.
+//!
+//! Every per-chain fact the backend needs (settings key, testnet flag, price-provider identifiers)
+//! is listed once in [`EVM_CHAINS`]; supporting a new chain means adding one entry here.
+
+use super::{EthereumNetworkId, NetworkSettingsFor, NetworkSettingsMap};
+
+/// Static metadata for one EVM chain.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct EvmChain {
+    pub network: EthereumNetworkId,
+    /// The key of this chain in the user's network settings.
+    pub settings: NetworkSettingsFor,
+    pub is_testnet: bool,
+    /// `CoinGecko` asset platform id, used to price contract tokens on this chain.
+    pub coingecko_platform: Option<&'static str>,
+    /// `CoinGecko` coin id of the chain's native token.
+    pub coingecko_native_coin: Option<&'static str>,
+    /// Alchemy network subdomain, e.g. `eth-mainnet`, used for on-chain reads.
+    pub alchemy_network: Option<&'static str>,
+}
+
+const fn mainnet(
+    network: EthereumNetworkId,
+    settings: NetworkSettingsFor,
+    coingecko_platform: &'static str,
+    coingecko_native_coin: &'static str,
+    alchemy_network: &'static str,
+) -> EvmChain {
+    EvmChain {
+        network,
+        settings,
+        is_testnet: false,
+        coingecko_platform: Some(coingecko_platform),
+        coingecko_native_coin: Some(coingecko_native_coin),
+        alchemy_network: Some(alchemy_network),
+    }
+}
+
+const fn testnet(network: EthereumNetworkId, settings: NetworkSettingsFor) -> EvmChain {
+    EvmChain {
+        network,
+        settings,
+        is_testnet: true,
+        coingecko_platform: None,
+        coingecko_native_coin: None,
+        alchemy_network: None,
+    }
+}
+
+/// All supported EVM chains.
+pub const EVM_CHAINS: &[EvmChain] = &[
+    mainnet(
+        EthereumNetworkId::Mainnet,
+        NetworkSettingsFor::EthereumMainnet,
+        "ethereum",
+        "ethereum",
+        "eth-mainnet",
+    ),
+    testnet(
+        EthereumNetworkId::Sepolia,
+        NetworkSettingsFor::EthereumSepolia,
+    ),
+    mainnet(
+        EthereumNetworkId::BaseMainnet,
+        NetworkSettingsFor::BaseMainnet,
+        "base",
+        "ethereum",
+        "base-mainnet",
+    ),
+    testnet(
+        EthereumNetworkId::BaseSepolia,
+        NetworkSettingsFor::BaseSepolia,
+    ),
+    mainnet(
+        EthereumNetworkId::BNBSmartChainMainnet,
+        NetworkSettingsFor::BscMainnet,
+        "binance-smart-chain",
+        "binancecoin",
+        "bnb-mainnet",
+    ),
+    testnet(
+        EthereumNetworkId::BNBSmartChainTestnet,
+        NetworkSettingsFor::BscTestnet,
+    ),
+    mainnet(
+        EthereumNetworkId::PolygonMainnet,
+        NetworkSettingsFor::PolygonMainnet,
+        "polygon-pos",
+        "polygon-ecosystem-token",
+        "polygon-mainnet",
+    ),
+    testnet(
+        EthereumNetworkId::PolygonAmoy,
+        NetworkSettingsFor::PolygonAmoy,
+    ),
+    mainnet(
+        EthereumNetworkId::ArbitrumMainnet,
+        NetworkSettingsFor::ArbitrumMainnet,
+        "arbitrum-one",
+        "ethereum",
+        "arb-mainnet",
+    ),
+    testnet(
+        EthereumNetworkId::ArbitrumSepolia,
+        NetworkSettingsFor::ArbitrumSepolia,
+    ),
+    mainnet(
+        EthereumNetworkId::OptimismMainnet,
+        NetworkSettingsFor::OptimismMainnet,
+        "optimistic-ethereum",
+        "ethereum",
+        "opt-mainnet",
+    ),
+    testnet(
+        EthereumNetworkId::OptimismSepolia,
+        NetworkSettingsFor::OptimismSepolia,
+    ),
+    mainnet(
+        EthereumNetworkId::AvalancheMainnet,
+        NetworkSettingsFor::AvalancheMainnet,
+        "avalanche",
+        "avalanche-2",
+        "avax-mainnet",
+    ),
+    testnet(
+        EthereumNetworkId::AvalancheFuji,
+        NetworkSettingsFor::AvalancheFuji,
+    ),
+    mainnet(
+        EthereumNetworkId::LineaMainnet,
+        NetworkSettingsFor::LineaMainnet,
+        "linea",
+        "ethereum",
+        "linea-mainnet",
+    ),
+    testnet(
+        EthereumNetworkId::LineaSepolia,
+        NetworkSettingsFor::LineaSepolia,
+    ),
+    mainnet(
+        EthereumNetworkId::ZkSyncMainnet,
+        NetworkSettingsFor::ZkSyncMainnet,
+        "zksync",
+        "ethereum",
+        "zksync-mainnet",
+    ),
+    testnet(
+        EthereumNetworkId::ZkSyncSepolia,
+        NetworkSettingsFor::ZkSyncSepolia,
+    ),
+];
+
+impl EvmChain {
+    /// The registry entry for `chain_id`, if the chain is supported.
+    #[must_use]
+    pub fn from_chain_id(chain_id: u64) -> Option<&'static EvmChain> {
+        EVM_CHAINS.iter().find(|chain| chain.chain_id() == chain_id)
+    }
+
+    #[must_use]
+    pub const fn chain_id(&self) -> u64 {
+        self.network as u64
+    }
+}
+
+impl EthereumNetworkId {
+    #[must_use]
+    pub const fn chain_id(self) -> u64 {
+        self as u64
+    }
+
+    /// The registry entry for this network.
+    #[must_use]
+    pub fn evm_chain(self) -> Option<&'static EvmChain> {
+        EvmChain::from_chain_id(self.chain_id())
+    }
+}
+
+impl NetworkSettingsFor {
+    /// The registry entry for this network, if it is an EVM chain.
+    #[must_use]
+    pub fn evm_chain(self) -> Option<&'static EvmChain> {
+        EVM_CHAINS.iter().find(|chain| chain.settings == self)
+    }
+}
+
+/// Sets the testnet flag of the EVM networks in `networks` to that of their registry entry, so
+/// that a client cannot file a mainnet under testnets or the other way round.
+pub fn set_evm_testnet_flags(networks: &mut NetworkSettingsMap) {
+    for (network, settings) in networks.iter_mut() {
+        if let Some(chain) = network.evm_chain() {
+            settings.is_testnet = chain.is_testnet;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::types::network::NetworkSettings;
+
+    #[test]
+    fn chain_ids_and_settings_keys_are_unique() {
+        let chain_ids: BTreeSet<u64> = EVM_CHAINS.iter().map(EvmChain::chain_id).collect();
+        let settings: BTreeSet<NetworkSettingsFor> =
+            EVM_CHAINS.iter().map(|chain| chain.settings).collect();
+
+        assert_eq!(chain_ids.len(), EVM_CHAINS.len());
+        assert_eq!(settings.len(), EVM_CHAINS.len());
+    }
+
+    #[test]
+    fn testnets_have_no_pricing_identifiers() {
+        for chain in EVM_CHAINS.iter().filter(|chain| chain.is_testnet) {
+            assert_eq!(chain.coingecko_platform, None, "{:?}", chain.network);
+            assert_eq!(chain.coingecko_native_coin, None, "{:?}", chain.network);
+        }
+    }
+
+    #[test]
+    fn lookups_agree_with_each_other() {
+        let optimism = EvmChain::from_chain_id(10).expect("Optimism is registered");
+
+        assert_eq!(optimism.network, EthereumNetworkId::OptimismMainnet);
+        assert_eq!(
+            NetworkSettingsFor::OptimismMainnet.evm_chain(),
+            Some(optimism)
+        );
+        assert_eq!(
+            EthereumNetworkId::OptimismMainnet.evm_chain(),
+            Some(optimism)
+        );
+        assert_eq!(NetworkSettingsFor::InternetComputer.evm_chain(), None);
+        assert_eq!(EvmChain::from_chain_id(999), None);
+    }
+
+    #[test]
+    fn set_evm_testnet_flags_follows_the_registry() {
+        let settings = |is_testnet| NetworkSettings {
+            enabled: true,
+            is_testnet,
+        };
+        let mut networks = NetworkSettingsMap::from([
+            (NetworkSettingsFor::OptimismMainnet, settings(true)),
+            (NetworkSettingsFor::OptimismSepolia, settings(false)),
+            (NetworkSettingsFor::SolanaDevnet, settings(false)),
+        ]);
+
+        set_evm_testnet_flags(&mut networks);
+
+        assert_eq!(
+            networks,
+            NetworkSettingsMap::from([
+                (NetworkSettingsFor::OptimismMainnet, settings(false)),
+                (NetworkSettingsFor::OptimismSepolia, settings(true)),
+                (NetworkSettingsFor::SolanaDevnet, settings(false)),
+            ])
+        );
+    }
+}
@@ -0,0 +1,82 @@
+use candid::{CandidType, Deserialize, Nat};
+
+use crate::types::{token_id::TokenId, Timestamp, Version};
+
+/// Maximum number of token balances in a single portfolio snapshot.
+pub const MAX_PORTFOLIO_SNAPSHOT_BALANCES: usize = 500;
+
+/// Maximum number of snapshots returned by a single `get_portfolio_snapshots` call.
+pub const MAX_GET_PORTFOLIO_SNAPSHOTS_RESULTS: u64 = 366;
+
+/// Number of days a portfolio snapshot is kept before housekeeping evicts it (2 years).
+pub const PORTFOLIO_SNAPSHOT_RETENTION_DAYS: u64 = 2 * 365;
+
+/// Portfolio snapshot settings. Snapshots are opt-in and disabled by default.
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq, Default)]
+pub struct PortfolioSettings {
+    pub snapshots_enabled: bool,
+}
+
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub enum UpdatePortfolioSettingsError {
+    UserNotFound,
+    VersionMismatch,
+}
+
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub struct UpdatePortfolioSettingsRequest {
+    pub settings: PortfolioSettings,
+    pub current_user_version: Option<Version>,
+}
+
+/// Balance of one token held by the user.
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub struct PortfolioTokenBalance {
+    pub token_id: TokenId,
+    /// Balance in the token's smallest unit.
+    pub balance: Nat,
+    pub decimals: u8,
+}
+
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub struct SavePortfolioSnapshotRequest {
+    pub balances: Vec<PortfolioTokenBalance>,
+}
+
+/// The user's portfolio value on one day. A day keeps the last snapshot saved during it.
+#[derive(CandidType, Deserialize, Clone, PartialEq, Debug)]
+pub struct PortfolioSnapshot {
+    /// When the snapshot was saved, in nanoseconds since epoch.
+    pub timestamp_ns: Timestamp,
+    /// Sum of the USD values of the priced balances, at the exchange rates cached when the
+    /// snapshot was saved.
+    pub total_usd: f64,
+    /// Number of submitted balances without a cached exchange rate, left out of `total_usd`.
+    pub unpriced_tokens: u32,
+}
+
+/// Request to retrieve the caller's portfolio snapshots, newest first.
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub struct GetPortfolioSnapshotsRequest {
+    /// Maximum number of snapshots to return (capped at `MAX_GET_PORTFOLIO_SNAPSHOTS_RESULTS`).
+    pub max_results: u64,
+    /// `next_cursor` of the previous page. `None` starts at the newest snapshot.
+    pub cursor: Option<Timestamp>,
+}
+
+#[derive(CandidType, Deserialize, Clone, PartialEq, Debug)]
+pub struct GetPortfolioSnapshotsResponse {
+    pub snapshots: Vec<PortfolioSnapshot>,
+    /// Cursor for the next (older) page. `None` when there are no older snapshots.
+    pub next_cursor: Option<Timestamp>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub enum PortfolioSnapshotError {
+    /// The caller has not enabled portfolio snapshots in their settings.
+    SnapshotsDisabled,
+    UserNotFound,
+    TooManyBalances {
+        max: u64,
+    },
+}
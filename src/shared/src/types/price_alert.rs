@@ -0,0 +1,76 @@
+use candid::{CandidType, Deserialize};
+
+use crate::types::{signer::RateLimitError, token_id::TokenId, Timestamp};
+
+/// Maximum number of price alerts a user can have.
+pub const MAX_PRICE_ALERTS_PER_USER: usize = 20;
+
+/// Maximum number of triggered alerts kept in a user's inbox. The oldest are dropped first.
+pub const MAX_TRIGGERED_PRICE_ALERTS_PER_USER: usize = 50;
+
+/// When a price alert triggers.
+#[derive(CandidType, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum PriceAlertCondition {
+    /// The USD price is above `usd`.
+    PriceAbove { usd: f64 },
+    /// The USD price is below `usd`.
+    PriceBelow { usd: f64 },
+    /// The 24h price change is above `pct` percent, e.g. `5.0` for a 5% rise.
+    Change24hAbove { pct: f64 },
+    /// The 24h price change is below `pct` percent, e.g. `-10.0` for a 10% drop.
+    Change24hBelow { pct: f64 },
+}
+
+/// A price alert of a user.
+///
+/// An alert triggers when its condition starts to hold, and re-arms once the condition no longer
+/// holds, so it triggers again on the next crossing rather than on every refresh.
+#[derive(CandidType, Deserialize, Clone, PartialEq, Debug)]
+pub struct PriceAlert {
+    pub id: u64,
+    pub token_id: TokenId,
+    pub condition: PriceAlertCondition,
+    pub created_at_ns: Timestamp,
+    /// `false` while the condition still holds after the alert triggered.
+    pub armed: bool,
+    pub last_triggered_at_ns: Option<Timestamp>,
+}
+
+#[derive(CandidType, Deserialize, Clone, PartialEq, Debug)]
+pub struct CreatePriceAlertRequest {
+    pub token_id: TokenId,
+    pub condition: PriceAlertCondition,
+}
+
+#[derive(CandidType, Deserialize, Clone, Copy, Eq, PartialEq, Debug)]
+pub struct DeletePriceAlertRequest {
+    pub alert_id: u64,
+}
+
+/// A triggered price alert in the user's inbox.
+#[derive(CandidType, Deserialize, Clone, PartialEq, Debug)]
+pub struct TriggeredPriceAlert {
+    pub alert_id: u64,
+    pub token_id: TokenId,
+    pub condition: PriceAlertCondition,
+    /// USD price at the time the alert triggered.
+    pub price_usd: f64,
+    /// 24h price change at the time the alert triggered, if known.
+    pub price_24h_change_pct: Option<f64>,
+    pub triggered_at_ns: Timestamp,
+}
+
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub enum PriceAlertError {
+    /// The caller already has `MAX_PRICE_ALERTS_PER_USER` alerts.
+    TooManyAlerts {
+        max: u64,
+    },
+    /// The condition's threshold is not a finite number, or a price threshold is not positive.
+    InvalidThreshold,
+    /// No exchange rate is ever fetched for the token.
+    TokenNotPriceable,
+    AlertNotFound,
+    /// The caller has exceeded the call rate limit.
+    RateLimited(RateLimitError),
+}
@@ -3,7 +3,8 @@ use serde_bytes::ByteBuf;
 
 use super::{
     bitcoin::{
-        BtcAddPendingTransactionError, BtcGetPendingTransactionsError,
+        BtcAddPendingTransactionError, BtcBuildTransactionError, BtcBuildTransactionResponse,
+        BtcBumpFeeError, BtcBumpFeeResponse, BtcGetPendingTransactionsError,
         BtcGetPendingTransactionsReponse,
     },
     dapp::AddDappSettingsError,
@@ -18,15 +19,24 @@ use crate::types::{
         ActiveUserTransaction, ActiveUserTransactionError, GetActiveUserTransactionsResponse,
     },
     agreement::{AgreementHistoryEntry, GetAgreementHistoryError, UpdateAgreementsError},
-    bitcoin::{BtcGetFeePercentilesError, BtcGetFeePercentilesResponse},
-    contact::{Contact, ContactError},
+    bitcoin::{BtcGetFeePercentilesError, BtcGetFeePercentilesResponse, UpdateBtcSettingsError},
+    contact::{
+        BatchContactsError, Contact, ContactError, ContactOperationResult,
+        CreateVerifiedContactError, ImportContactsResponse,
+    },
+    exchange::{SetPriceOverrideError, UpdateFiatSettingsError},
     experimental_feature::UpdateExperimentalFeaturesSettingsError,
     network::{SetTestnetsSettingsError, UpdateNetworksSettingsError},
     onramper::{SignOnramperWidgetUrlError, SignOnramperWidgetUrlResponse},
     personal_note::{PersonalNoteEntry, PersonalNoteError},
     personal_note_share::{PersonalNoteShareContent, PersonalNoteShareError},
+    portfolio::{PortfolioSnapshot, PortfolioSnapshotError, UpdatePortfolioSettingsError},
+    price_alert::{PriceAlert, PriceAlertError},
     transaction_settings::UpdateTransactionFilterSettingsError,
-    user_transaction::{GetUserTransactionsResponse, UserTransactionError},
+    user_transaction::{
+        ExportUserTransactionsResponse, GetUserActivityResponse, GetUserTransactionsResponse,
+        UserTransactionError,
+    },
 };
 
 #[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
@@ -46,6 +56,23 @@ impl From<Result<Contact, ContactError>> for CreateContactResult {
     }
 }
 
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub enum CreateVerifiedContactResult {
+    /// The contact was created successfully.
+    Ok(Contact),
+    /// The contact could not be created due to an error.
+    Err(CreateVerifiedContactError),
+}
+
+impl From<Result<Contact, CreateVerifiedContactError>> for CreateVerifiedContactResult {
+    fn from(result: Result<Contact, CreateVerifiedContactError>) -> Self {
+        match result {
+            Ok(contact) => CreateVerifiedContactResult::Ok(contact),
+            Err(err) => CreateVerifiedContactResult::Err(err),
+        }
+    }
+}
+
 #[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
 pub enum UpdateContactResult {
     /// The contact was updated successfully.
@@ -111,6 +138,54 @@ impl From<Result<Vec<Contact>, ContactError>> for GetContactsResult {
     }
 }
 
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub enum BatchContactsResult {
+    /// All operations were applied; the results are in the order of the operations.
+    Ok(Vec<ContactOperationResult>),
+    /// None of the operations were applied due to an error.
+    Err(BatchContactsError),
+}
+impl From<Result<Vec<ContactOperationResult>, BatchContactsError>> for BatchContactsResult {
+    fn from(result: Result<Vec<ContactOperationResult>, BatchContactsError>) -> Self {
+        match result {
+            Ok(results) => BatchContactsResult::Ok(results),
+            Err(err) => BatchContactsResult::Err(err),
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub enum ImportContactsResult {
+    /// The import ran; see the response for the contacts that were skipped.
+    Ok(ImportContactsResponse),
+    /// Nothing was imported due to an error.
+    Err(ContactError),
+}
+impl From<Result<ImportContactsResponse, ContactError>> for ImportContactsResult {
+    fn from(result: Result<ImportContactsResponse, ContactError>) -> Self {
+        match result {
+            Ok(response) => ImportContactsResult::Ok(response),
+            Err(err) => ImportContactsResult::Err(err),
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub enum ExportContactsResult {
+    /// The caller's contacts in the requested format.
+    Ok(String),
+    /// The contacts were not exported due to an error.
+    Err(ContactError),
+}
+impl From<Result<String, ContactError>> for ExportContactsResult {
+    fn from(result: Result<String, ContactError>) -> Self {
+        match result {
+            Ok(content) => ExportContactsResult::Ok(content),
+            Err(err) => ExportContactsResult::Err(err),
+        }
+    }
+}
+
 #[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
 pub enum UpdateUserNetworkSettingsResult {
     /// The user's network settings were updated successfully.
@@ -191,6 +266,40 @@ impl From<Result<GetAllowedCyclesResponse, GetAllowedCyclesError>> for GetAllowe
     }
 }
 
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub enum BtcBuildTransactionResult {
+    /// The transaction was built and its UTXOs reserved.
+    Ok(BtcBuildTransactionResponse),
+    /// The transaction was not built due to an error.
+    Err(BtcBuildTransactionError),
+}
+impl From<Result<BtcBuildTransactionResponse, BtcBuildTransactionError>>
+    for BtcBuildTransactionResult
+{
+    fn from(result: Result<BtcBuildTransactionResponse, BtcBuildTransactionError>) -> Self {
+        match result {
+            Ok(response) => BtcBuildTransactionResult::Ok(response),
+            Err(err) => BtcBuildTransactionResult::Err(err),
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub enum BtcBumpFeeResult {
+    /// The fee-bumping transaction was built and the pending transaction updated.
+    Ok(BtcBumpFeeResponse),
+    /// The fee was not bumped due to an error.
+    Err(BtcBumpFeeError),
+}
+impl From<Result<BtcBumpFeeResponse, BtcBumpFeeError>> for BtcBumpFeeResult {
+    fn from(result: Result<BtcBumpFeeResponse, BtcBumpFeeError>) -> Self {
+        match result {
+            Ok(response) => BtcBumpFeeResult::Ok(response),
+            Err(err) => BtcBumpFeeResult::Err(err),
+        }
+    }
+}
+
 #[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
 pub enum BtcGetFeePercentilesResult {
     /// The fee was selected successfully.
@@ -357,6 +466,104 @@ impl From<Result<(), UpdateTransactionFilterSettingsError>>
     }
 }
 
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub enum UpdatePortfolioSettingsResult {
+    Ok(()),
+    Err(UpdatePortfolioSettingsError),
+}
+impl From<Result<(), UpdatePortfolioSettingsError>> for UpdatePortfolioSettingsResult {
+    fn from(result: Result<(), UpdatePortfolioSettingsError>) -> Self {
+        match result {
+            Ok(()) => UpdatePortfolioSettingsResult::Ok(()),
+            Err(err) => UpdatePortfolioSettingsResult::Err(err),
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub enum UpdateFiatSettingsResult {
+    Ok(()),
+    Err(UpdateFiatSettingsError),
+}
+impl From<Result<(), UpdateFiatSettingsError>> for UpdateFiatSettingsResult {
+    fn from(result: Result<(), UpdateFiatSettingsError>) -> Self {
+        match result {
+            Ok(()) => UpdateFiatSettingsResult::Ok(()),
+            Err(err) => UpdateFiatSettingsResult::Err(err),
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub enum UpdateBtcSettingsResult {
+    Ok(()),
+    Err(UpdateBtcSettingsError),
+}
+impl From<Result<(), UpdateBtcSettingsError>> for UpdateBtcSettingsResult {
+    fn from(result: Result<(), UpdateBtcSettingsError>) -> Self {
+        match result {
+            Ok(()) => UpdateBtcSettingsResult::Ok(()),
+            Err(err) => UpdateBtcSettingsResult::Err(err),
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub enum SetPriceOverrideResult {
+    Ok(()),
+    Err(SetPriceOverrideError),
+}
+impl From<Result<(), SetPriceOverrideError>> for SetPriceOverrideResult {
+    fn from(result: Result<(), SetPriceOverrideError>) -> Self {
+        match result {
+            Ok(()) => SetPriceOverrideResult::Ok(()),
+            Err(err) => SetPriceOverrideResult::Err(err),
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, PartialEq, Debug)]
+pub enum SavePortfolioSnapshotResult {
+    Ok(PortfolioSnapshot),
+    Err(PortfolioSnapshotError),
+}
+impl From<Result<PortfolioSnapshot, PortfolioSnapshotError>> for SavePortfolioSnapshotResult {
+    fn from(result: Result<PortfolioSnapshot, PortfolioSnapshotError>) -> Self {
+        match result {
+            Ok(snapshot) => SavePortfolioSnapshotResult::Ok(snapshot),
+            Err(err) => SavePortfolioSnapshotResult::Err(err),
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, PartialEq, Debug)]
+pub enum CreatePriceAlertResult {
+    Ok(PriceAlert),
+    Err(PriceAlertError),
+}
+impl From<Result<PriceAlert, PriceAlertError>> for CreatePriceAlertResult {
+    fn from(result: Result<PriceAlert, PriceAlertError>) -> Self {
+        match result {
+            Ok(alert) => CreatePriceAlertResult::Ok(alert),
+            Err(err) => CreatePriceAlertResult::Err(err),
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub enum DeletePriceAlertResult {
+    Ok(()),
+    Err(PriceAlertError),
+}
+impl From<Result<(), PriceAlertError>> for DeletePriceAlertResult {
+    fn from(result: Result<(), PriceAlertError>) -> Self {
+        match result {
+            Ok(()) => DeletePriceAlertResult::Ok(()),
+            Err(err) => DeletePriceAlertResult::Err(err),
+        }
+    }
+}
+
 #[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
 pub enum GetUserTransactionsResult {
     Ok(GetUserTransactionsResponse),
@@ -371,6 +578,36 @@ impl From<Result<GetUserTransactionsResponse, UserTransactionError>> for GetUser
     }
 }
 
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub enum GetUserActivityResult {
+    Ok(GetUserActivityResponse),
+    Err(UserTransactionError),
+}
+impl From<Result<GetUserActivityResponse, UserTransactionError>> for GetUserActivityResult {
+    fn from(result: Result<GetUserActivityResponse, UserTransactionError>) -> Self {
+        match result {
+            Ok(response) => GetUserActivityResult::Ok(response),
+            Err(err) => GetUserActivityResult::Err(err),
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub enum ExportUserTransactionsResult {
+    Ok(ExportUserTransactionsResponse),
+    Err(UserTransactionError),
+}
+impl From<Result<ExportUserTransactionsResponse, UserTransactionError>>
+    for ExportUserTransactionsResult
+{
+    fn from(result: Result<ExportUserTransactionsResponse, UserTransactionError>) -> Self {
+        match result {
+            Ok(response) => ExportUserTransactionsResult::Ok(response),
+            Err(err) => ExportUserTransactionsResult::Err(err),
+        }
+    }
+}
+
 #[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
 pub enum SaveUserTransactionsResult {
     Ok(()),
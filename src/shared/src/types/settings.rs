@@ -1,8 +1,9 @@
 use candid::{CandidType, Deserialize};
 
 use crate::types::{
-    dapp::DappSettings, experimental_feature::ExperimentalFeaturesSettings,
-    network::NetworksSettings, notification::NotificationSettings,
+    bitcoin::BtcSettings, dapp::DappSettings, exchange::FiatSettings,
+    experimental_feature::ExperimentalFeaturesSettings, network::NetworksSettings,
+    notification::NotificationSettings, portfolio::PortfolioSettings,
     transaction_settings::TransactionSettings,
 };
 
@@ -13,4 +14,7 @@ pub struct Settings {
     pub experimental_features: ExperimentalFeaturesSettings,
     pub notifications: Option<NotificationSettings>,
     pub transactions: Option<TransactionSettings>,
+    pub portfolio: Option<PortfolioSettings>,
+    pub fiat: Option<FiatSettings>,
+    pub btc: Option<BtcSettings>,
 }
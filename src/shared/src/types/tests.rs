@@ -7,7 +7,8 @@ mod bitcoin {
 
     use crate::{
         types::bitcoin::{
-            BtcAddPendingTransactionRequest, PendingTransaction, MAX_TXID_BYTES, MAX_UTXOS_LEN,
+            BtcAddPendingTransactionRequest, PendingTransaction, PendingTransactionStatus,
+            MAX_TXID_BYTES, MAX_UTXOS_LEN,
         },
         validate::{test_validate_on_deserialize, TestVector, Validate},
     };
@@ -21,6 +22,7 @@ mod bitcoin {
                     txid: vec![0; MAX_TXID_BYTES],
                     utxos: vec![],
                     network: BitcoinNetwork::Mainnet,
+                    address_type: None,
                     ii_delegation_chain: None,
                 },
                 valid: true,
@@ -31,6 +33,7 @@ mod bitcoin {
                     txid: vec![0; MAX_TXID_BYTES + 1],
                     utxos: vec![],
                     network: BitcoinNetwork::Mainnet,
+                    address_type: None,
                     ii_delegation_chain: None,
                 },
                 valid: false,
@@ -48,6 +51,7 @@ mod bitcoin {
                         height: 0,
                     }],
                     network: BitcoinNetwork::Mainnet,
+                    address_type: None,
                     ii_delegation_chain: None,
                 },
                 valid: true,
@@ -65,6 +69,7 @@ mod bitcoin {
                         height: 0,
                     }],
                     network: BitcoinNetwork::Mainnet,
+                    address_type: None,
                     ii_delegation_chain: None,
                 },
                 valid: false,
@@ -85,6 +90,7 @@ mod bitcoin {
                         MAX_UTXOS_LEN + 1
                     ],
                     network: BitcoinNetwork::Mainnet,
+                    address_type: None,
                     ii_delegation_chain: None,
                 },
                 valid: false,
@@ -100,6 +106,8 @@ mod bitcoin {
                 input: PendingTransaction {
                     txid: vec![0; MAX_TXID_BYTES],
                     utxos: vec![],
+                    status: PendingTransactionStatus::Pending,
+                    confirmations: 0,
                 },
                 valid: true,
             },
@@ -108,6 +116,8 @@ mod bitcoin {
                 input: PendingTransaction {
                     txid: vec![0; MAX_TXID_BYTES + 1],
                     utxos: vec![],
+                    status: PendingTransactionStatus::Pending,
+                    confirmations: 0,
                 },
                 valid: false,
             },
@@ -126,6 +136,8 @@ mod bitcoin {
                         };
                         MAX_UTXOS_LEN + 1
                     ],
+                    status: PendingTransactionStatus::Pending,
+                    confirmations: 0,
                 },
                 valid: false,
             },
@@ -141,6 +153,8 @@ mod bitcoin {
                         value: 0,
                         height: 0,
                     }],
+                    status: PendingTransactionStatus::Pending,
+                    confirmations: 0,
                 },
                 valid: false,
             }
@@ -737,3 +751,59 @@ mod user_profile {
         }]
     );
 }
+
+mod user_transaction {
+    use candid::{Decode, Encode, Nat};
+
+    use crate::{
+        types::{
+            token_id::TokenId,
+            user_transaction::{
+                IcrcTransactionData, IcrcTransactionType, NetworkTransactionData,
+                SaveUserTransactionsRequest, UserTransaction, RESERVED_BLOCK_INDEX,
+                RESERVED_TIMESTAMP,
+            },
+        },
+        validate::{test_validate_on_deserialize, TestVector, Validate},
+    };
+
+    fn request(block_index: u64, timestamp: u64) -> SaveUserTransactionsRequest {
+        SaveUserTransactionsRequest {
+            token_id: TokenId::IcpNative,
+            transactions: vec![UserTransaction {
+                id: "1".to_string(),
+                block_index,
+                timestamp,
+                from: "sender".to_string(),
+                to: None,
+                value: Nat::from(1u64),
+                network_data: NetworkTransactionData::Icrc(IcrcTransactionData {
+                    fee: None,
+                    memo: None,
+                    tx_type: IcrcTransactionType::Transfer,
+                }),
+            }],
+        }
+    }
+
+    test_validate_on_deserialize!(
+        SaveUserTransactionsRequest,
+        [
+            TestVector {
+                description: "SaveUserTransactionsRequest with valid transactions",
+                input: request(RESERVED_BLOCK_INDEX - 1, RESERVED_TIMESTAMP - 1),
+                valid: true,
+            },
+            TestVector {
+                description: "SaveUserTransactionsRequest with a reserved block index",
+                input: request(RESERVED_BLOCK_INDEX, 1),
+                valid: false,
+            },
+            TestVector {
+                description: "SaveUserTransactionsRequest with a reserved timestamp",
+                input: request(1, RESERVED_TIMESTAMP),
+                valid: false,
+            }
+        ]
+    );
+}
@@ -8,9 +8,24 @@ pub const MAX_USER_TRANSACTIONS_PER_TOKEN: usize = 10_000;
 /// Maximum number of transactions that can be saved in a single request.
 pub const MAX_SAVE_USER_TRANSACTIONS_BATCH: usize = 500;
 
+/// `block_index` reserved as the exclusive upper bound of a `(principal, token_id)` key range.
+/// Requests saving transactions that carry it are rejected on deserialization.
+pub const RESERVED_BLOCK_INDEX: u64 = u64::MAX;
+
+/// `timestamp` reserved as the exclusive upper bound of a principal's activity index range.
+/// Requests saving transactions that carry it are rejected on deserialization.
+pub const RESERVED_TIMESTAMP: u64 = u64::MAX;
+
 /// Maximum number of transactions that can be returned in a single response.
 pub const MAX_GET_USER_TRANSACTIONS_RESULTS: u64 = 100;
 
+/// Maximum number of rows in a single transaction export chunk.
+pub const MAX_EXPORT_USER_TRANSACTIONS_ROWS: u64 = 1_000;
+
+/// Maximum number of stored transactions examined by a single filtered request.
+/// A filtered page may therefore hold fewer than `max_results` transactions while more remain.
+pub const MAX_SCANNED_USER_TRANSACTIONS: u64 = 5_000;
+
 /// A finalized transaction stored in the backend.
 ///
 /// Contains common fields shared across all networks plus a network-specific
@@ -92,6 +107,79 @@ pub struct SolTransactionData {
     pub to_owner: Option<String>,
 }
 
+/// Position of a stored transaction in the `(block_index, id)` order used by the backend.
+///
+/// Used as a stable pagination cursor: unlike the positional `start`, it stays valid when
+/// transactions are inserted or evicted between two page requests.
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub struct UserTransactionCursor {
+    pub block_index: u64,
+    pub id: String,
+}
+
+/// Criteria a stored transaction must meet to be returned. All set fields must match.
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug, Default)]
+pub struct UserTransactionFilter {
+    /// Exact sender address or account.
+    pub from: Option<String>,
+    /// Exact recipient address or account.
+    pub to: Option<String>,
+    /// Only transactions sent from or to this address, in the given direction.
+    pub direction: Option<TransactionDirectionFilter>,
+    /// Inclusive lower bound on `timestamp`.
+    pub min_timestamp: Option<u64>,
+    /// Inclusive upper bound on `timestamp`.
+    pub max_timestamp: Option<u64>,
+    /// Inclusive lower bound on `value`.
+    pub min_value: Option<Nat>,
+    /// Inclusive upper bound on `value`.
+    pub max_value: Option<Nat>,
+    /// Network-specific criteria; also restricts the results to that network family.
+    pub network: Option<NetworkTransactionFilter>,
+}
+
+/// Direction of a transaction relative to `address`.
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub struct TransactionDirectionFilter {
+    pub address: String,
+    pub direction: TransactionDirection,
+}
+
+/// Self-transfers (`from == to == address`) match both directions.
+#[derive(CandidType, Deserialize, Clone, Copy, Eq, PartialEq, Debug)]
+pub enum TransactionDirection {
+    /// `to` is the address.
+    Incoming,
+    /// `from` is the address.
+    Outgoing,
+}
+
+/// Criteria on [`NetworkTransactionData`]. Unset fields match any value.
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub enum NetworkTransactionFilter {
+    Evm {
+        chain_id: Option<ChainId>,
+        nft_token_id: Option<Nat>,
+    },
+    Icrc {
+        tx_type: Option<IcrcTransactionTypeFilter>,
+    },
+    Btc,
+    Sol,
+}
+
+/// Criteria on [`IcrcTransactionType`].
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub enum IcrcTransactionTypeFilter {
+    Transfer,
+    /// Approvals, optionally restricted to one spender.
+    Approve {
+        spender: Option<String>,
+    },
+    Mint,
+    Burn,
+}
+
 /// Request to retrieve stored transactions with cursor-based pagination.
 #[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
 pub struct GetUserTransactionsRequest {
@@ -99,9 +187,20 @@ pub struct GetUserTransactionsRequest {
     pub token_id: TokenId,
     /// Opaque pagination cursor returned as `next_start` from a previous response.
     /// `None` starts from the newest transactions.
+    ///
+    /// Skipping to a positional cursor costs time proportional to the number of newer
+    /// transactions; prefer `cursor` for deep pagination.
     pub start: Option<u64>,
     /// Maximum number of transactions to return (capped at `MAX_GET_USER_TRANSACTIONS_RESULTS`)
     pub max_results: u64,
+    /// Stable pagination cursor returned as `next_cursor` from a previous response.
+    /// When set, only transactions strictly older than the cursor are returned and `start` is
+    /// ignored.
+    pub cursor: Option<UserTransactionCursor>,
+    /// Only return transactions matching this filter. At most `MAX_SCANNED_USER_TRANSACTIONS`
+    /// stored transactions are examined per request; keep paginating until no next cursor is
+    /// returned.
+    pub filter: Option<UserTransactionFilter>,
 }
 
 /// Response containing stored transactions and pagination info.
@@ -121,12 +220,17 @@ pub struct GetUserTransactionsResponse {
     /// saving older transactions that would be immediately evicted.
     pub total_stored: u64,
     /// Opaque cursor for the next page. Pass as `start` to fetch older transactions.
-    /// `None` when there are no more older transactions.
+    /// `None` when there are no more older transactions, or when the request paginated by
+    /// `cursor`.
     pub next_start: Option<u64>,
+    /// Stable cursor for the next page. Pass as `cursor` to fetch older transactions.
+    /// `None` when there are no more older transactions.
+    pub next_cursor: Option<UserTransactionCursor>,
 }
 
 /// Request to save finalized transactions.
 #[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+#[serde(remote = "Self")]
 pub struct SaveUserTransactionsRequest {
     /// Which token these transactions belong to
     pub token_id: TokenId,
@@ -134,6 +238,84 @@ pub struct SaveUserTransactionsRequest {
     pub transactions: Vec<UserTransaction>,
 }
 
+/// Position of a stored transaction in the caller's activity feed, ordered by
+/// `(timestamp, token_id, block_index, id)`.
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub struct UserActivityCursor {
+    pub timestamp: u64,
+    pub token_id: TokenId,
+    pub block_index: u64,
+    pub id: String,
+}
+
+/// Request to retrieve the caller's stored transactions across tokens, newest first.
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub struct GetUserActivityRequest {
+    /// Restrict the feed to these tokens. `None` includes every token.
+    pub token_ids: Option<Vec<TokenId>>,
+    /// Maximum number of transactions to return (capped at `MAX_GET_USER_TRANSACTIONS_RESULTS`)
+    pub max_results: u64,
+    /// Cursor returned as `next_cursor` from a previous response. `None` starts from the newest
+    /// transaction.
+    pub cursor: Option<UserActivityCursor>,
+    /// Only return transactions matching this filter. At most `MAX_SCANNED_USER_TRANSACTIONS`
+    /// stored transactions are examined per request; keep paginating until no next cursor is
+    /// returned.
+    pub filter: Option<UserTransactionFilter>,
+}
+
+/// A stored transaction together with the token it belongs to.
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub struct UserActivityItem {
+    pub token_id: TokenId,
+    pub transaction: UserTransaction,
+}
+
+/// Response containing a page of the caller's activity feed.
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub struct GetUserActivityResponse {
+    /// The requested transactions, sorted by timestamp, newest first
+    pub items: Vec<UserActivityItem>,
+    /// Cursor for the next page. Pass as `cursor` to fetch older transactions.
+    /// `None` when there are no more older transactions.
+    pub next_cursor: Option<UserActivityCursor>,
+}
+
+/// Output format of a transaction export.
+#[derive(CandidType, Deserialize, Clone, Copy, Eq, PartialEq, Debug)]
+pub enum UserTransactionExportFormat {
+    /// RFC 4180 CSV. Only the first chunk (requested without a cursor) starts with a header row.
+    Csv,
+    /// A JSON array of row objects per chunk.
+    Json,
+}
+
+/// Request to export the caller's stored transactions across tokens, newest first.
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub struct ExportUserTransactionsRequest {
+    pub format: UserTransactionExportFormat,
+    /// Restrict the export to these tokens. `None` includes every token.
+    pub token_ids: Option<Vec<TokenId>>,
+    /// Inclusive lower bound on the transaction timestamp, in seconds since epoch.
+    pub min_timestamp: Option<u64>,
+    /// Inclusive upper bound on the transaction timestamp, in seconds since epoch.
+    pub max_timestamp: Option<u64>,
+    /// Cursor returned as `next_cursor` from the previous chunk. `None` starts a new export.
+    pub cursor: Option<UserActivityCursor>,
+}
+
+/// One chunk of a transaction export.
+#[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub struct ExportUserTransactionsResponse {
+    /// The rows of this chunk (at most `MAX_EXPORT_USER_TRANSACTIONS_ROWS`) in the requested
+    /// format.
+    pub content: String,
+    /// Number of rows in `content`.
+    pub row_count: u64,
+    /// Cursor for the next chunk. `None` when the export is complete.
+    pub next_cursor: Option<UserActivityCursor>,
+}
+
 #[derive(CandidType, Deserialize, Clone, Eq, PartialEq, Debug)]
 pub enum UserTransactionError {
     /// Reserved for future caller-validation logic.